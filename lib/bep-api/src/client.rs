@@ -0,0 +1,456 @@
+use crate::error::Error;
+use crate::types::*;
+
+use reqwest::{header, Method, RequestBuilder};
+
+use serde::de::DeserializeOwned;
+
+/// Client of the HTTP API mounted at `<base_url>/api/v1`. It keeps the
+/// session token of the last `login` and sends it with every request.
+#[derive(Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Client {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            token: None,
+        }
+    }
+
+    /// A client resuming a session opened earlier, or calling the API with
+    /// an API key.
+    pub fn with_token(base_url: &str, token: String) -> Client {
+        Client { token: Some(token), ..Client::new(base_url) }
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_ref().map(String::as_str)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let builder = self.http.request(method, &format!("{}/api/v1{}", self.base_url, path));
+
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    fn send_raw(builder: RequestBuilder) -> Result<reqwest::Response, Error> {
+        let mut response = builder.send()?;
+
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            let body = response.text()?;
+            Err(Error::from_response(response.status(), &body))
+        }
+    }
+
+    fn send<T: DeserializeOwned>(builder: RequestBuilder) -> Result<T, Error> {
+        Ok(Client::send_raw(builder)?.json()?)
+    }
+
+    fn send_empty(builder: RequestBuilder) -> Result<(), Error> {
+        Client::send_raw(builder).map(|_| ())
+    }
+
+    /// Reads the body of a file response.
+    fn download(builder: RequestBuilder) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        Client::send_raw(builder)?.copy_to(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// Sends the version the entity was loaded at, failing the update with
+    /// `Error::Conflict` if it was saved since.
+    fn if_match(builder: RequestBuilder, version: Option<i32>) -> RequestBuilder {
+        match version {
+            Some(version) => builder.header(header::IF_MATCH, format!("\"{}\"", version)),
+            None => builder,
+        }
+    }
+
+    /// Creates an organization and its admin, returning the admin's id.
+    pub fn register_new_organisation(&self, fields: &NewOrganization) -> Result<uuid::Uuid, Error> {
+        let registered: Registered = Client::send(self.request(Method::POST, "/organisations").json(fields))?;
+
+        Ok(registered.user_id)
+    }
+
+    /// Opens a session, used by the following requests.
+    pub fn login(&mut self, email: &str, password: &str) -> Result<(), Error> {
+        self.send_credentials(Credentials { email, password, code: None })
+    }
+
+    /// Opens a session of a user with two-factor authentication, given a
+    /// code of the authenticator app or a recovery code.
+    pub fn login_with_code(&mut self, email: &str, password: &str, code: &str) -> Result<(), Error> {
+        self.send_credentials(Credentials { email, password, code: Some(code) })
+    }
+
+    fn send_credentials(&mut self, credentials: Credentials) -> Result<(), Error> {
+        let token: Token = Client::send(self.request(Method::POST, "/login").json(&credentials))?;
+        self.token = Some(token.token);
+
+        Ok(())
+    }
+
+    pub fn logout(&mut self) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::POST, "/logout"))?;
+        self.token = None;
+
+        Ok(())
+    }
+
+    /// The URL of the organization's identity provider to send the user to,
+    /// who comes back to the provider's redirect URI with a code and state.
+    pub fn begin_sso(&self, org_id: uuid::Uuid) -> Result<String, Error> {
+        let authorization: SsoAuthorization = Client::send(self.request(Method::GET, &format!("/sso/{}", org_id)))?;
+
+        Ok(authorization.authorization_url)
+    }
+
+    /// Opens a session given the code and state the user came back with.
+    pub fn complete_sso(&mut self, code: &str, state: &str) -> Result<(), Error> {
+        let token: Token = Client::send(self.request(Method::POST, "/sso/login").json(&SsoCallback { code, state }))?;
+        self.token = Some(token.token);
+
+        Ok(())
+    }
+
+    /// Draws a TOTP secret, enabled by `confirm_two_factor`.
+    pub fn begin_two_factor(&self) -> Result<TwoFactorEnrollment, Error> {
+        Client::send(self.request(Method::POST, "/two_factor"))
+    }
+
+    /// Enables two-factor authentication given a first code, returning the
+    /// recovery codes.
+    pub fn confirm_two_factor(&self, code: &str) -> Result<Vec<String>, Error> {
+        let codes: RecoveryCodes = Client::send(self.request(Method::POST, "/two_factor/confirm").json(&TwoFactorCode { code }))?;
+
+        Ok(codes.recovery_codes)
+    }
+
+    pub fn regenerate_recovery_codes(&self, code: &str) -> Result<Vec<String>, Error> {
+        let builder = self.request(Method::POST, "/two_factor/recovery_codes").json(&TwoFactorCode { code });
+        let codes: RecoveryCodes = Client::send(builder)?;
+
+        Ok(codes.recovery_codes)
+    }
+
+    /// Turns two-factor authentication off given a code of the authenticator
+    /// app or a recovery code.
+    pub fn disable_two_factor(&self, code: &str) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, "/two_factor").json(&TwoFactorCode { code }))
+    }
+
+    pub fn get_organisation_info(&self) -> Result<OrganizationResponse, Error> {
+        Client::send(self.request(Method::GET, "/organisation"))
+    }
+
+    pub fn modify_organisation_info(&self, if_match: Option<i32>, patch: &OrganizationPatch) -> Result<OrganizationResponse, Error> {
+        Client::send(Client::if_match(self.request(Method::PATCH, "/organisation"), if_match).json(patch))
+    }
+
+    pub fn get_sso_config(&self) -> Result<OidcProviderResponse, Error> {
+        Client::send(self.request(Method::GET, "/organisation/sso"))
+    }
+
+    /// Sets the identity provider of the organization, replacing the
+    /// previous one.
+    pub fn set_sso_config(&self, fields: &NewOidcProvider) -> Result<OidcProviderResponse, Error> {
+        Client::send(self.request(Method::PUT, "/organisation/sso").json(fields))
+    }
+
+    pub fn delete_sso_config(&self) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, "/organisation/sso"))
+    }
+
+    pub fn create_coordinate(&self, fields: &NewCoordinate) -> Result<uuid::Uuid, Error> {
+        let created: Created = Client::send(self.request(Method::POST, "/coordinates").json(fields))?;
+
+        Ok(created.id)
+    }
+
+    pub fn modify_coordinate(
+        &self,
+        coordinates_id: uuid::Uuid,
+        if_match: Option<i32>,
+        patch: &CoordinatePatch
+    ) -> Result<CoordinateResponse, Error> {
+        let path = format!("/coordinates/{}", coordinates_id);
+
+        Client::send(Client::if_match(self.request(Method::PATCH, &path), if_match).json(patch))
+    }
+
+    pub fn get_users(&self, filter: &UserFilter, query: &ListQuery) -> Result<Page<UserResponse>, Error> {
+        Client::send(self.request(Method::GET, "/users").query(filter).query(query))
+    }
+
+    pub fn create_new_user(&self, fields: &NewUser) -> Result<uuid::Uuid, Error> {
+        let created: Created = Client::send(self.request(Method::POST, "/users").json(fields))?;
+
+        Ok(created.id)
+    }
+
+    /// Lets a user of the admin's organization log in again right away after
+    /// too many failed logins.
+    pub fn unlock_user(&self, user_id: uuid::Uuid) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::POST, &format!("/users/{}/unlock", user_id)))
+    }
+
+    /// Deactivates a user of the admin's organization, which fails while the
+    /// user is the respondant of buildings.
+    pub fn deactivate_user(&self, user_id: uuid::Uuid) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::POST, &format!("/users/{}/deactivate", user_id)))
+    }
+
+    /// Makes another user the respondant of a user's buildings, and moves
+    /// the mentions of the user in notes to them.
+    pub fn hand_over_user(&self, user_id: uuid::Uuid, fields: &Handover) -> Result<HandoverReport, Error> {
+        Client::send(self.request(Method::POST, &format!("/users/{}/handover", user_id)).json(fields))
+    }
+
+    /// Lists the open sessions of a user of the admin's organization.
+    pub fn get_user_sessions(&self, user_id: uuid::Uuid) -> Result<Vec<SessionResponse>, Error> {
+        Client::send(self.request(Method::GET, &format!("/users/{}/sessions", user_id)))
+    }
+
+    /// Logs a user of the admin's organization out everywhere.
+    pub fn revoke_user_sessions(&self, user_id: uuid::Uuid) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, &format!("/users/{}/sessions", user_id)))
+    }
+
+    pub fn revoke_user_session(&self, user_id: uuid::Uuid, session_id: uuid::Uuid) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, &format!("/users/{}/sessions/{}", user_id, session_id)))
+    }
+
+    pub fn get_api_keys(&self) -> Result<Vec<ApiKeyResponse>, Error> {
+        Client::send(self.request(Method::GET, "/api_keys"))
+    }
+
+    /// Creates an API key, whose token is only returned here.
+    pub fn create_api_key(&self, fields: &NewApiKey) -> Result<CreatedApiKey, Error> {
+        Client::send(self.request(Method::POST, "/api_keys").json(fields))
+    }
+
+    pub fn revoke_api_key(&self, key_id: uuid::Uuid) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, &format!("/api_keys/{}", key_id)))
+    }
+
+    pub fn get_user_profile(&self) -> Result<UserResponse, Error> {
+        Client::send(self.request(Method::GET, "/profile"))
+    }
+
+    /// Changes the profile. A new email is only `pending_email` until
+    /// confirmed with the token mailed to it.
+    pub fn modify_user_profile(&self, if_match: Option<i32>, patch: &UserPatch) -> Result<UserResponse, Error> {
+        Client::send(Client::if_match(self.request(Method::PATCH, "/profile"), if_match).json(patch))
+    }
+
+    /// Changes the password, closing every session of the user, this
+    /// client's included.
+    pub fn change_password(&self, fields: &PasswordChange) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::POST, "/profile/password").json(fields))
+    }
+
+    pub fn get_sessions(&self) -> Result<Vec<SessionResponse>, Error> {
+        Client::send(self.request(Method::GET, "/profile/sessions"))
+    }
+
+    /// Closes the user's sessions but the one of this client.
+    pub fn revoke_other_sessions(&self) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, "/profile/sessions"))
+    }
+
+    pub fn revoke_session(&self, session_id: uuid::Uuid) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, &format!("/profile/sessions/{}", session_id)))
+    }
+
+    /// Mails the link to verify the user's email again, or the pending one.
+    pub fn resend_email_verification(&self) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::POST, "/profile/email_verification"))
+    }
+
+    /// Verifies an email with the token of the link mailed to it, no session
+    /// needed.
+    pub fn verify_email(&self, token: &str) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::POST, "/verify_email").json(&EmailVerificationToken { token }))
+    }
+
+    pub fn get_owners(&self, filter: &OwnerFilter, query: &ListQuery) -> Result<Page<BuildingOwnerResponse>, Error> {
+        Client::send(self.request(Method::GET, "/owners").query(filter).query(query))
+    }
+
+    pub fn create_new_owner(&self, fields: &NewBuildingOwner) -> Result<uuid::Uuid, Error> {
+        let created: Created = Client::send(self.request(Method::POST, "/owners").json(fields))?;
+
+        Ok(created.id)
+    }
+
+    pub fn get_owner_info(&self, owner_id: uuid::Uuid) -> Result<OwnerInfo, Error> {
+        Client::send(self.request(Method::GET, &format!("/owners/{}", owner_id)))
+    }
+
+    pub fn modify_owner_info(
+        &self,
+        owner_id: uuid::Uuid,
+        if_match: Option<i32>,
+        patch: &BuildingOwnerPatch
+    ) -> Result<BuildingOwnerResponse, Error> {
+        let path = format!("/owners/{}", owner_id);
+
+        Client::send(Client::if_match(self.request(Method::PATCH, &path), if_match).json(patch))
+    }
+
+    pub fn get_buildings(&self, filter: &BuildingFilter, query: &ListQuery) -> Result<Page<BuildingResponse>, Error> {
+        Client::send(self.request(Method::GET, "/buildings").query(filter).query(query))
+    }
+
+    pub fn create_new_building(&self, fields: &NewBuilding) -> Result<uuid::Uuid, Error> {
+        let created: Created = Client::send(self.request(Method::POST, "/buildings").json(fields))?;
+
+        Ok(created.id)
+    }
+
+    pub fn get_building_information(&self, building_id: uuid::Uuid) -> Result<BuildingInfo, Error> {
+        Client::send(self.request(Method::GET, &format!("/buildings/{}", building_id)))
+    }
+
+    pub fn modify_building(
+        &self,
+        building_id: uuid::Uuid,
+        if_match: Option<i32>,
+        patch: &BuildingPatch
+    ) -> Result<BuildingResponse, Error> {
+        let path = format!("/buildings/{}", building_id);
+
+        Client::send(Client::if_match(self.request(Method::PATCH, &path), if_match).json(patch))
+    }
+
+    /// Downloads the printable PDF dossier of a building.
+    pub fn get_building_dossier(&self, building_id: uuid::Uuid) -> Result<Vec<u8>, Error> {
+        Client::download(self.request(Method::GET, &format!("/buildings/{}/dossier", building_id)))
+    }
+
+    pub fn get_registers(&self, filter: &RegisterFilter, query: &ListQuery) -> Result<Page<RegisterResponse>, Error> {
+        Client::send(self.request(Method::GET, "/registers").query(filter).query(query))
+    }
+
+    pub fn create_new_register(&self, fields: &NewRegister) -> Result<uuid::Uuid, Error> {
+        let created: Created = Client::send(self.request(Method::POST, "/registers").json(fields))?;
+
+        Ok(created.id)
+    }
+
+    pub fn modify_register(
+        &self,
+        register_id: uuid::Uuid,
+        if_match: Option<i32>,
+        patch: &RegisterPatch
+    ) -> Result<RegisterResponse, Error> {
+        let path = format!("/registers/{}", register_id);
+
+        Client::send(Client::if_match(self.request(Method::PATCH, &path), if_match).json(patch))
+    }
+
+    /// Imports buildings and their owners from a file. The report lists the
+    /// errors of every row, nothing being saved if there is any.
+    pub fn import_buildings(&self, format: ImportFormat, data: Vec<u8>, dry_run: bool) -> Result<ImportReport, Error> {
+        let builder = self.request(Method::POST, "/imports")
+            .query(&ImportQuery { format, dry_run })
+            .header(header::CONTENT_TYPE, format.media_type())
+            .body(data);
+
+        Client::send(builder)
+    }
+
+    /// Downloads the organization's buildings, or those of one owner, as a
+    /// file of the format.
+    pub fn export_buildings(&self, format: ExportFormat, owner_id: Option<uuid::Uuid>) -> Result<Vec<u8>, Error> {
+        Client::download(self.request(Method::GET, "/exports/buildings").query(&ExportQuery { format, owner_id }))
+    }
+
+    /// Exports the buildings as JSON rows.
+    pub fn export_buildings_rows(&self, owner_id: Option<uuid::Uuid>) -> Result<Vec<ExportRow>, Error> {
+        let query = ExportQuery { format: ExportFormat::Json, owner_id };
+
+        Client::send(self.request(Method::GET, "/exports/buildings").query(&query))
+    }
+
+    pub fn get_entity_files(&self, entity_id: uuid::Uuid) -> Result<EntityFiles, Error> {
+        Client::send(self.request(Method::GET, &format!("/entities/{}/files", entity_id)))
+    }
+
+    pub fn create_note_on_file(&self, entity_id: uuid::Uuid, file_id: uuid::Uuid, note: &str) -> Result<(), Error> {
+        let path = format!("/entities/{}/files/{}/notes", entity_id, file_id);
+
+        Client::send_empty(self.request(Method::POST, &path).json(&NoteText { note }))
+    }
+
+    pub fn get_entity_notes(&self, entity_id: uuid::Uuid, page: i64, page_size: i64) -> Result<Vec<EntityNoteInfo>, Error> {
+        let path = format!("/entities/{}/notes", entity_id);
+
+        Client::send(self.request(Method::GET, &path).query(&NotesQuery { page, page_size }))
+    }
+
+    pub fn create_entity_note(&self, entity_id: uuid::Uuid, note: &str) -> Result<uuid::Uuid, Error> {
+        let path = format!("/entities/{}/notes", entity_id);
+        let created: Created = Client::send(self.request(Method::POST, &path).json(&NoteText { note }))?;
+
+        Ok(created.id)
+    }
+
+    pub fn reply_to_entity_note(&self, note_id: uuid::Uuid, note: &str) -> Result<uuid::Uuid, Error> {
+        let path = format!("/notes/{}/replies", note_id);
+        let created: Created = Client::send(self.request(Method::POST, &path).json(&NoteText { note }))?;
+
+        Ok(created.id)
+    }
+
+    pub fn edit_entity_note(&self, note_id: uuid::Uuid, note: &str) -> Result<(), Error> {
+        let path = format!("/notes/{}", note_id);
+
+        Client::send_empty(self.request(Method::PATCH, &path).json(&NoteText { note }))
+    }
+
+    pub fn delete_entity_note(&self, note_id: uuid::Uuid) -> Result<(), Error> {
+        Client::send_empty(self.request(Method::DELETE, &format!("/notes/{}", note_id)))
+    }
+
+    pub fn get_entity_note_edits(&self, note_id: uuid::Uuid) -> Result<Vec<NoteEditInfo>, Error> {
+        Client::send(self.request(Method::GET, &format!("/notes/{}/edits", note_id)))
+    }
+
+    pub fn get_owner_buildings(&self) -> Result<Vec<OwnerBuildingInfo>, Error> {
+        Client::send(self.request(Method::GET, "/portal/buildings"))
+    }
+
+    pub fn get_owner_building(&self, building_id: uuid::Uuid) -> Result<OwnerBuildingInfo, Error> {
+        Client::send(self.request(Method::GET, &format!("/portal/buildings/{}", building_id)))
+    }
+
+    pub fn get_shared_folders(&self, building_id: uuid::Uuid) -> Result<Vec<String>, Error> {
+        Client::send(self.request(Method::GET, &format!("/buildings/{}/shared_folders", building_id)))
+    }
+
+    pub fn share_folder_with_owner(&self, building_id: uuid::Uuid, folder: &str) -> Result<(), Error> {
+        let path = format!("/buildings/{}/shared_folders", building_id);
+
+        Client::send_empty(self.request(Method::POST, &path).json(&Folder { folder }))
+    }
+
+    pub fn unshare_folder_with_owner(&self, building_id: uuid::Uuid, folder: &str) -> Result<(), Error> {
+        let path = format!("/buildings/{}/shared_folders", building_id);
+
+        Client::send_empty(self.request(Method::DELETE, &path).query(&Folder { folder }))
+    }
+}
@@ -0,0 +1,107 @@
+use reqwest::StatusCode;
+
+use serde_derive::Deserialize;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// An error returned by the API, or a failure to reach it. The `String`s
+/// hold the `error` message of the response.
+#[derive(Debug)]
+pub enum Error {
+    /// Missing, closed or expired session token.
+    Unauthorized(String),
+    NotFound(String),
+    Forbidden(String),
+    /// The entity was saved by someone else since the `If-Match` version.
+    Conflict(String),
+    /// The request contradicts the stored data.
+    InvalidInput(String),
+    /// Maps each invalid field to the codes of the validations it failed.
+    Validation(String, BTreeMap<String, Vec<String>>),
+    /// Too many failed logins from the account or the address.
+    TooManyRequests(String),
+    Server(String),
+    Unavailable(String),
+    UnexpectedStatus(StatusCode, String),
+    Http(reqwest::Error),
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: String,
+    #[serde(default)]
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+impl Error {
+    /// Builds the error of a failed response from its status and body.
+    pub(crate) fn from_response(status: StatusCode, body: &str) -> Error {
+        let ErrorBody { error, fields } = serde_json::from_str(body)
+            .unwrap_or_else(|_| ErrorBody { error: body.to_owned(), fields: BTreeMap::new() });
+
+        match status {
+            StatusCode::UNAUTHORIZED => Error::Unauthorized(error),
+            StatusCode::NOT_FOUND => Error::NotFound(error),
+            StatusCode::FORBIDDEN => Error::Forbidden(error),
+            StatusCode::CONFLICT => Error::Conflict(error),
+            StatusCode::BAD_REQUEST => Error::InvalidInput(error),
+            StatusCode::UNPROCESSABLE_ENTITY => Error::Validation(error, fields),
+            StatusCode::TOO_MANY_REQUESTS => Error::TooManyRequests(error),
+            StatusCode::INTERNAL_SERVER_ERROR => Error::Server(error),
+            StatusCode::SERVICE_UNAVAILABLE => Error::Unavailable(error),
+            _ => Error::UnexpectedStatus(status, error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unauthorized(msg)
+            | Error::NotFound(msg)
+            | Error::Forbidden(msg)
+            | Error::Conflict(msg)
+            | Error::InvalidInput(msg)
+            | Error::Validation(msg, _)
+            | Error::TooManyRequests(msg)
+            | Error::Server(msg)
+            | Error::Unavailable(msg) => write!(f, "{}", msg),
+            Error::UnexpectedStatus(status, msg) => write!(f, "Unexpected status {}: {}", status, msg),
+            Error::Http(e) => write!(f, "HTTP error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_from_response() {
+        match Error::from_response(StatusCode::CONFLICT, r#"{"error": "Modified since it was loaded"}"#) {
+            Error::Conflict(msg) => assert_eq!(msg, "Modified since it was loaded"),
+            other => panic!("Expected a conflict, got {:?}", other),
+        }
+
+        let body = r#"{"error": "Invalid fields", "fields": {"email": ["email"]}}"#;
+        match Error::from_response(StatusCode::UNPROCESSABLE_ENTITY, body) {
+            Error::Validation(_, fields) => assert_eq!(fields["email"], vec!["email"]),
+            other => panic!("Expected a validation error, got {:?}", other),
+        }
+
+        match Error::from_response(StatusCode::BAD_GATEWAY, "Bad gateway") {
+            Error::UnexpectedStatus(status, msg) => assert_eq!((status, msg.as_str()), (StatusCode::BAD_GATEWAY, "Bad gateway")),
+            other => panic!("Expected an unexpected status, got {:?}", other),
+        }
+    }
+}
@@ -1,7 +1,15 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-}
+//! Typed client of the bep HTTP API described in `API.md`.
+//!
+//! ```no_run
+//! let mut client = bep_api::Client::new("http://127.0.0.1:8080");
+//! client.login("admin@example.com", "password").unwrap();
+//! let profile = client.get_user_profile().unwrap();
+//! ```
+
+pub mod client;
+pub mod error;
+pub mod types;
+
+pub use self::client::Client;
+pub use self::error::Error;
+pub use self::types::*;
@@ -0,0 +1,646 @@
+use chrono::NaiveDateTime;
+
+use serde_derive::{Deserialize, Serialize};
+
+pub const PERMISSION_MEMBER: i16 = 1;
+pub const PERMISSION_ADMIN: i16 = 2;
+pub const PERMISSION_OWNER: i16 = 3;
+
+pub const OWNER_KIND_INDIVIDUAL: i16 = 0;
+pub const OWNER_KIND_COMPANY: i16 = 1;
+pub const OWNER_KIND_MANAGER: i16 = 2;
+
+/// Scopes of an API key.
+pub const SCOPE_READ_BUILDINGS: &str = "read_buildings";
+pub const SCOPE_WRITE_REGISTERS: &str = "write_registers";
+pub const SCOPE_UPLOAD_FILES: &str = "upload_files";
+
+/// Paging parameters of a list operation. `sort` names a sort field of the
+/// listing, prefixed with `-` for a descending order.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ListQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// The query for the page following this one, `None` on the last page.
+    pub fn next_query(&self, query: &ListQuery) -> Option<ListQuery> {
+        self.next_cursor.as_ref().map(|cursor| ListQuery { cursor: Some(cursor.clone()), ..query.clone() })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Created {
+    pub id: uuid::Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Registered {
+    pub user_id: uuid::Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Token {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SsoAuthorization {
+    pub authorization_url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SsoCallback<'a> {
+    pub code: &'a str,
+    pub state: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct EmailVerificationToken<'a> {
+    pub token: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Credentials<'a> {
+    pub email: &'a str,
+    pub password: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TwoFactorCode<'a> {
+    pub code: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct NoteText<'a> {
+    pub note: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Folder<'a> {
+    pub folder: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct NotesQuery {
+    pub page: i64,
+    pub page_size: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ImportQuery {
+    pub format: ImportFormat,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ExportQuery {
+    pub format: ExportFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewOrganization {
+    pub org_name: String,
+    pub admin: NewUser,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct OrganizationPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_name: Option<String>,
+    /// `Some(None)` clears the coordinates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coordinates_id: Option<Option<uuid::Uuid>>,
+    /// Whether every member must log in with a second factor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_two_factor: Option<bool>,
+    /// At least 8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_min_length: Option<i16>,
+    /// Estimated bits of entropy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_min_entropy: Option<i16>,
+    /// Whether passwords may not contain part of their user's name or email.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_reject_personal_info: Option<bool>,
+}
+
+/// The TOTP secret to add to an authenticator app, by hand or by scanning
+/// the QR code of the provisioning URI.
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+    pub qr_code_svg: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrganizationResponse {
+    pub id: uuid::Uuid,
+    pub org_name: String,
+    pub coordinates_id: Option<uuid::Uuid>,
+    pub require_two_factor: bool,
+    pub password_min_length: i16,
+    pub password_min_entropy: i16,
+    pub password_reject_personal_info: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+/// The OpenID Connect provider the members of an organization sign in with.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewOidcProvider {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    /// Where the provider sends the user back with a code and a state, to
+    /// pass on to `Client::complete_sso`.
+    pub redirect_uri: String,
+    /// The ID token claim listing the user's groups, usually `groups`.
+    pub groups_claim: String,
+    pub admin_groups: Vec<String>,
+    /// Any user of the provider is a member when empty.
+    pub member_groups: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcProviderResponse {
+    pub issuer: String,
+    pub client_id: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub redirect_uri: String,
+    pub groups_claim: String,
+    pub admin_groups: Vec<String>,
+    pub member_groups: Vec<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewUser {
+    pub full_name: String,
+    pub email: String,
+    pub password: String,
+    pub job_title: String,
+    pub profile_picture: String,
+    /// `member` when left out, or `owner` for the account of a building
+    /// owner. Only read by `create_new_user`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+/// A new password, which must follow the organization's password policy.
+/// Broken rules come as the `length`, `entropy`, `personal_info` and
+/// `compromised` codes of `Error::Validation` for `new_password`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordChange {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct UserPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_picture: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct UserFilter {
+    /// `member`, `admin` or `owner`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserResponse {
+    pub id: uuid::Uuid,
+    pub org_id: uuid::Uuid,
+    pub permission: i16,
+    pub full_name: String,
+    pub email: String,
+    pub job_title: String,
+    pub profile_picture: String,
+    pub email_verified: bool,
+    /// The address the user is changing to, until they confirm it. Only
+    /// given by `Client::get_user_profile` and `Client::modify_user_profile`.
+    pub pending_email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewApiKey {
+    pub name: String,
+    /// Some of the `SCOPE_*` constants.
+    pub scopes: Vec<String>,
+    /// Never expires when `None`.
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_by: uuid::Uuid,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+/// The user taking over from a user who leaves.
+#[derive(Debug, Clone, Serialize)]
+pub struct Handover {
+    pub successor_id: uuid::Uuid,
+}
+
+/// What a handover moved to the successor.
+#[derive(Debug, Deserialize)]
+pub struct HandoverReport {
+    /// Buildings the successor is now the respondant of.
+    pub buildings: usize,
+    /// Mentions in notes moved to the successor.
+    pub mentions: usize,
+}
+
+/// An open session of a user, where it was opened from and when it was
+/// last used.
+#[derive(Debug, Deserialize)]
+pub struct SessionResponse {
+    pub id: uuid::Uuid,
+    /// The browser and system of the user agent, such as `Firefox on Windows`.
+    pub device: String,
+    pub ip_address: String,
+    pub user_agent: String,
+    /// Whether this is the session of the client listing it.
+    pub current: bool,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// A new API key and its token, which the API only returns once.
+#[derive(Debug, Deserialize)]
+pub struct CreatedApiKey {
+    pub id: uuid::Uuid,
+    pub key: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct NewCoordinate {
+    pub address: Option<String>,
+    pub telephone_no: Option<String>,
+    pub fax: Option<String>,
+    pub cellphone_no: Option<String>,
+    pub email: Option<String>,
+    pub company_name: Option<String>,
+    pub company_number: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CoordinatePatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telephone_no: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fax: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cellphone_no: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub company_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub company_number: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CoordinateResponse {
+    pub id: uuid::Uuid,
+    pub address: String,
+    pub telephone_no: String,
+    pub fax: String,
+    pub cellphone_no: String,
+    pub email: String,
+    pub company_name: String,
+    pub company_number: String,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewBuildingOwner {
+    pub full_name: String,
+    pub same_as_manager: bool,
+    pub kind: i16,
+    pub linked_user_id: Option<uuid::Uuid>,
+    pub coordinates_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BuildingOwnerPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub same_as_manager: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<i16>,
+    /// `Some(None)` unlinks the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_user_id: Option<Option<uuid::Uuid>>,
+    /// `Some(None)` clears the coordinates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coordinates_id: Option<Option<uuid::Uuid>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct OwnerFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<i16>,
+    /// Part of the name, case insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuildingOwnerResponse {
+    pub id: uuid::Uuid,
+    pub full_name: String,
+    pub kind: i16,
+    pub is_manager: bool,
+    pub org_id: Option<uuid::Uuid>,
+    pub linked_user_id: Option<uuid::Uuid>,
+    pub coordinates_id: Option<uuid::Uuid>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewBuilding {
+    pub owner_id: uuid::Uuid,
+    pub respondant_id: uuid::Uuid,
+    pub name: String,
+    pub address: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BuildingPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respondant_id: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BuildingFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respondant_id: Option<uuid::Uuid>,
+    /// Part of the address, case insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuildingResponse {
+    pub id: uuid::Uuid,
+    pub owner_id: uuid::Uuid,
+    pub org_id: uuid::Uuid,
+    pub respondant_id: uuid::Uuid,
+    pub name: String,
+    pub address: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewRegister {
+    pub building_id: uuid::Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RegisterPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RegisterFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub building_id: Option<uuid::Uuid>,
+    /// Part of the name, case insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub building_id: uuid::Uuid,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub version: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuildingInfo {
+    #[serde(flatten)]
+    pub building: BuildingResponse,
+    pub registers: Vec<RegisterResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OwnerInfo {
+    pub profile: BuildingOwnerResponse,
+    pub coordinates: Option<CoordinateResponse>,
+    pub buildings: Vec<BuildingInfo>,
+    pub users: Vec<UserResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FileNoteInfo {
+    pub note_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub user_name: String,
+    /// Who took over from the author, once deactivated.
+    pub handed_over_to: Option<uuid::Uuid>,
+    pub date: NaiveDateTime,
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityFileInfo {
+    pub file_id: uuid::Uuid,
+    pub filename: String,
+    pub notes: Vec<FileNoteInfo>,
+    pub metadata: serde_json::Value,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityInfo {
+    pub date: NaiveDateTime,
+    pub action_id: i16,
+    pub file_id: uuid::Uuid,
+    pub filename: String,
+    pub url: String,
+    pub user_id: uuid::Uuid,
+    pub user_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityFiles {
+    pub files: Vec<EntityFileInfo>,
+    pub activity_feed: Vec<ActivityInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityNoteInfo {
+    pub user_name: String,
+    pub user_id: uuid::Uuid,
+    /// Who took over from the author, once deactivated.
+    pub handed_over_to: Option<uuid::Uuid>,
+    pub date: NaiveDateTime,
+    pub note: String,
+    pub note_id: uuid::Uuid,
+    pub edited: bool,
+    pub mentions: Vec<uuid::Uuid>,
+    pub replies: Vec<EntityNoteInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoteEditInfo {
+    pub user_name: String,
+    pub user_id: uuid::Uuid,
+    pub date: NaiveDateTime,
+    pub previous_note: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SharedFileInfo {
+    pub file_id: uuid::Uuid,
+    pub entity_id: uuid::Uuid,
+    pub folder: String,
+    pub filename: String,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OwnerRegisterInfo {
+    pub register_id: uuid::Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OwnerBuildingInfo {
+    pub building_id: uuid::Uuid,
+    pub name: String,
+    pub address: String,
+    pub registers: Vec<OwnerRegisterInfo>,
+    pub files: Vec<SharedFileInfo>,
+}
+
+/// Format of an import file.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ImportFormat {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ImportFormat::Csv => "text/csv",
+            ImportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RowError {
+    pub row: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub committed: bool,
+    pub rows: usize,
+    pub buildings_created: usize,
+    pub owners_created: usize,
+    pub owners_reused: usize,
+    pub coordinates_created: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Format of an export file.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+    Json,
+}
+
+/// A building of the portfolio, as exported in JSON.
+#[derive(Debug, Deserialize)]
+pub struct ExportRow {
+    pub building_id: uuid::Uuid,
+    pub building_name: String,
+    pub address: String,
+    pub owner_name: String,
+    pub owner_kind: String,
+    pub owner_company_name: String,
+    pub owner_company_number: String,
+    pub owner_address: String,
+    pub owner_telephone_no: String,
+    pub owner_email: String,
+    pub respondant_name: String,
+    pub respondant_email: String,
+    pub registers: usize,
+    pub register_names: Vec<String>,
+    pub last_activity: Option<NaiveDateTime>,
+}
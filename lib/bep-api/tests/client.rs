@@ -0,0 +1,985 @@
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate lazy_static;
+
+use actix_web::{http::header, web, App, HttpResponse, HttpServer};
+
+use bep_api::*;
+
+use diesel::{Connection, PgConnection, RunQueryDsl};
+
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+const PASSWORD: &str = "correct horse battery";
+
+lazy_static! {
+    /// Base URL of a server started in-process, shared by the tests.
+    static ref BASE_URL: String = start_server();
+    /// The schema of this run in the test database, which the server uses.
+    static ref DATABASE: bep::config::DatabaseConfig = throwaway_database(&test_database_url().unwrap());
+    /// Where the server writes the emails it sends.
+    static ref MAIL_DIR: PathBuf = std::env::temp_dir().join(format!("bep-api-tests-{}", uuid::Uuid::new_v4()));
+    /// Base URL of a mock OpenID Connect provider, shared by the tests.
+    static ref MOCK_IDP: String = start_mock_idp();
+    /// The user the mock provider signs in.
+    static ref MOCK_USER: Mutex<MockUser> = Mutex::new(MockUser::default());
+    /// The sign-ins of the mock provider by code: its user, nonce and PKCE
+    /// challenge.
+    static ref MOCK_CODES: Mutex<HashMap<String, (MockUser, String, String)>> = Mutex::new(HashMap::new());
+}
+
+/// The claims of a user of the mock provider.
+#[derive(Clone, Default)]
+struct MockUser {
+    subject: String,
+    email: String,
+    /// Left out of the ID token when `None`, as some providers do.
+    email_verified: Option<bool>,
+    groups: Vec<String>,
+}
+
+/// Names the database the tests run in. It must not be the database of the
+/// server: the tests create schemas and drop those of earlier runs.
+const TEST_DATABASE_VAR: &str = "BEP_TEST_DATABASE_URL";
+
+/// Prefix of the schemas the tests run in.
+const SCHEMA_PREFIX: &str = "bep_api_tests_";
+/// Schemas left by runs older than this many seconds are dropped.
+const STALE_SCHEMA_SECONDS: i64 = 24 * 60 * 60;
+
+#[derive(QueryableByName)]
+struct SchemaName {
+    #[sql_type = "diesel::sql_types::Text"]
+    nspname: String,
+}
+
+#[derive(QueryableByName)]
+struct Outcome {
+    #[sql_type = "diesel::sql_types::Text"]
+    outcome: String,
+}
+
+/// The URL of `TEST_DATABASE_VAR`, refused if it is the `DATABASE_URL` of
+/// the server.
+fn test_database_url() -> Option<String> {
+    let url = std::env::var(TEST_DATABASE_VAR).ok().filter(|url| !url.is_empty())?;
+    assert_ne!(std::env::var("DATABASE_URL").ok().as_ref(), Some(&url), "{} must not be the DATABASE_URL of the server", TEST_DATABASE_VAR);
+
+    Some(url)
+}
+
+/// Whether to skip a test, for lack of a database to run it in.
+fn skipped() -> bool {
+    let skipped = test_database_url().is_none();
+    if skipped {
+        eprintln!("Skipped: {} is not set", TEST_DATABASE_VAR);
+    }
+
+    skipped
+}
+
+/// Creates a schema of its own for this run in the test database, migrated
+/// from scratch, and returns the database configuration using it. No hook
+/// runs once all the tests are done, so the schemas of earlier runs are
+/// dropped instead.
+fn throwaway_database(database_url: &str) -> bep::config::DatabaseConfig {
+    let conn = PgConnection::establish(database_url).unwrap();
+    let now = chrono::Utc::now().timestamp();
+
+    let schemas = diesel::sql_query(format!("SELECT nspname FROM pg_namespace WHERE nspname LIKE '{}%'", SCHEMA_PREFIX))
+        .load::<SchemaName>(&conn)
+        .unwrap();
+    for schema in schemas {
+        let created_at = schema.nspname[SCHEMA_PREFIX.len()..].split('_').next().and_then(|t| t.parse::<i64>().ok());
+        if created_at.map_or(false, |created_at| now - created_at > STALE_SCHEMA_SECONDS) {
+            diesel::sql_query(format!("DROP SCHEMA \"{}\" CASCADE", schema.nspname)).execute(&conn).unwrap();
+        }
+    }
+
+    let schema = format!("{}{}_{}", SCHEMA_PREFIX, now, uuid::Uuid::new_v4().to_string().replace("-", ""));
+    diesel::sql_query(format!("CREATE SCHEMA \"{}\"", schema)).execute(&conn).unwrap();
+
+    // Extensions such as uuid-ossp stay in the public schema
+    let separator = if database_url.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}options=-c%20search_path%3D{}%2Cpublic", database_url, separator, schema);
+    bep::db::migrations::run_pending(&PgConnection::establish(&url).unwrap()).unwrap();
+
+    bep::config::DatabaseConfig { url, ..bep::config::DatabaseConfig::default() }
+}
+
+/// The outcomes of the logins recorded for an email, oldest first.
+fn login_outcomes(email: &str) -> Vec<String> {
+    let conn = PgConnection::establish(&DATABASE.url).unwrap();
+
+    diesel::sql_query("SELECT outcome FROM login_attempts WHERE email = $1 ORDER BY created_at")
+        .bind::<diesel::sql_types::Text, _>(email)
+        .load::<Outcome>(&conn)
+        .unwrap()
+        .into_iter()
+        .map(|o| o.outcome)
+        .collect()
+}
+
+fn start_server() -> String {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let sys = actix_rt::System::new("bep-api-tests");
+        let mut config = bep::config::Config::default();
+        config.database = DATABASE.clone();
+        let pool = bep::db::connection::establish_pool(&config.database);
+        let transport = Arc::new(bep::mail::FileTransport::new(MAIL_DIR.clone()));
+        let mailer = bep::mail::Mailer::new(
+            transport,
+            "BEP <bep@example.com>",
+            "https://bep.example.com",
+            config.tokens.email_verification_hours
+        );
+        let server = HttpServer::new(move || App::new()
+                .data(pool.clone())
+                .data(mailer.clone())
+                .data(config.clone())
+                .configure(|cfg| bep::routes::config(cfg, &config)))
+            .bind("127.0.0.1:0")
+            .unwrap();
+
+        tx.send(server.addrs()[0]).unwrap();
+        server.start();
+        sys.run()
+    });
+
+    format!("http://{}", rx.recv().unwrap())
+}
+
+/// The token of the last link mailed to an address.
+fn mailed_token(email: &str) -> String {
+    let mut mails: Vec<(std::time::SystemTime, String)> = fs::read_dir(&*MAIL_DIR).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .map(|path| (fs::metadata(&path).unwrap().modified().unwrap(), fs::read_to_string(&path).unwrap()))
+        .filter(|(_, mail)| mail.contains(&format!("\r\nTo: {}\r\n", email)) && mail.contains("token="))
+        .collect();
+    mails.sort();
+    let mail = &mails.last().expect("No link mailed").1;
+
+    let start = mail.find("token=").unwrap() + "token=".len();
+    mail[start..].split_whitespace().next().unwrap().to_owned()
+}
+
+/// Signs `MOCK_USER` in right away and sends it back with a code.
+fn mock_authorize(query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let code = uuid::Uuid::new_v4().to_string();
+    let user = MOCK_USER.lock().unwrap().clone();
+    MOCK_CODES.lock().unwrap().insert(code.clone(), (user, query["nonce"].clone(), query["code_challenge"].clone()));
+
+    let mut location = reqwest::Url::parse(&query["redirect_uri"]).unwrap();
+    location.query_pairs_mut().append_pair("code", &code).append_pair("state", &query["state"]);
+    HttpResponse::Found().header(header::LOCATION, location.into_string()).finish()
+}
+
+/// Trades a code for an unsigned ID token, once the code verifier matches
+/// the challenge.
+fn mock_token(form: web::Form<HashMap<String, String>>) -> HttpResponse {
+    let (user, nonce, challenge) = match MOCK_CODES.lock().unwrap().remove(&form["code"]) {
+        Some(sign_in) => sign_in,
+        None => return HttpResponse::BadRequest().json(serde_json::json!({"error": "invalid_grant"})),
+    };
+    let verified = base64::encode_config(&Sha256::digest(form["code_verifier"].as_bytes()), base64::URL_SAFE_NO_PAD);
+    if verified != challenge {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "invalid_grant"}));
+    }
+
+    let encode = |value: serde_json::Value| base64::encode_config(&value.to_string(), base64::URL_SAFE_NO_PAD);
+    let claims = serde_json::json!({
+        "iss": *MOCK_IDP,
+        "aud": form["client_id"],
+        "exp": chrono::Utc::now().timestamp() + 300,
+        "nonce": nonce,
+        "sub": user.subject,
+        "email": user.email,
+        "email_verified": user.email_verified,
+        "name": "Employé SSO",
+        "groups": user.groups,
+    });
+    let id_token = format!("{}.{}.", encode(serde_json::json!({"alg": "none"})), encode(claims));
+
+    HttpResponse::Ok().json(serde_json::json!({"access_token": "unused", "token_type": "Bearer", "id_token": id_token}))
+}
+
+fn start_mock_idp() -> String {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let sys = actix_rt::System::new("mock-idp");
+        let server = HttpServer::new(|| App::new()
+                .route("/authorize", web::get().to(mock_authorize))
+                .route("/token", web::post().to(mock_token)))
+            .bind("127.0.0.1:0")
+            .unwrap();
+
+        tx.send(server.addrs()[0]).unwrap();
+        server.start();
+        sys.run()
+    });
+
+    format!("http://{}", rx.recv().unwrap())
+}
+
+/// Signs a user in at the mock provider, its email verified, returning the
+/// code and state it sends the user back with.
+fn sign_in_at_idp(authorization_url: &str, subject: &str, email: &str, groups: &[&str]) -> (String, String) {
+    let user = MockUser {
+        subject: subject.to_owned(),
+        email: email.to_owned(),
+        email_verified: Some(true),
+        groups: groups.iter().map(|g| g.to_string()).collect(),
+    };
+    sign_in_as(authorization_url, user)
+}
+
+/// Signs a given user in at the mock provider.
+fn sign_in_as(authorization_url: &str, user: MockUser) -> (String, String) {
+    *MOCK_USER.lock().unwrap() = user;
+
+    let http = reqwest::Client::builder().redirect(reqwest::RedirectPolicy::none()).build().unwrap();
+    let response = http.get(authorization_url).send().unwrap();
+    let location = reqwest::Url::parse(response.headers()[reqwest::header::LOCATION].to_str().unwrap()).unwrap();
+    let param = |name: &str| location.query_pairs().find(|(key, _)| key == name).unwrap().1.into_owned();
+
+    (param("code"), param("state"))
+}
+
+fn new_user(full_name: &str) -> NewUser {
+    NewUser {
+        full_name: full_name.to_owned(),
+        email: format!("{}@example.com", uuid::Uuid::new_v4()),
+        password: PASSWORD.to_owned(),
+        job_title: String::new(),
+        profile_picture: String::new(),
+        role: None,
+    }
+}
+
+/// Registers a new organization and returns a client logged in as its admin.
+fn admin_client() -> Client {
+    let admin = new_user("Admin");
+    let mut client = Client::new(&BASE_URL);
+    client.register_new_organisation(&NewOrganization { org_name: String::from("Gestion Test"), admin: admin.clone() })
+        .unwrap();
+    client.login(&admin.email, PASSWORD).unwrap();
+
+    client
+}
+
+/// Creates an owner, a building and one of its registers, returning the
+/// building and register ids.
+fn create_building(client: &Client) -> (uuid::Uuid, uuid::Uuid) {
+    let owner_id = client.create_new_owner(&NewBuildingOwner {
+        full_name: String::from("Propriétaire"),
+        same_as_manager: false,
+        kind: OWNER_KIND_INDIVIDUAL,
+        linked_user_id: None,
+        coordinates_id: None,
+    }).unwrap();
+    let respondant_id = client.get_user_profile().unwrap().id;
+
+    let building_id = client.create_new_building(&NewBuilding {
+        owner_id,
+        respondant_id,
+        name: String::from("Tour A"),
+        address: String::from("1 rue Principale"),
+    }).unwrap();
+    let register_id = client.create_new_register(&NewRegister { building_id, name: String::from("Registre 1") })
+        .unwrap();
+
+    (building_id, register_id)
+}
+
+#[test]
+fn test_session() {
+    if skipped() {
+        return;
+    }
+
+    let mut client = admin_client();
+    let profile = client.get_user_profile().unwrap();
+    assert_eq!(profile.permission, PERMISSION_ADMIN);
+
+    client.logout().unwrap();
+    assert!(client.token().is_none());
+    match client.get_user_profile() {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected unauthorized, got {:?}", other),
+    }
+
+    match client.login(&profile.email, "wrong password") {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected unauthorized, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_manage_sessions() {
+    if skipped() {
+        return;
+    }
+
+    let admin = admin_client();
+    let member = new_user("Membre");
+    let member_id = admin.create_new_user(&member).unwrap();
+    let mut laptop = Client::new(&BASE_URL);
+    laptop.login(&member.email, PASSWORD).unwrap();
+    let mut phone = Client::new(&BASE_URL);
+    phone.login(&member.email, PASSWORD).unwrap();
+    let mut tablet = Client::new(&BASE_URL);
+    tablet.login(&member.email, PASSWORD).unwrap();
+
+    let sessions = laptop.get_sessions().unwrap();
+    assert_eq!(sessions.len(), 3);
+    assert_eq!(sessions.iter().filter(|s| s.current).count(), 1);
+    assert!(sessions.iter().all(|s| !s.ip_address.is_empty() && s.last_seen_at >= s.created_at));
+
+    let phone_id = phone.get_sessions().unwrap().into_iter().find(|s| s.current).unwrap().id;
+    laptop.revoke_session(phone_id).unwrap();
+    assert!(phone.get_user_profile().is_err());
+    match admin.revoke_session(phone_id) {
+        Err(Error::NotFound(_)) => (),
+        other => panic!("Expected another user's session to be unknown, got {:?}", other),
+    }
+
+    laptop.revoke_other_sessions().unwrap();
+    assert!(tablet.get_user_profile().is_err());
+    let sessions = admin.get_user_sessions(member_id).unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert!(!sessions[0].current);
+
+    admin.revoke_user_sessions(member_id).unwrap();
+    match laptop.get_user_profile() {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected the session to be closed, got {:?}", other),
+    }
+    match laptop.get_sessions() {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected the session to be closed, got {:?}", other),
+    }
+}
+
+/// The current code of an authenticator app set up with the secret.
+fn totp_code(secret: &str) -> String {
+    libreauth::oath::TOTPBuilder::new().base32_key(secret).finalize().unwrap().generate()
+}
+
+#[test]
+fn test_two_factor() {
+    if skipped() {
+        return;
+    }
+
+    let admin = new_user("Admin");
+    let mut client = Client::new(&BASE_URL);
+    client.register_new_organisation(&NewOrganization { org_name: String::from("Gestion 2FA"), admin: admin.clone() })
+        .unwrap();
+    client.login(&admin.email, PASSWORD).unwrap();
+
+    let patch = OrganizationPatch { require_two_factor: Some(true), ..OrganizationPatch::default() };
+    client.modify_organisation_info(None, &patch).unwrap();
+    match client.get_user_profile() {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected forbidden until enrolled, got {:?}", other),
+    }
+
+    let enrollment = client.begin_two_factor().unwrap();
+    assert!(enrollment.provisioning_uri.starts_with("otpauth://totp/"));
+    let recovery_codes = client.confirm_two_factor(&totp_code(&enrollment.secret)).unwrap();
+    assert!(client.get_organisation_info().unwrap().require_two_factor);
+
+    client.logout().unwrap();
+    match client.login(&admin.email, PASSWORD) {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected a code to be required, got {:?}", other),
+    }
+    client.login_with_code(&admin.email, PASSWORD, &totp_code(&enrollment.secret)).unwrap();
+    client.logout().unwrap();
+
+    client.login_with_code(&admin.email, PASSWORD, &recovery_codes[0]).unwrap();
+    client.logout().unwrap();
+    assert!(client.login_with_code(&admin.email, PASSWORD, &recovery_codes[0]).is_err());
+}
+
+#[test]
+fn test_users_and_profile() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    client.create_new_user(&new_user("Membre")).unwrap();
+
+    let filter = UserFilter { role: Some(String::from("member")) };
+    let members = client.get_users(&filter, &ListQuery::default()).unwrap();
+    assert_eq!(members.items.len(), 1);
+    assert_eq!(members.items[0].full_name, "Membre");
+
+    let profile = client.get_user_profile().unwrap();
+    let patch = UserPatch { job_title: Some(String::from("Gestionnaire")), ..UserPatch::default() };
+    let updated = client.modify_user_profile(Some(profile.version), &patch).unwrap();
+    assert_eq!(updated.job_title, "Gestionnaire");
+    assert_eq!(updated.full_name, profile.full_name);
+
+    match client.modify_user_profile(Some(profile.version), &patch) {
+        Err(Error::Conflict(_)) => (),
+        other => panic!("Expected a conflict, got {:?}", other),
+    }
+
+    match client.create_new_user(&NewUser { email: String::from("not an email"), ..new_user("Membre") }) {
+        Err(Error::Validation(_, fields)) => assert_eq!(fields["email"], vec!["email"]),
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_email_verification() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let profile = client.get_user_profile().unwrap();
+    assert!(!profile.email_verified);
+
+    let token = mailed_token(&profile.email);
+    let anonymous = Client::new(&BASE_URL);
+    anonymous.verify_email(&token).unwrap();
+    assert!(client.get_user_profile().unwrap().email_verified);
+    match anonymous.verify_email(&token) {
+        Err(Error::NotFound(_)) => (),
+        other => panic!("Expected the link to be used up, got {:?}", other),
+    }
+    match client.resend_email_verification() {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected the email to be verified already, got {:?}", other),
+    }
+
+    let new_email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let patch = UserPatch { email: Some(new_email.clone()), ..UserPatch::default() };
+    let pending = client.modify_user_profile(None, &patch).unwrap();
+    assert_eq!(pending.email, profile.email);
+    assert_eq!(pending.pending_email, Some(new_email.clone()));
+
+    anonymous.verify_email(&mailed_token(&new_email)).unwrap();
+    let changed = client.get_user_profile().unwrap();
+    assert_eq!((changed.email, changed.pending_email), (new_email.clone(), None));
+    assert!(changed.email_verified);
+
+    Client::new(&BASE_URL).login(&new_email.to_uppercase(), PASSWORD).unwrap();
+    match client.create_new_user(&NewUser { email: new_email.to_uppercase(), ..new_user("Membre") }) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected the email to be taken, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_password_policy() {
+    if skipped() {
+        return;
+    }
+
+    let mut client = admin_client();
+    let org = client.get_organisation_info().unwrap();
+    assert_eq!((org.password_min_length, org.password_reject_personal_info), (8, true));
+
+    match client.create_new_user(&NewUser { password: String::from("Password123"), ..new_user("Membre") }) {
+        Err(Error::Validation(_, fields)) => assert!(fields["password"].contains(&String::from("compromised"))),
+        other => panic!("Expected a compromised password, got {:?}", other),
+    }
+    match client.create_new_user(&NewUser { password: String::from("membre membre"), ..new_user("Membre") }) {
+        Err(Error::Validation(_, fields)) => assert!(fields["password"].contains(&String::from("personal_info"))),
+        other => panic!("Expected a password with the user's name, got {:?}", other),
+    }
+
+    let patch = OrganizationPatch { password_min_length: Some(24), ..OrganizationPatch::default() };
+    assert_eq!(client.modify_organisation_info(None, &patch).unwrap().password_min_length, 24);
+    let new_password = "correct horse battery staple";
+    match client.change_password(&PasswordChange { old_password: PASSWORD.to_owned(), new_password: PASSWORD.to_owned() }) {
+        Err(Error::Validation(_, fields)) => assert_eq!(fields["new_password"], vec!["length"]),
+        other => panic!("Expected a password too short, got {:?}", other),
+    }
+    match client.change_password(&PasswordChange { old_password: new_password.to_owned(), new_password: new_password.to_owned() }) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected the old password to be wrong, got {:?}", other),
+    }
+    let email = client.get_user_profile().unwrap().email;
+    client.change_password(&PasswordChange { old_password: PASSWORD.to_owned(), new_password: new_password.to_owned() })
+        .unwrap();
+
+    match client.get_user_profile() {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected the session to be closed, got {:?}", other),
+    }
+    assert!(client.login(&email, PASSWORD).is_err());
+    client.login(&email, new_password).unwrap();
+}
+
+#[test]
+fn test_api_keys() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let (building_id, _) = create_building(&client);
+
+    let created = client.create_api_key(&NewApiKey {
+        name: String::from("Comptabilité"),
+        scopes: vec![SCOPE_READ_BUILDINGS.to_owned()],
+        expires_at: None,
+    }).unwrap();
+    let machine = Client::with_token(&BASE_URL, created.key);
+
+    let buildings = machine.get_buildings(&BuildingFilter::default(), &ListQuery::default()).unwrap();
+    assert_eq!(buildings.items[0].id, building_id);
+    match machine.create_new_register(&NewRegister { building_id, name: String::from("Registre 2") }) {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected the scope to be missing, got {:?}", other),
+    }
+    match machine.get_api_keys() {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected keys to be refused, got {:?}", other),
+    }
+
+    let keys = client.get_api_keys().unwrap();
+    assert_eq!(keys.len(), 1);
+    assert!(keys[0].last_used_at.is_some());
+
+    client.revoke_api_key(created.id).unwrap();
+    match machine.get_buildings(&BuildingFilter::default(), &ListQuery::default()) {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected the key to be revoked, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_single_sign_on() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let org_id = client.get_organisation_info().unwrap().id;
+    client.set_sso_config(&NewOidcProvider {
+        issuer: MOCK_IDP.clone(),
+        client_id: String::from("bep"),
+        client_secret: String::from("secret"),
+        authorization_endpoint: format!("{}/authorize", *MOCK_IDP),
+        token_endpoint: format!("{}/token", *MOCK_IDP),
+        redirect_uri: String::from("https://bep.example.com/sso"),
+        groups_claim: String::from("groups"),
+        admin_groups: vec![String::from("bep-admins")],
+        member_groups: vec![String::from("bep-staff")],
+    }).unwrap();
+
+    let subject = uuid::Uuid::new_v4().to_string();
+    let email = format!("{}@example.com", subject);
+    let mut employee = Client::new(&BASE_URL);
+    let (code, state) = sign_in_at_idp(&employee.begin_sso(org_id).unwrap(), &subject, &email, &["bep-staff"]);
+    employee.complete_sso(&code, &state).unwrap();
+    let profile = employee.get_user_profile().unwrap();
+    assert_eq!((profile.org_id, profile.permission), (org_id, PERMISSION_MEMBER));
+    assert_eq!(profile.email, email);
+
+    match employee.complete_sso(&code, &state) {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected the state to be used up, got {:?}", other),
+    }
+
+    let (code, state) = sign_in_at_idp(&employee.begin_sso(org_id).unwrap(), &subject, &email, &["bep-staff", "bep-admins"]);
+    employee.complete_sso(&code, &state).unwrap();
+    let admin = employee.get_user_profile().unwrap();
+    assert_eq!((admin.id, admin.permission), (profile.id, PERMISSION_ADMIN));
+
+    let (code, state) = sign_in_at_idp(&employee.begin_sso(org_id).unwrap(), &subject, &email, &["visitors"]);
+    match employee.complete_sso(&code, &state) {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected users outside the groups to be refused, got {:?}", other),
+    }
+    assert_eq!(login_outcomes(&email), vec!["success", "success", "sso_failed"]);
+
+    // An account is only matched by an email the provider verified
+    let member = new_user("Membre");
+    let member_id = client.create_new_user(&member).unwrap();
+    let mut unverified = MockUser {
+        subject: uuid::Uuid::new_v4().to_string(),
+        email: member.email.clone(),
+        email_verified: None,
+        groups: vec![String::from("bep-staff")],
+    };
+    let (code, state) = sign_in_as(&employee.begin_sso(org_id).unwrap(), unverified.clone());
+    match employee.complete_sso(&code, &state) {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected an unverified email to be refused, got {:?}", other),
+    }
+    assert_eq!(login_outcomes(&member.email), vec!["sso_failed"]);
+
+    unverified.email_verified = Some(true);
+    let (code, state) = sign_in_as(&employee.begin_sso(org_id).unwrap(), unverified);
+    employee.complete_sso(&code, &state).unwrap();
+    assert_eq!(employee.get_user_profile().unwrap().id, member_id);
+
+    client.delete_sso_config().unwrap();
+    match employee.begin_sso(org_id) {
+        Err(Error::NotFound(_)) => (),
+        other => panic!("Expected single sign-on to be off, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_organisation_and_coordinates() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let coordinates_id = client.create_coordinate(&NewCoordinate {
+        address: Some(String::from("2 rue Secondaire")),
+        ..NewCoordinate::default()
+    }).unwrap();
+
+    let org = client.get_organisation_info().unwrap();
+    let patch = OrganizationPatch { coordinates_id: Some(Some(coordinates_id)), ..OrganizationPatch::default() };
+    let org = client.modify_organisation_info(Some(org.version), &patch).unwrap();
+    assert_eq!(org.coordinates_id, Some(coordinates_id));
+
+    let patch = CoordinatePatch { company_name: Some(String::from("Gestion Test inc.")), ..CoordinatePatch::default() };
+    let coordinates = client.modify_coordinate(coordinates_id, None, &patch).unwrap();
+    assert_eq!(coordinates.address, "2 rue Secondaire");
+    assert_eq!(coordinates.company_name, "Gestion Test inc.");
+}
+
+#[test]
+fn test_buildings_and_registers() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let (building_id, register_id) = create_building(&client);
+
+    let info = client.get_building_information(building_id).unwrap();
+    assert_eq!(info.building.name, "Tour A");
+    assert_eq!(info.registers.len(), 1);
+
+    let patch = BuildingPatch { name: Some(String::from("Tour B")), ..BuildingPatch::default() };
+    let building = client.modify_building(building_id, Some(info.building.version), &patch).unwrap();
+    assert_eq!(building.name, "Tour B");
+
+    let patch = RegisterPatch { name: Some(String::from("Registre 2")) };
+    assert_eq!(client.modify_register(register_id, None, &patch).unwrap().name, "Registre 2");
+
+    let filter = BuildingFilter { address: Some(String::from("PRINCIPALE")), ..BuildingFilter::default() };
+    let buildings = client.get_buildings(&filter, &ListQuery::default()).unwrap();
+    assert_eq!(buildings.items.len(), 1);
+
+    let filter = RegisterFilter { building_id: Some(building_id), ..RegisterFilter::default() };
+    let registers = client.get_registers(&filter, &ListQuery::default()).unwrap();
+    assert_eq!(registers.items[0].id, register_id);
+
+    let owners = client.get_owners(&OwnerFilter::default(), &ListQuery::default()).unwrap();
+    let owner = client.get_owner_info(owners.items[0].id).unwrap();
+    assert_eq!(owner.buildings[0].building.id, building_id);
+
+    let other = admin_client();
+    match other.get_building_information(building_id) {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected forbidden, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_import_buildings() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let csv = "building_name,address,owner_name\nTour A,1 rue Principale,Jean Tremblay\nTour B,2 rue Principale,Jean Tremblay\n";
+
+    let report = client.import_buildings(ImportFormat::Csv, csv.as_bytes().to_vec(), true).unwrap();
+    assert!(report.errors.is_empty());
+    assert!(!report.committed);
+    assert!(client.get_buildings(&BuildingFilter::default(), &ListQuery::default()).unwrap().items.is_empty());
+
+    let report = client.import_buildings(ImportFormat::Csv, csv.as_bytes().to_vec(), false).unwrap();
+    assert!(report.committed);
+    assert_eq!((report.owners_created, report.owners_reused), (1, 1));
+    assert_eq!(client.get_buildings(&BuildingFilter::default(), &ListQuery::default()).unwrap().items.len(), 2);
+
+    match client.import_buildings(ImportFormat::Csv, b"name\nTour A\n".to_vec(), false) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected invalid input, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_export_buildings() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let (building_id, _) = create_building(&client);
+
+    let rows = client.export_buildings_rows(None).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].building_id, building_id);
+    assert_eq!(rows[0].register_names, vec![String::from("Registre 1")]);
+
+    let csv = String::from_utf8(client.export_buildings(ExportFormat::Csv, None).unwrap()).unwrap();
+    assert!(csv.starts_with("building_id,building_name,address"));
+    assert_eq!(csv.lines().count(), 2);
+    assert!(client.export_buildings(ExportFormat::Xlsx, None).unwrap().starts_with(b"PK"));
+
+    match client.export_buildings(ExportFormat::Csv, Some(uuid::Uuid::new_v4())) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected invalid input, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_building_dossier() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let (building_id, _) = create_building(&client);
+
+    let pdf = client.get_building_dossier(building_id).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+
+    match client.get_building_dossier(uuid::Uuid::new_v4()) {
+        Err(Error::NotFound(_)) => (),
+        other => panic!("Expected not found, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_owner_pages() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    for name in &["C", "A", "B"] {
+        client.create_new_owner(&NewBuildingOwner {
+            full_name: name.to_string(),
+            same_as_manager: false,
+            kind: OWNER_KIND_COMPANY,
+            linked_user_id: None,
+            coordinates_id: None,
+        }).unwrap();
+    }
+
+    let query = ListQuery { limit: Some(2), ..ListQuery::default() };
+    let first = client.get_owners(&OwnerFilter::default(), &query).unwrap();
+    let names: Vec<&str> = first.items.iter().map(|o| o.full_name.as_str()).collect();
+    assert_eq!(names, vec!["A", "B"]);
+
+    let second = client.get_owners(&OwnerFilter::default(), &first.next_query(&query).unwrap()).unwrap();
+    assert_eq!(second.items[0].full_name, "C");
+    assert!(second.next_cursor.is_none());
+}
+
+#[test]
+fn test_entity_notes() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let (building_id, _) = create_building(&client);
+
+    let note_id = client.create_entity_note(building_id, "Inspection prévue").unwrap();
+    let reply_id = client.reply_to_entity_note(note_id, "Confirmée").unwrap();
+    client.edit_entity_note(note_id, "Inspection reportée").unwrap();
+
+    let notes = client.get_entity_notes(building_id, 0, 10).unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].note, "Inspection reportée");
+    assert!(notes[0].edited);
+    assert_eq!(notes[0].replies[0].note, "Confirmée");
+
+    let edits = client.get_entity_note_edits(note_id).unwrap();
+    assert_eq!(edits[0].previous_note, "Inspection prévue");
+
+    assert!(client.get_entity_files(building_id).unwrap().files.is_empty());
+
+    for note in &[String::new(), String::from("  "), "a".repeat(10001)] {
+        match client.reply_to_entity_note(note_id, note) {
+            Err(Error::Validation(_, fields)) => assert!(fields.contains_key("note")),
+            other => panic!("Expected a validation error, got {:?}", other),
+        }
+    }
+
+    client.delete_entity_note(note_id).unwrap();
+    assert!(client.get_entity_notes(building_id, 0, 10).unwrap().is_empty());
+
+    // The replies go with the root of their thread
+    match client.reply_to_entity_note(reply_id, "Encore là ?") {
+        Err(Error::NotFound(_)) => (),
+        other => panic!("Expected not found, got {:?}", other),
+    }
+    match client.delete_entity_note(reply_id) {
+        Err(Error::NotFound(_)) => (),
+        other => panic!("Expected not found, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_offboarding() {
+    if skipped() {
+        return;
+    }
+
+    let admin = admin_client();
+    let leaving = new_user("Membre");
+    let leaving_id = admin.create_new_user(&leaving).unwrap();
+    let successor_id = admin.create_new_user(&new_user("Successeur")).unwrap();
+    let mut member = Client::new(&BASE_URL);
+    member.login(&leaving.email, PASSWORD).unwrap();
+
+    let (building_id, _) = create_building(&member);
+    let note_id = member.create_entity_note(building_id, "Chaudière à remplacer").unwrap();
+    admin.create_entity_note(building_id, &format!("@{} devis reçu ?", leaving.email)).unwrap();
+
+    match admin.deactivate_user(leaving_id) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected the buildings to be handed over first, got {:?}", other),
+    }
+    let report = admin.hand_over_user(leaving_id, &Handover { successor_id }).unwrap();
+    assert_eq!((report.buildings, report.mentions), (1, 1));
+    assert_eq!(admin.get_building_information(building_id).unwrap().building.respondant_id, successor_id);
+
+    admin.deactivate_user(leaving_id).unwrap();
+    match member.get_user_profile() {
+        Err(Error::Unauthorized(_)) => (),
+        other => panic!("Expected the session to be closed, got {:?}", other),
+    }
+    assert!(member.login(&leaving.email, PASSWORD).is_err());
+
+    let notes = admin.get_entity_notes(building_id, 0, 10).unwrap();
+    let written = notes.iter().find(|n| n.note_id == note_id).unwrap();
+    assert_eq!((written.user_id, written.handed_over_to), (leaving_id, Some(successor_id)));
+    let mentioning = notes.iter().find(|n| n.note_id != note_id).unwrap();
+    assert_eq!((mentioning.handed_over_to, &mentioning.mentions), (None, &vec![successor_id]));
+
+    let admin_id = admin.get_user_profile().unwrap().id;
+    match admin.deactivate_user(admin_id) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected admins not to deactivate themselves, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_shared_folders() {
+    if skipped() {
+        return;
+    }
+
+    let client = admin_client();
+    let (building_id, _) = create_building(&client);
+
+    client.share_folder_with_owner(building_id, "Plans & devis").unwrap();
+    assert_eq!(client.get_shared_folders(building_id).unwrap(), vec!["Plans & devis"]);
+
+    client.unshare_folder_with_owner(building_id, "Plans & devis").unwrap();
+    assert!(client.get_shared_folders(building_id).unwrap().is_empty());
+
+    match client.get_owner_buildings() {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected forbidden, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_owner_portal() {
+    if skipped() {
+        return;
+    }
+
+    let admin = admin_client();
+    let owner_user = NewUser { role: Some(String::from("owner")), ..new_user("Propriétaire") };
+    let owner_user_id = admin.create_new_user(&owner_user).unwrap();
+    match admin.create_new_user(&NewUser { role: Some(String::from("root")), ..new_user("Root") }) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("Expected an unknown role, got {:?}", other),
+    }
+
+    // Another organization manages a building of the owner
+    let manager = admin_client();
+    let owner_id = manager.create_new_owner(&NewBuildingOwner {
+        full_name: String::from("Propriétaire"),
+        same_as_manager: false,
+        kind: OWNER_KIND_INDIVIDUAL,
+        linked_user_id: Some(owner_user_id),
+        coordinates_id: None,
+    }).unwrap();
+    let building_id = manager.create_new_building(&NewBuilding {
+        owner_id,
+        respondant_id: manager.get_user_profile().unwrap().id,
+        name: String::from("Tour B"),
+        address: String::from("2 rue Principale"),
+    }).unwrap();
+
+    let mut owner = Client::new(&BASE_URL);
+    owner.login(&owner_user.email, PASSWORD).unwrap();
+    let buildings = owner.get_owner_buildings().unwrap();
+    assert_eq!(buildings.iter().map(|b| b.building_id).collect::<Vec<_>>(), vec![building_id]);
+    assert_eq!(owner.get_owner_building(building_id).unwrap().name, "Tour B");
+    match owner.get_users(&UserFilter::default(), &ListQuery::default()) {
+        Err(Error::Forbidden(_)) => (),
+        other => panic!("Expected owners to stay in the portal, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_request_ids() {
+    if skipped() {
+        return;
+    }
+
+    let http = reqwest::Client::new();
+    let profile_url = format!("{}/api/v1/profile", *BASE_URL);
+
+    let mut response = http.get(&profile_url).header("X-Request-Id", "support-ticket-42").send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["x-request-id"], "support-ticket-42");
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body["request_id"], "support-ticket-42");
+
+    // An ID unfit for the logs is replaced by one of the server, which the
+    // error names as well
+    let mut response = http.get(&profile_url).header("X-Request-Id", "two words").send().unwrap();
+    let request_id = response.headers()["x-request-id"].to_str().unwrap().to_owned();
+    assert!(uuid::Uuid::parse_str(&request_id).is_ok());
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body["request_id"], request_id.as_str());
+
+    let mut response = http.get(&profile_url).send().unwrap();
+    let request_id = response.headers()["x-request-id"].to_str().unwrap().to_owned();
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body["request_id"], request_id.as_str());
+
+    let client = admin_client();
+    let response = http.get(&profile_url)
+        .header("Authorization", format!("Bearer {}", client.token().unwrap()))
+        .send()
+        .unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers().contains_key("x-request-id"));
+}
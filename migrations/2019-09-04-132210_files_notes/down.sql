@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE files_notes;
+ALTER TABLE files DROP COLUMN metadata;
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+
+ALTER TABLE files ADD COLUMN metadata JSONB DEFAULT '{}' NOT NULL;
+
+--
+
+CREATE TABLE files_notes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    file_id UUID NOT NULL REFERENCES files(id),
+    entity_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id),
+    note VARCHAR DEFAULT '' NOT NULL,
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+SELECT diesel_manage_updated_at('files_notes');
+
+--
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE entities_notes_mentions;
+DROP TABLE entities_notes_edits;
+ALTER TABLE entities_notes DROP COLUMN parent_id;
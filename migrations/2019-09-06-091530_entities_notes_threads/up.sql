@@ -0,0 +1,34 @@
+-- Your SQL goes here
+
+ALTER TABLE entities_notes ADD COLUMN parent_id UUID REFERENCES entities_notes(id);
+
+--
+
+CREATE TABLE entities_notes_edits (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    note_id UUID NOT NULL REFERENCES entities_notes(id),
+    user_id UUID NOT NULL REFERENCES users(id),
+    previous_note VARCHAR DEFAULT '' NOT NULL,
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+SELECT diesel_manage_updated_at('entities_notes_edits');
+
+--
+
+CREATE TABLE entities_notes_mentions (
+    note_id UUID NOT NULL REFERENCES entities_notes(id),
+    user_id UUID NOT NULL REFERENCES users(id),
+    PRIMARY KEY (note_id, user_id),
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+SELECT diesel_manage_updated_at('entities_notes_mentions');
+
+--
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- 0: individual person, 1: company, 2: the managing organization itself
+ALTER TABLE building_owners ADD COLUMN kind SMALLINT DEFAULT 0 NOT NULL;
+
+UPDATE building_owners SET kind = 2 WHERE is_manager;
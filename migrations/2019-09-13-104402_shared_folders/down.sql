@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE shared_folders;
+ALTER TABLE entities_files DROP COLUMN folder;
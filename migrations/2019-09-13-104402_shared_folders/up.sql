@@ -0,0 +1,19 @@
+-- Your SQL goes here
+
+ALTER TABLE entities_files ADD COLUMN folder VARCHAR DEFAULT '' NOT NULL;
+
+--
+
+CREATE TABLE shared_folders (
+    building_id UUID NOT NULL REFERENCES buildings(id),
+    folder VARCHAR NOT NULL,
+    PRIMARY KEY (building_id, folder),
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+SELECT diesel_manage_updated_at('shared_folders');
+
+--
@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+
+DROP TRIGGER set_version ON entities_notes;
+ALTER TABLE entities_notes DROP COLUMN version;
+DROP TRIGGER set_version ON files;
+ALTER TABLE files DROP COLUMN version;
+DROP TRIGGER set_version ON registers;
+ALTER TABLE registers DROP COLUMN version;
+DROP TRIGGER set_version ON buildings;
+ALTER TABLE buildings DROP COLUMN version;
+DROP TRIGGER set_version ON building_owners;
+ALTER TABLE building_owners DROP COLUMN version;
+DROP TRIGGER set_version ON users;
+ALTER TABLE users DROP COLUMN version;
+DROP TRIGGER set_version ON organizations;
+ALTER TABLE organizations DROP COLUMN version;
+DROP TRIGGER set_version ON coordinates;
+ALTER TABLE coordinates DROP COLUMN version;
+
+DROP FUNCTION IF EXISTS bep_set_version();
+DROP FUNCTION IF EXISTS bep_manage_version(_tbl regclass);
@@ -0,0 +1,53 @@
+-- Your SQL goes here
+
+-- Sets up a trigger for the given table to increment its version column on
+-- every update, the previous version being the precondition of the update.
+--
+-- # Example
+--
+-- ```sql
+-- CREATE TABLE things (id UUID PRIMARY KEY, version INTEGER DEFAULT 1 NOT NULL);
+--
+-- SELECT bep_manage_version('things');
+-- ```
+CREATE OR REPLACE FUNCTION bep_manage_version(_tbl regclass) RETURNS VOID AS $$
+BEGIN
+    EXECUTE format('CREATE TRIGGER set_version BEFORE UPDATE ON %s
+                    FOR EACH ROW EXECUTE PROCEDURE bep_set_version()', _tbl);
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION bep_set_version() RETURNS trigger AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+--
+
+ALTER TABLE coordinates ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('coordinates');
+
+ALTER TABLE organizations ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('organizations');
+
+ALTER TABLE users ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('users');
+
+ALTER TABLE building_owners ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('building_owners');
+
+ALTER TABLE buildings ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('buildings');
+
+ALTER TABLE registers ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('registers');
+
+ALTER TABLE files ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('files');
+
+ALTER TABLE entities_notes ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+SELECT bep_manage_version('entities_notes');
+
+--
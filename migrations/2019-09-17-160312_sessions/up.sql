@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id),
+    secret VARCHAR NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+SELECT diesel_manage_updated_at('sessions');
+
+--
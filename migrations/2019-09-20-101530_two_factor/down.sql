@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE recovery_codes;
+DROP TABLE users_two_factor;
+ALTER TABLE organizations DROP COLUMN require_two_factor;
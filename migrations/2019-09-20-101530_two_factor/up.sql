@@ -0,0 +1,31 @@
+-- Your SQL goes here
+
+ALTER TABLE organizations ADD COLUMN require_two_factor BOOL DEFAULT FALSE NOT NULL;
+
+CREATE TABLE users_two_factor (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id),
+    secret VARCHAR NOT NULL,
+    enabled BOOL DEFAULT FALSE NOT NULL,
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+-- A user has at most one secret, pending or enabled
+CREATE UNIQUE INDEX users_two_factor_user_id ON users_two_factor (user_id) WHERE NOT deleted;
+
+SELECT diesel_manage_updated_at('users_two_factor');
+
+CREATE TABLE recovery_codes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id),
+    code VARCHAR NOT NULL,
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+SELECT diesel_manage_updated_at('recovery_codes');
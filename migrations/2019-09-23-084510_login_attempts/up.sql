@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+-- Audit of every login, kept when the user is purged
+CREATE TABLE login_attempts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    email VARCHAR NOT NULL,
+    ip_address VARCHAR NOT NULL,
+    user_agent VARCHAR NOT NULL,
+    outcome VARCHAR NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX login_attempts_ip_address ON login_attempts (ip_address, created_at);
+CREATE INDEX login_attempts_user_id ON login_attempts (user_id, created_at);
+
+-- Consecutive failed logins of an account, dropped on success or unlock
+CREATE TABLE account_lockouts (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    failures INTEGER NOT NULL,
+    locked_until TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+SELECT diesel_manage_updated_at('account_lockouts');
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    org_id UUID NOT NULL REFERENCES organizations(id),
+    created_by UUID NOT NULL REFERENCES users(id),
+    name VARCHAR NOT NULL,
+    secret VARCHAR NOT NULL,
+    scopes VARCHAR[] NOT NULL,
+    expires_at TIMESTAMP,
+    last_used_at TIMESTAMP,
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+CREATE INDEX api_keys_org_id ON api_keys (org_id);
+
+SELECT diesel_manage_updated_at('api_keys');
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE oidc_identities;
+DROP TABLE oidc_states;
+DROP TABLE oidc_providers;
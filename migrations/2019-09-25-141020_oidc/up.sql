@@ -0,0 +1,44 @@
+-- Your SQL goes here
+
+CREATE TABLE oidc_providers (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    org_id UUID NOT NULL REFERENCES organizations(id),
+    issuer VARCHAR NOT NULL,
+    client_id VARCHAR NOT NULL,
+    client_secret VARCHAR NOT NULL,
+    authorization_endpoint VARCHAR NOT NULL,
+    token_endpoint VARCHAR NOT NULL,
+    redirect_uri VARCHAR NOT NULL,
+    groups_claim VARCHAR NOT NULL,
+    admin_groups VARCHAR[] NOT NULL,
+    member_groups VARCHAR[] NOT NULL,
+    deleted BOOL DEFAULT FALSE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT TO_TIMESTAMP(0) NOT NULL
+);
+
+-- An organization has at most one identity provider
+CREATE UNIQUE INDEX oidc_providers_org_id ON oidc_providers (org_id) WHERE NOT deleted;
+
+SELECT diesel_manage_updated_at('oidc_providers');
+
+-- Sign-ins started at an identity provider and not completed yet
+CREATE TABLE oidc_states (
+    state VARCHAR PRIMARY KEY NOT NULL,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    code_verifier VARCHAR NOT NULL,
+    nonce VARCHAR NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- The users signing in through the identity provider of their organization
+CREATE TABLE oidc_identities (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    subject VARCHAR NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX oidc_identities_subject ON oidc_identities (org_id, subject);
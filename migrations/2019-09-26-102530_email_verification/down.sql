@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE email_verifications;
+
+ALTER TABLE users DROP COLUMN email_verified;
+
+DROP INDEX users_email_lower;
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
@@ -0,0 +1,39 @@
+-- Your SQL goes here
+
+-- Emails are unique whatever their case. Users whose emails only differ by
+-- their case must be merged or given another email before migrating, the
+-- migration stops listing them otherwise
+DO $$
+DECLARE
+    conflicts TEXT;
+BEGIN
+    SELECT string_agg(emails, '; ') INTO conflicts FROM (
+        SELECT string_agg(email, ', ' ORDER BY email) AS emails
+        FROM users
+        GROUP BY LOWER(email)
+        HAVING COUNT(*) > 1
+    ) AS duplicates;
+
+    IF conflicts IS NOT NULL THEN
+        RAISE EXCEPTION 'Emails differing only by case must be made unique first: %', conflicts;
+    END IF;
+END
+$$;
+
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+CREATE UNIQUE INDEX users_email_lower ON users (LOWER(email));
+
+ALTER TABLE users ADD COLUMN email_verified BOOL DEFAULT FALSE NOT NULL;
+
+-- Addresses waiting for their owner to follow the link mailed to them, the
+-- user's own email or the one they are changing to
+CREATE TABLE email_verifications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4() NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(254) NOT NULL,
+    secret VARCHAR NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX email_verifications_user_id ON email_verifications (user_id);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE organizations DROP COLUMN password_reject_personal_info;
+ALTER TABLE organizations DROP COLUMN password_min_entropy;
+ALTER TABLE organizations DROP COLUMN password_min_length;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE organizations ADD COLUMN password_min_length SMALLINT DEFAULT 8 NOT NULL;
+ALTER TABLE organizations ADD COLUMN password_min_entropy SMALLINT DEFAULT 30 NOT NULL;
+ALTER TABLE organizations ADD COLUMN password_reject_personal_info BOOL DEFAULT TRUE NOT NULL;
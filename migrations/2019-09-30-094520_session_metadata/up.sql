@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+-- Where each session was opened from, and when it was last used
+ALTER TABLE sessions ADD COLUMN ip_address VARCHAR DEFAULT '' NOT NULL;
+ALTER TABLE sessions ADD COLUMN user_agent VARCHAR DEFAULT '' NOT NULL;
+ALTER TABLE sessions ADD COLUMN last_seen_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL;
+
+CREATE INDEX sessions_user_id ON sessions (user_id) WHERE NOT deleted;
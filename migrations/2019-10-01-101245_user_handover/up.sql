@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+-- The user who took over the buildings and mentions of a user who left
+ALTER TABLE users ADD COLUMN successor_id UUID REFERENCES users(id);
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+-- The organization that created the coordinates, the only one allowed to
+-- assign them to itself or to its owners and to change them
+ALTER TABLE coordinates ADD COLUMN org_id UUID REFERENCES organizations(id) ON DELETE SET NULL;
+
+UPDATE coordinates SET org_id = organizations.id
+    FROM organizations
+    WHERE organizations.coordinates_id = coordinates.id;
+
+UPDATE coordinates SET org_id = building_owners.org_id
+    FROM building_owners
+    WHERE building_owners.coordinates_id = coordinates.id AND coordinates.org_id IS NULL;
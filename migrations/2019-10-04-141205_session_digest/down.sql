@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+-- The digests can no longer be checked as hashed secrets either
+UPDATE sessions SET deleted = TRUE, deleted_at = NOW() WHERE deleted = FALSE;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- Session secrets are now stored as SHA-256 digests, the sessions opened
+-- with a hashed secret can no longer be checked and are closed
+UPDATE sessions SET deleted = TRUE, deleted_at = NOW() WHERE deleted = FALSE;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+UPDATE users SET deleted = TRUE, deleted_at = deactivated_at WHERE deactivated;
+
+ALTER TABLE users DROP COLUMN deactivated_at;
+ALTER TABLE users DROP COLUMN deactivated;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+-- Deactivated users are kept for good, unlike the deleted ones that purges
+-- remove
+ALTER TABLE users ADD COLUMN deactivated BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN deactivated_at TIMESTAMP;
+
+-- Users used to be deactivated by deleting them
+UPDATE users SET deactivated = TRUE, deactivated_at = deleted_at, deleted = FALSE WHERE deleted;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- The time step of the last code accepted, so that a code cannot be used
+-- twice within the periods the codes are valid
+ALTER TABLE users_two_factor ADD COLUMN last_used_step BIGINT DEFAULT 0 NOT NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE files_notes DROP CONSTRAINT files_notes_entities_files_fkey;
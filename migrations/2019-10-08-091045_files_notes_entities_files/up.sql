@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- A note is on a file of its entity only. Purging a detached file removes
+-- its notes, which could no longer be read
+ALTER TABLE files_notes ADD CONSTRAINT files_notes_entities_files_fkey
+    FOREIGN KEY (file_id, entity_id) REFERENCES entities_files(file_id, entity_id) ON DELETE CASCADE;
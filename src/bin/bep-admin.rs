@@ -0,0 +1,110 @@
+use bep::config::Config;
+use bep::db;
+use bep::mail::Mailer;
+use bep::models::{admin, api};
+use bep::models::api::APIError;
+use bep::models::organizations::NewOrganization;
+use bep::models::users::NewUser;
+
+use std::io::{self, BufRead};
+use std::process;
+
+/// Days a soft-deleted row is kept before `purge-deleted` removes it.
+const DEFAULT_PURGE_DAYS: i64 = 30;
+
+const USAGE: &str = "Usage: bep-admin <command>
+
+Commands:
+    migrate                                   Runs the pending migrations
+    create-org <org_name> <full_name> <email> Creates an organization and its admin
+    reset-password <email>                    Sets a new password, closing the user's sessions
+    deactivate-user <email>                   Prevents a user from logging in, once handed over
+    unlock-user <email>                       Lifts a lockout after too many failed logins
+    list-tenants                              Lists the organizations
+    purge-deleted [<days>]                    Deletes rows soft-deleted more than <days> ago (30)
+
+Passwords are read from the first line of the standard input.
+The settings are read from bep.toml, or the file named by BEP_CONFIG, and
+from the environment or .env, DATABASE_URL first.";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+fn describe(error: APIError) -> String {
+    match &error {
+        APIError::Validation(errors) => format!("{}: {:?}", error, api::field_error_map(errors)),
+        APIError::NotFound => String::from("No active user with this email"),
+        _ => error.to_string(),
+    }
+}
+
+fn read_password() -> String {
+    eprintln!("Password:");
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password).unwrap_or_else(|e| fail(&e.to_string()));
+
+    password.trim_end_matches(|c| c == '\n' || c == '\r').to_owned()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let config = Config::load().unwrap_or_else(|e| fail(&e.to_string()));
+
+    match args.as_slice() {
+        ["migrate"] => {
+            let conn = db::connection::connect(&config.database);
+            db::migrations::run_pending(&conn).unwrap_or_else(|e| fail(&e.to_string()));
+        }
+        ["create-org", org_name, full_name, email] => {
+            let conn = db::connection::connect(&config.database);
+            let fields = NewOrganization {
+                org_name: org_name.to_string(),
+                admin: NewUser {
+                    full_name: full_name.to_string(),
+                    email: email.to_string(),
+                    password: read_password(),
+                    job_title: String::new(),
+                    profile_picture: String::new(),
+                    role: None,
+                },
+            };
+            let user_id = api::register_new_organisation(&conn, &Mailer::from_config(&config), fields).unwrap_or_else(|e| fail(&describe(e)));
+            println!("Created admin {}", user_id);
+        }
+        ["reset-password", email] => {
+            let conn = db::connection::connect(&config.database);
+            admin::reset_password(&conn, email, &read_password()).unwrap_or_else(|e| fail(&describe(e)));
+            println!("Password changed");
+        }
+        ["deactivate-user", email] => {
+            let conn = db::connection::connect(&config.database);
+            admin::deactivate_user(&conn, email).unwrap_or_else(|e| fail(&describe(e)));
+            println!("User deactivated");
+        }
+        ["unlock-user", email] => {
+            let conn = db::connection::connect(&config.database);
+            admin::unlock_user(&conn, email).unwrap_or_else(|e| fail(&describe(e)));
+            println!("User unlocked");
+        }
+        ["list-tenants"] => {
+            let conn = db::connection::connect(&config.database);
+            for tenant in admin::list_tenants(&conn) {
+                println!("{}\t{}\t{} users", tenant.org_id, tenant.org_name, tenant.users);
+            }
+        }
+        ["purge-deleted"] | ["purge-deleted", _] => {
+            let days = match args.get(1) {
+                Some(days) => days.parse().unwrap_or_else(|_| fail(USAGE)),
+                None => DEFAULT_PURGE_DAYS,
+            };
+            let conn = db::connection::connect(&config.database);
+            for (table, count) in admin::purge_deleted(&conn, days).unwrap_or_else(|e| fail(&describe(e))) {
+                println!("{}\t{}", table, count);
+            }
+        }
+        _ => fail(USAGE),
+    }
+}
@@ -0,0 +1,413 @@
+use crate::logging::Filter;
+use crate::models::email_verifications::EMAIL_VERIFICATION_HOURS;
+use crate::models::import::MAX_IMPORT_SIZE;
+use crate::models::sessions::SESSION_DURATION_HOURS;
+
+use dotenv::dotenv;
+
+use serde_derive::Deserialize;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Read when `BEP_CONFIG` does not name another file, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "bep.toml";
+
+/// The settings of the server, read from a TOML file then overridden by the
+/// environment, see `Config::load`. Sections and keys left out of the file
+/// keep their defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub tokens: TokenConfig,
+    pub mail: MailConfig,
+    pub uploads: UploadConfig,
+    pub storage: StorageConfig,
+    pub passwords: PasswordConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `host:port` to listen on, unless a socket is handed over by
+    /// `systemfd`.
+    pub bind: String,
+    /// HTTP workers, one per core when left out.
+    pub workers: Option<usize>,
+    /// Addresses of the reverse proxies whose `X-Forwarded-For` header is
+    /// believed. The client of any other peer is the peer itself.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig { bind: String::from("127.0.0.1:8080"), workers: None, trusted_proxies: Vec::new() }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    /// Connections shared by the HTTP workers.
+    pub pool_size: u32,
+    /// How long a request waits for a connection before failing with 503.
+    pub timeout_seconds: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig { url: String::new(), pool_size: 10, timeout_seconds: 30 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
+    /// How long a session stays valid after login.
+    pub session_hours: i64,
+    /// How long the link mailed to verify an email stays valid.
+    pub email_verification_hours: i64,
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        TokenConfig { session_hours: SESSION_DURATION_HOURS, email_verification_hours: EMAIL_VERIFICATION_HOURS }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+    /// Where the emails are written, one `.eml` file each.
+    pub dir: PathBuf,
+    pub from: String,
+    /// The front end the links of the emails point to.
+    pub app_url: String,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        MailConfig {
+            dir: PathBuf::from("mail"),
+            from: String::from("BEP <no-reply@localhost>"),
+            app_url: String::from("http://localhost:8080"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UploadConfig {
+    /// Largest spreadsheet `import_buildings` accepts, in bytes.
+    pub max_import_size: usize,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        UploadConfig { max_import_size: MAX_IMPORT_SIZE }
+    }
+}
+
+/// Where the content of uploaded files is kept.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// A directory of the server.
+    Local,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(StorageBackend::Local),
+            _ => Err(format!("unknown backend {}, expected local", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// The directory of the `local` backend.
+    pub dir: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig { backend: StorageBackend::Local, dir: PathBuf::from("uploads") }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordConfig {
+    /// A file of compromised passwords, one a line, refused along with the
+    /// bundled list.
+    pub compromised_list: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// A default level and levels of modules, such as
+    /// `info,bep::routes=debug,actix_web=warn`.
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig { level: String::from("info") }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// An environment variable that does not parse.
+    Env(&'static str, String),
+    /// Every setting out of bounds, as `section.key: problem`.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "Error reading {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "Error parsing {}: {}", path.display(), e),
+            ConfigError::Env(var, e) => write!(f, "Invalid {}: {}", var, e),
+            ConfigError::Invalid(problems) => write!(f, "Invalid configuration:\n  {}", problems.join("\n  ")),
+        }
+    }
+}
+
+/// Parses the value of an environment variable.
+fn parse_var<T: FromStr>(var: &'static str, value: &str) -> Result<T, ConfigError>
+    where T::Err: fmt::Display
+{
+    value.trim().parse().map_err(|e: T::Err| ConfigError::Env(var, e.to_string()))
+}
+
+impl Config {
+    /// Reads the file named by `BEP_CONFIG`, or `bep.toml` if there is one,
+    /// applies the environment variables, from `.env` too, and validates the
+    /// result.
+    pub fn load() -> Result<Config, ConfigError> {
+        dotenv().ok();
+
+        let (path, required) = match env::var("BEP_CONFIG") {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+        let mut config = match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| ConfigError::Parse(path, e))?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound && !required => Config::default(),
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+
+        config.apply_env(|var| env::var(var).ok())?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Overrides the settings given by environment variables, `var` telling
+    /// the value of each.
+    pub fn apply_env<F: Fn(&str) -> Option<String>>(&mut self, var: F) -> Result<(), ConfigError> {
+        if let Some(value) = var("BIND_ADDRESS") {
+            self.server.bind = value;
+        }
+        if let Some(value) = var("WORKERS") {
+            self.server.workers = Some(parse_var("WORKERS", &value)?);
+        }
+        if let Some(value) = var("TRUSTED_PROXIES") {
+            self.server.trusted_proxies = value.split(',')
+                .filter(|proxy| !proxy.trim().is_empty())
+                .map(|proxy| parse_var("TRUSTED_PROXIES", proxy))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(value) = var("DATABASE_URL") {
+            self.database.url = value;
+        }
+        if let Some(value) = var("DATABASE_POOL_SIZE") {
+            self.database.pool_size = parse_var("DATABASE_POOL_SIZE", &value)?;
+        }
+        if let Some(value) = var("DATABASE_TIMEOUT_SECONDS") {
+            self.database.timeout_seconds = parse_var("DATABASE_TIMEOUT_SECONDS", &value)?;
+        }
+        if let Some(value) = var("SESSION_HOURS") {
+            self.tokens.session_hours = parse_var("SESSION_HOURS", &value)?;
+        }
+        if let Some(value) = var("EMAIL_VERIFICATION_HOURS") {
+            self.tokens.email_verification_hours = parse_var("EMAIL_VERIFICATION_HOURS", &value)?;
+        }
+        if let Some(value) = var("MAIL_DIR") {
+            self.mail.dir = PathBuf::from(value);
+        }
+        if let Some(value) = var("MAIL_FROM") {
+            self.mail.from = value;
+        }
+        if let Some(value) = var("APP_URL") {
+            self.mail.app_url = value;
+        }
+        if let Some(value) = var("MAX_IMPORT_SIZE") {
+            self.uploads.max_import_size = parse_var("MAX_IMPORT_SIZE", &value)?;
+        }
+        if let Some(value) = var("STORAGE_BACKEND") {
+            self.storage.backend = parse_var("STORAGE_BACKEND", &value)?;
+        }
+        if let Some(value) = var("STORAGE_DIR") {
+            self.storage.dir = PathBuf::from(value);
+        }
+        if let Some(value) = var("COMPROMISED_PASSWORDS") {
+            self.passwords.compromised_list = Some(PathBuf::from(value));
+        }
+        if let Some(value) = var("LOG_LEVEL") {
+            self.logging.level = value;
+        }
+
+        Ok(())
+    }
+
+    /// Checks every setting, reporting all the problems at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_owned());
+            }
+        };
+
+        check(
+            self.server.bind.to_socket_addrs().map(|mut addrs| addrs.next().is_some()).unwrap_or(false),
+            "server.bind: expected host:port, such as 127.0.0.1:8080 (BIND_ADDRESS)",
+        );
+        check(self.server.workers != Some(0), "server.workers: must be at least 1 (WORKERS)");
+        check(
+            self.database.url.starts_with("postgres://") || self.database.url.starts_with("postgresql://"),
+            "database.url: must be set to a postgres:// URL (DATABASE_URL)",
+        );
+        check(self.database.pool_size > 0, "database.pool_size: must be at least 1 (DATABASE_POOL_SIZE)");
+        check(self.database.timeout_seconds > 0, "database.timeout_seconds: must be at least 1 (DATABASE_TIMEOUT_SECONDS)");
+        check(self.tokens.session_hours > 0, "tokens.session_hours: must be at least 1 (SESSION_HOURS)");
+        check(
+            self.tokens.email_verification_hours > 0,
+            "tokens.email_verification_hours: must be at least 1 (EMAIL_VERIFICATION_HOURS)",
+        );
+        check(!self.mail.from.trim().is_empty(), "mail.from: must be set (MAIL_FROM)");
+        check(
+            self.mail.app_url.starts_with("http://") || self.mail.app_url.starts_with("https://"),
+            "mail.app_url: must be an http:// or https:// URL (APP_URL)",
+        );
+        check(self.uploads.max_import_size > 0, "uploads.max_import_size: must be at least 1 (MAX_IMPORT_SIZE)");
+        match self.storage.backend {
+            StorageBackend::Local => {
+                let dir = &self.storage.dir;
+                check(!dir.as_os_str().is_empty(), "storage.dir: must be set for the local backend (STORAGE_DIR)");
+                check(!dir.exists() || dir.is_dir(), &format!("storage.dir: {} is not a directory (STORAGE_DIR)", dir.display()));
+            }
+        }
+        if let Some(path) = &self.passwords.compromised_list {
+            check(path.is_file(), &format!("passwords.compromised_list: no file {} (COMPROMISED_PASSWORDS)", path.display()));
+        }
+        if let Err(e) = Filter::parse(&self.logging.level) {
+            check(false, &format!("logging.level: {} (LOG_LEVEL)", e));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigError, StorageBackend};
+    use std::collections::HashMap;
+
+    fn valid_config() -> Config {
+        let mut config = Config::default();
+        config.database.url = String::from("postgres://bep@localhost/bep");
+        config
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let config: Config = toml::from_str("
+            [server]
+            bind = \"0.0.0.0:80\"
+            workers = 4
+
+            [database]
+            url = \"postgres://bep@db/bep\"
+
+            [storage]
+            backend = \"local\"
+            dir = \"/var/lib/bep/uploads\"
+        ").unwrap();
+        assert_eq!((config.server.bind.as_str(), config.server.workers), ("0.0.0.0:80", Some(4)));
+        assert_eq!((config.database.url.as_str(), config.database.pool_size), ("postgres://bep@db/bep", 10));
+        assert_eq!(config.tokens.session_hours, Config::default().tokens.session_hours);
+        assert_eq!((config.storage.backend, config.storage.dir.to_str()), (StorageBackend::Local, Some("/var/lib/bep/uploads")));
+        assert!(config.validate().is_ok());
+
+        assert!(toml::from_str::<Config>("[server]\nport = 80").is_err());
+        assert!(toml::from_str::<Config>("[storage]\nbackend = \"ftp\"").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars: HashMap<&str, &str> = [
+            ("WORKERS", "2"),
+            ("SESSION_HOURS", "12"),
+            ("APP_URL", "https://bep.example.com"),
+            ("TRUSTED_PROXIES", "10.0.0.1, ::1"),
+        ].iter().cloned().collect();
+        let mut config = valid_config();
+        config.apply_env(|var| vars.get(var).map(|value| value.to_string())).unwrap();
+        assert_eq!((config.server.workers, config.tokens.session_hours), (Some(2), 12));
+        assert_eq!(config.mail.app_url, "https://bep.example.com");
+        assert_eq!(config.server.trusted_proxies, vec!["10.0.0.1".parse::<std::net::IpAddr>().unwrap(), "::1".parse().unwrap()]);
+
+        match config.apply_env(|var| if var == "DATABASE_POOL_SIZE" { Some(String::from("many")) } else { None }) {
+            Err(ConfigError::Env(var, _)) => assert_eq!(var, "DATABASE_POOL_SIZE"),
+            other => panic!("Expected an invalid variable, got {:?}", other),
+        }
+        match config.apply_env(|var| if var == "STORAGE_BACKEND" { Some(String::from("ftp")) } else { None }) {
+            Err(ConfigError::Env(var, _)) => assert_eq!(var, "STORAGE_BACKEND"),
+            other => panic!("Expected an invalid variable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(valid_config().validate().is_ok());
+
+        let mut config = valid_config();
+        config.server.bind = String::from("8080");
+        config.database.url = String::new();
+        config.tokens.session_hours = 0;
+        config.storage.dir = std::path::PathBuf::from("Cargo.toml");
+        match config.validate() {
+            Err(ConfigError::Invalid(problems)) => {
+                let keys: Vec<&str> = problems.iter().map(|p| p.split(':').next().unwrap()).collect();
+                assert_eq!(keys, vec!["server.bind", "database.url", "tokens.session_hours", "storage.dir"]);
+            }
+            other => panic!("Expected invalid settings, got {:?}", other),
+        }
+    }
+}
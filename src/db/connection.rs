@@ -1,13 +1,43 @@
+use crate::config::{Config, DatabaseConfig};
 use diesel::connection::Connection;
 use diesel::pg::PgConnection;
-use dotenv::dotenv;
-use std::env;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel::sql_types::Text;
+use diesel::{QueryResult, RunQueryDsl};
+use std::time::Duration;
 
+pub type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
+pub type PooledConnection = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
+
+pub fn connect(config: &DatabaseConfig) -> PgConnection {
+    PgConnection::establish(&config.url)
+        .expect("Error connecting to database")
+}
+
+/// Connects to the database of the configuration `Config::load` reads, for
+/// the tests.
 pub fn establish_connection() -> PgConnection {
-    dotenv().ok();
+    let config = Config::load().unwrap_or_else(|e| panic!("{}", e));
+
+    connect(&config.database)
+}
+
+/// Names the connection after the request using it, so that the statements
+/// Postgres logs carry the request ID with `%a` in `log_line_prefix`. Diesel
+/// has no query logging of its own to put the ID in, this costs a statement
+/// each time a request takes a connection.
+pub fn tag_request(conn: &PgConnection, request_id: &str) -> QueryResult<()> {
+    diesel::sql_query("SELECT set_config('application_name', $1, false)")
+        .bind::<Text, _>(format!("bep {}", request_id))
+        .execute(conn)
+        .map(|_| ())
+}
 
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url)   
-        .expect("Error connecting to dabase")
+/// Connections shared by the HTTP workers.
+pub fn establish_pool(config: &DatabaseConfig) -> Pool {
+    r2d2::Pool::builder()
+        .max_size(config.pool_size)
+        .connection_timeout(Duration::from_secs(config.timeout_seconds))
+        .build(ConnectionManager::<PgConnection>::new(config.url.as_str()))
+        .expect("Error creating the database pool")
 }
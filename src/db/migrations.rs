@@ -0,0 +1,11 @@
+use diesel::pg::PgConnection;
+use diesel_migrations::RunMigrationsError;
+
+use std::io;
+
+embed_migrations!("migrations");
+
+/// Runs the migrations not applied yet, printing the name of each.
+pub fn run_pending(conn: &PgConnection) -> Result<(), RunMigrationsError> {
+    embedded_migrations::run_with_output(conn, &mut io::stdout())
+}
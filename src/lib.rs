@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
+extern crate dotenv;
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate validator_derive;
+
+pub mod config;
+pub mod schema;
+pub mod models;
+pub mod db;
+pub mod logging;
+pub mod mail;
+pub mod routes;
+pub mod utils;
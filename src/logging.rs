@@ -0,0 +1,267 @@
+use crate::config::LoggingConfig;
+
+use chrono::{SecondsFormat, Utc};
+
+use futures::{Future, Poll};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use serde_json::{json, Map, Value};
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// What is known of the request being handled, added to every line logged
+/// while handling it.
+#[derive(Clone, Debug, Default)]
+pub struct SpanFields {
+    pub request_id: String,
+    pub method: String,
+    pub path: String,
+    /// The authenticated user and their organization, once known.
+    pub user_id: Option<uuid::Uuid>,
+    pub org_id: Option<uuid::Uuid>,
+    /// Set when the response is ready, for the line closing the request.
+    pub status: Option<u16>,
+    pub duration_ms: Option<u64>,
+}
+
+/// The span of a request, shared by the polls of its future. Actix runs the
+/// futures of a worker on a single thread, so the span entered is kept in a
+/// thread local.
+#[derive(Clone, Debug, Default)]
+pub struct Span(Rc<RefCell<SpanFields>>);
+
+thread_local! {
+    static CURRENT: RefCell<Option<Span>> = RefCell::new(None);
+}
+
+impl Span {
+    pub fn new(request_id: &str, method: &str, path: &str) -> Span {
+        Span(Rc::new(RefCell::new(SpanFields {
+            request_id: request_id.to_owned(),
+            method: method.to_owned(),
+            path: path.to_owned(),
+            ..SpanFields::default()
+        })))
+    }
+
+    /// Runs `f` with the span as the current one, restoring the previous
+    /// one after.
+    pub fn enter<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let result = f();
+        CURRENT.with(|current| current.replace(previous));
+
+        result
+    }
+
+    pub fn fields(&self) -> SpanFields {
+        self.0.borrow().clone()
+    }
+
+    pub fn record_response(&self, status: u16, duration_ms: u64) {
+        let mut fields = self.0.borrow_mut();
+        fields.status = Some(status);
+        fields.duration_ms = Some(duration_ms);
+    }
+}
+
+/// A future polled within a span, such as the handling of a request.
+pub struct Instrumented<F> {
+    span: Span,
+    inner: F,
+}
+
+impl<F> Instrumented<F> {
+    pub fn new(span: Span, inner: F) -> Instrumented<F> {
+        Instrumented { span, inner }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        let Instrumented { span, inner } = self;
+        span.enter(|| inner.poll())
+    }
+}
+
+/// The ID of the request being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|span| span.0.borrow().request_id.clone()))
+}
+
+/// Tells the span of the request being handled who made it.
+pub fn record_user(user_id: uuid::Uuid, org_id: Option<uuid::Uuid>) {
+    CURRENT.with(|current| {
+        if let Some(span) = current.borrow().as_ref() {
+            let mut fields = span.0.borrow_mut();
+            fields.user_id = Some(user_id);
+            fields.org_id = org_id;
+        }
+    });
+}
+
+/// The level logged for each module, parsed from directives such as
+/// `info,bep::routes=debug,actix_web=warn`: a default level, then levels of
+/// modules and their submodules.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    default: LevelFilter,
+    /// Longest modules first, so that the most specific one applies.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    pub fn parse(directives: &str) -> Result<Filter, String> {
+        let mut filter = Filter { default: LevelFilter::Info, modules: Vec::new() };
+
+        for directive in directives.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let (module, level) = match (parts.next(), parts.next()) {
+                (Some(level), None) => (None, level),
+                (Some(module), Some(level)) if !module.trim().is_empty() => (Some(module.trim()), level),
+                _ => return Err(format!("invalid directive `{}`", directive)),
+            };
+            let level = level.trim().parse::<LevelFilter>()
+                .map_err(|_| format!("unknown level `{}`", level.trim()))?;
+
+            match module {
+                Some(module) => filter.modules.push((module.to_owned(), level)),
+                None => filter.default = level,
+            }
+        }
+        filter.modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        Ok(filter)
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules.iter()
+            .find(|(module, _)| target == module.as_str() || target.starts_with(&format!("{}::", module)))
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+}
+
+/// One JSON object a line, with the fields of the request being handled.
+pub fn format_record(record: &Record, span: Option<&SpanFields>) -> String {
+    let mut line = Map::new();
+    line.insert(String::from("timestamp"), json!(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)));
+    line.insert(String::from("level"), json!(record.level().to_string()));
+    line.insert(String::from("target"), json!(record.target()));
+    line.insert(String::from("message"), json!(record.args().to_string()));
+
+    if let Some(span) = span {
+        line.insert(String::from("request_id"), json!(span.request_id));
+        line.insert(String::from("method"), json!(span.method));
+        line.insert(String::from("path"), json!(span.path));
+        let optional = [
+            ("user_id", span.user_id.map(|id| json!(id))),
+            ("org_id", span.org_id.map(|id| json!(id))),
+            ("status", span.status.map(|status| json!(status))),
+            ("duration_ms", span.duration_ms.map(|duration| json!(duration))),
+        ];
+        for (key, value) in optional.iter() {
+            if let Some(value) = value {
+                line.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+
+    Value::Object(line).to_string()
+}
+
+/// Writes the records to the standard error.
+struct JsonLogger {
+    filter: Filter,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let span = CURRENT.with(|current| current.borrow().as_ref().map(Span::fields));
+        let line = format_record(record, span.as_ref());
+        writeln!(io::stderr().lock(), "{}", line).ok();
+    }
+
+    fn flush(&self) {
+        io::stderr().flush().ok();
+    }
+}
+
+/// Logs from now on at the levels of `logging.level`, which the
+/// configuration checked.
+pub fn init(config: &LoggingConfig) {
+    let filter = Filter::parse(&config.level).expect("Invalid logging.level");
+    log::set_max_level(filter.max_level());
+    log::set_boxed_logger(Box::new(JsonLogger { filter })).expect("Error installing the logger");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{current_request_id, format_record, record_user, Filter, Span};
+    use log::{Level, LevelFilter, Record};
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter::parse("warn, bep=info ,bep::routes=debug").unwrap();
+        assert_eq!(filter.level_for("actix_web::middleware"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("bep::models::api"), LevelFilter::Info);
+        assert_eq!(filter.level_for("bep::routes::tracing"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("bepsi"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+
+        assert_eq!(Filter::parse("").unwrap().level_for("bep"), LevelFilter::Info);
+        assert!(Filter::parse("verbose").is_err());
+        assert!(Filter::parse("=debug").is_err());
+    }
+
+    #[test]
+    fn test_span() {
+        let span = Span::new("abc-123", "GET", "/api/v1/profile");
+        let user_id = uuid::Uuid::new_v4();
+
+        assert_eq!(current_request_id(), None);
+        span.enter(|| {
+            assert_eq!(current_request_id(), Some(String::from("abc-123")));
+            record_user(user_id, None);
+        });
+        assert_eq!(current_request_id(), None);
+        assert_eq!(span.fields().user_id, Some(user_id));
+    }
+
+    #[test]
+    fn test_format_record() {
+        let span = Span::new("abc-123", "GET", "/api/v1/profile");
+        span.record_response(404, 12);
+
+        let line = format_record(
+            &Record::builder().args(format_args!("Request completed")).level(Level::Info).target("bep").build(),
+            Some(&span.fields())
+        );
+        let line: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(line["message"], "Request completed");
+        assert_eq!((&line["level"], &line["request_id"]), (&serde_json::json!("INFO"), &serde_json::json!("abc-123")));
+        assert_eq!((&line["status"], &line["duration_ms"]), (&serde_json::json!(404), &serde_json::json!(12)));
+        assert!(line.get("user_id").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::Config;
+
+use chrono::Utc;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A plain text email to a single recipient.
+#[derive(Clone, Debug)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers the emails the `Mailer` composes, such as an SMTP relay would.
+pub trait Transport: Send + Sync {
+    fn deliver(&self, from: &str, email: &Email) -> io::Result<()>;
+}
+
+/// Writes every email to a file of a directory instead of sending it, in
+/// the format an SMTP server receives, for development and tests.
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FileTransport {
+        FileTransport { dir: dir.into() }
+    }
+}
+
+impl Transport for FileTransport {
+    fn deliver(&self, from: &str, email: &Email) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let now = Utc::now();
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            from, email.to, email.subject, now.to_rfc2822(), email.body.replace("\n", "\r\n")
+        );
+        let name = format!("{}-{}.eml", now.format("%Y%m%d%H%M%S"), uuid::Uuid::new_v4());
+
+        fs::write(self.dir.join(name), message)
+    }
+}
+
+/// Sends the emails of the application, with links back to its front end
+/// that stay valid `link_hours`.
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Arc<dyn Transport>,
+    from: String,
+    app_url: String,
+    link_hours: i64,
+}
+
+impl Mailer {
+    pub fn new(transport: Arc<dyn Transport>, from: &str, app_url: &str, link_hours: i64) -> Mailer {
+        Mailer {
+            transport,
+            from: from.to_owned(),
+            app_url: app_url.trim_end_matches('/').to_owned(),
+            link_hours,
+        }
+    }
+
+    /// Writes the emails to the `mail.dir` directory of the configuration,
+    /// with the links expiring after `tokens.email_verification_hours`.
+    pub fn from_config(config: &Config) -> Mailer {
+        Mailer::new(
+            Arc::new(FileTransport::new(&config.mail.dir)),
+            &config.mail.from,
+            &config.mail.app_url,
+            config.tokens.email_verification_hours
+        )
+    }
+
+    pub fn link_hours(&self) -> i64 {
+        self.link_hours
+    }
+
+    /// A link to a page of the front end, passing it a token.
+    pub fn link(&self, page: &str, token: &str) -> String {
+        format!("{}/{}?token={}", self.app_url, page, token)
+    }
+
+    pub fn send(&self, email: &Email) -> Result<(), String> {
+        self.transport.deliver(&self.from, email).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Email, FileTransport, Mailer};
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_file_transport() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let mailer = Mailer::new(Arc::new(FileTransport::new(&dir)), "BEP <bep@example.com>", "https://bep.example.com/", 48);
+        let link = mailer.link("verify_email", "abc.def");
+        assert_eq!(link, "https://bep.example.com/verify_email?token=abc.def");
+
+        mailer.send(&Email {
+            to: String::from("jane@example.com"),
+            subject: String::from("Bienvenue"),
+            body: format!("Suivez ce lien :\n{}", link),
+        }).unwrap();
+
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let message = fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert!(message.starts_with("From: BEP <bep@example.com>\r\nTo: jane@example.com\r\nSubject: Bienvenue\r\n"));
+        assert!(message.contains(&link));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
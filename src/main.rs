@@ -1,17 +1,11 @@
 // systemfd --no-pid -s http::8080 -- cargo watch -x run
-#[macro_use]
-extern crate diesel;
-extern crate dotenv;
-#[macro_use]
-extern crate lazy_static;
-
 use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder, get};
+use bep::{db, logging, mail, routes};
+use bep::config::Config;
+use bep::models::password_policy;
 use listenfd::ListenFd;
 
-pub mod schema;
-pub mod models;
-pub mod db;
-pub mod utils;
+use std::process;
 
 #[get("/hello")]
 fn index3() -> impl Responder {
@@ -28,18 +22,41 @@ fn index2() -> impl Responder {
 }
 
 fn main() {
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1)
+    });
+    logging::init(&config.logging);
+
     let mut listenfd = ListenFd::from_env();
-    let mut server = HttpServer::new(|| App::new()
+    let pool = db::connection::establish_pool(&config.database);
+    if let Some(path) = &config.passwords.compromised_list {
+        password_policy::load_compromised_list(path)
+            .unwrap_or_else(|e| panic!("Error reading compromised passwords from {}: {}", path.display(), e));
+    }
+    let mailer = mail::Mailer::from_config(&config);
+    let settings = config.clone();
+    let mut server = HttpServer::new(move || App::new()
+        .data(pool.clone())
+        .data(mailer.clone())
+        .data(settings.clone())
+        .configure(|cfg| routes::config(cfg, &settings))
         .route("/", web::get().to(index))
         .route("/again", web::get().to(index2))
         .service(index3)
     );
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
 
     server = if let Some(l) = listenfd.take_tcp_listener(0).unwrap() {
         server.listen(l).unwrap()
     } else {
-        server.bind("127.0.0.1:8080").unwrap()
+        server.bind(&config.server.bind).unwrap()
     };
+    for addr in server.addrs() {
+        log::info!("Listening on {}", addr);
+    }
 
     server.run().unwrap();
 }
@@ -0,0 +1,214 @@
+use super::api::APIError;
+use super::login_attempts::AccountLockout;
+use super::organizations::Organization;
+use super::users::User;
+
+use chrono::{Duration, Utc};
+
+use diesel::{Connection, PgConnection, RunQueryDsl};
+use diesel::sql_types::Timestamp;
+
+/// Soft-deleted rows of a table are only purged once no row references them
+/// anymore. Tables come in purge order, referencing tables first, each with
+/// the `(table, column)` pairs referencing its ids.
+const PURGE_ORDER: &[(&str, &[(&str, &str)])] = &[
+    ("sessions", &[]),
+    ("api_keys", &[]),
+    ("oidc_providers", &[]),
+    ("recovery_codes", &[]),
+    ("users_two_factor", &[]),
+    ("entities_notes_mentions", &[]),
+    ("entities_notes_edits", &[]),
+    ("entities_notes", &[
+        ("entities_notes", "parent_id"),
+        ("entities_notes_edits", "note_id"),
+        ("entities_notes_mentions", "note_id"),
+    ]),
+    ("files_notes", &[]),
+    ("entities_files", &[]),
+    ("entities_history", &[]),
+    ("shared_folders", &[]),
+    ("registers", &[
+        ("entities_files", "entity_id"),
+        ("entities_history", "entity_id"),
+        ("entities_notes", "entity_id"),
+        ("files_notes", "entity_id"),
+    ]),
+    ("buildings", &[
+        ("registers", "building_id"),
+        ("shared_folders", "building_id"),
+        ("entities_files", "entity_id"),
+        ("entities_history", "entity_id"),
+        ("entities_notes", "entity_id"),
+        ("files_notes", "entity_id"),
+    ]),
+    ("building_owners", &[("buildings", "owner_id")]),
+    ("files", &[
+        ("entities_files", "file_id"),
+        ("entities_history", "file_id"),
+        ("files_notes", "file_id"),
+    ]),
+    ("users", &[
+        ("users", "successor_id"),
+        ("sessions", "user_id"),
+        ("api_keys", "created_by"),
+        ("recovery_codes", "user_id"),
+        ("users_two_factor", "user_id"),
+        ("building_owners", "linked_user_id"),
+        ("buildings", "respondant_id"),
+        ("entities_history", "user_id"),
+        ("entities_notes", "user_id"),
+        ("entities_notes_edits", "user_id"),
+        ("entities_notes_mentions", "user_id"),
+        ("files_notes", "user_id"),
+    ]),
+    ("organizations", &[
+        ("users", "org_id"),
+        ("api_keys", "org_id"),
+        ("oidc_providers", "org_id"),
+        ("building_owners", "org_id"),
+        ("buildings", "org_id"),
+    ]),
+    ("coordinates", &[
+        ("organizations", "coordinates_id"),
+        ("building_owners", "coordinates_id"),
+    ]),
+];
+
+#[derive(Debug)]
+pub struct TenantInfo {
+    pub org_id: uuid::Uuid,
+    pub org_name: String,
+    pub users: usize,
+}
+
+/// Lists the organizations with their number of active users.
+pub fn list_tenants(conn: &PgConnection) -> Vec<TenantInfo> {
+    let orgs = Organization::get_all(&conn);
+    let users = User::get_by_organizations(&conn, &orgs);
+
+    orgs.iter()
+        .zip(users)
+        .map(|(org, users)| TenantInfo { org_id: org.id(), org_name: org.org_name().clone(), users: users.len() })
+        .collect()
+}
+
+/// Sets a new password following the password policy of the user's
+/// organization, and closes the user's sessions.
+pub fn reset_password(conn: &PgConnection, email: &str, password: &str) -> Result<(), APIError> {
+    let mut user = User::find_by_email(&conn, email).ok_or(APIError::NotFound)?;
+    let org = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+    user.reset_password(&conn, password, &org.password_policy())?;
+
+    Ok(())
+}
+
+/// Deactivates a user, who can no longer log in, and closes the user's
+/// sessions. The buildings the user is the respondant of must be handed
+/// over first.
+pub fn deactivate_user(conn: &PgConnection, email: &str) -> Result<(), APIError> {
+    let mut user = User::find_by_email(&conn, email).ok_or(APIError::NotFound)?;
+
+    user.deactivate(&conn).map_err(APIError::InvalidInput)
+}
+
+/// Lets a user log in again right away after too many failed logins.
+pub fn unlock_user(conn: &PgConnection, email: &str) -> Result<(), APIError> {
+    let user = User::find_by_email(&conn, email).ok_or(APIError::NotFound)?;
+    AccountLockout::clear(&conn, user.id());
+
+    Ok(())
+}
+
+fn purge_statement(table: &str, references: &[(&str, &str)]) -> String {
+    let mut sql = format!("DELETE FROM {} WHERE deleted AND deleted_at < $1", table);
+    for (ref_table, ref_column) in references {
+        sql.push_str(&format!(
+            " AND NOT EXISTS (SELECT 1 FROM {} r WHERE r.{} = {}.id)",
+            ref_table, ref_column, table
+        ));
+    }
+
+    sql
+}
+
+/// Deletes for good the rows soft-deleted more than `older_than_days` ago
+/// and no longer referenced, returning the number of rows purged per table.
+pub fn purge_deleted(conn: &PgConnection, older_than_days: i64) -> Result<Vec<(&'static str, usize)>, APIError> {
+    let cutoff = Utc::now().naive_utc() - Duration::days(older_than_days);
+
+    let counts: Vec<(&'static str, usize)> = conn.transaction::<_, diesel::result::Error, _>(|| {
+        PURGE_ORDER.iter()
+            .map(|(table, references)| {
+                diesel::sql_query(purge_statement(table, references))
+                    .bind::<Timestamp, _>(cutoff)
+                    .execute(conn)
+                    .map(|count| (*table, count))
+            })
+            .collect()
+    })?;
+
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::api::APIError;
+    use super::super::buildings::{Building, test_functions::*};
+    use super::super::login_attempts::LoginClient;
+    use super::super::sessions::{Session, SESSION_DURATION_HOURS};
+    use super::super::users::{User, test_functions::*};
+    use crate::db;
+    use diesel::Connection;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_purge_statement() {
+        assert_eq!(
+            super::purge_statement("building_owners", &[("buildings", "owner_id")]),
+            "DELETE FROM building_owners WHERE deleted AND deleted_at < $1 \
+             AND NOT EXISTS (SELECT 1 FROM buildings r WHERE r.owner_id = building_owners.id)"
+        );
+    }
+
+    #[test]
+    fn test_deactivate_and_purge_user() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("ADMIN_PURGE"));
+            User::insert(&conn, &user);
+            let (session, _) = Session::new(user.id(), &LoginClient::default(), SESSION_DURATION_HOURS);
+            Session::insert(&conn, &session);
+
+            super::deactivate_user(&conn, user.email()).unwrap();
+            assert!(User::find_by_email(&conn, user.email()).is_none());
+            assert!(Session::find_active(&conn, session.id()).is_none());
+
+            // Its closed session goes, the deactivated user is kept
+            super::purge_deleted(&conn, -1).unwrap();
+            assert!(!User::find_by_id(&conn, user.id()).unwrap().is_active());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_deactivate_respondant() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let building = create_test_building1(&conn);
+            Building::insert(&conn, &building);
+            let respondant = User::find_by_id(&conn, building.respondant_id()).unwrap();
+
+            match super::deactivate_user(&conn, respondant.email()) {
+                Err(APIError::InvalidInput(_)) => (),
+                other => panic!("Expected the buildings to be handed over first, got {:?}", other),
+            }
+            assert!(User::find_by_id(&conn, respondant.id()).unwrap().is_active());
+
+            Ok(())
+        });
+    }
+}
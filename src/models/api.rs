@@ -1,20 +1,1971 @@
-use super::models::buildings::Building;
-use super::models::building_owners::BuildingOwner;
-use super::models::users::User;
+use super::api_keys::{ApiKey, ApiKeyResponse, ApiScope, NewApiKey};
+use super::buildings::{Building, BuildingFilter, BuildingPatch, BuildingResponse, NewBuilding, BUILDING_SORT_FIELDS};
+use super::building_owners::{BuildingOwner, BuildingOwnerPatch, BuildingOwnerResponse, NewBuildingOwner, OwnerFilter, OwnerKind};
+use super::building_owners::OWNER_SORT_FIELDS;
+use super::coordinates::{Coordinate, CoordinatePatch, CoordinateResponse, NewCoordinate};
+use super::dossier::{self, Dossier, DOSSIER_NOTES};
+use super::email_verifications::EmailVerification;
+use super::entities_files::EntitiesFile;
+use super::entities_history::EntitiesHistory;
+use super::entities_notes::{EntitiesNote, NoteText};
+use super::entities_notes_edits::EntitiesNotesEdit;
+use super::entities_notes_mentions::EntitiesNotesMention;
+use super::files::File;
+use super::export::{self, ExportFormat};
+use super::files_notes::FilesNote;
+use super::import::{self, ImportFormat, ImportReport};
+use super::list_query::{ListQuery, Page, MAX_PAGE_SIZE};
+use super::login_attempts::{AccountLockout, LoginAttempt, LoginClient, LoginOutcome, IP_WINDOW_MINUTES, MAX_IP_FAILURES};
+use super::oidc::{NewOidcProvider, OidcIdentity, OidcProvider, OidcProviderResponse, SsoIdentity, SsoState};
+use super::organizations::{NewOrganization, Organization, OrganizationPatch, OrganizationResponse};
+use super::registers::{Register, NewRegister, RegisterFilter, RegisterPatch, RegisterResponse, REGISTER_SORT_FIELDS};
+use super::sessions::{Session, SessionResponse};
+use super::shared_folders::SharedFolder;
+use super::two_factor::{RecoveryCode, TwoFactor, TwoFactorEnrollment};
+use super::users::{User, Handover, HandoverReport, NewUser, PasswordChange, PasswordError, UserFilter, UserPatch, UserResponse, PERMISSION_ADMIN, USER_SORT_FIELDS};
+use crate::logging;
+use crate::mail::{Email, Mailer};
+use crate::utils::validators::validate_not_blank;
 
-use diesel::PgConnection;
+use actix_web::{http::header, HttpResponse, ResponseError};
 
+use chrono::{Duration, Utc};
+
+use diesel::{Connection, PgConnection};
+
+use serde_derive::Serialize;
+use serde_json::json;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use validator::{Validate, ValidationErrors};
+
+/// Number of `entities_history` rows returned in an entity's activity feed.
+pub const ACTIVITY_FEED_LENGTH: i64 = 20;
+
+#[derive(Debug)]
 pub enum APIError {
+    Unauthorized,
+    NotFound,
+    Forbidden,
+    Conflict,
+    InvalidInput(String),
+    Validation(ValidationErrors),
+    Database(diesel::result::Error),
+    Unavailable,
+    /// The password was right, a code of the authenticator app or a
+    /// recovery code must come along with it.
+    TwoFactorRequired,
+    /// The organization requires two-factor authentication, which the user
+    /// has yet to enable.
+    TwoFactorEnrollmentRequired,
+    /// Too many failed logins, the number of seconds to wait before trying
+    /// again.
+    TooManyAttempts(i64),
+    /// A document, such as a dossier, could not be written.
+    Rendering(String),
+}
 
+impl From<diesel::result::Error> for APIError {
+    fn from(e: diesel::result::Error) -> Self {
+        APIError::Database(e)
+    }
 }
 
-pub fn register_new_organisation(conn: &PgConnection, user: User, organization: BuildingOwner, invites: Vec<User>) -> Result<(), APIError> {
-    // validate
-    User::insert(&conn, &user);
-    
+impl From<ValidationErrors> for APIError {
+    fn from(e: ValidationErrors) -> Self {
+        APIError::Validation(e)
+    }
+}
+
+impl fmt::Display for APIError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            APIError::Unauthorized => write!(f, "Authentication required"),
+            APIError::NotFound => write!(f, "Not found"),
+            APIError::Forbidden => write!(f, "Forbidden"),
+            APIError::Conflict => write!(f, "Modified since it was loaded"),
+            APIError::InvalidInput(msg) => write!(f, "{}", msg),
+            APIError::Validation(_) => write!(f, "Invalid fields"),
+            APIError::Database(e) => write!(f, "Database error: {}", e),
+            APIError::Unavailable => write!(f, "Service unavailable"),
+            APIError::TwoFactorRequired => write!(f, "Two-factor code required"),
+            APIError::TwoFactorEnrollmentRequired => write!(f, "The organization requires two-factor authentication"),
+            APIError::TooManyAttempts(_) => write!(f, "Too many failed logins, try again later"),
+            APIError::Rendering(e) => write!(f, "Rendering error: {}", e),
+        }
+    }
+}
+
+/// Maps each invalid field to the codes of the validations it failed, e.g.
+/// `{"email": ["email"], "full_name": ["length", "blank"]}`.
+pub fn field_error_map(errors: &ValidationErrors) -> BTreeMap<&'static str, Vec<String>> {
+    errors.field_errors()
+        .into_iter()
+        .map(|(field, errors)| (field, errors.iter().map(|e| e.code.to_string()).collect()))
+        .collect()
+}
+
+impl ResponseError for APIError {
+    /// The body carries the `request_id` of the request failing, for support
+    /// to find it in the logs.
+    fn error_response(&self) -> HttpResponse {
+        let mut response = match self {
+            APIError::Unauthorized | APIError::TwoFactorRequired => HttpResponse::Unauthorized(),
+            APIError::NotFound => HttpResponse::NotFound(),
+            APIError::Forbidden | APIError::TwoFactorEnrollmentRequired => HttpResponse::Forbidden(),
+            APIError::Conflict => HttpResponse::Conflict(),
+            APIError::InvalidInput(_) => HttpResponse::BadRequest(),
+            APIError::Validation(_) => HttpResponse::UnprocessableEntity(),
+            APIError::Database(_) | APIError::Rendering(_) => HttpResponse::InternalServerError(),
+            APIError::Unavailable => HttpResponse::ServiceUnavailable(),
+            APIError::TooManyAttempts(seconds) => {
+                let mut response = HttpResponse::TooManyRequests();
+                response.header(header::RETRY_AFTER, seconds.to_string());
+                response
+            }
+        };
+
+        let mut body = match self {
+            APIError::Validation(errors) => json!({
+                "error": self.to_string(),
+                "fields": field_error_map(errors),
+            }),
+            // Database details stay in the server logs
+            APIError::Database(e) => {
+                error!("Database error: {}", e);
+                json!({"error": "Internal server error"})
+            }
+            APIError::Rendering(e) => {
+                error!("Rendering error: {}", e);
+                json!({"error": "Internal server error"})
+            }
+            _ => json!({"error": self.to_string()}),
+        };
+        if let Some(request_id) = logging::current_request_id() {
+            body["request_id"] = json!(request_id);
+        }
+
+        response.json(body)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileNoteInfo {
+    pub note_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub user_name: String,
+    /// Who took over from the author, once deactivated.
+    pub handed_over_to: Option<uuid::Uuid>,
+    pub date: chrono::NaiveDateTime,
+    pub note: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntityFileInfo {
+    pub file_id: uuid::Uuid,
+    pub filename: String,
+    pub notes: Vec<FileNoteInfo>,
+    pub metadata: serde_json::Value,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityInfo {
+    pub date: chrono::NaiveDateTime,
+    pub action_id: i16,
+    pub file_id: uuid::Uuid,
+    pub filename: String,
+    pub url: String,
+    pub user_id: uuid::Uuid,
+    pub user_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntityFiles {
+    pub files: Vec<EntityFileInfo>,
+    pub activity_feed: Vec<ActivityInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntityNoteInfo {
+    pub user_name: String,
+    pub user_id: uuid::Uuid,
+    /// Who took over from the author, once deactivated.
+    pub handed_over_to: Option<uuid::Uuid>,
+    pub date: chrono::NaiveDateTime,
+    pub note: String,
+    pub note_id: uuid::Uuid,
+    pub edited: bool,
+    pub mentions: Vec<uuid::Uuid>,
+    pub replies: Vec<EntityNoteInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NoteEditInfo {
+    pub user_name: String,
+    pub user_id: uuid::Uuid,
+    pub date: chrono::NaiveDateTime,
+    pub previous_note: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildingInfo {
+    #[serde(flatten)]
+    pub building: BuildingResponse,
+    pub registers: Vec<RegisterResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OwnerInfo {
+    pub profile: BuildingOwnerResponse,
+    pub coordinates: Option<CoordinateResponse>,
+    pub buildings: Vec<BuildingInfo>,
+    pub users: Vec<UserResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SharedFileInfo {
+    pub file_id: uuid::Uuid,
+    pub entity_id: uuid::Uuid,
+    pub folder: String,
+    pub filename: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OwnerRegisterInfo {
+    pub register_id: uuid::Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OwnerBuildingInfo {
+    pub building_id: uuid::Uuid,
+    pub name: String,
+    pub address: String,
+    pub registers: Vec<OwnerRegisterInfo>,
+    pub files: Vec<SharedFileInfo>,
+}
+
+/// Resolves the version an update applies to. A client sends the version it
+/// loaded, through `If-Match`, to be told when someone else saved in the
+/// meantime. Without one, the stored version still guards the update against
+/// a save racing it.
+fn expected_version(expected: Option<i32>, current: i32) -> Result<i32, APIError> {
+    match expected {
+        Some(version) if version != current => Err(APIError::Conflict),
+        _ => Ok(current),
+    }
+}
+
+/// Owner-role users only get read-only views of the buildings they own.
+fn ensure_not_owner(user: &User) -> Result<(), APIError> {
+    if user.is_owner() {
+        Err(APIError::Forbidden)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that the coordinates being assigned, if any, were created by the
+/// organization.
+fn ensure_org_coordinates(
+    conn: &PgConnection,
+    org_id: uuid::Uuid,
+    coordinates_id: Option<Option<uuid::Uuid>>
+) -> Result<(), APIError> {
+    if let Some(Some(coordinates_id)) = coordinates_id {
+        Coordinate::find_in_org(&conn, org_id, coordinates_id)
+            .ok_or_else(|| APIError::InvalidInput(String::from("Unknown coordinates")))?;
+    }
+
+    Ok(())
+}
+
+/// Checks that an entity, a building or one of its registers, is managed by
+/// the user's organization.
+fn ensure_manages_entity(conn: &PgConnection, user: &User, entity_id: uuid::Uuid) -> Result<(), APIError> {
+    let building_id = Register::find_by_id(&conn, entity_id)
+        .filter(|r| !r.is_deleted())
+        .map(|r| r.building_id())
+        .unwrap_or(entity_id);
+    let building = Building::find_by_id(&conn, building_id)
+        .filter(|b| !b.is_deleted())
+        .ok_or(APIError::NotFound)?;
+
+    if building.org_id() != user.org_id() {
+        return Err(APIError::Forbidden);
+    }
+
+    Ok(())
+}
+
+/// Creates an organization and its first admin, returning the admin's id.
+/// The admin's password follows the default password policy, and the admin
+/// is mailed a link to verify their email.
+pub fn register_new_organisation(conn: &PgConnection, mailer: &Mailer, fields: NewOrganization) -> Result<uuid::Uuid, APIError> {
+    fields.validate()?;
+    fields.admin.validate()?;
+    let org = Organization::new(fields.org_name, Vec::new(), None);
+
+    if User::email_taken(&conn, &fields.admin.email) {
+        return Err(APIError::InvalidInput(String::from("Email already in use")));
+    }
+
+    let admin = User::new(org.id(), PERMISSION_ADMIN, fields.admin, &org.password_policy())?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        Organization::insert(&conn, &org);
+        User::insert(&conn, &admin);
+        Ok(())
+    })?;
+    // The admin can ask for the link again if it could not be sent
+    send_email_verification(&conn, mailer, &admin, admin.email()).ok();
+
+    Ok(admin.id())
+}
+
+/// Mails the user a link to verify an address, their own email or the one
+/// they are changing to, in place of the link mailed before.
+fn send_email_verification(conn: &PgConnection, mailer: &Mailer, user: &User, email: &str) -> Result<(), APIError> {
+    let (verification, token) = EmailVerification::new(user.id(), email, mailer.link_hours());
+    EmailVerification::replace(&conn, &verification);
+
+    let (subject, request) = if user.has_email(email) {
+        ("Verify your email address", "Please confirm this is your email address")
+    } else {
+        ("Confirm your new email address", "Please confirm you want to use this email address for your account")
+    };
+    let body = format!(
+        "Hello {},\n\n{} by following this link:\n\n{}\n\nThe link expires in {} hours.\n",
+        user.full_name(), request, mailer.link("verify_email", &token), mailer.link_hours()
+    );
+
+    mailer.send(&Email { to: email.to_owned(), subject: subject.to_owned(), body })
+        .map_err(|_| APIError::Unavailable)
+}
+
+/// Verifies the address of the link mailed to a user. When the user was
+/// changing their email, the new one replaces it.
+pub fn verify_email(conn: &PgConnection, token: &str) -> Result<(), APIError> {
+    let (verification_id, secret) = EmailVerification::parse_token(token).ok_or(APIError::NotFound)?;
+    let verification = EmailVerification::find_active(&conn, verification_id)
+        .filter(|v| v.is_valid_secret(secret))
+        .ok_or(APIError::NotFound)?;
+    let mut user = User::find_by_id(&conn, verification.user_id())
+        .filter(|u| u.is_active())
+        .ok_or(APIError::NotFound)?;
+
+    if !user.has_email(verification.email()) && User::email_taken(&conn, verification.email()) {
+        return Err(APIError::InvalidInput(String::from("Email already in use")));
+    }
+
+    conn.transaction::<_, APIError, _>(|| {
+        user.confirm_email(verification.email().clone());
+        User::update(&conn, &user).ok_or(APIError::Conflict)?;
+        EmailVerification::delete_for_user(&conn, user.id());
+        Ok(())
+    })
+}
+
+/// Mails the user the link to verify their email again, or the new one they
+/// are changing to.
+pub fn resend_email_verification(conn: &PgConnection, mailer: &Mailer, user_id: uuid::Uuid) -> Result<(), APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+
+    let email = match EmailVerification::find_pending_for_user(&conn, user.id()) {
+        Some(pending) => pending.email().clone(),
+        None if !user.is_email_verified() => user.email().clone(),
+        None => return Err(APIError::InvalidInput(String::from("Email already verified"))),
+    };
+
+    send_email_verification(&conn, mailer, &user, &email)
+}
+
+/// Maps the deactivated users among the authors to the users who took over
+/// from them, shown along their notes.
+fn successors_of(conn: &PgConnection, user_ids: &[uuid::Uuid]) -> BTreeMap<uuid::Uuid, uuid::Uuid> {
+    User::get_successors(&conn, user_ids).into_iter().collect()
+}
+
+/// The profile of a user, with the email they are changing to.
+fn profile_response(conn: &PgConnection, user: &User) -> UserResponse {
+    let mut profile = UserResponse::from(user);
+    profile.pending_email = EmailVerification::find_pending_for_user(&conn, user.id())
+        .map(|pending| pending.email().clone())
+        .filter(|email| !user.has_email(email));
+
+    profile
+}
+
+pub fn get_entity_files(conn: &PgConnection, user_id: uuid::Uuid, entity_id: uuid::Uuid) -> Result<EntityFiles, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    ensure_manages_entity(&conn, &user, entity_id)?;
+
+    let files = File::get_by_entity_id(&conn, entity_id);
+    let file_ids: Vec<uuid::Uuid> = files.iter().map(|f| f.id()).collect();
+    let notes = FilesNote::get_by_file_ids(&conn, entity_id, &file_ids);
+    let author_ids: Vec<uuid::Uuid> = notes.iter().map(|(note, _)| note.user_id()).collect();
+    let successors = successors_of(&conn, &author_ids);
+
+    let files = files.into_iter().map(|file| EntityFileInfo {
+        file_id: file.id(),
+        filename: file.filename().clone(),
+        notes: notes.iter()
+            .filter(|(note, _)| note.file_id() == file.id())
+            .map(|(note, user_name)| FileNoteInfo {
+                note_id: note.id(),
+                user_id: note.user_id(),
+                user_name: user_name.clone(),
+                handed_over_to: successors.get(&note.user_id()).cloned(),
+                date: note.created_at(),
+                note: note.note().clone(),
+            })
+            .collect(),
+        metadata: file.metadata().clone(),
+        url: file.url().clone(),
+    }).collect();
+
+    let activity_feed = EntitiesHistory::get_recent_by_entity_id(&conn, entity_id, ACTIVITY_FEED_LENGTH)
+        .into_iter()
+        .map(|(hist, filename, url, user_name)| ActivityInfo {
+            date: hist.created_at(),
+            action_id: hist.action_id(),
+            file_id: hist.file_id(),
+            filename,
+            url,
+            user_id: hist.user_id(),
+            user_name,
+        })
+        .collect();
+
+    Ok(EntityFiles { files, activity_feed })
+}
+
+pub fn create_note_on_file(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    entity_id: uuid::Uuid,
+    file_id: uuid::Uuid,
+    fields: NoteText
+) -> Result<(), APIError> {
+    fields.validate()?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    ensure_manages_entity(&conn, &user, entity_id)?;
+
+    if !EntitiesFile::is_attached(&conn, file_id, entity_id) {
+        return Err(APIError::NotFound);
+    }
+
+    FilesNote::insert(&conn, &FilesNote::new(file_id, entity_id, user_id, fields.note));
+
+    Ok(())
+}
+
+/// Stores the `@email` mentions of a note, keeping only users of the
+/// author's organization.
+fn save_note_mentions(conn: &PgConnection, author: &User, entnote: &EntitiesNote) {
+    let mentioned = User::get_by_emails_in_org(&conn, author.org_id(), &entnote.mentioned_emails());
+    let mentions: Vec<EntitiesNotesMention> = mentioned.iter()
+        .map(|user| EntitiesNotesMention::new(entnote.id(), user.id()))
+        .collect();
+
+    if !mentions.is_empty() {
+        EntitiesNotesMention::insert_all(&conn, &mentions);
+    }
+}
+
+pub fn create_entity_note(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    entity_id: uuid::Uuid,
+    fields: NoteText
+) -> Result<uuid::Uuid, APIError> {
+    fields.validate()?;
+    let author = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&author)?;
+    ensure_manages_entity(&conn, &author, entity_id)?;
+    let entnote = EntitiesNote::new(entity_id, user_id, fields.note);
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        EntitiesNote::insert(&conn, &entnote);
+        save_note_mentions(&conn, &author, &entnote);
+        Ok(())
+    })?;
+
+    Ok(entnote.id())
+}
+
+pub fn reply_to_entity_note(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    note_id: uuid::Uuid,
+    fields: NoteText
+) -> Result<uuid::Uuid, APIError> {
+    fields.validate()?;
+    let author = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&author)?;
+    let parent = EntitiesNote::find_by_id(&conn, note_id)
+        .filter(|n| !n.is_deleted())
+        .ok_or(APIError::NotFound)?;
+    ensure_manages_entity(&conn, &author, parent.entity_id())?;
+    let reply = EntitiesNote::new_reply(&parent, user_id, fields.note);
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        EntitiesNote::insert(&conn, &reply);
+        save_note_mentions(&conn, &author, &reply);
+        Ok(())
+    })?;
+
+    Ok(reply.id())
+}
+
+/// Changes the text of a note. Only the author may edit a note, and the
+/// previous text is kept in the note's edit log.
+pub fn edit_entity_note(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    note_id: uuid::Uuid,
+    fields: NoteText
+) -> Result<(), APIError> {
+    fields.validate()?;
+    let author = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&author)?;
+    let mut entnote = EntitiesNote::find_by_id(&conn, note_id)
+        .filter(|n| !n.is_deleted())
+        .ok_or(APIError::NotFound)?;
+
+    if entnote.user_id() != user_id {
+        return Err(APIError::Forbidden);
+    }
+
+    conn.transaction::<_, APIError, _>(|| {
+        let previous_note = entnote.set_note(fields.note);
+        EntitiesNotesEdit::insert(&conn, &EntitiesNotesEdit::new(entnote.id(), user_id, previous_note));
+        EntitiesNote::update(&conn, &entnote).ok_or(APIError::Conflict)?;
+        EntitiesNotesMention::delete_by_note_id(&conn, entnote.id());
+        save_note_mentions(&conn, &author, &entnote);
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Deletes a note. Only the author or an admin of the author's organization
+/// may delete a note. Deleting the root of a thread deletes its replies.
+pub fn delete_entity_note(conn: &PgConnection, user_id: uuid::Uuid, note_id: uuid::Uuid) -> Result<(), APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    let mut entnote = EntitiesNote::find_by_id(&conn, note_id)
+        .filter(|n| !n.is_deleted())
+        .ok_or(APIError::NotFound)?;
+
+    if entnote.user_id() != user_id {
+        let author = User::find_by_id(&conn, entnote.user_id()).ok_or(APIError::NotFound)?;
+        if !user.is_admin() || user.org_id() != author.org_id() {
+            return Err(APIError::Forbidden);
+        }
+    }
+
+    entnote.mark_deleted();
+    conn.transaction::<_, APIError, _>(|| {
+        EntitiesNote::update(&conn, &entnote).ok_or(APIError::Conflict)?;
+        if entnote.parent_id().is_none() {
+            EntitiesNote::delete_replies(&conn, entnote.id());
+        }
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Returns one page of the note threads of an entity, newest thread first,
+/// each with its replies oldest first.
+pub fn get_entity_notes(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    entity_id: uuid::Uuid,
+    page: i64,
+    page_size: i64
+) -> Result<Vec<EntityNoteInfo>, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    ensure_manages_entity(&conn, &user, entity_id)?;
+
+    let page_size = page_size.max(1).min(MAX_PAGE_SIZE);
+    let threads = EntitiesNote::get_threads_by_entity_id(&conn, entity_id, page.max(0) * page_size, page_size);
+    let thread_ids: Vec<uuid::Uuid> = threads.iter().map(|(n, _)| n.id()).collect();
+    let replies = EntitiesNote::get_replies(&conn, &thread_ids);
+
+    let note_ids: Vec<uuid::Uuid> = thread_ids.iter().cloned()
+        .chain(replies.iter().map(|(n, _)| n.id()))
+        .collect();
+    let mentions = EntitiesNotesMention::get_by_note_ids(&conn, &note_ids);
+    let edited = EntitiesNotesEdit::get_edited_note_ids(&conn, &note_ids);
+    let author_ids: Vec<uuid::Uuid> = threads.iter().chain(replies.iter()).map(|(n, _)| n.user_id()).collect();
+    let successors = successors_of(&conn, &author_ids);
+
+    let to_info = |entnote: &EntitiesNote, user_name: &String| EntityNoteInfo {
+        user_name: user_name.clone(),
+        user_id: entnote.user_id(),
+        handed_over_to: successors.get(&entnote.user_id()).cloned(),
+        date: entnote.created_at(),
+        note: entnote.note().clone(),
+        note_id: entnote.id(),
+        edited: edited.contains(&entnote.id()),
+        mentions: mentions.iter()
+            .filter(|m| m.note_id() == entnote.id())
+            .map(|m| m.user_id())
+            .collect(),
+        replies: Vec::new(),
+    };
+
+    Ok(threads.iter().map(|(thread, user_name)| EntityNoteInfo {
+        replies: replies.iter()
+            .filter(|(reply, _)| reply.parent_id() == Some(thread.id()))
+            .map(|(reply, reply_user_name)| to_info(reply, reply_user_name))
+            .collect(),
+        ..to_info(thread, user_name)
+    }).collect())
+}
+
+pub fn get_entity_note_edits(conn: &PgConnection, user_id: uuid::Uuid, note_id: uuid::Uuid) -> Result<Vec<NoteEditInfo>, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    let entnote = EntitiesNote::find_by_id(&conn, note_id)
+        .filter(|n| !n.is_deleted())
+        .ok_or(APIError::NotFound)?;
+    ensure_manages_entity(&conn, &user, entnote.entity_id())?;
+
+    Ok(EntitiesNotesEdit::get_by_note_id(&conn, note_id)
+        .into_iter()
+        .map(|(edit, user_name)| NoteEditInfo {
+            user_name,
+            user_id: edit.user_id(),
+            date: edit.created_at(),
+            previous_note: edit.previous_note().clone(),
+        })
+        .collect())
+}
+
+/// Returns the owner profile with its buildings, their registers and the
+/// users involved with the owner: its linked user and the respondants of
+/// its buildings, unless deleted or deactivated. Deleted owners are not
+/// found. Runs in a fixed number of queries.
+pub fn get_owner_info(conn: &PgConnection, user_id: uuid::Uuid, owner_id: uuid::Uuid) -> Result<OwnerInfo, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    let owner = BuildingOwner::find_by_id(&conn, owner_id)
+        .filter(|o| !o.is_deleted())
+        .ok_or(APIError::NotFound)?;
+
+    if owner.org_id() != Some(user.org_id()) {
+        return Err(APIError::Forbidden);
+    }
+
+    let coordinates = owner.coordinates_id()
+        .and_then(|id| Coordinate::find_by_id(&conn, id))
+        .map(|c| CoordinateResponse::from(&c));
+
+    let buildings = Building::get_by_owners(&conn, std::slice::from_ref(&owner))
+        .pop()
+        .unwrap_or_default();
+    let registers = Register::get_by_buildings(&conn, &buildings);
+
+    let mut user_ids: Vec<uuid::Uuid> = buildings.iter().map(|b| b.respondant_id()).collect();
+    user_ids.extend(owner.linked_user_id());
+    let users = User::get_by_ids(&conn, &user_ids).iter().map(UserResponse::from).collect();
+
+    Ok(OwnerInfo {
+        profile: BuildingOwnerResponse::from(&owner),
+        coordinates,
+        buildings: buildings.iter()
+            .zip(registers)
+            .map(|(building, registers)| BuildingInfo {
+                building: BuildingResponse::from(building),
+                registers: registers.iter().map(RegisterResponse::from).collect(),
+            })
+            .collect(),
+        users,
+    })
+}
+
+/// Lists the owners of the user's organization, one page at a time.
+pub fn get_owners(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    filter: OwnerFilter,
+    query: ListQuery
+) -> Result<Page<BuildingOwnerResponse>, APIError> {
+    let request = query.page_request(OWNER_SORT_FIELDS).map_err(APIError::InvalidInput)?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let owners = BuildingOwner::list(&conn, user.org_id(), &filter, &request);
+
+    Ok(Page::new(owners, &request, |o| o.sort_key(request.field)).map(|o| BuildingOwnerResponse::from(o)))
+}
+
+/// Checks the `same_as_manager` flag against the requested owner kind: only
+/// the managing organization itself is the same as the manager.
+fn same_as_manager_kind(same_as_manager: bool, kind: OwnerKind) -> Result<OwnerKind, APIError> {
+    match (same_as_manager, kind) {
+        (true, OwnerKind::Manager) | (false, OwnerKind::Individual) | (false, OwnerKind::Company) => Ok(kind),
+        _ => Err(APIError::InvalidInput(String::from("same_as_manager contradicts the owner kind"))),
+    }
+}
+
+/// Applies the requested owner fields, `None` leaving a field unchanged.
+/// A linked user must be an active owner user, whichever organization
+/// created the account, and coordinates must belong to the owner's
+/// organization. The managing
+/// organization keeps its own name and coordinates, any other value given
+/// for it is left for `BuildingOwner::validate` to reject.
+fn apply_owner_fields(
+    conn: &PgConnection,
+    owner: &mut BuildingOwner,
+    kind: OwnerKind,
+    full_name: Option<String>,
+    linked_user_id: Option<Option<uuid::Uuid>>,
+    coordinates_id: Option<Option<uuid::Uuid>>
+) -> Result<(), APIError> {
+    let org_id = owner.org_id().ok_or(APIError::NotFound)?;
+    if let Some(Some(linked_id)) = linked_user_id {
+        User::find_by_id(&conn, linked_id)
+            .filter(|u| u.is_owner() && u.is_active())
+            .ok_or_else(|| APIError::InvalidInput(String::from("Unknown linked user")))?;
+    }
+    ensure_org_coordinates(&conn, org_id, coordinates_id)?;
+
+    if kind == OwnerKind::Manager {
+        if let Some(Some(linked_id)) = linked_user_id {
+            owner.set_linked_user_id(Some(linked_id));
+        }
+    } else {
+        if let Some(full_name) = full_name {
+            owner.set_full_name(full_name);
+        }
+        if let Some(linked_user_id) = linked_user_id {
+            owner.set_linked_user_id(linked_user_id);
+        }
+    }
+
+    if let Some(coordinates_id) = coordinates_id {
+        owner.set_coordinates_id(coordinates_id);
+    }
+
+    Ok(())
+}
+
+fn requested_owner_kind(fields: &NewBuildingOwner) -> Result<OwnerKind, APIError> {
+    fields.validate_owner()?;
+    let kind = OwnerKind::from_i16(fields.kind)
+        .ok_or_else(|| APIError::InvalidInput(String::from("Unknown owner kind")))?;
+    same_as_manager_kind(fields.same_as_manager, kind)
+}
+
+/// Resolves the owner kind after a patch: a missing kind is deduced from
+/// `same_as_manager`, or kept when it does not contradict it.
+fn patched_owner_kind(owner: &BuildingOwner, patch: &BuildingOwnerPatch) -> Result<OwnerKind, APIError> {
+    let kind = match patch.kind {
+        Some(kind) => OwnerKind::from_i16(kind)
+            .ok_or_else(|| APIError::InvalidInput(String::from("Unknown owner kind")))?,
+        None => match (patch.same_as_manager, owner.kind()) {
+            (Some(true), _) => OwnerKind::Manager,
+            (Some(false), Some(OwnerKind::Manager)) | (_, None) => {
+                return Err(APIError::InvalidInput(String::from("The owner kind is required")));
+            },
+            (_, Some(kind)) => kind,
+        },
+    };
+
+    same_as_manager_kind(patch.same_as_manager.unwrap_or(kind == OwnerKind::Manager), kind)
+}
+
+pub fn create_new_owner(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    fields: NewBuildingOwner
+) -> Result<uuid::Uuid, APIError> {
+    let kind = requested_owner_kind(&fields)?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    let manager = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+
+    if kind == OwnerKind::Manager && BuildingOwner::find_manager_owner(&conn, manager.id()).is_some() {
+        return Err(APIError::InvalidInput(String::from("The organization already has an owner profile")));
+    }
+
+    let mut owner = BuildingOwner::new(String::new(), kind, Some(manager.id()), None, None);
+    owner.set_kind(kind, &manager);
+    let coordinates_id = if kind == OwnerKind::Manager && fields.coordinates_id.is_none() {
+        None
+    } else {
+        Some(fields.coordinates_id)
+    };
+    apply_owner_fields(&conn, &mut owner, kind, Some(fields.full_name), Some(fields.linked_user_id), coordinates_id)?;
+    owner.validate(&manager).map_err(APIError::InvalidInput)?;
+
+    BuildingOwner::insert(&conn, &owner);
+
+    Ok(owner.id())
+}
+
+/// Applies a partial update to an owner, checking the owner rules on the
+/// result.
+pub fn modify_owner_info(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    owner_id: uuid::Uuid,
+    version: Option<i32>,
+    patch: BuildingOwnerPatch
+) -> Result<BuildingOwnerResponse, APIError> {
+    patch.validate()?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    let manager = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+    let mut owner = BuildingOwner::find_by_id(&conn, owner_id)
+        .filter(|o| !o.is_deleted())
+        .ok_or(APIError::NotFound)?;
+
+    if owner.org_id() != Some(manager.id()) {
+        return Err(APIError::Forbidden);
+    }
+    expected_version(version, owner.version())?;
+
+    let kind = patched_owner_kind(&owner, &patch)?;
+
+    if kind == OwnerKind::Manager {
+        if let Some(existing) = BuildingOwner::find_manager_owner(&conn, manager.id()) {
+            if existing.id() != owner.id() {
+                return Err(APIError::InvalidInput(String::from("The organization already has an owner profile")));
+            }
+        }
+    }
+
+    owner.set_kind(kind, &manager);
+    apply_owner_fields(&conn, &mut owner, kind, patch.full_name, patch.linked_user_id, patch.coordinates_id)?;
+    owner.validate(&manager).map_err(APIError::InvalidInput)?;
+
+    let owner = BuildingOwner::update(&conn, &owner).ok_or(APIError::Conflict)?;
+
+    Ok(BuildingOwnerResponse::from(&owner))
+}
+
+/// Builds the owner views of the given buildings: their registers and the
+/// files, of the buildings or of their registers, filed in a shared folder.
+fn owner_building_views(conn: &PgConnection, buildings: Vec<Building>) -> Vec<OwnerBuildingInfo> {
+    let registers = Register::get_by_buildings(&conn, &buildings);
+    let building_ids: Vec<uuid::Uuid> = buildings.iter().map(|b| b.id()).collect();
+    let shared = SharedFolder::get_by_building_ids(&conn, &building_ids);
+
+    let entity_ids: Vec<uuid::Uuid> = building_ids.iter().cloned()
+        .chain(registers.iter().flatten().map(|r| r.id()))
+        .collect();
+    let files = File::get_with_folders_by_entity_ids(&conn, &entity_ids);
+
+    buildings.into_iter().zip(registers).map(|(building, registers)| {
+        let shared_folders: Vec<&String> = shared.iter()
+            .filter(|s| s.building_id() == building.id())
+            .map(|s| s.folder())
+            .collect();
+
+        let files = files.iter()
+            .filter(|(_, entity_id, folder)| {
+                (*entity_id == building.id() || registers.iter().any(|r| r.id() == *entity_id)) &&
+                shared_folders.contains(&folder)
+            })
+            .map(|(file, entity_id, folder)| SharedFileInfo {
+                file_id: file.id(),
+                entity_id: *entity_id,
+                folder: folder.clone(),
+                filename: file.filename().clone(),
+                url: file.url().clone(),
+            })
+            .collect();
+
+        OwnerBuildingInfo {
+            building_id: building.id(),
+            name: building.name().clone(),
+            address: building.address().clone(),
+            registers: registers.iter()
+                .map(|r| OwnerRegisterInfo { register_id: r.id(), name: r.name().clone() })
+                .collect(),
+            files,
+        }
+    }).collect()
+}
+
+/// Returns the buildings owned by every owner profile linked to an
+/// owner-role user, whichever organization manages them.
+pub fn get_owner_buildings(conn: &PgConnection, user_id: uuid::Uuid) -> Result<Vec<OwnerBuildingInfo>, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !user.is_owner() {
+        return Err(APIError::Forbidden);
+    }
+
+    let owners = BuildingOwner::get_by_linked_user_id(&conn, user_id);
+    let buildings = Building::get_by_owners(&conn, &owners).into_iter().flatten().collect();
+
+    Ok(owner_building_views(&conn, buildings))
+}
+
+pub fn get_owner_building(conn: &PgConnection, user_id: uuid::Uuid, building_id: uuid::Uuid) -> Result<OwnerBuildingInfo, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !user.is_owner() {
+        return Err(APIError::Forbidden);
+    }
+
+    let building = Building::find_by_id(&conn, building_id)
+        .filter(|b| !b.is_deleted())
+        .ok_or(APIError::NotFound)?;
+    let owner = BuildingOwner::find_by_id(&conn, building.owner_id()).ok_or(APIError::NotFound)?;
+
+    // Buildings of other owners are reported as missing, not forbidden
+    if owner.linked_user_id() != Some(user_id) {
+        return Err(APIError::NotFound);
+    }
+
+    owner_building_views(&conn, vec![building]).pop().ok_or(APIError::NotFound)
+}
+
+/// Loads a building the user's organization manages, for the staff
+/// operations controlling what the owner sees.
+fn managed_building(conn: &PgConnection, user_id: uuid::Uuid, building_id: uuid::Uuid) -> Result<Building, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let building = Building::find_by_id(&conn, building_id)
+        .filter(|b| !b.is_deleted())
+        .ok_or(APIError::NotFound)?;
+
+    if building.org_id() != user.org_id() {
+        return Err(APIError::Forbidden);
+    }
+
+    Ok(building)
+}
+
+pub fn get_shared_folders(conn: &PgConnection, user_id: uuid::Uuid, building_id: uuid::Uuid) -> Result<Vec<String>, APIError> {
+    let building = managed_building(&conn, user_id, building_id)?;
+
+    Ok(SharedFolder::get_by_building_ids(&conn, &[building.id()])
+        .into_iter()
+        .map(|s| s.folder().clone())
+        .collect())
+}
+
+pub fn share_folder_with_owner(conn: &PgConnection, user_id: uuid::Uuid, building_id: uuid::Uuid, folder: String) -> Result<(), APIError> {
+    if validate_not_blank(&folder).is_err() {
+        return Err(APIError::InvalidInput(String::from("The folder name is required")));
+    }
+    let building = managed_building(&conn, user_id, building_id)?;
+    SharedFolder::insert(&conn, &SharedFolder::new(building.id(), folder));
+
+    Ok(())
+}
+
+pub fn unshare_folder_with_owner(conn: &PgConnection, user_id: uuid::Uuid, building_id: uuid::Uuid, folder: String) -> Result<(), APIError> {
+    let building = managed_building(&conn, user_id, building_id)?;
+
+    if SharedFolder::unshare(&conn, building.id(), &folder) == 0 {
+        return Err(APIError::NotFound);
+    }
+
+    Ok(())
+}
+
+/// Opens a session for the user with these credentials and returns its
+/// token. An unknown email and a wrong password are not told apart. Users
+/// with two-factor authentication also send a code of their authenticator
+/// app or one of their recovery codes.
+///
+/// Every login is recorded along with where it comes from. An account is
+/// locked for a while after too many failed logins in a row, and so is an
+/// address with too many failed logins lately. The session expires after
+/// `session_hours`.
+pub fn login(
+    conn: &PgConnection,
+    email: &str,
+    password: &str,
+    code: Option<&str>,
+    client: &LoginClient,
+    session_hours: i64
+) -> Result<String, APIError> {
+    let user = User::find_by_email(&conn, email);
+    check_throttling(&conn, user.as_ref(), email, client)?;
+    let user_id = user.as_ref().map(|u| u.id());
+    let record = |outcome| LoginAttempt::insert(&conn, &LoginAttempt::new(user_id, email, client, outcome));
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            record(LoginOutcome::BadCredentials);
+            return Err(APIError::Unauthorized);
+        }
+    };
+
+    if !user.is_valid_password(password) {
+        record(LoginOutcome::BadCredentials);
+        AccountLockout::record_failure(&conn, user.id());
+        return Err(APIError::Unauthorized);
+    }
+
+    if let Some(mut two_factor) = TwoFactor::find_by_user_id(&conn, user.id()).filter(|t| t.is_enabled()) {
+        let code = code.ok_or(APIError::TwoFactorRequired)?;
+        if !two_factor.redeem_code(&conn, code) && !RecoveryCode::redeem(&conn, user.id(), code) {
+            record(LoginOutcome::BadCode);
+            AccountLockout::record_failure(&conn, user.id());
+            return Err(APIError::Unauthorized);
+        }
+    }
+
+    record(LoginOutcome::Success);
+    AccountLockout::clear(&conn, user.id());
+    let (session, token) = Session::new(user.id(), client, session_hours);
+    Session::insert(&conn, &session);
+
+    Ok(token)
+}
+
+/// Refuses, recording it, a password from an address with too many failed
+/// logins lately, or for a locked account.
+fn check_throttling(conn: &PgConnection, user: Option<&User>, email: &str, client: &LoginClient) -> Result<(), APIError> {
+    let record = |outcome| LoginAttempt::insert(&conn, &LoginAttempt::new(user.map(|u| u.id()), email, client, outcome));
+
+    let window_start = Utc::now().naive_utc() - Duration::minutes(IP_WINDOW_MINUTES);
+    if LoginAttempt::count_ip_failures(&conn, &client.ip_address, window_start) >= MAX_IP_FAILURES {
+        record(LoginOutcome::Throttled);
+        return Err(APIError::TooManyAttempts(IP_WINDOW_MINUTES * 60));
+    }
+
+    if let Some(until) = user.and_then(|u| AccountLockout::find(&conn, u.id())).and_then(|l| l.locked_until()) {
+        record(LoginOutcome::Locked);
+        return Err(APIError::TooManyAttempts((until - Utc::now().naive_utc()).num_seconds().max(1)));
+    }
+
     Ok(())
 }
 
-//pub fn create_new_building() -> Building {
+/// Registers the identity provider the members of the admin's organization
+/// sign in with, replacing the previous one.
+pub fn set_sso_config(conn: &PgConnection, user_id: uuid::Uuid, fields: NewOidcProvider) -> Result<OidcProviderResponse, APIError> {
+    fields.validate()?;
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
 
-//}
+    let provider = OidcProvider::new(admin.org_id(), fields);
+    let provider = conn.transaction::<_, diesel::result::Error, _>(|| {
+        OidcProvider::delete_for_org(&conn, admin.org_id());
+        Ok(OidcProvider::insert(&conn, &provider))
+    })?;
+
+    Ok(OidcProviderResponse::from(&provider))
+}
+
+pub fn get_sso_config(conn: &PgConnection, user_id: uuid::Uuid) -> Result<OidcProviderResponse, APIError> {
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    let provider = OidcProvider::find_by_org(&conn, admin.org_id()).ok_or(APIError::NotFound)?;
+
+    Ok(OidcProviderResponse::from(&provider))
+}
+
+/// Turns single sign-on off. The users it created keep their accounts, but
+/// have no password until an admin sets one.
+pub fn delete_sso_config(conn: &PgConnection, user_id: uuid::Uuid) -> Result<(), APIError> {
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    match OidcProvider::delete_for_org(&conn, admin.org_id()) {
+        0 => Err(APIError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Starts signing in to the organization through its identity provider,
+/// returning the URL to send the user to.
+pub fn begin_sso(conn: &PgConnection, org_id: uuid::Uuid) -> Result<String, APIError> {
+    let provider = OidcProvider::find_by_org(&conn, org_id).ok_or(APIError::NotFound)?;
+
+    let state = SsoState::insert(&conn, &SsoState::new(org_id));
+    provider.authorization_url(&state).map_err(APIError::InvalidInput)
+}
+
+/// The provider of a sign-in's state, and the identity it vouches for given
+/// the code it sent the user back with.
+fn sso_identity(conn: &PgConnection, code: &str, state: &str) -> Result<(OidcProvider, SsoIdentity), APIError> {
+    let state = SsoState::take(&conn, state).ok_or(APIError::Unauthorized)?;
+    let provider = OidcProvider::find_by_org(&conn, state.org_id()).ok_or(APIError::Unauthorized)?;
+
+    let id_token = provider.exchange_code(code, &state).map_err(|_| APIError::Unauthorized)?;
+    let identity = provider.identity(&id_token, state.nonce()).map_err(|_| APIError::Unauthorized)?;
+
+    Ok((provider, identity))
+}
+
+/// Completes a sign-in with the code and state the identity provider sent
+/// the user back with, and opens a session. Users are created on their
+/// first sign-in, and their permission follows their groups at every
+/// sign-in. An account of the organization is only matched by its email if
+/// the provider verified it. Failed sign-ins are recorded, and throttled
+/// like logins. The session expires after `session_hours`.
+pub fn complete_sso(
+    conn: &PgConnection,
+    code: &str,
+    state: &str,
+    client: &LoginClient,
+    session_hours: i64
+) -> Result<String, APIError> {
+    check_throttling(&conn, None, "", client)?;
+    let record_failure = |email: &str| LoginAttempt::insert(&conn, &LoginAttempt::new(None, email, client, LoginOutcome::SsoFailed));
+
+    let (provider, identity) = sso_identity(&conn, code, state).map_err(|e| {
+        record_failure("");
+        e
+    })?;
+
+    let token = conn.transaction::<_, APIError, _>(|| {
+        let permission = provider.permission_for(&identity.groups).ok_or(APIError::Forbidden)?;
+        let mut user = match OidcIdentity::find_user_id(&conn, provider.org_id(), &identity.subject) {
+            Some(user_id) => User::find_by_id(&conn, user_id)
+                .filter(|u| u.is_active())
+                .ok_or(APIError::Unauthorized)?,
+            None => {
+                let user = match User::find_by_email(&conn, &identity.email) {
+                    Some(user) if user.org_id() == provider.org_id() => {
+                        if !identity.email_verified || !user.is_active() {
+                            return Err(APIError::Forbidden);
+                        }
+                        user
+                    }
+                    Some(_) => return Err(APIError::InvalidInput(String::from("Email already in use"))),
+                    None if User::email_taken(&conn, &identity.email) => {
+                        return Err(APIError::InvalidInput(String::from("Email already in use")));
+                    }
+                    None => {
+                        // Unknown to anyone
+                        let mut user = User::new_federated(
+                            provider.org_id(),
+                            permission,
+                            identity.full_name.clone(),
+                            identity.email.clone()
+                        );
+                        if identity.email_verified {
+                            user.confirm_email(identity.email.clone());
+                        }
+                        User::insert(&conn, &user)
+                    }
+                };
+                OidcIdentity::insert(&conn, &OidcIdentity::new(provider.org_id(), user.id(), &identity.subject));
+                user
+            }
+        };
+
+        if user.is_owner() {
+            return Err(APIError::Forbidden);
+        }
+        if user.permission() != permission {
+            user.set_permission(permission);
+            user = User::update(&conn, &user).ok_or(APIError::Conflict)?;
+        }
+
+        LoginAttempt::insert(&conn, &LoginAttempt::new(Some(user.id()), &identity.email, client, LoginOutcome::Success));
+        let (session, token) = Session::new(user.id(), client, session_hours);
+        Session::insert(&conn, &session);
+
+        Ok(token)
+    });
+    if token.is_err() {
+        record_failure(&identity.email);
+    }
+
+    token
+}
+
+/// Lets a user of the admin's organization log in again right away after
+/// too many failed logins.
+pub fn unlock_user(conn: &PgConnection, admin_id: uuid::Uuid, user_id: uuid::Uuid) -> Result<(), APIError> {
+    let user = managed_user(&conn, admin_id, user_id)?;
+    AccountLockout::clear(&conn, user.id());
+
+    Ok(())
+}
+
+/// Fails when the user's organization requires two-factor authentication
+/// and the user has not enabled it. Such users may only enroll or log out.
+pub fn ensure_two_factor_enrolled(conn: &PgConnection, user_id: uuid::Uuid) -> Result<(), APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::Unauthorized)?;
+    let org = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+
+    if org.requires_two_factor() && !TwoFactor::is_enabled_for_user(&conn, user_id) {
+        Err(APIError::TwoFactorEnrollmentRequired)
+    } else {
+        Ok(())
+    }
+}
+
+/// Draws a new TOTP secret for the user, replacing a pending one. It is only
+/// used once confirmed with a first code.
+pub fn begin_two_factor(conn: &PgConnection, user_id: uuid::Uuid) -> Result<TwoFactorEnrollment, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if TwoFactor::is_enabled_for_user(&conn, user_id) {
+        return Err(APIError::InvalidInput(String::from("Two-factor authentication is already enabled")));
+    }
+
+    let two_factor = TwoFactor::new(user_id);
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        TwoFactor::delete_for_user(&conn, user_id);
+        TwoFactor::insert(&conn, &two_factor);
+        Ok(())
+    })?;
+
+    Ok(two_factor.enrollment(user.email()))
+}
+
+/// Enables the pending secret of the user given one of its codes, returning
+/// the recovery codes, which are only shown this once.
+pub fn confirm_two_factor(conn: &PgConnection, user_id: uuid::Uuid, code: &str) -> Result<Vec<String>, APIError> {
+    let mut two_factor = TwoFactor::find_by_user_id(&conn, user_id)
+        .filter(|t| !t.is_enabled())
+        .ok_or(APIError::NotFound)?;
+    if !two_factor.redeem_code(&conn, code) {
+        return Err(APIError::InvalidInput(String::from("Invalid code")));
+    }
+
+    two_factor.enable();
+    let codes = conn.transaction::<_, diesel::result::Error, _>(|| {
+        TwoFactor::update(&conn, &two_factor);
+        Ok(RecoveryCode::regenerate(&conn, user_id))
+    })?;
+
+    Ok(codes)
+}
+
+/// Replaces the recovery codes of the user given a code of the
+/// authenticator app.
+pub fn regenerate_recovery_codes(conn: &PgConnection, user_id: uuid::Uuid, code: &str) -> Result<Vec<String>, APIError> {
+    let mut two_factor = TwoFactor::find_by_user_id(&conn, user_id)
+        .filter(|t| t.is_enabled())
+        .ok_or(APIError::NotFound)?;
+    if !two_factor.redeem_code(&conn, code) {
+        return Err(APIError::InvalidInput(String::from("Invalid code")));
+    }
+
+    let codes = conn.transaction::<_, diesel::result::Error, _>(|| Ok(RecoveryCode::regenerate(&conn, user_id)))?;
+
+    Ok(codes)
+}
+
+/// Turns two-factor authentication off given a code of the authenticator
+/// app or a recovery code, unless the organization requires it.
+pub fn disable_two_factor(conn: &PgConnection, user_id: uuid::Uuid, code: &str) -> Result<(), APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    let org = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+    if org.requires_two_factor() {
+        return Err(APIError::Forbidden);
+    }
+
+    let mut two_factor = TwoFactor::find_by_user_id(&conn, user_id)
+        .filter(|t| t.is_enabled())
+        .ok_or(APIError::NotFound)?;
+
+    conn.transaction::<_, APIError, _>(|| {
+        if !two_factor.redeem_code(&conn, code) && !RecoveryCode::redeem(&conn, user_id, code) {
+            return Err(APIError::InvalidInput(String::from("Invalid code")));
+        }
+
+        TwoFactor::delete_for_user(&conn, user_id);
+        RecoveryCode::delete_all_for_user(&conn, user_id);
+        Ok(())
+    })
+}
+
+/// Returns the user of an open session token.
+pub fn authenticate(conn: &PgConnection, token: &str) -> Result<uuid::Uuid, APIError> {
+    let (session_id, secret) = Session::parse_token(token).ok_or(APIError::Unauthorized)?;
+    let session = Session::find_active(&conn, session_id)
+        .filter(|s| s.is_valid_secret(secret))
+        .ok_or(APIError::Unauthorized)?;
+    Session::touch(&conn, session.id());
+
+    Ok(session.user_id())
+}
+
+/// The organization of a user, for the logs of their requests.
+pub fn get_user_org_id(conn: &PgConnection, user_id: uuid::Uuid) -> Option<uuid::Uuid> {
+    User::find_by_id(&conn, user_id).map(|user| user.org_id())
+}
+
+pub fn logout(conn: &PgConnection, token: &str) -> Result<(), APIError> {
+    authenticate(&conn, token)?;
+    let (session_id, _) = Session::parse_token(token).ok_or(APIError::Unauthorized)?;
+    Session::close(&conn, session_id);
+
+    Ok(())
+}
+
+/// Lists the open sessions of the user, telling apart the one of the token.
+pub fn get_sessions(conn: &PgConnection, user_id: uuid::Uuid, token: &str) -> Result<Vec<SessionResponse>, APIError> {
+    let current_id = Session::parse_token(token).map(|(id, _)| id);
+
+    Ok(Session::get_active_for_user(&conn, user_id).iter()
+        .map(|session| SessionResponse::new(session, Some(session.id()) == current_id))
+        .collect())
+}
+
+pub fn revoke_session(conn: &PgConnection, user_id: uuid::Uuid, session_id: uuid::Uuid) -> Result<(), APIError> {
+    match Session::close_for_user(&conn, user_id, session_id) {
+        0 => Err(APIError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Closes the sessions of the user but the one of the token.
+pub fn revoke_other_sessions(conn: &PgConnection, user_id: uuid::Uuid, token: &str) -> Result<(), APIError> {
+    let (session_id, _) = Session::parse_token(token).ok_or(APIError::Unauthorized)?;
+    Session::close_others_for_user(&conn, user_id, session_id);
+
+    Ok(())
+}
+
+/// Loads a user of the admin's organization.
+fn managed_user(conn: &PgConnection, admin_id: uuid::Uuid, user_id: uuid::Uuid) -> Result<User, APIError> {
+    let admin = User::find_by_id(&conn, admin_id).ok_or(APIError::NotFound)?;
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    User::find_by_id(&conn, user_id)
+        .filter(|u| u.org_id() == admin.org_id())
+        .ok_or(APIError::NotFound)
+}
+
+/// Deactivates a user of the admin's organization, who can no longer log in
+/// nor use the API keys they created, and closes the user's sessions. The
+/// buildings the user is the respondant of must be handed over first.
+pub fn deactivate_user(conn: &PgConnection, admin_id: uuid::Uuid, user_id: uuid::Uuid) -> Result<(), APIError> {
+    let mut user = managed_user(&conn, admin_id, user_id)?;
+    if user.id() == admin_id {
+        return Err(APIError::InvalidInput(String::from("Cannot deactivate yourself")));
+    }
+    if !user.is_active() {
+        return Ok(());
+    }
+
+    user.deactivate(&conn).map_err(APIError::InvalidInput)
+}
+
+/// Hands the work of a user of the admin's organization over to another
+/// active user: the buildings the user is the respondant of, and the
+/// mentions calling on the user in notes. The notes the user wrote keep
+/// their author, and point to the successor once the user is deactivated.
+pub fn hand_over_user(
+    conn: &PgConnection,
+    admin_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    fields: Handover
+) -> Result<HandoverReport, APIError> {
+    let mut user = managed_user(&conn, admin_id, user_id)?;
+    let successor = User::find_by_id(&conn, fields.successor_id)
+        .filter(|s| s.org_id() == user.org_id() && s.is_active() && !s.is_owner() && s.id() != user.id())
+        .ok_or_else(|| APIError::InvalidInput(String::from("Unknown successor")))?;
+
+    user.hand_over_to(successor.id());
+    conn.transaction::<_, APIError, _>(|| {
+        User::update(&conn, &user).ok_or(APIError::Conflict)?;
+
+        Ok(HandoverReport {
+            buildings: Building::reassign_respondant(&conn, user.id(), successor.id()),
+            mentions: EntitiesNotesMention::reassign(&conn, user.id(), successor.id()),
+        })
+    })
+}
+
+/// Lists the open sessions of a user of the admin's organization.
+pub fn get_user_sessions(conn: &PgConnection, admin_id: uuid::Uuid, user_id: uuid::Uuid) -> Result<Vec<SessionResponse>, APIError> {
+    let user = managed_user(&conn, admin_id, user_id)?;
+
+    Ok(Session::get_active_for_user(&conn, user.id()).iter()
+        .map(|session| SessionResponse::new(session, false))
+        .collect())
+}
+
+pub fn revoke_user_session(
+    conn: &PgConnection,
+    admin_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    session_id: uuid::Uuid
+) -> Result<(), APIError> {
+    let user = managed_user(&conn, admin_id, user_id)?;
+
+    match Session::close_for_user(&conn, user.id(), session_id) {
+        0 => Err(APIError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Logs a user of the admin's organization out everywhere.
+pub fn revoke_user_sessions(conn: &PgConnection, admin_id: uuid::Uuid, user_id: uuid::Uuid) -> Result<(), APIError> {
+    let user = managed_user(&conn, admin_id, user_id)?;
+    Session::close_all_for_user(&conn, user.id());
+
+    Ok(())
+}
+
+/// Checks an API key and returns the id of the admin it acts as. Keys are
+/// only accepted by the routes requiring a scope, and only if they were
+/// granted it.
+pub fn authenticate_api_key(conn: &PgConnection, token: &str, scope: Option<ApiScope>) -> Result<uuid::Uuid, APIError> {
+    let (key_id, secret) = ApiKey::parse_token(token).ok_or(APIError::Unauthorized)?;
+    let key = ApiKey::find_active(&conn, key_id)
+        .filter(|k| k.is_valid_secret(secret))
+        .ok_or(APIError::Unauthorized)?;
+    User::find_by_id(&conn, key.created_by())
+        .filter(|u| u.is_active() && u.org_id() == key.org_id())
+        .ok_or(APIError::Unauthorized)?;
+
+    match scope {
+        Some(scope) if key.has_scope(scope) => (),
+        _ => return Err(APIError::Forbidden),
+    }
+    ApiKey::touch(&conn, key.id());
+
+    Ok(key.created_by())
+}
+
+/// Lists the API keys of the admin's organization that were not revoked.
+pub fn get_api_keys(conn: &PgConnection, user_id: uuid::Uuid) -> Result<Vec<ApiKeyResponse>, APIError> {
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    Ok(ApiKey::get_by_org(&conn, admin.org_id()).iter().map(ApiKeyResponse::from).collect())
+}
+
+/// Creates an API key of the admin's organization, returning its id and
+/// its token, which is only ever shown here.
+pub fn create_api_key(conn: &PgConnection, user_id: uuid::Uuid, fields: NewApiKey) -> Result<(uuid::Uuid, String), APIError> {
+    fields.validate()?;
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    if fields.expires_at.map_or(false, |date| date <= Utc::now().naive_utc()) {
+        return Err(APIError::InvalidInput(String::from("Expiry date in the past")));
+    }
+
+    let (key, token) = ApiKey::new(admin.org_id(), admin.id(), fields);
+    ApiKey::insert(&conn, &key);
+
+    Ok((key.id(), token))
+}
+
+pub fn revoke_api_key(conn: &PgConnection, user_id: uuid::Uuid, key_id: uuid::Uuid) -> Result<(), APIError> {
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    match ApiKey::revoke(&conn, admin.org_id(), key_id) {
+        0 => Err(APIError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Creates a member of the admin's organization, or the account of a
+/// building owner with the `owner` role, who is mailed a link to verify
+/// their email. The password follows the organization's policy.
+pub fn create_new_user(conn: &PgConnection, mailer: &Mailer, user_id: uuid::Uuid, fields: NewUser) -> Result<uuid::Uuid, APIError> {
+    fields.validate()?;
+    let permission = fields.permission().map_err(APIError::InvalidInput)?;
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    let org = Organization::find_by_id(&conn, admin.org_id()).ok_or(APIError::NotFound)?;
+
+    if User::email_taken(&conn, &fields.email) {
+        return Err(APIError::InvalidInput(String::from("Email already in use")));
+    }
+
+    let user = User::new(admin.org_id(), permission, fields, &org.password_policy())?;
+    User::insert(&conn, &user);
+    // The user can ask for the link again if it could not be sent
+    send_email_verification(&conn, mailer, &user, user.email()).ok();
+
+    Ok(user.id())
+}
+
+pub fn create_coordinate(conn: &PgConnection, user_id: uuid::Uuid, fields: NewCoordinate) -> Result<uuid::Uuid, APIError> {
+    fields.validate()?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let coordinate = Coordinate::from_new(user.org_id(), fields);
+    Coordinate::insert(&conn, &coordinate);
+
+    Ok(coordinate.id())
+}
+
+/// Creates a building managed by the user's organization. The owner and the
+/// respondant must both belong to that organization.
+pub fn create_new_building(conn: &PgConnection, user_id: uuid::Uuid, fields: NewBuilding) -> Result<uuid::Uuid, APIError> {
+    fields.validate()?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let owner = BuildingOwner::find_by_id(&conn, fields.owner_id)
+        .filter(|o| o.org_id() == Some(user.org_id()))
+        .ok_or_else(|| APIError::InvalidInput(String::from("Unknown owner")))?;
+    let respondant = User::find_by_id(&conn, fields.respondant_id)
+        .filter(|r| r.org_id() == user.org_id() && r.is_active() && !r.is_owner())
+        .ok_or_else(|| APIError::InvalidInput(String::from("Unknown respondant")))?;
+
+    let building = Building::new(owner.id(), user.org_id(), respondant.id(), fields.name, fields.address);
+    Building::insert(&conn, &building);
+
+    Ok(building.id())
+}
+
+pub fn create_new_register(conn: &PgConnection, user_id: uuid::Uuid, fields: NewRegister) -> Result<uuid::Uuid, APIError> {
+    fields.validate()?;
+    let building = managed_building(&conn, user_id, fields.building_id)?;
+
+    let register = Register::new(fields.name, building.id());
+    Register::insert(&conn, &register);
+
+    Ok(register.id())
+}
+
+/// Imports buildings with their owners from a CSV or XLSX file. Nothing is
+/// saved unless every row is valid, and nothing at all for a dry run.
+pub fn import_buildings(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    format: &str,
+    data: &[u8],
+    dry_run: bool
+) -> Result<ImportReport, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let format = ImportFormat::from_name(format)
+        .ok_or_else(|| APIError::InvalidInput(String::from("Expected the csv or xlsx format")))?;
+    let records = import::read_records(format, data).map_err(APIError::InvalidInput)?;
+
+    import::import_records(&conn, &user, records, dry_run)
+}
+
+/// Exports the organization's buildings, or only those of one of its
+/// owners, as a file of the format. A file the writer refuses, such as an
+/// xlsx cell longer than a spreadsheet holds, fails with a 500.
+pub fn export_buildings(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    format: &str,
+    owner_id: Option<uuid::Uuid>
+) -> Result<(ExportFormat, Vec<u8>), APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let format = ExportFormat::from_name(format)
+        .ok_or_else(|| APIError::InvalidInput(String::from("Expected the csv, xlsx or json format")))?;
+    if let Some(owner_id) = owner_id {
+        BuildingOwner::find_by_id(&conn, owner_id)
+            .filter(|o| o.org_id() == Some(user.org_id()))
+            .ok_or_else(|| APIError::InvalidInput(String::from("Unknown owner")))?;
+    }
+
+    let rows = export::portfolio(&conn, user.org_id(), owner_id);
+    let data = export::render(format, &rows).map_err(APIError::Rendering)?;
+
+    Ok((format, data))
+}
+
+/// Lists the users of the user's organization, one page at a time.
+pub fn get_users(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    filter: UserFilter,
+    query: ListQuery
+) -> Result<Page<UserResponse>, APIError> {
+    let request = query.page_request(USER_SORT_FIELDS).map_err(APIError::InvalidInput)?;
+    let permission = filter.permission().map_err(APIError::InvalidInput)?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let users = User::list(&conn, user.org_id(), permission, &request);
+
+    Ok(Page::new(users, &request, |u| u.sort_key(request.field)).map(|u| UserResponse::from(u)))
+}
+
+pub fn get_user_profile(conn: &PgConnection, user_id: uuid::Uuid) -> Result<UserResponse, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+
+    Ok(profile_response(&conn, &user))
+}
+
+/// Changes the user's password given the current one. The new password
+/// follows the organization's policy. Every session of the user is closed,
+/// the one asking included, so that the user logs in again everywhere.
+///
+/// A wrong current password counts as a failed login from `client`, so
+/// that a stolen session cannot be used to guess it.
+pub fn change_password(conn: &PgConnection, user_id: uuid::Uuid, fields: PasswordChange, client: &LoginClient) -> Result<(), APIError> {
+    fields.validate()?;
+    let mut user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    let org = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+    check_throttling(&conn, Some(&user), user.email(), client)?;
+
+    match user.change_password(&conn, &fields.old_password, &fields.new_password, &org.password_policy()) {
+        Ok(()) => {
+            AccountLockout::clear(&conn, user.id());
+            Ok(())
+        }
+        Err(PasswordError::WrongPassword) => {
+            LoginAttempt::insert(&conn, &LoginAttempt::new(Some(user.id()), user.email(), client, LoginOutcome::BadCredentials));
+            AccountLockout::record_failure(&conn, user.id());
+            Err(APIError::InvalidInput(String::from("Wrong password entered")))
+        }
+        Err(PasswordError::Policy(errors)) => Err(APIError::from(errors)),
+    }
+}
+
+/// Changes the profile of the user. A new email only replaces the current
+/// one once confirmed from the link mailed to it, changing its case does
+/// not need confirming.
+pub fn modify_user_profile(
+    conn: &PgConnection,
+    mailer: &Mailer,
+    user_id: uuid::Uuid,
+    version: Option<i32>,
+    mut patch: UserPatch
+) -> Result<UserResponse, APIError> {
+    patch.validate()?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    let version = expected_version(version, user.version())?;
+
+    let new_email = match patch.email.take() {
+        Some(email) if !user.has_email(&email) => Some(email),
+        same => {
+            patch.email = same;
+            None
+        }
+    };
+    if new_email.as_ref().map_or(false, |email| User::email_taken(&conn, email)) {
+        return Err(APIError::InvalidInput(String::from("Email already in use")));
+    }
+
+    let user = if patch.is_empty() {
+        user
+    } else {
+        User::patch(&conn, user.id(), version, &patch).ok_or(APIError::Conflict)?
+    };
+
+    if let Some(email) = new_email {
+        send_email_verification(&conn, mailer, &user, &email)?;
+        // In case someone else is taking over the account
+        mailer.send(&Email {
+            to: user.email().clone(),
+            subject: String::from("Your email address is changing"),
+            body: format!(
+                "Hello {},\n\nYou asked to use {} as the email address of your account instead of this one. \
+                 The change takes effect once confirmed from the new address. If you did not ask for it, \
+                 change your password.\n",
+                user.full_name(), email
+            ),
+        }).ok();
+    }
+
+    Ok(profile_response(&conn, &user))
+}
+
+pub fn get_organisation_info(conn: &PgConnection, user_id: uuid::Uuid) -> Result<OrganizationResponse, APIError> {
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    let org = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+
+    Ok(OrganizationResponse::from(&org))
+}
+
+/// Applies a partial update to the admin's organization. Its owner profile,
+/// if it owns buildings itself, follows a change of coordinates.
+pub fn modify_organisation_info(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    version: Option<i32>,
+    patch: OrganizationPatch
+) -> Result<OrganizationResponse, APIError> {
+    patch.validate()?;
+    let admin = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+
+    if !admin.is_admin() {
+        return Err(APIError::Forbidden);
+    }
+
+    let org = Organization::find_by_id(&conn, admin.org_id()).ok_or(APIError::NotFound)?;
+    let version = expected_version(version, org.version())?;
+    if patch.is_empty() {
+        return Ok(OrganizationResponse::from(&org));
+    }
+    ensure_org_coordinates(&conn, org.id(), patch.coordinates_id)?;
+
+    let org = conn.transaction::<_, APIError, _>(|| {
+        let org = Organization::patch(&conn, org.id(), version, &patch).ok_or(APIError::Conflict)?;
+
+        if let Some(mut owner) = BuildingOwner::find_manager_owner(&conn, org.id()) {
+            owner.set_kind(OwnerKind::Manager, &org);
+            BuildingOwner::update(&conn, &owner).ok_or(APIError::Conflict)?;
+        }
+
+        Ok(org)
+    })?;
+
+    Ok(OrganizationResponse::from(&org))
+}
+
+/// Applies a partial update to coordinates created by the user's
+/// organization.
+pub fn modify_coordinate(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    coordinates_id: uuid::Uuid,
+    version: Option<i32>,
+    patch: CoordinatePatch
+) -> Result<CoordinateResponse, APIError> {
+    patch.validate()?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+    let org = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+    let coordinate = Coordinate::find_by_id(&conn, coordinates_id).ok_or(APIError::NotFound)?;
+
+    if coordinate.org_id() != Some(org.id()) {
+        return Err(APIError::Forbidden);
+    }
+    let version = expected_version(version, coordinate.version())?;
+
+    if patch.is_empty() {
+        return Ok(CoordinateResponse::from(&coordinate));
+    }
+
+    let coordinate = Coordinate::patch(&conn, coordinate.id(), version, &patch).ok_or(APIError::Conflict)?;
+
+    Ok(CoordinateResponse::from(&coordinate))
+}
+
+/// Lists the buildings managed by the user's organization, one page at a
+/// time.
+pub fn get_buildings(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    filter: BuildingFilter,
+    query: ListQuery
+) -> Result<Page<BuildingResponse>, APIError> {
+    let request = query.page_request(BUILDING_SORT_FIELDS).map_err(APIError::InvalidInput)?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let buildings = Building::list(&conn, user.org_id(), &filter, &request);
+
+    Ok(Page::new(buildings, &request, |b| b.sort_key(request.field)).map(|b| BuildingResponse::from(b)))
+}
+
+/// Lists the registers of the buildings managed by the user's organization,
+/// one page at a time.
+pub fn get_registers(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    filter: RegisterFilter,
+    query: ListQuery
+) -> Result<Page<RegisterResponse>, APIError> {
+    let request = query.page_request(REGISTER_SORT_FIELDS).map_err(APIError::InvalidInput)?;
+    let user = User::find_by_id(&conn, user_id).ok_or(APIError::NotFound)?;
+    ensure_not_owner(&user)?;
+
+    let registers = Register::list(&conn, user.org_id(), &filter, &request);
+
+    Ok(Page::new(registers, &request, |r| r.sort_key(request.field)).map(|r| RegisterResponse::from(r)))
+}
+
+pub fn get_building_information(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    building_id: uuid::Uuid
+) -> Result<BuildingInfo, APIError> {
+    let building = managed_building(&conn, user_id, building_id)?;
+    let registers = Register::get_by_buildings(&conn, std::slice::from_ref(&building))
+        .pop()
+        .unwrap_or_default();
+
+    Ok(BuildingInfo {
+        building: BuildingResponse::from(&building),
+        registers: registers.iter().map(RegisterResponse::from).collect(),
+    })
+}
+
+/// Renders the printable PDF dossier of a building: its owner, respondant,
+/// registers with their recent entries, latest notes and files.
+pub fn get_building_dossier(conn: &PgConnection, user_id: uuid::Uuid, building_id: uuid::Uuid) -> Result<Vec<u8>, APIError> {
+    let building = managed_building(&conn, user_id, building_id)?;
+    let org = Organization::find_by_id(&conn, building.org_id()).ok_or(APIError::NotFound)?;
+    let owner = BuildingOwner::find_by_id(&conn, building.owner_id()).ok_or(APIError::NotFound)?;
+    let owner_coordinates = owner.coordinates_id().and_then(|id| Coordinate::find_by_id(&conn, id));
+    let respondant = User::find_by_id(&conn, building.respondant_id()).ok_or(APIError::NotFound)?;
+
+    let registers = Register::get_by_buildings(&conn, std::slice::from_ref(&building))
+        .pop()
+        .unwrap_or_default();
+    let register_ids: Vec<uuid::Uuid> = registers.iter().map(|r| r.id()).collect();
+    let mut activity: BTreeMap<uuid::Uuid, Vec<ActivityInfo>> = BTreeMap::new();
+    for (hist, filename, url, user_name) in EntitiesHistory::get_recent_by_entity_ids(&conn, &register_ids, ACTIVITY_FEED_LENGTH) {
+        activity.entry(hist.entity_id()).or_insert_with(Vec::new).push(ActivityInfo {
+            date: hist.created_at(),
+            action_id: hist.action_id(),
+            file_id: hist.file_id(),
+            filename,
+            url,
+            user_id: hist.user_id(),
+            user_name,
+        });
+    }
+    let registers = registers.iter()
+        .map(|r| (RegisterResponse::from(r), activity.remove(&r.id()).unwrap_or_default()))
+        .collect();
+
+    let dossier = Dossier {
+        org_name: org.org_name().clone(),
+        logo: org.profile_picture().clone(),
+        building: BuildingResponse::from(&building),
+        owner: BuildingOwnerResponse::from(&owner),
+        owner_coordinates: owner_coordinates.as_ref().map(CoordinateResponse::from),
+        respondant: UserResponse::from(&respondant),
+        registers,
+        files: get_entity_files(&conn, user_id, building.id())?.files,
+        notes: get_entity_notes(&conn, user_id, building.id(), 0, DOSSIER_NOTES)?,
+    };
+
+    dossier::render(&dossier).map_err(APIError::Rendering)
+}
+
+/// Applies a partial update to a building of the user's organization. A new
+/// owner or respondant must belong to that organization.
+pub fn modify_building(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    building_id: uuid::Uuid,
+    version: Option<i32>,
+    patch: BuildingPatch
+) -> Result<BuildingResponse, APIError> {
+    patch.validate()?;
+    let building = managed_building(&conn, user_id, building_id)?;
+    let version = expected_version(version, building.version())?;
+
+    if let Some(owner_id) = patch.owner_id {
+        BuildingOwner::find_by_id(&conn, owner_id)
+            .filter(|o| o.org_id() == Some(building.org_id()))
+            .ok_or_else(|| APIError::InvalidInput(String::from("Unknown owner")))?;
+    }
+    if let Some(respondant_id) = patch.respondant_id {
+        User::find_by_id(&conn, respondant_id)
+            .filter(|r| r.org_id() == building.org_id() && r.is_active() && !r.is_owner())
+            .ok_or_else(|| APIError::InvalidInput(String::from("Unknown respondant")))?;
+    }
+
+    if patch.is_empty() {
+        return Ok(BuildingResponse::from(&building));
+    }
+
+    let building = Building::patch(&conn, building.id(), version, &patch).ok_or(APIError::Conflict)?;
+
+    Ok(BuildingResponse::from(&building))
+}
+
+pub fn modify_register(
+    conn: &PgConnection,
+    user_id: uuid::Uuid,
+    register_id: uuid::Uuid,
+    version: Option<i32>,
+    patch: RegisterPatch
+) -> Result<RegisterResponse, APIError> {
+    patch.validate()?;
+    let register = Register::find_by_id(&conn, register_id)
+        .filter(|r| !r.is_deleted())
+        .ok_or(APIError::NotFound)?;
+    managed_building(&conn, user_id, register.building_id())?;
+    let version = expected_version(version, register.version())?;
+
+    if patch.is_empty() {
+        return Ok(RegisterResponse::from(&register));
+    }
+
+    let register = Register::patch(&conn, register.id(), version, &patch).ok_or(APIError::Conflict)?;
+
+    Ok(RegisterResponse::from(&register))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{APIError, ResponseError};
+    use actix_web::http::{header, StatusCode};
+    use validator::{ValidationError, ValidationErrors};
+
+    #[test]
+    fn test_error_response_status() {
+        assert_eq!(APIError::Unauthorized.error_response().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(APIError::NotFound.error_response().status(), StatusCode::NOT_FOUND);
+        assert_eq!(APIError::Forbidden.error_response().status(), StatusCode::FORBIDDEN);
+        assert_eq!(APIError::Conflict.error_response().status(), StatusCode::CONFLICT);
+        assert_eq!(APIError::InvalidInput(String::from("Unknown owner")).error_response().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(APIError::Database(diesel::result::Error::NotFound).error_response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(APIError::Unavailable.error_response().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = APIError::TooManyAttempts(60).error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "60");
+    }
+
+    #[test]
+    fn test_expected_version() {
+        assert_eq!(super::expected_version(None, 3).unwrap(), 3);
+        assert_eq!(super::expected_version(Some(3), 3).unwrap(), 3);
+        match super::expected_version(Some(2), 3) {
+            Err(APIError::Conflict) => (),
+            other => panic!("Expected a conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validation_error_response() {
+        let mut errors = ValidationErrors::new();
+        errors.add("email", ValidationError::new("email"));
+        errors.add("full_name", ValidationError::new("length"));
+        errors.add("full_name", ValidationError::new("blank"));
+
+        let error = APIError::from(errors);
+        assert_eq!(error.error_response().status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        if let APIError::Validation(errors) = &error {
+            let fields = super::field_error_map(errors);
+            assert_eq!(fields["email"], vec!["email"]);
+            assert_eq!(fields["full_name"], vec!["length", "blank"]);
+        } else {
+            panic!("Expected a validation error");
+        }
+    }
+}
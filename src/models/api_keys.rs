@@ -0,0 +1,290 @@
+use crate::schema::api_keys;
+use crate::schema::api_keys::dsl;
+use crate::utils::HASHER;
+use crate::utils::validators::validate_not_blank;
+
+use chrono::Utc;
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use libreauth::pass::HashBuilder;
+
+use serde_derive::{Deserialize, Serialize};
+
+use validator::{Validate, ValidationError};
+
+/// Starts the tokens of API keys, telling them apart from session tokens.
+pub const API_KEY_PREFIX: &str = "key_";
+
+/// What an API key may do. Routes accepting API keys require one scope,
+/// the others refuse them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiScope {
+    /// Reading buildings, their owners, registers, files and notes.
+    ReadBuildings,
+    /// Creating and renaming registers.
+    WriteRegisters,
+    /// Uploading files, which for now means importing buildings.
+    UploadFiles,
+}
+
+impl ApiScope {
+    pub const ALL: &'static [ApiScope] = &[ApiScope::ReadBuildings, ApiScope::WriteRegisters, ApiScope::UploadFiles];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiScope::ReadBuildings => "read_buildings",
+            ApiScope::WriteRegisters => "write_registers",
+            ApiScope::UploadFiles => "upload_files",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ApiScope> {
+        ApiScope::ALL.iter().cloned().find(|scope| scope.name() == name)
+    }
+}
+
+fn validate_scopes(scopes: &[String]) -> Result<(), ValidationError> {
+    if scopes.is_empty() || scopes.iter().any(|scope| ApiScope::from_name(scope).is_none()) {
+        return Err(ValidationError::new("scopes"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewApiKey {
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub name: String,
+    /// Names of the `ApiScope`s granted to the key.
+    #[validate(custom = "validate_scopes")]
+    pub scopes: Vec<String>,
+    /// Never expires when left out.
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// What the API returns for a key, without its secret.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_by: uuid::Uuid,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<&ApiKey> for ApiKeyResponse {
+    fn from(key: &ApiKey) -> Self {
+        ApiKeyResponse {
+            id: key.id,
+            name: key.name.clone(),
+            scopes: key.scopes.clone(),
+            created_by: key.created_by,
+            expires_at: key.expires_at,
+            last_used_at: key.last_used_at,
+            created_at: key.created_at,
+        }
+    }
+}
+
+/// A key letting a machine call the API on behalf of the organization,
+/// acting as the admin who created it within the key's scopes. Like with
+/// sessions, the client holds the key id and a random secret, only a hash
+/// of the secret is stored.
+#[derive(Insertable, Queryable, Identifiable, Debug)]
+pub struct ApiKey {
+    id: uuid::Uuid,
+    org_id: uuid::Uuid,
+    created_by: uuid::Uuid,
+    name: String,
+    secret: String,
+    scopes: Vec<String>,
+    expires_at: Option<chrono::NaiveDateTime>,
+    last_used_at: Option<chrono::NaiveDateTime>,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime
+}
+
+impl ApiKey {
+    /// Creates a key of the organization, returning it along with the token
+    /// to hand to the client, `key_<key id>.<secret>`.
+    pub fn new(org_id: uuid::Uuid, created_by: uuid::Uuid, fields: NewApiKey) -> (ApiKey, String) {
+        let id = uuid::Uuid::new_v4();
+        let secret = format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4()).replace("-", "");
+
+        let key = ApiKey {
+            id,
+            org_id,
+            created_by,
+            name: fields.name,
+            secret: HASHER.hash(&secret).unwrap(),
+            scopes: fields.scopes,
+            expires_at: fields.expires_at,
+            last_used_at: None,
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+        };
+
+        (key, format!("{}{}.{}", API_KEY_PREFIX, id, secret))
+    }
+
+    pub fn is_key_token(token: &str) -> bool {
+        token.starts_with(API_KEY_PREFIX)
+    }
+
+    /// Splits a key token into its key id and secret.
+    pub fn parse_token(token: &str) -> Option<(uuid::Uuid, &str)> {
+        if !ApiKey::is_key_token(token) {
+            return None;
+        }
+
+        let mut parts = token[API_KEY_PREFIX.len()..].splitn(2, '.');
+        let id = parts.next().and_then(|id| uuid::Uuid::parse_str(id).ok())?;
+        let secret = parts.next().filter(|secret| !secret.is_empty())?;
+
+        Some((id, secret))
+    }
+
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    pub fn org_id(&self) -> uuid::Uuid {
+        self.org_id
+    }
+
+    pub fn created_by(&self) -> uuid::Uuid {
+        self.created_by
+    }
+
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.iter().any(|name| name == scope.name())
+    }
+
+    pub fn is_valid_secret(&self, secret: &str) -> bool {
+        HashBuilder::from_phc(&self.secret)
+            .map(|checker| checker.is_valid(secret))
+            .unwrap_or(false)
+    }
+
+    pub(super) fn insert(conn: &PgConnection, key: &ApiKey) -> ApiKey {
+        diesel::insert_into(api_keys::table)
+            .values(key)
+            .get_result(conn)
+            .expect("Error saving API key")
+    }
+
+    /// Loads a key that was neither revoked nor expired.
+    pub(super) fn find_active(conn: &PgConnection, key_id: uuid::Uuid) -> Option<ApiKey> {
+        dsl::api_keys
+            .filter(dsl::id.eq(key_id))
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::expires_at.is_null().or(dsl::expires_at.gt(Utc::now().naive_utc())))
+            .first(conn)
+            .optional()
+            .expect("Error loading API key")
+    }
+
+    /// Lists the keys of the organization that were not revoked, expired
+    /// ones included, oldest first.
+    pub(super) fn get_by_org(conn: &PgConnection, org_id: uuid::Uuid) -> Vec<ApiKey> {
+        dsl::api_keys
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::created_at.asc())
+            .load::<ApiKey>(conn)
+            .expect("Error loading API keys")
+    }
+
+    pub(super) fn touch(conn: &PgConnection, key_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::api_keys.filter(dsl::id.eq(key_id)))
+            .set(dsl::last_used_at.eq(Utc::now().naive_utc()))
+            .execute(conn)
+            .expect("Error saving API key")
+    }
+
+    /// Revokes a key of the organization, returning the number of keys
+    /// revoked.
+    pub(super) fn revoke(conn: &PgConnection, org_id: uuid::Uuid, key_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::api_keys
+                .filter(dsl::id.eq(key_id))
+                .filter(dsl::org_id.eq(org_id))
+                .filter(dsl::deleted.eq(false)))
+            .set((dsl::deleted.eq(true), dsl::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error revoking API key")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiKey, ApiScope, NewApiKey, Validate};
+    use super::super::users::{User, test_functions::*};
+    use crate::db;
+    use chrono::{Duration, Utc};
+    use diesel::Connection;
+    use diesel::result::Error;
+
+    fn new_key(scopes: &[&str]) -> NewApiKey {
+        NewApiKey {
+            name: String::from("Comptabilité"),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_scopes() {
+        assert!(new_key(&["read_buildings", "upload_files"]).validate().is_ok());
+        assert!(new_key(&[]).validate().is_err());
+        assert!(new_key(&["read_buildings", "delete_everything"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_key_tokens() {
+        let (key, token) = ApiKey::new(uuid::Uuid::new_v4(), uuid::Uuid::new_v4(), new_key(&["read_buildings"]));
+
+        let (id, secret) = ApiKey::parse_token(&token).unwrap();
+        assert_eq!(id, key.id());
+        assert!(key.is_valid_secret(secret));
+        assert!(!key.is_valid_secret("wrong"));
+        assert!(key.has_scope(ApiScope::ReadBuildings));
+        assert!(!key.has_scope(ApiScope::WriteRegisters));
+
+        assert!(ApiKey::parse_token(&format!("{}.{}", key.id(), secret)).is_none());
+    }
+
+    #[test]
+    fn test_expired_and_revoked_keys() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("API_KEYS"));
+            User::insert(&conn, &user);
+
+            let (key, _) = ApiKey::new(user.org_id(), user.id(), new_key(&["read_buildings"]));
+            ApiKey::insert(&conn, &key);
+            let mut expired = new_key(&["read_buildings"]);
+            expired.expires_at = Some(Utc::now().naive_utc() - Duration::days(1));
+            let (expired, _) = ApiKey::new(user.org_id(), user.id(), expired);
+            ApiKey::insert(&conn, &expired);
+
+            assert!(ApiKey::find_active(&conn, key.id()).is_some());
+            assert!(ApiKey::find_active(&conn, expired.id()).is_none());
+            assert_eq!(ApiKey::get_by_org(&conn, user.org_id()).len(), 2);
+
+            assert_eq!(ApiKey::revoke(&conn, uuid::Uuid::new_v4(), key.id()), 0);
+            assert_eq!(ApiKey::revoke(&conn, user.org_id(), key.id()), 1);
+            assert!(ApiKey::find_active(&conn, key.id()).is_none());
+
+            Ok(())
+        });
+    }
+}
@@ -1,5 +1,9 @@
 use crate::schema::building_owners;
 use crate::schema::building_owners::dsl;
+use crate::utils::nullable::deserialize_nullable;
+use crate::utils::validators::validate_not_blank;
+use super::list_query::{contains_pattern, PageRequest};
+use super::organizations::Organization;
 
 use chrono::Utc;
 use chrono::naive::NaiveDateTime;
@@ -9,12 +13,120 @@ use diesel::pg::PgConnection;
 
 use serde_derive::{Deserialize, Serialize};
 
+use validator::{Validate, ValidationError, ValidationErrors};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OwnerKind {
+    Individual = 0,
+    Company = 1,
+    Manager = 2,
+}
+
+impl OwnerKind {
+    pub fn from_i16(kind: i16) -> Option<OwnerKind> {
+        match kind {
+            0 => Some(OwnerKind::Individual),
+            1 => Some(OwnerKind::Company),
+            2 => Some(OwnerKind::Manager),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OwnerKind::Individual => "individual",
+            OwnerKind::Company => "company",
+            OwnerKind::Manager => "manager",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
 pub struct NewBuildingOwner {
-    full_name: String,
-    is_manager: bool,
-    org_id: Option<uuid::Uuid>,
-    linked_user_id: Option<uuid::Uuid>,
-    coordinates_id: Option<uuid::Uuid>
+    #[validate(length(max = 255))]
+    #[serde(default)]
+    pub full_name: String,
+    pub same_as_manager: bool,
+    #[validate(range(min = 0, max = 2))]
+    pub kind: i16,
+    pub linked_user_id: Option<uuid::Uuid>,
+    pub coordinates_id: Option<uuid::Uuid>
+}
+
+impl NewBuildingOwner {
+    /// Validates the fields, and requires a name from every owner but the
+    /// managing organization, which uses the organization's name.
+    pub fn validate_owner(&self) -> Result<(), ValidationErrors> {
+        let mut errors = self.validate().err().unwrap_or_else(ValidationErrors::new);
+
+        if !self.same_as_manager && validate_not_blank(&self.full_name).is_err() {
+            errors.add("full_name", ValidationError::new("required"));
+        }
+
+        if errors.errors().is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Owner fields to change, fields left to `None` are not updated. Unlike
+/// the other patches it is not a changeset: the owner rules span several
+/// fields, so the patch is applied to the stored owner and the result is
+/// validated as a whole before being saved.
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct BuildingOwnerPatch {
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub full_name: Option<String>,
+    pub same_as_manager: Option<bool>,
+    #[validate(range(min = 0, max = 2))]
+    pub kind: Option<i16>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub linked_user_id: Option<Option<uuid::Uuid>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub coordinates_id: Option<Option<uuid::Uuid>>,
+}
+
+/// Fields the owners listing can be sorted by.
+pub const OWNER_SORT_FIELDS: &[&str] = &["full_name"];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OwnerFilter {
+    pub kind: Option<i16>,
+    /// Part of the name, case insensitive.
+    pub full_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildingOwnerResponse {
+    pub id: uuid::Uuid,
+    pub full_name: String,
+    pub kind: i16,
+    pub is_manager: bool,
+    pub org_id: Option<uuid::Uuid>,
+    pub linked_user_id: Option<uuid::Uuid>,
+    pub coordinates_id: Option<uuid::Uuid>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub version: i32,
+}
+
+impl From<&BuildingOwner> for BuildingOwnerResponse {
+    fn from(bo: &BuildingOwner) -> Self {
+        BuildingOwnerResponse {
+            id: bo.id,
+            full_name: bo.full_name.clone(),
+            kind: bo.kind,
+            is_manager: bo.is_manager,
+            org_id: bo.org_id,
+            linked_user_id: bo.linked_user_id,
+            coordinates_id: bo.coordinates_id,
+            created_at: bo.created_at,
+            updated_at: bo.updated_at,
+            version: bo.version,
+        }
+    }
 }
 
 #[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
@@ -29,7 +141,9 @@ pub struct BuildingOwner {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    kind: i16,
+    version: i32
 }
 
 impl PartialEq for BuildingOwner {
@@ -43,14 +157,15 @@ impl PartialEq for BuildingOwner {
         self.deleted == other.deleted &&
         self.created_at.timestamp() == other.created_at.timestamp() &&
         self.updated_at.timestamp() == other.updated_at.timestamp() &&
-        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.kind == other.kind
     }
 }
 
 impl BuildingOwner {
     pub fn new(
         full_name: String,
-        is_manager: bool,
+        kind: OwnerKind,
         org_id: Option<uuid::Uuid>,
         linked_user_id: Option<uuid::Uuid>,
         coordinates_id: Option<uuid::Uuid>
@@ -58,21 +173,127 @@ impl BuildingOwner {
         BuildingOwner {
             id: uuid::Uuid::new_v4(),
             full_name,
-            is_manager,
+            is_manager: kind == OwnerKind::Manager,
             org_id,
             linked_user_id,
             coordinates_id,
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            kind: kind as i16,
+            version: 1
         }
     }
 
+    /// Creates the owner profile of an organization that owns the buildings
+    /// it manages. The profile reuses the organization's name and coordinates.
+    pub fn new_same_as_manager(manager: &Organization) -> BuildingOwner {
+        BuildingOwner::new(
+            manager.org_name().clone(),
+            OwnerKind::Manager,
+            Some(manager.id()),
+            None,
+            manager.coordinates_id()
+        )
+    }
+
     pub fn id(&self) -> uuid::Uuid {
         self.id
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn full_name(&self) -> &String {
+        &self.full_name
+    }
+
+    pub fn kind(&self) -> Option<OwnerKind> {
+        OwnerKind::from_i16(self.kind)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    pub fn org_id(&self) -> Option<uuid::Uuid> {
+        self.org_id
+    }
+
+    /// Changes the owner kind. Becoming the managing organization reuses the
+    /// organization's name and coordinates and drops any linked user.
+    pub fn set_kind(&mut self, kind: OwnerKind, manager: &Organization) {
+        self.kind = kind as i16;
+        self.is_manager = kind == OwnerKind::Manager;
+
+        if kind == OwnerKind::Manager {
+            self.full_name = manager.org_name().clone();
+            self.org_id = Some(manager.id());
+            self.linked_user_id = None;
+            self.coordinates_id = manager.coordinates_id();
+        }
+    }
+
+    pub fn set_full_name(&mut self, full_name: String) {
+        self.full_name = full_name;
+    }
+
+    pub fn set_linked_user_id(&mut self, linked_user_id: Option<uuid::Uuid>) {
+        self.linked_user_id = linked_user_id;
+    }
+
+    pub fn set_coordinates_id(&mut self, coordinates_id: Option<uuid::Uuid>) {
+        self.coordinates_id = coordinates_id;
+    }
+
+    /// Checks that the owner kind, manager flag, organization and
+    /// coordinates of the owner agree with each other.
+    pub fn validate(&self, manager: &Organization) -> Result<(), String> {
+        if self.org_id != Some(manager.id()) {
+            return Err(String::from("The owner must belong to the managing organization"));
+        }
+
+        match self.kind() {
+            None => Err(String::from("Unknown owner kind")),
+            Some(OwnerKind::Manager) => {
+                if !self.is_manager {
+                    Err(String::from("An owner that is the managing organization must be flagged as manager"))
+                } else if self.coordinates_id != manager.coordinates_id() {
+                    Err(String::from("An owner that is the managing organization must use the organization's coordinates"))
+                } else if self.linked_user_id.is_some() {
+                    Err(String::from("An owner that is the managing organization cannot be linked to a user"))
+                } else {
+                    Ok(())
+                }
+            },
+            Some(kind) => {
+                if self.is_manager {
+                    Err(String::from("Only the managing organization can be flagged as manager"))
+                } else if self.coordinates_id.is_some() && self.coordinates_id == manager.coordinates_id() {
+                    Err(String::from("Only the managing organization can use the organization's coordinates"))
+                } else if kind == OwnerKind::Company && self.coordinates_id.is_none() {
+                    Err(String::from("A company owner must have coordinates"))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    pub fn linked_user_id(&self) -> Option<uuid::Uuid> {
+        self.linked_user_id
+    }
+
+    pub fn coordinates_id(&self) -> Option<uuid::Uuid> {
+        self.coordinates_id
+    }
+
+    pub fn sort_key(&self, _field: &str) -> (String, uuid::Uuid) {
+        (self.full_name.clone(), self.id)
+    }
+
     pub(super) fn insert(conn: &PgConnection, bo: &BuildingOwner) -> BuildingOwner {
         diesel::insert_into(building_owners::table)
             .values(bo)
@@ -80,22 +301,85 @@ impl BuildingOwner {
             .expect("Error saving building owners")
     }
 
-    fn update(conn: &PgConnection, bo: &BuildingOwner) -> BuildingOwner {
-        diesel::update(building_owners::table)
+    pub(super) fn update(conn: &PgConnection, bo: &BuildingOwner) -> Option<BuildingOwner> {
+        diesel::update(dsl::building_owners
+                .filter(dsl::id.eq(bo.id))
+                .filter(dsl::version.eq(bo.version)))
             .set(bo)
             .get_result(conn)
+            .optional()
             .expect("Error saving building owner")
     }
 
-    fn get_one_by_id(conn: &PgConnection, bo_id: uuid::Uuid) -> BuildingOwner {
+    pub(super) fn get_one_by_id(conn: &PgConnection, bo_id: uuid::Uuid) -> BuildingOwner {
         dsl::building_owners.filter(dsl::id.eq(bo_id)).first(conn)
             .expect("Could not find building owner")
     }
+
+    pub(super) fn find_by_id(conn: &PgConnection, bo_id: uuid::Uuid) -> Option<BuildingOwner> {
+        dsl::building_owners.filter(dsl::id.eq(bo_id)).first(conn)
+            .optional()
+            .expect("Error loading building owner")
+    }
+
+    /// Loads every owner profile a user account is linked to, across all the
+    /// managing organizations.
+    pub(super) fn get_by_linked_user_id(conn: &PgConnection, user_id: uuid::Uuid) -> Vec<BuildingOwner> {
+        dsl::building_owners
+            .filter(dsl::linked_user_id.eq(user_id))
+            .filter(dsl::deleted.eq(false))
+            .load::<BuildingOwner>(conn)
+            .expect("Error loading building owners")
+    }
+
+    pub(super) fn get_by_org(conn: &PgConnection, org_id: uuid::Uuid) -> Vec<BuildingOwner> {
+        dsl::building_owners
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::deleted.eq(false))
+            .load::<BuildingOwner>(conn)
+            .expect("Error loading building owners")
+    }
+
+    /// Finds the owner profile of an organization that owns buildings itself.
+    pub(super) fn find_manager_owner(conn: &PgConnection, org_id: uuid::Uuid) -> Option<BuildingOwner> {
+        dsl::building_owners
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::kind.eq(OwnerKind::Manager as i16))
+            .filter(dsl::deleted.eq(false))
+            .first(conn)
+            .optional()
+            .expect("Error loading building owner")
+    }
+
+    /// Loads a page of the organization's owners, along with the first owner
+    /// of the next page if there is one.
+    pub(super) fn list(
+        conn: &PgConnection,
+        org_id: uuid::Uuid,
+        filter: &OwnerFilter,
+        request: &PageRequest
+    ) -> Vec<BuildingOwner> {
+        let mut query = dsl::building_owners
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::deleted.eq(false))
+            .into_boxed();
+
+        if let Some(kind) = filter.kind {
+            query = query.filter(dsl::kind.eq(kind));
+        }
+        if let Some(full_name) = &filter.full_name {
+            query = query.filter(dsl::full_name.ilike(contains_pattern(full_name)));
+        }
+
+        keyset_page!(query, dsl::full_name, dsl::id, request)
+            .load::<BuildingOwner>(conn)
+            .expect("Error loading building owners")
+    }
 }
 
 #[cfg(test)]
 pub mod test_functions {
-    use super::BuildingOwner;
+    use super::{BuildingOwner, OwnerKind};
     use super::super::organizations::{Organization, test_functions::*};
     use super::super::users::{User, test_functions::*};
     use super::super::coordinates::{Coordinate, test_functions::*};
@@ -113,7 +397,7 @@ pub mod test_functions {
         Coordinate::insert(&conn, &test_coord);
 
         BuildingOwner::new(String::from("FULL NAME #1"), 
-                           false, 
+                           OwnerKind::Individual, 
                            Some(test_manager.id()),
                            Some(test_user.id()),
                            Some(test_coord.id()))
@@ -130,7 +414,7 @@ pub mod test_functions {
         Coordinate::insert(&conn, &test_coord);
 
         BuildingOwner::new(String::from("FULL NAME #2"), 
-                           false, 
+                           OwnerKind::Company, 
                            Some(test_manager.id()),
                            Some(test_user.id()),
                            Some(test_coord.id()))
@@ -140,7 +424,12 @@ pub mod test_functions {
 
 #[cfg(test)]
 mod tests {
-    use super::{BuildingOwner, Connection, test_functions::*};
+    use super::{BuildingOwner, BuildingOwnerPatch, NewBuildingOwner, OwnerFilter, OwnerKind, Connection, Validate, test_functions::*};
+    use super::super::api::{self, APIError};
+    use super::super::list_query::{ListQuery, Page};
+    use super::OWNER_SORT_FIELDS;
+    use super::super::organizations::Organization;
+    use super::super::users::{User, test_functions::create_test_user};
     use crate::db;
     use diesel::result::Error;
 
@@ -187,4 +476,184 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn test_get_owners_by_linked_user() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let bo = create_test_building_owner1(&conn);
+            BuildingOwner::insert(&conn, &bo);
+
+            let owners = BuildingOwner::get_by_linked_user_id(&conn, bo.linked_user_id.unwrap());
+            assert_eq!(owners, vec![bo]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_same_as_manager_owner() {
+        let manager = Organization::new(
+            String::from("MANAGER"),
+            Vec::new(),
+            Some(uuid::Uuid::new_v4())
+        );
+        let bo = BuildingOwner::new_same_as_manager(&manager);
+
+        assert_eq!(bo.kind(), Some(OwnerKind::Manager));
+        assert!(bo.is_manager);
+        assert_eq!(bo.full_name, "MANAGER");
+        assert_eq!(bo.org_id, Some(manager.id()));
+        assert_eq!(bo.coordinates_id, manager.coordinates_id());
+        assert!(bo.validate(&manager).is_ok());
+    }
+
+    #[test]
+    fn test_validate_owner_rules() {
+        let manager = Organization::new(
+            String::from("MANAGER"),
+            Vec::new(),
+            Some(uuid::Uuid::new_v4())
+        );
+        let other = Organization::new(String::from("OTHER"), Vec::new(), None);
+
+        let individual = BuildingOwner::new(String::from("INDIVIDUAL"), OwnerKind::Individual, Some(manager.id()), None, None);
+        assert!(individual.validate(&manager).is_ok());
+        assert!(individual.validate(&other).is_err());
+
+        let mut flagged = BuildingOwner::new(String::from("INDIVIDUAL"), OwnerKind::Individual, Some(manager.id()), None, None);
+        flagged.is_manager = true;
+        assert!(flagged.validate(&manager).is_err());
+
+        let company = BuildingOwner::new(String::from("COMPANY"), OwnerKind::Company, Some(manager.id()), None, None);
+        assert!(company.validate(&manager).is_err());
+
+        let shared = BuildingOwner::new(String::from("COMPANY"), OwnerKind::Company, Some(manager.id()), None, manager.coordinates_id());
+        assert!(shared.validate(&manager).is_err());
+
+        let mut own_coordinates = BuildingOwner::new_same_as_manager(&manager);
+        own_coordinates.coordinates_id = Some(uuid::Uuid::new_v4());
+        assert!(own_coordinates.validate(&manager).is_err());
+
+        let mut linked = BuildingOwner::new_same_as_manager(&manager);
+        linked.linked_user_id = Some(uuid::Uuid::new_v4());
+        assert!(linked.validate(&manager).is_err());
+
+        let mut unknown = BuildingOwner::new_same_as_manager(&manager);
+        unknown.kind = 42;
+        assert!(unknown.validate(&manager).is_err());
+
+        let mut becomes_manager = individual;
+        becomes_manager.set_kind(OwnerKind::Manager, &manager);
+        assert!(becomes_manager.validate(&manager).is_ok());
+    }
+
+    #[test]
+    fn test_validate_new_building_owner() {
+        let owner = NewBuildingOwner {
+            full_name: String::from("Gestion Tremblay inc."),
+            same_as_manager: false,
+            kind: OwnerKind::Company as i16,
+            linked_user_id: None,
+            coordinates_id: None,
+        };
+        assert!(owner.validate_owner().is_ok());
+
+        let manager = NewBuildingOwner {
+            full_name: String::new(),
+            same_as_manager: true,
+            kind: OwnerKind::Manager as i16,
+            ..owner
+        };
+        assert!(manager.validate_owner().is_ok());
+
+        let unnamed = NewBuildingOwner {
+            full_name: String::from("  "),
+            same_as_manager: false,
+            kind: 7,
+            ..manager
+        };
+        let errors = unnamed.validate_owner().unwrap_err();
+        let fields = errors.field_errors();
+        assert_eq!(fields.len(), 2);
+        assert!(fields.contains_key("full_name"));
+        assert!(fields.contains_key("kind"));
+    }
+
+    #[test]
+    fn test_deserialize_building_owner_patch() {
+        let patch: BuildingOwnerPatch = serde_json::from_str(r#"{"full_name": "Gestion Tremblay inc.", "coordinates_id": null}"#).unwrap();
+
+        assert_eq!(patch.full_name, Some(String::from("Gestion Tremblay inc.")));
+        assert_eq!(patch.same_as_manager, None);
+        assert_eq!(patch.kind, None);
+        assert_eq!(patch.linked_user_id, None);
+        assert_eq!(patch.coordinates_id, Some(None));
+        assert!(patch.validate().is_ok());
+
+        let invalid = BuildingOwnerPatch {
+            full_name: Some(String::new()),
+            kind: Some(-1),
+            ..BuildingOwnerPatch::default()
+        };
+        assert_eq!(invalid.validate().unwrap_err().field_errors().len(), 2);
+    }
+
+    #[test]
+    fn test_list_building_owners() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let owner = create_test_building_owner1(&conn);
+            BuildingOwner::insert(&conn, &owner);
+            let company = BuildingOwner::new(String::from("A COMPANY"), OwnerKind::Company, owner.org_id(), None, owner.coordinates_id());
+            BuildingOwner::insert(&conn, &company);
+            let org_id = owner.org_id().unwrap();
+
+            let query = ListQuery { limit: Some(1), sort: Some(String::from("-full_name")), cursor: None };
+            let request = query.page_request(OWNER_SORT_FIELDS).unwrap();
+            let page = Page::new(BuildingOwner::list(&conn, org_id, &OwnerFilter::default(), &request), &request, |o| o.sort_key(request.field));
+            assert_eq!(page.items, vec![owner]);
+
+            let query = ListQuery { cursor: page.next_cursor, ..query };
+            let request = query.page_request(OWNER_SORT_FIELDS).unwrap();
+            let page = Page::new(BuildingOwner::list(&conn, org_id, &OwnerFilter::default(), &request), &request, |o| o.sort_key(request.field));
+            assert_eq!(page.items, vec![company]);
+            assert!(page.next_cursor.is_none());
+
+            let filter = OwnerFilter { kind: Some(OwnerKind::Company as i16), ..OwnerFilter::default() };
+            let request = ListQuery::default().page_request(OWNER_SORT_FIELDS).unwrap();
+            assert_eq!(BuildingOwner::list(&conn, org_id, &filter, &request).len(), 1);
+
+            Ok(())
+        });
+    }
+    #[test]
+    fn test_owner_info_of_deleted_owner() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("OWNER INFO"));
+            User::insert(&conn, &user);
+            let mut linked = create_test_user(&conn, String::from("OWNER INFO LINKED"));
+            User::insert(&conn, &linked);
+            let mut bo = BuildingOwner::new(String::from("OWNER INFO"), OwnerKind::Individual, Some(user.org_id()), Some(linked.id()), None);
+            BuildingOwner::insert(&conn, &bo);
+
+            assert_eq!(api::get_owner_info(&conn, user.id(), bo.id).unwrap().users.len(), 1);
+
+            linked.deactivate(&conn).unwrap();
+            assert!(api::get_owner_info(&conn, user.id(), bo.id).unwrap().users.is_empty());
+
+            bo.deleted = true;
+            BuildingOwner::update(&conn, &bo).unwrap();
+            match api::get_owner_info(&conn, user.id(), bo.id) {
+                Err(APIError::NotFound) => (),
+                other => panic!("Expected not found, got {:?}", other),
+            }
+
+            Ok(())
+        });
+    }
 }
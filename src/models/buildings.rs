@@ -1,5 +1,8 @@
 use crate::schema::buildings;
 use crate::schema::buildings::dsl;
+use crate::utils::validators::validate_not_blank;
+use super::building_owners::BuildingOwner;
+use super::list_query::{contains_pattern, PageRequest};
 
 use chrono::Utc;
 use chrono::naive::NaiveDateTime;
@@ -11,8 +14,82 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::vec::Vec;
 
+use validator::Validate;
 
-#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewBuilding {
+    pub owner_id: uuid::Uuid,
+    pub respondant_id: uuid::Uuid,
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub name: String,
+    #[validate(length(min = 1, max = 500), custom = "validate_not_blank")]
+    pub address: String,
+}
+
+/// Building fields to change, fields left to `None` are not updated.
+#[derive(AsChangeset, Debug, Default, Deserialize, Validate)]
+#[table_name = "buildings"]
+pub struct BuildingPatch {
+    pub owner_id: Option<uuid::Uuid>,
+    pub respondant_id: Option<uuid::Uuid>,
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub name: Option<String>,
+    #[validate(length(min = 1, max = 500), custom = "validate_not_blank")]
+    pub address: Option<String>,
+}
+
+impl BuildingPatch {
+    pub fn is_empty(&self) -> bool {
+        self.owner_id.is_none() &&
+        self.respondant_id.is_none() &&
+        self.name.is_none() &&
+        self.address.is_none()
+    }
+}
+
+/// Fields the buildings listing can be sorted by, the first being the default.
+pub const BUILDING_SORT_FIELDS: &[&str] = &["name", "address"];
+
+/// Filters of the buildings listing, absent ones matching every building.
+#[derive(Debug, Default, Deserialize)]
+pub struct BuildingFilter {
+    pub owner_id: Option<uuid::Uuid>,
+    pub respondant_id: Option<uuid::Uuid>,
+    /// Part of the address, case insensitive.
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildingResponse {
+    pub id: uuid::Uuid,
+    pub owner_id: uuid::Uuid,
+    pub org_id: uuid::Uuid,
+    pub respondant_id: uuid::Uuid,
+    pub name: String,
+    pub address: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub version: i32,
+}
+
+impl From<&Building> for BuildingResponse {
+    fn from(building: &Building) -> Self {
+        BuildingResponse {
+            id: building.id,
+            owner_id: building.owner_id,
+            org_id: building.org_id,
+            respondant_id: building.respondant_id,
+            name: building.name.clone(),
+            address: building.address.clone(),
+            created_at: building.created_at,
+            updated_at: building.updated_at,
+            version: building.version,
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Identifiable, Associations, AsChangeset, Debug, Serialize, Deserialize)]
+#[belongs_to(BuildingOwner, foreign_key = "owner_id")]
 pub struct Building {
     id: uuid::Uuid,
     owner_id: uuid::Uuid,
@@ -23,7 +100,8 @@ pub struct Building {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    version: i32
 }
 
 impl PartialEq for Building {
@@ -59,13 +137,49 @@ impl Building {
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            version: 1
         }
     }
 
     pub fn id(&self) -> uuid::Uuid {
         self.id
     }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn owner_id(&self) -> uuid::Uuid {
+        self.owner_id
+    }
+
+    pub fn org_id(&self) -> uuid::Uuid {
+        self.org_id
+    }
+
+    pub fn respondant_id(&self) -> uuid::Uuid {
+        self.respondant_id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn address(&self) -> &String {
+        &self.address
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    pub fn sort_key(&self, field: &str) -> (String, uuid::Uuid) {
+        match field {
+            "address" => (self.address.clone(), self.id),
+            _ => (self.name.clone(), self.id),
+        }
+    }
     
     pub(super) fn insert(conn: &PgConnection, building: &Building) -> Building {
         diesel::insert_into(buildings::table)
@@ -74,19 +188,124 @@ impl Building {
             .expect("Error saving new building")
     }
 
-    fn update(conn: &PgConnection, building: &Building) -> Building {
-        diesel::update(buildings::table)
+    /// Saves the building unless it was saved since it was loaded, in which
+    /// case nothing is written and `None` is returned. The version is bumped
+    /// by a trigger on every update.
+    fn update(conn: &PgConnection, building: &Building) -> Option<Building> {
+        diesel::update(dsl::buildings
+                .filter(dsl::id.eq(building.id))
+                .filter(dsl::version.eq(building.version)))
             .set(building)
             .get_result(conn)
+            .optional()
             .expect("Error saving building")
     }
 
-    fn get_one_by_id(conn: &PgConnection, bid: uuid::Uuid) -> Building {
+    pub(super) fn get_one_by_id(conn: &PgConnection, bid: uuid::Uuid) -> Building {
         dsl::buildings.filter(dsl::id.eq(bid)).first(conn)
             .expect("Could not load building")
     }
 
-    fn get_all(conn: &PgConnection) -> Vec<Building> {
+    /// Loads the buildings of every given owner in a single query, grouped
+    /// in the same order as `owners`.
+    pub(super) fn get_by_owners(conn: &PgConnection, owners: &[BuildingOwner]) -> Vec<Vec<Building>> {
+        Building::belonging_to(owners)
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::name.asc())
+            .load::<Building>(conn)
+            .expect("Error loading buildings")
+            .grouped_by(owners)
+    }
+
+    pub(super) fn patch(conn: &PgConnection, bid: uuid::Uuid, version: i32, patch: &BuildingPatch) -> Option<Building> {
+        diesel::update(dsl::buildings
+                .filter(dsl::id.eq(bid))
+                .filter(dsl::version.eq(version)))
+            .set(patch)
+            .get_result(conn)
+            .optional()
+            .expect("Error saving building")
+    }
+
+    pub(super) fn find_by_id(conn: &PgConnection, bid: uuid::Uuid) -> Option<Building> {
+        dsl::buildings.filter(dsl::id.eq(bid)).first(conn)
+            .optional()
+            .expect("Error loading building")
+    }
+
+    /// Tells whether the user is the respondant of a building that was not
+    /// deleted.
+    pub(super) fn has_respondant(conn: &PgConnection, user_id: uuid::Uuid) -> bool {
+        diesel::select(diesel::dsl::exists(dsl::buildings
+                .filter(dsl::respondant_id.eq(user_id))
+                .filter(dsl::deleted.eq(false))))
+            .get_result(conn)
+            .expect("Error loading buildings")
+    }
+
+    /// Makes another user the respondant of every building of a user,
+    /// returning the number of buildings changed. Their versions are bumped
+    /// like on any update.
+    pub(super) fn reassign_respondant(conn: &PgConnection, from_id: uuid::Uuid, to_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::buildings
+                .filter(dsl::respondant_id.eq(from_id))
+                .filter(dsl::deleted.eq(false)))
+            .set(dsl::respondant_id.eq(to_id))
+            .execute(conn)
+            .expect("Error saving buildings")
+    }
+
+    /// Loads every building of the organization, or only those of one of
+    /// its owners, by name.
+    pub(super) fn get_by_org(conn: &PgConnection, org_id: uuid::Uuid, owner_id: Option<uuid::Uuid>) -> Vec<Building> {
+        let mut query = dsl::buildings
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::deleted.eq(false))
+            .into_boxed();
+
+        if let Some(owner_id) = owner_id {
+            query = query.filter(dsl::owner_id.eq(owner_id));
+        }
+
+        query.order((dsl::name.asc(), dsl::id.asc()))
+            .load::<Building>(conn)
+            .expect("Error loading buildings")
+    }
+
+    /// Loads a page of the organization's buildings, along with the first
+    /// building of the next page if there is one.
+    pub(super) fn list(
+        conn: &PgConnection,
+        org_id: uuid::Uuid,
+        filter: &BuildingFilter,
+        request: &PageRequest
+    ) -> Vec<Building> {
+        let mut query = dsl::buildings
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::deleted.eq(false))
+            .into_boxed();
+
+        if let Some(owner_id) = filter.owner_id {
+            query = query.filter(dsl::owner_id.eq(owner_id));
+        }
+        if let Some(respondant_id) = filter.respondant_id {
+            query = query.filter(dsl::respondant_id.eq(respondant_id));
+        }
+        if let Some(address) = &filter.address {
+            query = query.filter(dsl::address.ilike(contains_pattern(address)));
+        }
+
+        let query = match request.field {
+            "address" => keyset_page!(query, dsl::address, dsl::id, request),
+            _ => keyset_page!(query, dsl::name, dsl::id, request),
+        };
+
+        query.load::<Building>(conn)
+            .expect("Error loading buildings")
+    }
+
+    #[cfg(test)]
+    pub(super) fn get_all(conn: &PgConnection) -> Vec<Building> {
         buildings::table.load::<Building>(conn)
             .expect("Error loading buildings")
     }
@@ -142,7 +361,9 @@ pub mod test_functions {
 
 #[cfg(test)]
 mod tests {
-    use super::{Building, Connection};
+    use super::{Building, BuildingFilter, BuildingPatch, NewBuilding, Connection, Validate, BUILDING_SORT_FIELDS};
+    use super::super::list_query::{ListQuery, Page};
+    use super::super::building_owners::BuildingOwner;
     use crate::db;
     use diesel::result::Error;
     use super::test_functions::*;
@@ -188,4 +409,140 @@ mod tests {
         });
 
     }
+
+    #[test]
+    fn test_stale_building_update() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let mut b1 = create_test_building1(&conn);
+            Building::insert(&conn, &b1);
+
+            b1.name = String::from("First manager");
+            let saved = Building::update(&conn, &b1).unwrap();
+            assert_eq!(saved.version, 2);
+
+            // b1 still holds version 1, the second manager's save is rejected
+            b1.name = String::from("Second manager");
+            assert!(Building::update(&conn, &b1).is_none());
+
+            let patch = BuildingPatch {
+                address: Some(String::from("Somewhere else")),
+                ..BuildingPatch::default()
+            };
+            assert!(Building::patch(&conn, b1.id, 1, &patch).is_none());
+
+            let stored_b = Building::get_one_by_id(&conn, b1.id);
+            assert_eq!(stored_b.name, "First manager");
+            assert_eq!(stored_b.address, b1.address);
+            assert_eq!(stored_b.version, 2);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_list_buildings() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let b1 = create_test_building1(&conn);
+            Building::insert(&conn, &b1);
+            let b3 = Building::new(b1.owner_id, b1.org_id, b1.respondant_id, String::from("BUILDING NAME #3"), String::from("OTHER ADDRESS"));
+            Building::insert(&conn, &b3);
+            let other_org = create_test_building2(&conn);
+            Building::insert(&conn, &other_org);
+
+            let query = ListQuery { limit: Some(1), ..ListQuery::default() };
+            let request = query.page_request(BUILDING_SORT_FIELDS).unwrap();
+            let rows = Building::list(&conn, b1.org_id, &BuildingFilter::default(), &request);
+            let page = Page::new(rows, &request, |b| b.sort_key(request.field));
+            assert_eq!(page.items, vec![b1]);
+
+            let query = ListQuery { cursor: page.next_cursor, ..query };
+            let request = query.page_request(BUILDING_SORT_FIELDS).unwrap();
+            let rows = Building::list(&conn, b3.org_id, &BuildingFilter::default(), &request);
+            let page = Page::new(rows, &request, |b| b.sort_key(request.field));
+            assert_eq!(page.items, vec![b3]);
+            assert!(page.next_cursor.is_none());
+
+            let filter = BuildingFilter { address: Some(String::from("other")), ..BuildingFilter::default() };
+            let request = ListQuery::default().page_request(BUILDING_SORT_FIELDS).unwrap();
+            let rows = Building::list(&conn, b3.org_id, &filter, &request);
+            assert_eq!(rows.len(), 1);
+            assert_eq!(rows[0].id, b3.id);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_get_buildings_by_owners() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let b1 = create_test_building1(&conn);
+            let b2 = create_test_building2(&conn);
+            Building::insert(&conn, &b1);
+            Building::insert(&conn, &b2);
+
+            let owners = vec![
+                BuildingOwner::get_one_by_id(&conn, b2.owner_id),
+                BuildingOwner::get_one_by_id(&conn, b1.owner_id),
+            ];
+            let buildings = Building::get_by_owners(&conn, &owners);
+
+            assert_eq!(buildings.len(), 2);
+            assert_eq!(buildings[0], vec![b2]);
+            assert_eq!(buildings[1], vec![b1]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_validate_new_building() {
+        let building = NewBuilding {
+            owner_id: uuid::Uuid::new_v4(),
+            respondant_id: uuid::Uuid::new_v4(),
+            name: String::from("Tour de la Bourse"),
+            address: String::from("800 Place Victoria, Montréal"),
+        };
+        assert!(building.validate().is_ok());
+
+        let invalid = NewBuilding {
+            name: String::from(" "),
+            address: "a".repeat(501),
+            ..building
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields = errors.field_errors();
+        assert_eq!(fields.len(), 2);
+        assert!(fields.contains_key("name"));
+        assert!(fields.contains_key("address"));
+    }
+
+    #[test]
+    fn test_patch_building() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let building = create_test_building1(&conn);
+            Building::insert(&conn, &building);
+
+            let patch = BuildingPatch {
+                name: Some(String::from("Réjean Tremblay")),
+                ..BuildingPatch::default()
+            };
+            assert!(!patch.is_empty());
+            assert!(BuildingPatch::default().is_empty());
+
+            let patched = Building::patch(&conn, building.id, building.version, &patch).unwrap();
+            assert_eq!(patched.name, "Réjean Tremblay");
+            assert_eq!(patched.address, building.address);
+            assert_eq!(patched.owner_id, building.owner_id);
+
+            Ok(())
+        });
+    }
 }
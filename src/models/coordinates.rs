@@ -1,5 +1,6 @@
 use crate::schema::coordinates;
 use crate::schema::coordinates::dsl;
+use crate::utils::validators::validate_phone;
 
 use chrono::Utc;
 use chrono::naive::NaiveDateTime;
@@ -9,6 +10,90 @@ use diesel::pg::PgConnection;
 
 use serde_derive::{Deserialize, Serialize};
 
+use validator::Validate;
+
+#[derive(Debug, Default, Deserialize, Validate)]
+#[serde(default)]
+pub struct NewCoordinate {
+    #[validate(length(max = 500))]
+    pub address: Option<String>,
+    #[validate(custom = "validate_phone")]
+    pub telephone_no: Option<String>,
+    #[validate(custom = "validate_phone")]
+    pub fax: Option<String>,
+    #[validate(custom = "validate_phone")]
+    pub cellphone_no: Option<String>,
+    #[validate(email, length(max = 254))]
+    pub email: Option<String>,
+    #[validate(length(max = 255))]
+    pub company_name: Option<String>,
+    #[validate(length(max = 50))]
+    pub company_number: Option<String>,
+}
+
+/// Coordinate fields to change, fields left to `None` are not updated.
+#[derive(AsChangeset, Debug, Default, Deserialize, Validate)]
+#[table_name = "coordinates"]
+pub struct CoordinatePatch {
+    #[validate(length(max = 500))]
+    pub address: Option<String>,
+    #[validate(custom = "validate_phone")]
+    pub telephone_no: Option<String>,
+    #[validate(custom = "validate_phone")]
+    pub fax: Option<String>,
+    #[validate(custom = "validate_phone")]
+    pub cellphone_no: Option<String>,
+    #[validate(email, length(max = 254))]
+    pub email: Option<String>,
+    #[validate(length(max = 255))]
+    pub company_name: Option<String>,
+    #[validate(length(max = 50))]
+    pub company_number: Option<String>,
+}
+
+impl CoordinatePatch {
+    pub fn is_empty(&self) -> bool {
+        self.address.is_none() &&
+        self.telephone_no.is_none() &&
+        self.fax.is_none() &&
+        self.cellphone_no.is_none() &&
+        self.email.is_none() &&
+        self.company_name.is_none() &&
+        self.company_number.is_none()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoordinateResponse {
+    pub id: uuid::Uuid,
+    pub address: String,
+    pub telephone_no: String,
+    pub fax: String,
+    pub cellphone_no: String,
+    pub email: String,
+    pub company_name: String,
+    pub company_number: String,
+    pub updated_at: chrono::NaiveDateTime,
+    pub version: i32,
+}
+
+impl From<&Coordinate> for CoordinateResponse {
+    fn from(coord: &Coordinate) -> Self {
+        CoordinateResponse {
+            id: coord.id,
+            address: coord.address.clone(),
+            telephone_no: coord.telephone_no.clone(),
+            fax: coord.fax.clone(),
+            cellphone_no: coord.cellphone_no.clone(),
+            email: coord.email.clone(),
+            company_name: coord.company_name.clone(),
+            company_number: coord.company_number.clone(),
+            updated_at: coord.updated_at,
+            version: coord.version,
+        }
+    }
+}
+
 #[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
 pub struct Coordinate {
     id: uuid::Uuid,
@@ -22,7 +107,9 @@ pub struct Coordinate {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    version: i32,
+    org_id: Option<uuid::Uuid>
 }
 
 impl PartialEq for Coordinate {
@@ -38,12 +125,14 @@ impl PartialEq for Coordinate {
         self.deleted == other.deleted &&
         self.created_at.timestamp() == other.created_at.timestamp() &&
         self.updated_at.timestamp() == other.updated_at.timestamp() &&
-        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.org_id == other.org_id
     }
 }
 
 impl Coordinate {
     pub fn new(
+        org_id: Option<uuid::Uuid>,
         address: String,
         telephone_no: String,
         fax: String,
@@ -64,14 +153,41 @@ impl Coordinate {
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            version: 1,
+            org_id
         }
     }
 
+    pub fn from_new(org_id: uuid::Uuid, coordinate: NewCoordinate) -> Coordinate {
+        Coordinate::new(
+            Some(org_id),
+            coordinate.address.unwrap_or_default(),
+            coordinate.telephone_no.unwrap_or_default(),
+            coordinate.fax.unwrap_or_default(),
+            coordinate.cellphone_no.unwrap_or_default(),
+            coordinate.email.unwrap_or_default(),
+            coordinate.company_name.unwrap_or_default(),
+            coordinate.company_number.unwrap_or_default()
+        )
+    }
+
     pub fn id(&self) -> uuid::Uuid {
         self.id
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn org_id(&self) -> Option<uuid::Uuid> {
+        self.org_id
+    }
+
+    pub fn company_number(&self) -> &String {
+        &self.company_number
+    }
+
     pub(super) fn insert(conn: &PgConnection, coordinate: &Coordinate) -> Coordinate {
         diesel::insert_into(coordinates::table)
             .values(coordinate)
@@ -79,10 +195,13 @@ impl Coordinate {
             .expect("Error saving coordinate")
     }
 
-    fn update(conn: &PgConnection, coordinate: &Coordinate) -> Coordinate {
-        diesel::update(coordinates::table)
+    fn update(conn: &PgConnection, coordinate: &Coordinate) -> Option<Coordinate> {
+        diesel::update(dsl::coordinates
+                .filter(dsl::id.eq(coordinate.id))
+                .filter(dsl::version.eq(coordinate.version)))
             .set(coordinate)
             .get_result(conn)
+            .optional()
             .expect("Error saving coordinate")
     }
 
@@ -90,6 +209,40 @@ impl Coordinate {
         dsl::coordinates.filter(dsl::id.eq(coord_id)).first(conn)
             .expect("Could not find coordinates")
     }
+
+    pub(super) fn patch(conn: &PgConnection, coord_id: uuid::Uuid, version: i32, patch: &CoordinatePatch) -> Option<Coordinate> {
+        diesel::update(dsl::coordinates
+                .filter(dsl::id.eq(coord_id))
+                .filter(dsl::version.eq(version)))
+            .set(patch)
+            .get_result(conn)
+            .optional()
+            .expect("Error saving coordinate")
+    }
+
+    pub(super) fn find_by_id(conn: &PgConnection, coord_id: uuid::Uuid) -> Option<Coordinate> {
+        dsl::coordinates.filter(dsl::id.eq(coord_id)).first(conn)
+            .optional()
+            .expect("Error loading coordinates")
+    }
+
+    /// Finds coordinates created by the given organization.
+    pub(super) fn find_in_org(conn: &PgConnection, org_id: uuid::Uuid, coord_id: uuid::Uuid) -> Option<Coordinate> {
+        dsl::coordinates
+            .filter(dsl::id.eq(coord_id))
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::deleted.eq(false))
+            .first(conn)
+            .optional()
+            .expect("Error loading coordinates")
+    }
+
+    pub(super) fn get_by_ids(conn: &PgConnection, coord_ids: &[uuid::Uuid]) -> Vec<Coordinate> {
+        dsl::coordinates
+            .filter(dsl::id.eq_any(coord_ids))
+            .load::<Coordinate>(conn)
+            .expect("Error loading coordinates")
+    }
     
 }
 
@@ -98,7 +251,8 @@ pub mod test_functions {
     use super::Coordinate;
 
     pub fn create_test_coordinate1() -> Coordinate {
-        Coordinate::new(String::from("STREET ADDRESS   #1"),
+        Coordinate::new(None,
+                        String::from("STREET ADDRESS   #1"),
                         String::from("TELEPHONE NUMBER #1"),
                         String::from("FAX NUMBER       #1"),
                         String::from("CELLPHONE NUMBER #1"),
@@ -108,7 +262,8 @@ pub mod test_functions {
     }
 
     pub fn create_test_coordinate2() -> Coordinate {
-        Coordinate::new(String::from("STREET ADDRESS   #2"),
+        Coordinate::new(None,
+                        String::from("STREET ADDRESS   #2"),
                         String::from("TELEPHONE NUMBER #2"),
                         String::from("FAX NUMBER       #2"),
                         String::from("CELLPHONE NUMBER #2"),
@@ -121,9 +276,10 @@ pub mod test_functions {
 
 #[cfg(test)]
 mod tests {
-    use super::{Coordinate, Connection};
+    use super::{Coordinate, NewCoordinate, CoordinatePatch, Connection, Validate};
     use super::test_functions::*;
     use crate::db;
+    use crate::models::organizations::test_functions::*;
     use diesel::result::Error;
 
     #[test]
@@ -175,4 +331,85 @@ mod tests {
         });
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_validate_new_coordinate() {
+        assert!(NewCoordinate::default().validate().is_ok());
+
+        let valid = NewCoordinate {
+            address: Some(String::from("1000 rue De La Gauchetière, Montréal")),
+            telephone_no: Some(String::from("(514) 555-1234")),
+            fax: Some(String::from("514-555-1235")),
+            cellphone_no: Some(String::from("+1 438 555 1236")),
+            email: Some(String::from("info@propfile.com")),
+            company_name: Some(String::from("Propfile")),
+            company_number: Some(String::from("1170001234")),
+        };
+        assert!(valid.validate().is_ok());
+
+        let org_id = uuid::Uuid::new_v4();
+        let coord = Coordinate::from_new(org_id, valid);
+        assert_eq!(coord.org_id(), Some(org_id));
+        assert_eq!(coord.telephone_no, "(514) 555-1234");
+        assert_eq!(coord.company_name, "Propfile");
+    }
+
+    #[test]
+    fn test_validate_new_coordinate_errors() {
+        let invalid = NewCoordinate {
+            telephone_no: Some(String::from("call me")),
+            fax: Some(String::new()),
+            email: Some(String::from("info@")),
+            company_number: Some("1".repeat(51)),
+            ..NewCoordinate::default()
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields = errors.field_errors();
+
+        assert_eq!(fields.len(), 4);
+        assert!(fields.contains_key("telephone_no"));
+        assert!(fields.contains_key("fax"));
+        assert!(fields.contains_key("email"));
+        assert!(fields.contains_key("company_number"));
+    }
+
+    #[test]
+    fn test_patch_coordinate() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let coord = create_test_coordinate1();
+            Coordinate::insert(&conn, &coord);
+
+            let patch = CoordinatePatch {
+                telephone_no: Some(String::from("514-555-1234")),
+                ..CoordinatePatch::default()
+            };
+            assert!(patch.validate().is_ok());
+
+            let patched = Coordinate::patch(&conn, coord.id, coord.version, &patch).unwrap();
+            assert_eq!(patched.telephone_no, "514-555-1234");
+            assert_eq!(patched.address, coord.address);
+            assert_eq!(patched.email, coord.email);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_find_coordinate_in_org() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let org1 = create_test_organization1(&conn);
+            let org2 = create_test_organization2(&conn);
+            let coord = Coordinate::from_new(org1.id(), NewCoordinate::default());
+            Coordinate::insert(&conn, &coord);
+
+            assert_eq!(Coordinate::find_in_org(&conn, org1.id(), coord.id).map(|c| c.id), Some(coord.id));
+            assert_eq!(Coordinate::find_in_org(&conn, org2.id(), coord.id), None);
+            assert_eq!(Coordinate::find_in_org(&conn, org1.id(), org1.coordinates_id().unwrap()), None);
+
+            Ok(())
+        });
+    }
+}
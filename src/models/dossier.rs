@@ -0,0 +1,280 @@
+use super::api::{ActivityInfo, EntityFileInfo, EntityNoteInfo};
+use super::buildings::BuildingResponse;
+use super::building_owners::{BuildingOwnerResponse, OwnerKind};
+use super::coordinates::CoordinateResponse;
+use super::registers::RegisterResponse;
+use super::users::UserResponse;
+
+use chrono::NaiveDateTime;
+
+use printpdf::{BuiltinFont, Image, ImageTransform, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference};
+use printpdf::image_crate;
+
+/// Number of note threads printed in a dossier, newest first.
+pub const DOSSIER_NOTES: i64 = 20;
+
+const PAGE_WIDTH: f64 = 210.0;
+const PAGE_HEIGHT: f64 = 297.0;
+const MARGIN: f64 = 20.0;
+const LOGO_HEIGHT: f64 = 20.0;
+const TITLE_SIZE: f64 = 18.0;
+const HEADING_SIZE: f64 = 13.0;
+const TEXT_SIZE: f64 = 10.0;
+/// Characters of body text fitting between the margins.
+const LINE_LENGTH: usize = 95;
+
+/// Everything printed in the dossier of a building.
+pub struct Dossier {
+    pub org_name: String,
+    /// The organization's `profile_picture`, printed as a logo when it is an
+    /// image of a known format.
+    pub logo: Vec<u8>,
+    pub building: BuildingResponse,
+    pub owner: BuildingOwnerResponse,
+    pub owner_coordinates: Option<CoordinateResponse>,
+    pub respondant: UserResponse,
+    pub registers: Vec<(RegisterResponse, Vec<ActivityInfo>)>,
+    pub files: Vec<EntityFileInfo>,
+    pub notes: Vec<EntityNoteInfo>,
+}
+
+/// Splits a text into lines of at most `width` characters, breaking between
+/// words when it can.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.to_owned();
+            while word.chars().count() > width {
+                let head: String = word.chars().take(width).collect();
+                word = word.chars().skip(width).collect();
+                if !line.is_empty() {
+                    lines.push(std::mem::replace(&mut line, String::new()));
+                }
+                lines.push(head);
+            }
+
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::replace(&mut line, String::new()));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn format_date(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// Writes lines of text down the pages, adding a page when one is full.
+struct PageWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f64,
+}
+
+impl PageWriter {
+    fn new(title: &str) -> Result<PageWriter, printpdf::Error> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Dossier");
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+        let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Ok(PageWriter { doc, layer, font, bold, y: PAGE_HEIGHT - MARGIN })
+    }
+
+    /// Moves down by `height`, on a new page if there is no room left.
+    fn advance(&mut self, height: f64) {
+        if self.y - height < MARGIN {
+            let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Dossier");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        self.y -= height;
+    }
+
+    /// Prints the logo at the top of the page, ignoring pictures that are
+    /// not images.
+    fn logo(&mut self, data: &[u8]) {
+        let image = match image_crate::load_from_memory(data) {
+            Ok(image) => image,
+            Err(_) => return,
+        };
+        let dpi = f64::from(image.height()) * 25.4 / LOGO_HEIGHT;
+
+        self.advance(LOGO_HEIGHT);
+        Image::from_dynamic_image(&image).add_to_layer(self.layer.clone(), ImageTransform {
+            translate_x: Some(Mm(MARGIN)),
+            translate_y: Some(Mm(self.y)),
+            dpi: Some(dpi),
+            ..ImageTransform::default()
+        });
+        self.advance(4.0);
+    }
+
+    fn text(&mut self, text: &str, size: f64, bold: bool, indent: f64) {
+        // Line height of 1.4 times the font size, in millimeters
+        self.advance(size * 1.4 * 25.4 / 72.0);
+        let font = if bold { &self.bold } else { &self.font };
+        self.layer.use_text(text, size, Mm(MARGIN + indent), Mm(self.y), font);
+    }
+
+    fn title(&mut self, text: &str) {
+        self.text(text, TITLE_SIZE, true, 0.0);
+        self.advance(2.0);
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.advance(4.0);
+        self.text(text, HEADING_SIZE, true, 0.0);
+        self.advance(1.0);
+    }
+
+    fn paragraph(&mut self, text: &str, indent: f64) {
+        for line in wrap(text, LINE_LENGTH - (indent / 2.0) as usize) {
+            self.text(&line, TEXT_SIZE, false, indent);
+        }
+    }
+
+    /// Prints a labelled value, leaving out empty values.
+    fn field(&mut self, label: &str, value: &str) {
+        if !value.trim().is_empty() {
+            self.paragraph(&format!("{}: {}", label, value), 0.0);
+        }
+    }
+}
+
+/// Renders the dossier as a PDF document.
+pub fn render(dossier: &Dossier) -> Result<Vec<u8>, String> {
+    let building = &dossier.building;
+    let mut writer = PageWriter::new(&building.name).map_err(|e| e.to_string())?;
+
+    writer.logo(&dossier.logo);
+    writer.text(&dossier.org_name, TEXT_SIZE, false, 0.0);
+    writer.title(&building.name);
+    writer.field("Address", &building.address);
+    writer.field("Updated", &format_date(building.updated_at));
+
+    writer.heading("Owner");
+    writer.field("Name", &dossier.owner.full_name);
+    let kind = OwnerKind::from_i16(dossier.owner.kind).map(OwnerKind::name).unwrap_or_default();
+    writer.field("Kind", kind);
+    if let Some(coordinates) = &dossier.owner_coordinates {
+        writer.field("Company", &coordinates.company_name);
+        writer.field("Company number", &coordinates.company_number);
+        writer.field("Address", &coordinates.address);
+        writer.field("Telephone", &coordinates.telephone_no);
+        writer.field("Cellphone", &coordinates.cellphone_no);
+        writer.field("Fax", &coordinates.fax);
+        writer.field("Email", &coordinates.email);
+    }
+
+    writer.heading("Respondant");
+    writer.field("Name", &dossier.respondant.full_name);
+    writer.field("Job title", &dossier.respondant.job_title);
+    writer.field("Email", &dossier.respondant.email);
+
+    writer.heading("Registers");
+    if dossier.registers.is_empty() {
+        writer.paragraph("No register", 0.0);
+    }
+    for (register, activity) in &dossier.registers {
+        writer.text(&register.name, TEXT_SIZE, true, 0.0);
+        if activity.is_empty() {
+            writer.paragraph("No recent entry", 5.0);
+        }
+        for entry in activity {
+            writer.paragraph(&format!("{}  {}  {}", format_date(entry.date), entry.filename, entry.user_name), 5.0);
+        }
+    }
+
+    writer.heading("Notes");
+    if dossier.notes.is_empty() {
+        writer.paragraph("No note", 0.0);
+    }
+    for thread in &dossier.notes {
+        writer.paragraph(&format!("{}, {}: {}", thread.user_name, format_date(thread.date), thread.note), 0.0);
+        for reply in &thread.replies {
+            writer.paragraph(&format!("{}, {}: {}", reply.user_name, format_date(reply.date), reply.note), 5.0);
+        }
+    }
+
+    writer.heading("Files");
+    if dossier.files.is_empty() {
+        writer.paragraph("No file", 0.0);
+    }
+    for file in &dossier.files {
+        writer.paragraph(&file.filename, 0.0);
+    }
+
+    writer.doc.save_to_bytes().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, wrap, Dossier};
+    use super::super::api::{ActivityInfo, EntityFileInfo, EntityNoteInfo};
+    use super::super::buildings::BuildingResponse;
+    use super::super::building_owners::BuildingOwnerResponse;
+    use super::super::registers::RegisterResponse;
+    use super::super::users::UserResponse;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("un deux trois quatre", 9), vec!["un deux", "trois", "quatre"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("un\n\ndeux", 10), vec!["un", "", "deux"]);
+    }
+
+    #[test]
+    fn test_render() {
+        let now = chrono::Utc::now().naive_utc();
+        let id = uuid::Uuid::new_v4();
+        let note = || EntityNoteInfo {
+            user_name: String::from("Bernard Landry"), user_id: id, handed_over_to: None, date: now,
+            note: "Inspection des gicleurs ".repeat(20), note_id: id, edited: false, mentions: Vec::new(), replies: Vec::new(),
+        };
+
+        let dossier = Dossier {
+            org_name: String::from("Propfile"),
+            logo: b"not an image".to_vec(),
+            building: BuildingResponse {
+                id, owner_id: id, org_id: id, respondant_id: id, name: String::from("Le Montcalm"),
+                address: String::from("1 rue Principale"), created_at: now, updated_at: now, version: 1,
+            },
+            owner: BuildingOwnerResponse {
+                id, full_name: String::from("Jeanne Tremblay"), kind: 0, is_manager: false, org_id: Some(id),
+                linked_user_id: None, coordinates_id: None, created_at: now, updated_at: now, version: 1,
+            },
+            owner_coordinates: None,
+            respondant: UserResponse {
+                id, org_id: id, permission: 1, full_name: String::from("Bernard Landry"), email: String::from("blandry@gmail.com"),
+                job_title: String::new(), profile_picture: String::new(), email_verified: true, pending_email: None,
+                created_at: now, updated_at: now, version: 1,
+            },
+            registers: vec![(
+                RegisterResponse { id, name: String::from("Gicleurs"), building_id: id, created_at: now, updated_at: now, version: 1 },
+                vec![ActivityInfo {
+                    date: now, action_id: 1, file_id: id, filename: String::from("rapport.pdf"), url: String::new(),
+                    user_id: id, user_name: String::from("Bernard Landry"),
+                }],
+            )],
+            files: vec![EntityFileInfo {
+                file_id: id, filename: String::from("plan.pdf"), notes: Vec::new(), metadata: serde_json::json!({}), url: String::new(),
+            }],
+            notes: (0..10).map(|_| EntityNoteInfo { replies: vec![note()], ..note() }).collect(),
+        };
+
+        assert!(render(&dossier).unwrap().starts_with(b"%PDF"));
+    }
+}
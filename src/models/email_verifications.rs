@@ -0,0 +1,187 @@
+use crate::schema::email_verifications;
+use crate::schema::email_verifications::dsl;
+use crate::utils::HASHER;
+
+use chrono::{Duration, Utc};
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use libreauth::pass::HashBuilder;
+
+/// How long the link mailed to verify an address stays valid, unless
+/// `tokens.email_verification_hours` of the configuration says otherwise.
+pub const EMAIL_VERIFICATION_HOURS: i64 = 48;
+
+/// An address mailed a link to prove the user owns it: the user's own email,
+/// or the one they are changing to. The link holds the verification id and
+/// a random secret, only a hash of the secret is stored. A user has at most
+/// one verification pending.
+#[derive(Insertable, Queryable, Identifiable, Debug)]
+pub struct EmailVerification {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    email: String,
+    secret: String,
+    expires_at: chrono::NaiveDateTime,
+    created_at: chrono::NaiveDateTime
+}
+
+impl EmailVerification {
+    /// Starts verifying an address of the user, returning the verification
+    /// along with the token to put in the link, `<verification id>.<secret>`.
+    /// The link expires after `hours`.
+    pub fn new(user_id: uuid::Uuid, email: &str, hours: i64) -> (EmailVerification, String) {
+        let id = uuid::Uuid::new_v4();
+        let secret = format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4()).replace("-", "");
+
+        let verification = EmailVerification {
+            id,
+            user_id,
+            email: email.to_owned(),
+            secret: HASHER.hash(&secret).unwrap(),
+            expires_at: Utc::now().naive_utc() + Duration::hours(hours),
+            created_at: Utc::now().naive_utc()
+        };
+
+        (verification, format!("{}.{}", id, secret))
+    }
+
+    /// Splits a token into its verification id and secret.
+    pub fn parse_token(token: &str) -> Option<(uuid::Uuid, &str)> {
+        let mut parts = token.trim().splitn(2, '.');
+        let id = parts.next().and_then(|id| uuid::Uuid::parse_str(id).ok())?;
+        let secret = parts.next().filter(|secret| !secret.is_empty())?;
+
+        Some((id, secret))
+    }
+
+    pub fn user_id(&self) -> uuid::Uuid {
+        self.user_id
+    }
+
+    pub fn email(&self) -> &String {
+        &self.email
+    }
+
+    pub fn is_valid_secret(&self, secret: &str) -> bool {
+        HashBuilder::from_phc(&self.secret)
+            .map(|checker| checker.is_valid(secret))
+            .unwrap_or(false)
+    }
+
+    /// Saves the verification in place of the one pending for the user.
+    pub(super) fn replace(conn: &PgConnection, verification: &EmailVerification) -> EmailVerification {
+        EmailVerification::delete_for_user(&conn, verification.user_id);
+
+        diesel::insert_into(email_verifications::table)
+            .values(verification)
+            .get_result(conn)
+            .expect("Error saving email verification")
+    }
+
+    /// Loads a verification that did not expire.
+    pub(super) fn find_active(conn: &PgConnection, verification_id: uuid::Uuid) -> Option<EmailVerification> {
+        dsl::email_verifications
+            .filter(dsl::id.eq(verification_id))
+            .filter(dsl::expires_at.gt(Utc::now().naive_utc()))
+            .first(conn)
+            .optional()
+            .expect("Error loading email verification")
+    }
+
+    /// Loads the verification of the user that did not expire, if any.
+    pub(super) fn find_pending_for_user(conn: &PgConnection, user_id: uuid::Uuid) -> Option<EmailVerification> {
+        dsl::email_verifications
+            .filter(dsl::user_id.eq(user_id))
+            .filter(dsl::expires_at.gt(Utc::now().naive_utc()))
+            .first(conn)
+            .optional()
+            .expect("Error loading email verification")
+    }
+
+    pub(super) fn delete_for_user(conn: &PgConnection, user_id: uuid::Uuid) -> usize {
+        diesel::delete(dsl::email_verifications.filter(dsl::user_id.eq(user_id)))
+            .execute(conn)
+            .expect("Error deleting email verification")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmailVerification, EMAIL_VERIFICATION_HOURS};
+    use super::super::api;
+    use super::super::users::{User, UserPatch, test_functions::*};
+    use crate::db;
+    use crate::mail::{FileTransport, Mailer};
+    use diesel::Connection;
+    use diesel::result::Error;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_one_verification_per_user() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("VERIFICATION"));
+            User::insert(&conn, &user);
+
+            let (first, first_token) = EmailVerification::new(user.id(), user.email(), EMAIL_VERIFICATION_HOURS);
+            EmailVerification::replace(&conn, &first);
+            let (second, token) = EmailVerification::new(user.id(), "nouvelle@gmail.com", EMAIL_VERIFICATION_HOURS);
+            EmailVerification::replace(&conn, &second);
+
+            let (id, _) = EmailVerification::parse_token(&first_token).unwrap();
+            assert!(EmailVerification::find_active(&conn, id).is_none());
+
+            let (id, secret) = EmailVerification::parse_token(&token).unwrap();
+            let pending = EmailVerification::find_active(&conn, id).unwrap();
+            assert!(pending.is_valid_secret(secret));
+            assert!(!pending.is_valid_secret("wrong"));
+            assert_eq!(EmailVerification::find_pending_for_user(&conn, user.id()).unwrap().email(), "nouvelle@gmail.com");
+
+            assert_eq!(EmailVerification::delete_for_user(&conn, user.id()), 1);
+            assert!(EmailVerification::find_pending_for_user(&conn, user.id()).is_none());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_change_email() {
+        let conn = db::connection::establish_connection();
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let mailer = Mailer::new(Arc::new(FileTransport::new(&dir)), "bep@example.com", "https://bep.example.com", EMAIL_VERIFICATION_HOURS);
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("CHANGE_EMAIL"));
+            User::insert(&conn, &user);
+
+            let patch = UserPatch { email: Some(String::from("nouvelle@gmail.com")), ..UserPatch::default() };
+            let profile = api::modify_user_profile(&conn, &mailer, user.id(), None, patch).unwrap();
+            assert_eq!(&profile.email, user.email());
+            assert_eq!(profile.pending_email, Some(String::from("nouvelle@gmail.com")));
+
+            let mail = fs::read_dir(&dir).unwrap()
+                .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+                .find(|mail| mail.contains("To: nouvelle@gmail.com"))
+                .unwrap();
+            let token = mail.split("token=").nth(1).unwrap().split_whitespace().next().unwrap();
+            api::verify_email(&conn, token).unwrap();
+
+            let profile = api::get_user_profile(&conn, user.id()).unwrap();
+            assert_eq!((profile.email.as_str(), profile.pending_email), ("nouvelle@gmail.com", None));
+            assert!(profile.email_verified);
+            assert!(api::verify_email(&conn, token).is_err());
+
+            let patch = UserPatch { email: Some(String::from("Nouvelle@Gmail.com")), ..UserPatch::default() };
+            let profile = api::modify_user_profile(&conn, &mailer, user.id(), None, patch).unwrap();
+            assert_eq!((profile.email.as_str(), profile.pending_email), ("Nouvelle@Gmail.com", None));
+
+            Ok(())
+        });
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -19,7 +19,8 @@ pub struct EntitiesFile {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    folder: String
 }
 
 impl PartialEq for EntitiesFile {
@@ -29,7 +30,8 @@ impl PartialEq for EntitiesFile {
         self.deleted == other.deleted &&
         self.created_at.timestamp() == other.created_at.timestamp() &&
         self.updated_at.timestamp() == other.updated_at.timestamp() &&
-        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.folder == other.folder
     }
 }
 
@@ -44,7 +46,19 @@ impl EntitiesFile {
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            folder: String::new()
+        }
+    }
+
+    pub fn new_in_folder(
+        file_id: uuid::Uuid,
+        entity_id: uuid::Uuid,
+        folder: String
+    ) -> EntitiesFile {
+        EntitiesFile {
+            folder,
+            ..EntitiesFile::new(file_id, entity_id)
         }
     }
 
@@ -52,6 +66,14 @@ impl EntitiesFile {
         self.file_id 
     }
 
+    pub fn entity_id(&self) -> uuid::Uuid {
+        self.entity_id
+    }
+
+    pub fn folder(&self) -> &String {
+        &self.folder
+    }
+
     pub(super) fn insert(conn: &PgConnection, entfile: &EntitiesFile) -> EntitiesFile {
         diesel::insert_into(entities_files::table)
             .values(entfile)
@@ -64,6 +86,17 @@ impl EntitiesFile {
             .expect("Error loading entities files")
     }
 
+    pub(super) fn is_attached(conn: &PgConnection, fileid: uuid::Uuid, entityid: uuid::Uuid) -> bool {
+        diesel::select(diesel::dsl::exists(
+            dsl::entities_files
+                .filter(dsl::file_id.eq(fileid))
+                .filter(dsl::entity_id.eq(entityid))
+                .filter(dsl::deleted.eq(false))
+        ))
+        .get_result(conn)
+        .expect("Error loading entities files")
+    }
+
 }
 
 #[cfg(test)]
@@ -105,4 +138,17 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn test_is_attached() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let entfile = create_test_entity_file(&conn);
+            assert!(!EntitiesFile::is_attached(&conn, entfile.file_id, entfile.entity_id));
+            EntitiesFile::insert(&conn, &entfile);
+            assert!(EntitiesFile::is_attached(&conn, entfile.file_id, entfile.entity_id));
+            Ok(())
+        });
+    }
 }
\ No newline at end of file
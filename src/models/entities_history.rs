@@ -1,4 +1,4 @@
-use crate::schema::entities_history;
+use crate::schema::{entities_history, files, users};
 use crate::schema::entities_history::dsl;
 
 use chrono::Utc;
@@ -6,10 +6,11 @@ use chrono::naive::NaiveDateTime;
 
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
+use diesel::sql_types::{Array, BigInt, Text};
 
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+#[derive(Insertable, Queryable, QueryableByName, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
 #[table_name = "entities_history"]
 pub struct EntitiesHistory {
     id: uuid::Uuid,
@@ -23,6 +24,20 @@ pub struct EntitiesHistory {
     deleted_at: chrono::NaiveDateTime
 }
 
+/// A history row with the filename, file url and user full name that go
+/// with it.
+#[derive(QueryableByName)]
+struct HistoryEntry {
+    #[diesel(embed)]
+    history: EntitiesHistory,
+    #[sql_type = "Text"]
+    filename: String,
+    #[sql_type = "Text"]
+    url: String,
+    #[sql_type = "Text"]
+    user_name: String,
+}
+
 impl PartialEq for EntitiesHistory {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id &&
@@ -61,6 +76,26 @@ impl EntitiesHistory {
         self.id
     }
 
+    pub fn entity_id(&self) -> uuid::Uuid {
+        self.entity_id
+    }
+
+    pub fn action_id(&self) -> i16 {
+        self.action_id
+    }
+
+    pub fn file_id(&self) -> uuid::Uuid {
+        self.file_id
+    }
+
+    pub fn user_id(&self) -> uuid::Uuid {
+        self.user_id
+    }
+
+    pub fn created_at(&self) -> chrono::NaiveDateTime {
+        self.created_at
+    }
+
     pub(super) fn insert(conn: &PgConnection, enthist: &EntitiesHistory) -> EntitiesHistory {
         diesel::insert_into(entities_history::table)
             .values(enthist)
@@ -73,6 +108,62 @@ impl EntitiesHistory {
             .expect("Could not find entities history")
     }
 
+    /// Loads the most recent history rows of an entity, newest first, along
+    /// with the filename, file url and user full name of each row.
+    pub(super) fn get_recent_by_entity_id(
+        conn: &PgConnection,
+        entity_id: uuid::Uuid,
+        limit: i64
+    ) -> Vec<(EntitiesHistory, String, String, String)> {
+        entities_history::table
+            .inner_join(files::table)
+            .inner_join(users::table)
+            .filter(dsl::entity_id.eq(entity_id))
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::created_at.desc())
+            .limit(limit)
+            .select((entities_history::all_columns, files::filename, files::url, users::full_name))
+            .load::<(EntitiesHistory, String, String, String)>(conn)
+            .expect("Error loading entities history")
+    }
+
+    /// Loads the most recent history rows of each given entity in a single
+    /// query, like `get_recent_by_entity_id` does for one, grouped by entity.
+    pub(super) fn get_recent_by_entity_ids(
+        conn: &PgConnection,
+        entity_ids: &[uuid::Uuid],
+        limit: i64
+    ) -> Vec<(EntitiesHistory, String, String, String)> {
+        diesel::sql_query(
+            "SELECT h.*, f.filename, f.url, u.full_name AS user_name FROM ( \
+                SELECT *, ROW_NUMBER() OVER (PARTITION BY entity_id ORDER BY created_at DESC) AS position \
+                FROM entities_history WHERE entity_id = ANY($1) AND NOT deleted \
+            ) h \
+            INNER JOIN files f ON f.id = h.file_id \
+            INNER JOIN users u ON u.id = h.user_id \
+            WHERE h.position <= $2 \
+            ORDER BY h.entity_id, h.created_at DESC")
+            .bind::<Array<diesel::sql_types::Uuid>, _>(entity_ids)
+            .bind::<BigInt, _>(limit)
+            .load::<HistoryEntry>(conn)
+            .expect("Error loading entities history")
+            .into_iter()
+            .map(|entry| (entry.history, entry.filename, entry.url, entry.user_name))
+            .collect()
+    }
+
+    /// Loads the date of the latest history row of each given entity that
+    /// has one.
+    pub(super) fn get_last_activity(conn: &PgConnection, entity_ids: &[uuid::Uuid]) -> Vec<(uuid::Uuid, Option<NaiveDateTime>)> {
+        dsl::entities_history
+            .filter(dsl::entity_id.eq_any(entity_ids))
+            .filter(dsl::deleted.eq(false))
+            .group_by(dsl::entity_id)
+            .select((dsl::entity_id, diesel::dsl::max(dsl::created_at)))
+            .load::<(uuid::Uuid, Option<NaiveDateTime>)>(conn)
+            .expect("Error loading entities history")
+    }
+
 }
 
 #[cfg(test)]
@@ -124,4 +215,48 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn test_get_recent_entities_history() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let enthist = create_test_entities_histories(&conn);
+            EntitiesHistory::insert(&conn, &enthist);
+
+            let recent = EntitiesHistory::get_recent_by_entity_id(&conn, enthist.entity_id, 10);
+            assert_eq!(recent.len(), 1);
+            assert_eq!(recent[0].0, enthist);
+            assert_eq!(recent[0].1, "FILENAME.TXT");
+            assert_eq!(recent[0].3, "USER NAME ENTITY HISTORY");
+
+            assert!(EntitiesHistory::get_recent_by_entity_id(&conn, enthist.entity_id, 0).is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_get_recent_entities_history_of_entities() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let enthist = create_test_entities_histories(&conn);
+            EntitiesHistory::insert(&conn, &enthist);
+            let again = EntitiesHistory::new(enthist.entity_id, 2, enthist.file_id, enthist.user_id);
+            EntitiesHistory::insert(&conn, &again);
+            let entity_ids = [enthist.entity_id, uuid::Uuid::new_v4()];
+
+            let recent = EntitiesHistory::get_recent_by_entity_ids(&conn, &entity_ids, 10);
+            assert_eq!(recent.len(), 2);
+            assert_eq!(recent[0].1, "FILENAME.TXT");
+            assert_eq!(recent[0].3, "USER NAME ENTITY HISTORY");
+
+            let recent = EntitiesHistory::get_recent_by_entity_ids(&conn, &entity_ids, 1);
+            assert_eq!(recent.len(), 1);
+            assert_eq!(recent[0].0.entity_id, enthist.entity_id);
+
+            Ok(())
+        });
+    }
 }
\ No newline at end of file
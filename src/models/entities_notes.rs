@@ -1,5 +1,6 @@
-use crate::schema::entities_notes;
+use crate::schema::{entities_notes, users};
 use crate::schema::entities_notes::dsl;
+use crate::utils::validators::validate_not_blank;
 
 use chrono::Utc;
 use chrono::naive::NaiveDateTime;
@@ -11,6 +12,15 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::vec::Vec;
 
+use validator::Validate;
+
+/// The text of a note, on a file or in the threads of an entity.
+#[derive(Debug, Deserialize, Validate)]
+pub struct NoteText {
+    #[validate(length(min = 1, max = 10000), custom = "validate_not_blank")]
+    pub note: String,
+}
+
 #[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
 #[changeset_options(treat_none_as_null = "true")]
 pub struct EntitiesNote {
@@ -21,7 +31,9 @@ pub struct EntitiesNote {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    parent_id: Option<uuid::Uuid>,
+    version: i32
 }
 
 impl PartialEq for EntitiesNote {
@@ -33,7 +45,8 @@ impl PartialEq for EntitiesNote {
         self.deleted == other.deleted &&
         self.created_at.timestamp() == other.created_at.timestamp() &&
         // self.updated_at.timestamp() == other.updated_at.timestamp() &&
-        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.parent_id == other.parent_id
     }
 }
 
@@ -51,7 +64,22 @@ impl EntitiesNote {
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            parent_id: None,
+            version: 1
+        }
+    }
+
+    /// Creates a reply in the thread of `parent`. Replies to a reply are
+    /// attached to the root of the thread so threads stay one level deep.
+    pub fn new_reply(
+        parent: &EntitiesNote,
+        user_id: uuid::Uuid,
+        note: String,
+    ) -> EntitiesNote {
+        EntitiesNote {
+            parent_id: Some(parent.parent_id.unwrap_or(parent.id)),
+            ..EntitiesNote::new(parent.entity_id, user_id, note)
         }
     }
 
@@ -59,24 +87,130 @@ impl EntitiesNote {
         self.id
     }
 
-    fn insert(conn: &PgConnection, entnote: &EntitiesNote) -> EntitiesNote {
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn entity_id(&self) -> uuid::Uuid {
+        self.entity_id
+    }
+
+    pub fn user_id(&self) -> uuid::Uuid {
+        self.user_id
+    }
+
+    pub fn note(&self) -> &String {
+        &self.note
+    }
+
+    pub fn parent_id(&self) -> Option<uuid::Uuid> {
+        self.parent_id
+    }
+
+    pub fn created_at(&self) -> chrono::NaiveDateTime {
+        self.created_at
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    /// Replaces the note text and returns the previous one.
+    pub fn set_note(&mut self, note: String) -> String {
+        std::mem::replace(&mut self.note, note)
+    }
+
+    pub fn mark_deleted(&mut self) {
+        self.deleted = true;
+        self.deleted_at = Utc::now().naive_utc();
+    }
+
+    /// Emails mentioned in the note with the `@email` syntax, without duplicates.
+    pub fn mentioned_emails(&self) -> Vec<String> {
+        let mut emails: Vec<String> = Vec::new();
+        for word in self.note.split_whitespace() {
+            if !word.starts_with('@') {
+                continue;
+            }
+            let email = word[1..].trim_end_matches(|c: char| !c.is_alphanumeric());
+            if !email.is_empty() && !emails.iter().any(|e| e == email) {
+                emails.push(email.to_owned());
+            }
+        }
+        emails
+    }
+
+    pub(super) fn insert(conn: &PgConnection, entnote: &EntitiesNote) -> EntitiesNote {
         diesel::insert_into(entities_notes::table)
             .values(entnote)
             .get_result(conn)
             .expect("Error saving new note")
     }
 
-    fn update(conn: &PgConnection, entnote: &EntitiesNote) -> EntitiesNote {
-        diesel::update(entities_notes::table)
+    pub(super) fn update(conn: &PgConnection, entnote: &EntitiesNote) -> Option<EntitiesNote> {
+        diesel::update(dsl::entities_notes
+                .filter(dsl::id.eq(entnote.id))
+                .filter(dsl::version.eq(entnote.version)))
             .set(entnote)
             .get_result(conn)
+            .optional()
             .expect("Error saving note")
     }
 
+    /// Deletes the replies of a thread root, which are not listed without it.
+    pub(super) fn delete_replies(conn: &PgConnection, root_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::entities_notes
+                .filter(dsl::parent_id.eq(root_id))
+                .filter(dsl::deleted.eq(false)))
+            .set((dsl::deleted.eq(true), dsl::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error deleting note replies")
+    }
+
     fn get_one_by_id(conn: &PgConnection, entnote_id: uuid::Uuid) -> EntitiesNote {
         dsl::entities_notes.filter(dsl::id.eq(entnote_id)).first(conn)
             .expect("Could not find entities note")
     }
+
+    pub(super) fn find_by_id(conn: &PgConnection, entnote_id: uuid::Uuid) -> Option<EntitiesNote> {
+        dsl::entities_notes.filter(dsl::id.eq(entnote_id)).first(conn)
+            .optional()
+            .expect("Error loading entities note")
+    }
+
+    /// Loads one page of the thread roots of an entity, newest first, along
+    /// with the full name of each note's author.
+    pub(super) fn get_threads_by_entity_id(
+        conn: &PgConnection,
+        entity_id: uuid::Uuid,
+        offset: i64,
+        limit: i64
+    ) -> Vec<(EntitiesNote, String)> {
+        entities_notes::table
+            .inner_join(users::table)
+            .filter(dsl::entity_id.eq(entity_id))
+            .filter(dsl::parent_id.is_null())
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::created_at.desc())
+            .offset(offset)
+            .limit(limit)
+            .select((entities_notes::all_columns, users::full_name))
+            .load::<(EntitiesNote, String)>(conn)
+            .expect("Error loading entities notes")
+    }
+
+    /// Loads the replies of the given thread roots, oldest first, along with
+    /// the full name of each reply's author.
+    pub(super) fn get_replies(conn: &PgConnection, parent_ids: &[uuid::Uuid]) -> Vec<(EntitiesNote, String)> {
+        entities_notes::table
+            .inner_join(users::table)
+            .filter(dsl::parent_id.eq_any(parent_ids))
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::created_at.asc())
+            .select((entities_notes::all_columns, users::full_name))
+            .load::<(EntitiesNote, String)>(conn)
+            .expect("Error loading entities notes replies")
+    }
 }
 
 #[cfg(test)]
@@ -140,4 +274,48 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn test_entity_note_threads() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let root = create_test_note(&conn);
+            EntitiesNote::insert(&conn, &root);
+            let reply = EntitiesNote::new_reply(&root, root.user_id(), String::from("REPLY"));
+            EntitiesNote::insert(&conn, &reply);
+            let nested = EntitiesNote::new_reply(&reply, root.user_id(), String::from("NESTED REPLY"));
+            EntitiesNote::insert(&conn, &nested);
+
+            assert_eq!(nested.parent_id(), Some(root.id()));
+
+            let threads = EntitiesNote::get_threads_by_entity_id(&conn, root.entity_id(), 0, 10);
+            assert_eq!(threads.len(), 1);
+            assert_eq!(threads[0].0, root);
+
+            let replies = EntitiesNote::get_replies(&conn, &[root.id()]);
+            assert_eq!(replies.len(), 2);
+            assert_eq!(replies[0].0, reply);
+            assert_eq!(replies[1].0, nested);
+
+            assert!(EntitiesNote::get_threads_by_entity_id(&conn, root.entity_id(), 1, 10).is_empty());
+
+            assert_eq!(EntitiesNote::delete_replies(&conn, root.id()), 2);
+            assert!(EntitiesNote::get_replies(&conn, &[root.id()]).is_empty());
+            assert!(EntitiesNote::find_by_id(&conn, nested.id()).unwrap().is_deleted());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_mentioned_emails() {
+        let entnote = EntitiesNote::new(
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            String::from("@jean@propfile.com can you check with @marie@propfile.com, and @jean@propfile.com? @ @@")
+        );
+
+        assert_eq!(entnote.mentioned_emails(), vec!["jean@propfile.com", "marie@propfile.com"]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use crate::schema::{entities_notes_edits, users};
+use crate::schema::entities_notes_edits::dsl;
+
+use chrono::Utc;
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::vec::Vec;
+
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+pub struct EntitiesNotesEdit {
+    id: uuid::Uuid,
+    note_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    previous_note: String,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime
+}
+
+impl PartialEq for EntitiesNotesEdit {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id &&
+        self.note_id == other.note_id &&
+        self.user_id == other.user_id &&
+        self.previous_note == other.previous_note &&
+        self.deleted == other.deleted &&
+        self.created_at.timestamp() == other.created_at.timestamp() &&
+        // self.updated_at.timestamp() == other.updated_at.timestamp() &&
+        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+    }
+}
+
+impl EntitiesNotesEdit {
+    pub fn new(
+        note_id: uuid::Uuid,
+        user_id: uuid::Uuid,
+        previous_note: String,
+    ) -> EntitiesNotesEdit {
+        EntitiesNotesEdit {
+            id: uuid::Uuid::new_v4(),
+            note_id,
+            user_id,
+            previous_note,
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+        }
+    }
+
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    pub fn note_id(&self) -> uuid::Uuid {
+        self.note_id
+    }
+
+    pub fn user_id(&self) -> uuid::Uuid {
+        self.user_id
+    }
+
+    pub fn previous_note(&self) -> &String {
+        &self.previous_note
+    }
+
+    pub fn created_at(&self) -> chrono::NaiveDateTime {
+        self.created_at
+    }
+
+    pub(super) fn insert(conn: &PgConnection, edit: &EntitiesNotesEdit) -> EntitiesNotesEdit {
+        diesel::insert_into(entities_notes_edits::table)
+            .values(edit)
+            .get_result(conn)
+            .expect("Error saving note edit")
+    }
+
+    /// Loads the edit log of a note, oldest first, along with the full name
+    /// of the user who made each edit.
+    pub(super) fn get_by_note_id(conn: &PgConnection, note_id: uuid::Uuid) -> Vec<(EntitiesNotesEdit, String)> {
+        entities_notes_edits::table
+            .inner_join(users::table)
+            .filter(dsl::note_id.eq(note_id))
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::created_at.asc())
+            .select((entities_notes_edits::all_columns, users::full_name))
+            .load::<(EntitiesNotesEdit, String)>(conn)
+            .expect("Error loading note edits")
+    }
+
+    /// Returns which of the given notes have been edited at least once.
+    pub(super) fn get_edited_note_ids(conn: &PgConnection, note_ids: &[uuid::Uuid]) -> Vec<uuid::Uuid> {
+        dsl::entities_notes_edits
+            .filter(dsl::note_id.eq_any(note_ids))
+            .filter(dsl::deleted.eq(false))
+            .select(dsl::note_id)
+            .distinct()
+            .load::<uuid::Uuid>(conn)
+            .expect("Error loading note edits")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EntitiesNotesEdit, Connection};
+    use super::super::entities_notes::{EntitiesNote, test_functions::*};
+    use crate::db;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_note_edit_log() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let entnote = create_test_note(&conn);
+            EntitiesNote::insert(&conn, &entnote);
+            assert!(EntitiesNotesEdit::get_edited_note_ids(&conn, &[entnote.id()]).is_empty());
+
+            let edit = EntitiesNotesEdit::new(entnote.id(), entnote.user_id(), entnote.note().clone());
+            EntitiesNotesEdit::insert(&conn, &edit);
+
+            let edits = EntitiesNotesEdit::get_by_note_id(&conn, entnote.id());
+            assert_eq!(edits.len(), 1);
+            assert_eq!(edits[0].0, edit);
+            assert_eq!(edits[0].0.previous_note(), "THIS IS A NOTE");
+            assert_eq!(EntitiesNotesEdit::get_edited_note_ids(&conn, &[entnote.id()]), vec![entnote.id()]);
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,154 @@
+use crate::schema::entities_notes_mentions;
+use crate::schema::entities_notes_mentions::dsl;
+
+use chrono::Utc;
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::vec::Vec;
+
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+#[primary_key(note_id, user_id)]
+pub struct EntitiesNotesMention {
+    note_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime
+}
+
+impl PartialEq for EntitiesNotesMention {
+    fn eq(&self, other: &Self) -> bool {
+        self.note_id == other.note_id &&
+        self.user_id == other.user_id &&
+        self.deleted == other.deleted &&
+        self.created_at.timestamp() == other.created_at.timestamp() &&
+        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+    }
+}
+
+impl EntitiesNotesMention {
+    pub fn new(
+        note_id: uuid::Uuid,
+        user_id: uuid::Uuid
+    ) -> EntitiesNotesMention {
+        EntitiesNotesMention {
+            note_id,
+            user_id,
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+        }
+    }
+
+    pub fn note_id(&self) -> uuid::Uuid {
+        self.note_id
+    }
+
+    pub fn user_id(&self) -> uuid::Uuid {
+        self.user_id
+    }
+
+    pub(super) fn insert_all(conn: &PgConnection, mentions: &[EntitiesNotesMention]) -> Vec<EntitiesNotesMention> {
+        diesel::insert_into(entities_notes_mentions::table)
+            .values(mentions)
+            .on_conflict_do_nothing()
+            .get_results(conn)
+            .expect("Error saving note mentions")
+    }
+
+    /// Removes every mention of a note, used before the mentions of an
+    /// edited note are parsed again.
+    pub(super) fn delete_by_note_id(conn: &PgConnection, note_id: uuid::Uuid) -> usize {
+        diesel::delete(dsl::entities_notes_mentions.filter(dsl::note_id.eq(note_id)))
+            .execute(conn)
+            .expect("Error deleting note mentions")
+    }
+
+    /// Moves the mentions of a user to another, returning the number moved.
+    /// Notes already mentioning the other user keep their mention of the
+    /// first one.
+    pub(super) fn reassign(conn: &PgConnection, from_id: uuid::Uuid, to_id: uuid::Uuid) -> usize {
+        let already_mentioned = dsl::entities_notes_mentions
+            .filter(dsl::user_id.eq(to_id))
+            .select(dsl::note_id);
+
+        diesel::update(dsl::entities_notes_mentions
+                .filter(dsl::user_id.eq(from_id))
+                .filter(dsl::deleted.eq(false))
+                .filter(dsl::note_id.ne_all(already_mentioned)))
+            .set(dsl::user_id.eq(to_id))
+            .execute(conn)
+            .expect("Error saving note mentions")
+    }
+
+    pub(super) fn get_by_note_ids(conn: &PgConnection, note_ids: &[uuid::Uuid]) -> Vec<EntitiesNotesMention> {
+        dsl::entities_notes_mentions
+            .filter(dsl::note_id.eq_any(note_ids))
+            .filter(dsl::deleted.eq(false))
+            .load::<EntitiesNotesMention>(conn)
+            .expect("Error loading note mentions")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EntitiesNotesMention, Connection};
+    use super::super::entities_notes::{EntitiesNote, test_functions::*};
+    use super::super::users::{User, test_functions::*};
+    use crate::db;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_insert_note_mentions() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let entnote = create_test_note(&conn);
+            EntitiesNote::insert(&conn, &entnote);
+
+            let mention = EntitiesNotesMention::new(entnote.id(), entnote.user_id());
+            EntitiesNotesMention::insert_all(&conn, &[mention]);
+            // Mentioning the same user twice is ignored
+            EntitiesNotesMention::insert_all(&conn, &[EntitiesNotesMention::new(entnote.id(), entnote.user_id())]);
+
+            let stored = EntitiesNotesMention::get_by_note_ids(&conn, &[entnote.id()]);
+            assert_eq!(stored.len(), 1);
+            assert_eq!(stored[0].user_id(), entnote.user_id());
+
+            assert_eq!(EntitiesNotesMention::delete_by_note_id(&conn, entnote.id()), 1);
+            assert!(EntitiesNotesMention::get_by_note_ids(&conn, &[entnote.id()]).is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_reassign_mentions() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let entnote = create_test_note(&conn);
+            EntitiesNote::insert(&conn, &entnote);
+            let successor = create_test_user(&conn, String::from("SUCCESSOR"));
+            User::insert(&conn, &successor);
+
+            EntitiesNotesMention::insert_all(&conn, &[EntitiesNotesMention::new(entnote.id(), entnote.user_id())]);
+            assert_eq!(EntitiesNotesMention::reassign(&conn, entnote.user_id(), successor.id()), 1);
+            let stored = EntitiesNotesMention::get_by_note_ids(&conn, &[entnote.id()]);
+            assert_eq!(stored.iter().map(|m| m.user_id()).collect::<Vec<_>>(), vec![successor.id()]);
+
+            // Already mentioned, the successor is not mentioned twice
+            EntitiesNotesMention::insert_all(&conn, &[EntitiesNotesMention::new(entnote.id(), entnote.user_id())]);
+            assert_eq!(EntitiesNotesMention::reassign(&conn, entnote.user_id(), successor.id()), 0);
+
+            Ok(())
+        });
+    }
+}
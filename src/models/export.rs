@@ -0,0 +1,300 @@
+use super::buildings::Building;
+use super::building_owners::BuildingOwner;
+use super::coordinates::{Coordinate, CoordinateResponse};
+use super::entities_history::EntitiesHistory;
+use super::registers::Register;
+use super::users::User;
+
+use chrono::NaiveDateTime;
+
+use diesel::PgConnection;
+
+use rust_xlsxwriter::Workbook;
+
+use serde_derive::Serialize;
+
+use std::collections::HashMap;
+
+/// Header of the CSV and XLSX exports, in the order of `ExportRow`'s fields.
+pub const EXPORT_COLUMNS: &[&str] = &[
+    "building_id",
+    "building_name",
+    "address",
+    "owner_name",
+    "owner_kind",
+    "owner_company_name",
+    "owner_company_number",
+    "owner_address",
+    "owner_telephone_no",
+    "owner_email",
+    "respondant_name",
+    "respondant_email",
+    "registers",
+    "register_names",
+    "last_activity",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "xlsx" => Some(ExportFormat::Xlsx),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// A building of the portfolio with its owner, respondant and registers.
+/// The last activity is the date of the latest file change of the building
+/// or of one of its registers.
+#[derive(Debug, Serialize)]
+pub struct ExportRow {
+    pub building_id: uuid::Uuid,
+    pub building_name: String,
+    pub address: String,
+    pub owner_name: String,
+    pub owner_kind: String,
+    pub owner_company_name: String,
+    pub owner_company_number: String,
+    pub owner_address: String,
+    pub owner_telephone_no: String,
+    pub owner_email: String,
+    pub respondant_name: String,
+    pub respondant_email: String,
+    pub registers: usize,
+    pub register_names: Vec<String>,
+    pub last_activity: Option<NaiveDateTime>,
+}
+
+impl ExportRow {
+    /// The row as spreadsheet cells, in the order of `EXPORT_COLUMNS`.
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.building_id.to_string(),
+            self.building_name.clone(),
+            self.address.clone(),
+            self.owner_name.clone(),
+            self.owner_kind.clone(),
+            self.owner_company_name.clone(),
+            self.owner_company_number.clone(),
+            self.owner_address.clone(),
+            self.owner_telephone_no.clone(),
+            self.owner_email.clone(),
+            self.respondant_name.clone(),
+            self.respondant_email.clone(),
+            self.registers.to_string(),
+            self.register_names.join("; "),
+            self.last_activity.map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+/// Loads the organization's buildings, or only those of one of its owners,
+/// as export rows sorted by building name.
+pub fn portfolio(conn: &PgConnection, org_id: uuid::Uuid, owner_id: Option<uuid::Uuid>) -> Vec<ExportRow> {
+    let buildings = Building::get_by_org(&conn, org_id, owner_id);
+    let registers = Register::get_by_buildings(&conn, &buildings);
+
+    let owners: HashMap<uuid::Uuid, BuildingOwner> = BuildingOwner::get_by_org(&conn, org_id)
+        .into_iter()
+        .map(|o| (o.id(), o))
+        .collect();
+    let coord_ids: Vec<uuid::Uuid> = owners.values().filter_map(|o| o.coordinates_id()).collect();
+    let coordinates: HashMap<uuid::Uuid, CoordinateResponse> = Coordinate::get_by_ids(&conn, &coord_ids)
+        .iter()
+        .map(|c| (c.id(), CoordinateResponse::from(c)))
+        .collect();
+    let respondant_ids: Vec<uuid::Uuid> = buildings.iter().map(|b| b.respondant_id()).collect();
+    let respondants: HashMap<uuid::Uuid, User> = User::get_by_ids(&conn, &respondant_ids)
+        .into_iter()
+        .map(|u| (u.id(), u))
+        .collect();
+
+    let entity_ids: Vec<uuid::Uuid> = buildings.iter().map(|b| b.id())
+        .chain(registers.iter().flatten().map(|r| r.id()))
+        .collect();
+    let activity: HashMap<uuid::Uuid, NaiveDateTime> = EntitiesHistory::get_last_activity(&conn, &entity_ids)
+        .into_iter()
+        .filter_map(|(id, date)| date.map(|date| (id, date)))
+        .collect();
+
+    buildings.iter()
+        .zip(registers)
+        .map(|(building, registers)| {
+            let owner = owners.get(&building.owner_id());
+            let coordinate = owner.and_then(|o| o.coordinates_id()).and_then(|id| coordinates.get(&id));
+            let respondant = respondants.get(&building.respondant_id());
+            let coordinate_field = |field: fn(&CoordinateResponse) -> &String| {
+                coordinate.map(field).cloned().unwrap_or_default()
+            };
+
+            ExportRow {
+                building_id: building.id(),
+                building_name: building.name().clone(),
+                address: building.address().clone(),
+                owner_name: owner.map(|o| o.full_name().clone()).unwrap_or_default(),
+                owner_kind: owner.and_then(|o| o.kind()).map(|k| k.name().to_owned()).unwrap_or_default(),
+                owner_company_name: coordinate_field(|c| &c.company_name),
+                owner_company_number: coordinate_field(|c| &c.company_number),
+                owner_address: coordinate_field(|c| &c.address),
+                owner_telephone_no: coordinate_field(|c| &c.telephone_no),
+                owner_email: coordinate_field(|c| &c.email),
+                respondant_name: respondant.map(|u| u.full_name().clone()).unwrap_or_default(),
+                respondant_email: respondant.map(|u| u.email().clone()).unwrap_or_default(),
+                registers: registers.len(),
+                last_activity: std::iter::once(building.id())
+                    .chain(registers.iter().map(|r| r.id()))
+                    .filter_map(|id| activity.get(&id).cloned())
+                    .max(),
+                register_names: registers.iter().map(|r| r.name().clone()).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Keeps spreadsheet apps from running a cell as a formula, by prefixing
+/// the characters that start one with a quote, as they do for text typed in.
+fn escape_formula(cell: String) -> String {
+    match cell.chars().next() {
+        Some('=') | Some('+') | Some('-') | Some('@') | Some('\t') | Some('\r') => format!("'{}", cell),
+        _ => cell,
+    }
+}
+
+fn write_csv(rows: &[ExportRow]) -> Result<Vec<u8>, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(EXPORT_COLUMNS).map_err(|e| e.to_string())?;
+    for row in rows {
+        let cells: Vec<String> = row.cells().into_iter().map(escape_formula).collect();
+        writer.write_record(&cells).map_err(|e| e.to_string())?;
+    }
+
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+fn write_xlsx(rows: &[ExportRow]) -> Result<Vec<u8>, String> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Buildings").map_err(|e| e.to_string())?;
+
+    for (col, name) in EXPORT_COLUMNS.iter().enumerate() {
+        sheet.write_string(0, col as u16, *name).map_err(|e| e.to_string())?;
+    }
+    // Written as strings, cells are never run as formulas
+    for (i, row) in rows.iter().enumerate() {
+        for (col, cell) in row.cells().iter().enumerate() {
+            sheet.write_string(i as u32 + 1, col as u16, cell).map_err(|e| e.to_string())?;
+        }
+    }
+
+    workbook.save_to_buffer().map_err(|e| e.to_string())
+}
+
+/// Renders the rows as a file of the format.
+pub fn render(format: ExportFormat, rows: &[ExportRow]) -> Result<Vec<u8>, String> {
+    match format {
+        ExportFormat::Csv => write_csv(rows),
+        ExportFormat::Xlsx => write_xlsx(rows),
+        ExportFormat::Json => serde_json::to_vec(rows).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_formula, portfolio, render, ExportFormat, EXPORT_COLUMNS};
+    use super::super::api::{self, APIError};
+    use super::super::building_owners::{BuildingOwner, test_functions::*};
+    use super::super::buildings::{Building, test_functions::*};
+    use super::super::users::{User, test_functions::*};
+    use crate::db;
+    use diesel::Connection;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_export_formats() {
+        assert_eq!(ExportFormat::from_name("XLSX"), Some(ExportFormat::Xlsx));
+        assert_eq!(ExportFormat::from_name("pdf"), None);
+
+        let csv = String::from_utf8(render(ExportFormat::Csv, &[]).unwrap()).unwrap();
+        assert_eq!(csv.trim_end(), EXPORT_COLUMNS.join(","));
+        assert_eq!(render(ExportFormat::Json, &[]).unwrap(), b"[]");
+        assert!(render(ExportFormat::Xlsx, &[]).unwrap().starts_with(b"PK"));
+    }
+
+    #[test]
+    fn test_escape_formula() {
+        assert_eq!(escape_formula(String::from("=HYPERLINK(\"http://evil\")")), "'=HYPERLINK(\"http://evil\")");
+        assert_eq!(escape_formula(String::from("+1 438 555 1236")), "'+1 438 555 1236");
+        assert_eq!(escape_formula(String::from("-2+3")), "'-2+3");
+        assert_eq!(escape_formula(String::from("@SUM(A1)")), "'@SUM(A1)");
+        assert_eq!(escape_formula(String::from("12 rue Principale")), "12 rue Principale");
+        assert_eq!(escape_formula(String::new()), "");
+    }
+
+    #[test]
+    fn test_portfolio() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let building = create_test_building1(&conn);
+            Building::insert(&conn, &building);
+
+            let rows = portfolio(&conn, building.org_id(), None);
+            let row = rows.iter().find(|r| r.building_id == building.id()).unwrap();
+            assert_eq!(&row.building_name, building.name());
+            assert_eq!(row.registers, 0);
+
+            assert!(portfolio(&conn, building.org_id(), Some(uuid::Uuid::new_v4())).is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_export_writer_errors() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("EXPORT"));
+            User::insert(&conn, &user);
+            let owner = create_test_building_owner1(&conn);
+            BuildingOwner::insert(&conn, &owner);
+            // Longer than an xlsx cell holds
+            let address = "a".repeat(40_000);
+            let building = Building::new(owner.id(), user.org_id(), user.id(), String::from("EXPORT"), address);
+            Building::insert(&conn, &building);
+
+            match api::export_buildings(&conn, user.id(), "xlsx", None) {
+                Err(APIError::Rendering(_)) => (),
+                other => panic!("Expected a rendering error, got {:?}", other.map(|(format, _)| format)),
+            }
+            assert!(api::export_buildings(&conn, user.id(), "csv", None).is_ok());
+
+            Ok(())
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use crate::schema::files;
+use crate::schema::{entities_files, files};
 use crate::schema::files::dsl;
 
 use chrono::Utc;
@@ -20,7 +20,9 @@ pub struct File {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    metadata: serde_json::Value,
+    version: i32
 }
 
 impl PartialEq for File {
@@ -32,7 +34,8 @@ impl PartialEq for File {
         self.deleted == other.deleted &&
         self.created_at.timestamp() == other.created_at.timestamp() &&
         // self.updated_at.timestamp() == other.updated_at.timestamp() &&
-        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.metadata == other.metadata
     }
 }
 
@@ -50,7 +53,9 @@ impl File {
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            metadata: serde_json::Value::Object(serde_json::Map::new()),
+            version: 1
         }
     }
 
@@ -58,6 +63,35 @@ impl File {
         self.id
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn filename(&self) -> &String {
+        &self.filename
+    }
+
+    pub fn url(&self) -> &String {
+        &self.url
+    }
+
+    pub fn metadata(&self) -> &serde_json::Value {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, key: String, value: serde_json::Value) {
+        if !self.metadata.is_object() {
+            self.metadata = serde_json::Value::Object(serde_json::Map::new());
+        }
+        if let Some(map) = self.metadata.as_object_mut() {
+            map.insert(key, value);
+        }
+    }
+
+    pub fn remove_metadata(&mut self, key: &str) -> Option<serde_json::Value> {
+        self.metadata.as_object_mut().and_then(|map| map.remove(key))
+    }
+
     pub(super) fn insert(conn: &PgConnection, file: &File) -> File {
         diesel::insert_into(files::table)
             .values(file)
@@ -65,10 +99,13 @@ impl File {
             .expect("Error saving file")
     }
 
-    fn update(conn: &PgConnection, file: &File) -> File {
-        diesel::update(files::table)
+    fn update(conn: &PgConnection, file: &File) -> Option<File> {
+        diesel::update(dsl::files
+                .filter(dsl::id.eq(file.id))
+                .filter(dsl::version.eq(file.version)))
             .set(file)
             .get_result(conn)
+            .optional()
             .expect("Error saving file")
     }
 
@@ -77,6 +114,35 @@ impl File {
             .expect("Could not find specified file")
     }
 
+    pub(super) fn get_by_entity_id(conn: &PgConnection, entity_id: uuid::Uuid) -> Vec<File> {
+        files::table
+            .inner_join(entities_files::table)
+            .filter(entities_files::entity_id.eq(entity_id))
+            .filter(entities_files::deleted.eq(false))
+            .filter(dsl::deleted.eq(false))
+            .order(entities_files::created_at.asc())
+            .select(files::all_columns)
+            .load::<File>(conn)
+            .expect("Error loading entity files")
+    }
+
+    /// Loads the files of every given entity along with the entity each file
+    /// is attached to and the folder it is filed in.
+    pub(super) fn get_with_folders_by_entity_ids(
+        conn: &PgConnection,
+        entity_ids: &[uuid::Uuid]
+    ) -> Vec<(File, uuid::Uuid, String)> {
+        files::table
+            .inner_join(entities_files::table)
+            .filter(entities_files::entity_id.eq_any(entity_ids))
+            .filter(entities_files::deleted.eq(false))
+            .filter(dsl::deleted.eq(false))
+            .order(entities_files::created_at.asc())
+            .select((files::all_columns, entities_files::entity_id, entities_files::folder))
+            .load::<(File, uuid::Uuid, String)>(conn)
+            .expect("Error loading entity files")
+    }
+
 }
 
 #[cfg(test)]
@@ -97,8 +163,10 @@ pub mod test_functions {
 pub mod tests {
     use super::{File, Connection};
     use super::test_functions::*;
+    use super::super::entities_files::{EntitiesFile, test_functions::*};
     use crate::db;
     use diesel::result::Error;
+    use serde_json::json;
 
     #[test]
     fn test_create_new_file() {
@@ -136,4 +204,61 @@ pub mod tests {
         });
     }
 
+    #[test]
+    fn test_file_metadata() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let mut file = create_test_file(&conn);
+            file.set_metadata(String::from("inspector"), json!("Jean Tremblay"));
+            file.set_metadata(String::from("pages"), json!(12));
+            File::insert(&conn, &file);
+
+            let mut stored_file = File::get_one_by_id(&conn, file.id());
+            assert_eq!(stored_file.metadata(), &json!({"inspector": "Jean Tremblay", "pages": 12}));
+
+            stored_file.remove_metadata("pages");
+            File::update(&conn, &stored_file);
+            let saved_file = File::get_one_by_id(&conn, file.id());
+            assert_eq!(saved_file.metadata(), &json!({"inspector": "Jean Tremblay"}));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_get_files_by_entity_id() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let entfile = create_test_entity_file(&conn);
+            EntitiesFile::insert(&conn, &entfile);
+
+            let files = File::get_by_entity_id(&conn, entfile.entity_id());
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].id(), entfile.id());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_get_files_with_folders() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let entfile = create_test_entity_file(&conn);
+            let entfile = EntitiesFile::new_in_folder(entfile.id(), entfile.entity_id(), String::from("Plans"));
+            EntitiesFile::insert(&conn, &entfile);
+
+            let files = File::get_with_folders_by_entity_ids(&conn, &[entfile.entity_id()]);
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].0.id(), entfile.id());
+            assert_eq!(files[0].1, entfile.entity_id());
+            assert_eq!(files[0].2, "Plans");
+
+            Ok(())
+        });
+    }
+
 }
@@ -0,0 +1,193 @@
+use crate::schema::{files_notes, users};
+use crate::schema::files_notes::dsl;
+
+use chrono::Utc;
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::vec::Vec;
+
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+pub struct FilesNote {
+    id: uuid::Uuid,
+    file_id: uuid::Uuid,
+    entity_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    note: String,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime
+}
+
+impl PartialEq for FilesNote {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id &&
+        self.file_id == other.file_id &&
+        self.entity_id == other.entity_id &&
+        self.user_id == other.user_id &&
+        self.note == other.note &&
+        self.deleted == other.deleted &&
+        self.created_at.timestamp() == other.created_at.timestamp() &&
+        // self.updated_at.timestamp() == other.updated_at.timestamp() &&
+        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+    }
+}
+
+impl FilesNote {
+    pub fn new(
+        file_id: uuid::Uuid,
+        entity_id: uuid::Uuid,
+        user_id: uuid::Uuid,
+        note: String,
+    ) -> FilesNote {
+        FilesNote {
+            id: uuid::Uuid::new_v4(),
+            file_id,
+            entity_id,
+            user_id,
+            note,
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+        }
+    }
+
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    pub fn file_id(&self) -> uuid::Uuid {
+        self.file_id
+    }
+
+    pub fn user_id(&self) -> uuid::Uuid {
+        self.user_id
+    }
+
+    pub fn note(&self) -> &String {
+        &self.note
+    }
+
+    pub fn created_at(&self) -> chrono::NaiveDateTime {
+        self.created_at
+    }
+
+    pub(super) fn insert(conn: &PgConnection, filenote: &FilesNote) -> FilesNote {
+        diesel::insert_into(files_notes::table)
+            .values(filenote)
+            .get_result(conn)
+            .expect("Error saving new file note")
+    }
+
+    fn get_one_by_id(conn: &PgConnection, filenote_id: uuid::Uuid) -> FilesNote {
+        dsl::files_notes.filter(dsl::id.eq(filenote_id)).first(conn)
+            .expect("Could not find file note")
+    }
+
+    /// Loads the notes of every given file for one entity, along with the
+    /// full name of each note's author.
+    pub(super) fn get_by_file_ids(
+        conn: &PgConnection,
+        entity_id: uuid::Uuid,
+        file_ids: &[uuid::Uuid]
+    ) -> Vec<(FilesNote, String)> {
+        files_notes::table
+            .inner_join(users::table)
+            .filter(dsl::entity_id.eq(entity_id))
+            .filter(dsl::file_id.eq_any(file_ids))
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::created_at.asc())
+            .select((files_notes::all_columns, users::full_name))
+            .load::<(FilesNote, String)>(conn)
+            .expect("Error loading file notes")
+    }
+}
+
+#[cfg(test)]
+pub mod test_functions {
+    use super::FilesNote;
+    use super::super::entities_files::{EntitiesFile, test_functions::*};
+    use super::super::users::{User, test_functions::*};
+
+    use diesel::PgConnection;
+
+    pub fn create_test_file_note(conn: &PgConnection) -> FilesNote {
+        let test_entfile = create_test_entity_file(&conn);
+        EntitiesFile::insert(&conn, &test_entfile);
+
+        let test_user = create_test_user(&conn, String::from("FILES NOTES"));
+        User::insert(&conn, &test_user);
+
+        FilesNote::new(
+            test_entfile.id(),
+            test_entfile.entity_id(),
+            test_user.id(),
+            String::from("THIS IS A FILE NOTE")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilesNote, Connection};
+    use super::test_functions::*;
+    use crate::db;
+    use crate::schema::files_notes;
+    use diesel::RunQueryDsl;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_insert_file_note() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let filenote = create_test_file_note(&conn);
+            FilesNote::insert(&conn, &filenote);
+            let stored_filenote = FilesNote::get_one_by_id(&conn, filenote.id());
+            assert_eq!(filenote, stored_filenote);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_get_file_notes_by_file_ids() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let filenote = create_test_file_note(&conn);
+            FilesNote::insert(&conn, &filenote);
+
+            let notes = FilesNote::get_by_file_ids(&conn, filenote.entity_id, &[filenote.file_id()]);
+            assert_eq!(notes.len(), 1);
+            assert_eq!(notes[0].0, filenote);
+            assert_eq!(notes[0].1, "USER NAME FILES NOTES");
+
+            let other_entity = FilesNote::get_by_file_ids(&conn, uuid::Uuid::new_v4(), &[filenote.file_id()]);
+            assert!(other_entity.is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_file_note_of_another_entity() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let filenote = create_test_file_note(&conn);
+            let other_entity = FilesNote { entity_id: uuid::Uuid::new_v4(), ..filenote };
+
+            let inserted = diesel::insert_into(files_notes::table).values(&other_entity).execute(&conn);
+            assert!(inserted.is_err());
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,453 @@
+use super::api::{field_error_map, APIError};
+use super::buildings::{Building, NewBuilding};
+use super::building_owners::{BuildingOwner, OwnerKind};
+use super::coordinates::{Coordinate, NewCoordinate};
+use super::organizations::Organization;
+use super::users::User;
+
+use calamine::{Reader, Xlsx};
+
+use diesel::{Connection, PgConnection};
+
+use serde_derive::Serialize;
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use validator::Validate;
+
+/// Largest file accepted by an import, unless `uploads.max_import_size` of
+/// the configuration says otherwise.
+pub const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024;
+
+/// Columns of an import file, matched with its header row without regard to
+/// case. Only `building_name` and `address` are required, the owner
+/// columns describe the building's owner and the owner's coordinates.
+pub const IMPORT_COLUMNS: &[&str] = &[
+    "building_name",
+    "address",
+    "respondant_email",
+    "owner_name",
+    "owner_kind",
+    "owner_address",
+    "owner_telephone_no",
+    "owner_fax",
+    "owner_cellphone_no",
+    "owner_email",
+    "owner_company_name",
+    "owner_company_number",
+];
+
+const REQUIRED_COLUMNS: &[&str] = &["building_name", "address"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ImportFormat {
+    pub fn from_name(name: &str) -> Option<ImportFormat> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(ImportFormat::Csv),
+            "xlsx" => Some(ImportFormat::Xlsx),
+            _ => None,
+        }
+    }
+}
+
+/// The cells of a row, by column. Absent and blank cells are left out.
+pub type Record = HashMap<&'static str, String>;
+
+#[derive(Debug, Serialize)]
+pub struct RowError {
+    /// Line of the row in the file, the header being line 1.
+    pub row: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Whether the rows were saved: never for a dry run, and only when every
+    /// row is valid otherwise.
+    pub committed: bool,
+    pub rows: usize,
+    pub buildings_created: usize,
+    pub owners_created: usize,
+    pub owners_reused: usize,
+    pub coordinates_created: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Turns a header row and its data rows into records.
+fn to_records<I>(header: Vec<String>, rows: I) -> Result<Vec<Record>, String>
+    where I: Iterator<Item = Result<Vec<String>, String>>
+{
+    let columns = header.iter()
+        .map(|name| {
+            let name = name.trim().to_lowercase();
+            IMPORT_COLUMNS.iter()
+                .find(|c| **c == name)
+                .cloned()
+                .ok_or_else(|| format!("Unknown column {}", name))
+        })
+        .collect::<Result<Vec<&'static str>, String>>()?;
+
+    if let Some(missing) = REQUIRED_COLUMNS.iter().find(|c| !columns.contains(c)) {
+        return Err(format!("Missing column {}", missing));
+    }
+
+    rows.map(|row| {
+        Ok(columns.iter()
+            .zip(row?)
+            .map(|(column, cell)| (*column, cell.trim().to_owned()))
+            .filter(|(_, cell)| !cell.is_empty())
+            .collect())
+    }).collect()
+}
+
+fn read_csv(data: &[u8]) -> Result<Vec<Record>, String> {
+    let mut reader = csv::ReaderBuilder::new().from_reader(data);
+    let header = reader.headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(String::from)
+        .collect();
+    let rows = reader.records()
+        .map(|row| row.map(|r| r.iter().map(String::from).collect()).map_err(|e| e.to_string()));
+
+    to_records(header, rows)
+}
+
+/// Reads the first sheet of a workbook.
+fn read_xlsx(data: &[u8]) -> Result<Vec<Record>, String> {
+    let mut workbook = Xlsx::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let sheet = workbook.worksheet_range_at(0)
+        .ok_or_else(|| String::from("The workbook has no sheet"))?
+        .map_err(|e| e.to_string())?;
+
+    let mut rows = sheet.rows().map(|row| row.iter().map(|cell| cell.to_string()).collect::<Vec<String>>());
+    let header = rows.next().ok_or_else(|| String::from("The sheet is empty"))?;
+
+    to_records(header, rows.map(Ok))
+}
+
+pub fn read_records(format: ImportFormat, data: &[u8]) -> Result<Vec<Record>, String> {
+    match format {
+        ImportFormat::Csv => read_csv(data),
+        ImportFormat::Xlsx => read_xlsx(data),
+    }
+}
+
+/// Name of an owner as compared when looking for duplicates.
+fn name_key(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+/// The owners of the organization, those already stored and those created
+/// by the import. Owners are the same when they have the same company
+/// number or, when the row gives none, the same name.
+#[derive(Default)]
+struct OwnerIndex {
+    by_number: HashMap<String, uuid::Uuid>,
+    by_name: HashMap<String, uuid::Uuid>,
+    manager: Option<uuid::Uuid>,
+}
+
+impl OwnerIndex {
+    fn load(conn: &PgConnection, org: &Organization) -> OwnerIndex {
+        let owners = BuildingOwner::get_by_org(&conn, org.id());
+        let coord_ids: Vec<uuid::Uuid> = owners.iter().filter_map(|o| o.coordinates_id()).collect();
+        let coordinates = Coordinate::get_by_ids(&conn, &coord_ids);
+        let mut index = OwnerIndex::default();
+
+        for owner in &owners {
+            if owner.kind() == Some(OwnerKind::Manager) {
+                index.manager = Some(owner.id());
+                continue;
+            }
+
+            let number = owner.coordinates_id()
+                .and_then(|id| coordinates.iter().find(|c| c.id() == id))
+                .map(|c| c.company_number().trim().to_owned())
+                .unwrap_or_default();
+            index.add(owner, &number);
+        }
+
+        index
+    }
+
+    fn add(&mut self, owner: &BuildingOwner, company_number: &str) {
+        if !company_number.is_empty() {
+            self.by_number.insert(company_number.to_owned(), owner.id());
+        }
+        self.by_name.entry(name_key(owner.full_name())).or_insert_with(|| owner.id());
+    }
+
+    fn find(&self, name: &str, company_number: &str) -> Option<uuid::Uuid> {
+        if company_number.is_empty() {
+            self.by_name.get(&name_key(name)).cloned()
+        } else {
+            self.by_number.get(company_number).cloned()
+        }
+    }
+}
+
+/// Counts and errors gathered while importing rows.
+struct Import<'a> {
+    conn: &'a PgConnection,
+    user: &'a User,
+    org: Organization,
+    owners: OwnerIndex,
+    report: ImportReport,
+}
+
+impl<'a> Import<'a> {
+    fn error(&mut self, row: usize, field: Option<&str>, message: String) {
+        self.report.errors.push(RowError { row, field: field.map(String::from), message });
+    }
+
+    /// Reports the invalid fields of a row under their column names.
+    fn validation_errors(&mut self, row: usize, column: fn(&str) -> String, errors: &validator::ValidationErrors) {
+        for (field, codes) in field_error_map(errors) {
+            self.error(row, Some(&column(field)), format!("Invalid value: {}", codes.join(", ")));
+        }
+    }
+
+    fn respondant(&mut self, row: usize, record: &Record) -> Option<uuid::Uuid> {
+        let email = match record.get("respondant_email") {
+            Some(email) => email.clone(),
+            None => return Some(self.user.id()),
+        };
+
+        let respondant = User::get_by_emails_in_org(&self.conn, self.org.id(), &[email])
+            .into_iter()
+            .find(|u| !u.is_owner());
+        if respondant.is_none() {
+            self.error(row, Some("respondant_email"), String::from("Unknown respondant"));
+        }
+
+        respondant.map(|u| u.id())
+    }
+
+    /// Finds the row's owner, or validates and saves a new one with its
+    /// coordinates.
+    fn owner(&mut self, row: usize, record: &Record) -> Option<uuid::Uuid> {
+        let kind = match record.get("owner_kind").map(|k| k.to_lowercase()).as_ref().map(String::as_str) {
+            None | Some("individual") => OwnerKind::Individual,
+            Some("company") => OwnerKind::Company,
+            Some("manager") => OwnerKind::Manager,
+            Some(_) => {
+                self.error(row, Some("owner_kind"), String::from("Expected individual, company or manager"));
+                return None;
+            }
+        };
+
+        if kind == OwnerKind::Manager {
+            return Some(match self.owners.manager {
+                Some(id) => {
+                    self.report.owners_reused += 1;
+                    id
+                }
+                None => {
+                    let owner = BuildingOwner::new_same_as_manager(&self.org);
+                    BuildingOwner::insert(&self.conn, &owner);
+                    self.report.owners_created += 1;
+                    self.owners.manager = Some(owner.id());
+                    owner.id()
+                }
+            });
+        }
+
+        let name = record.get("owner_name").cloned().unwrap_or_default();
+        let number = record.get("owner_company_number").cloned().unwrap_or_default();
+        if let Some(id) = self.owners.find(&name, &number) {
+            self.report.owners_reused += 1;
+            return Some(id);
+        }
+
+        if name.is_empty() {
+            self.error(row, Some("owner_name"), String::from("Required"));
+            return None;
+        }
+
+        let fields = NewCoordinate {
+            address: record.get("owner_address").cloned(),
+            telephone_no: record.get("owner_telephone_no").cloned(),
+            fax: record.get("owner_fax").cloned(),
+            cellphone_no: record.get("owner_cellphone_no").cloned(),
+            email: record.get("owner_email").cloned(),
+            company_name: record.get("owner_company_name").cloned(),
+            company_number: record.get("owner_company_number").cloned(),
+        };
+        if let Err(errors) = fields.validate() {
+            self.validation_errors(row, |field| format!("owner_{}", field), &errors);
+            return None;
+        }
+
+        let has_coordinates = IMPORT_COLUMNS.iter()
+            .any(|c| c.starts_with("owner_") && *c != "owner_name" && *c != "owner_kind" && record.contains_key(c));
+        let coordinate = if has_coordinates { Some(Coordinate::from_new(self.org.id(), fields)) } else { None };
+
+        let owner = BuildingOwner::new(name, kind, Some(self.org.id()), None, coordinate.as_ref().map(|c| c.id()));
+        if let Err(message) = owner.validate(&self.org) {
+            self.error(row, None, message);
+            return None;
+        }
+
+        if let Some(coordinate) = &coordinate {
+            Coordinate::insert(&self.conn, coordinate);
+            self.report.coordinates_created += 1;
+        }
+        BuildingOwner::insert(&self.conn, &owner);
+        self.report.owners_created += 1;
+        self.owners.add(&owner, number.trim());
+
+        Some(owner.id())
+    }
+
+    fn row(&mut self, row: usize, record: &Record) {
+        let fields = NewBuilding {
+            owner_id: uuid::Uuid::nil(),
+            respondant_id: uuid::Uuid::nil(),
+            name: record.get("building_name").cloned().unwrap_or_default(),
+            address: record.get("address").cloned().unwrap_or_default(),
+        };
+        let errors_before = self.report.errors.len();
+
+        if let Err(errors) = fields.validate() {
+            self.validation_errors(row, |field| match field {
+                "name" => String::from("building_name"),
+                _ => String::from(field),
+            }, &errors);
+        }
+        let respondant_id = self.respondant(row, record);
+        let owner_id = self.owner(row, record);
+
+        if let (Some(owner_id), Some(respondant_id), true) = (owner_id, respondant_id, self.report.errors.len() == errors_before) {
+            Building::insert(&self.conn, &Building::new(owner_id, self.org.id(), respondant_id, fields.name, fields.address));
+            self.report.buildings_created += 1;
+        }
+    }
+}
+
+/// Imports the buildings of the records, with their owners, in the user's
+/// organization. Every row is checked, and the rows are saved all together
+/// only when none has an error and it is not a dry run.
+pub fn import_records(conn: &PgConnection, user: &User, records: Vec<Record>, dry_run: bool) -> Result<ImportReport, APIError> {
+    let org = Organization::find_by_id(&conn, user.org_id()).ok_or(APIError::NotFound)?;
+    let mut import = Import {
+        conn,
+        user,
+        owners: OwnerIndex::load(&conn, &org),
+        org,
+        report: ImportReport { dry_run, rows: records.len(), ..ImportReport::default() },
+    };
+
+    let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+        for (i, record) in records.iter().enumerate() {
+            import.row(i + 2, record);
+        }
+
+        if dry_run || !import.report.errors.is_empty() {
+            Err(diesel::result::Error::RollbackTransaction)
+        } else {
+            Ok(())
+        }
+    });
+
+    match result {
+        Ok(()) => import.report.committed = true,
+        Err(diesel::result::Error::RollbackTransaction) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(import.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import_records, name_key, read_records, ImportFormat};
+    use super::super::buildings::Building;
+    use super::super::users::{User, test_functions::*};
+    use crate::db;
+    use diesel::Connection;
+    use diesel::result::Error;
+
+    const CSV: &str = "Building_Name,Address,Owner_Name,Owner_Kind,Owner_Company_Number
+Tour A,1 rue Principale,Immeubles ABC,company,1234
+Tour B,2 rue Principale,IMMEUBLES abc inc,company,1234
+Maison C,3 rue Principale,Jean  Tremblay,,
+Maison D,4 rue Principale,jean tremblay,,
+";
+
+    #[test]
+    fn test_read_csv() {
+        let records = read_records(ImportFormat::Csv, CSV.as_bytes()).unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0]["building_name"], "Tour A");
+        assert_eq!(records[0]["owner_company_number"], "1234");
+        assert!(!records[2].contains_key("owner_kind"));
+
+        let unknown = read_records(ImportFormat::Csv, b"building_name,address,floors\nA,B,3\n");
+        assert_eq!(unknown.unwrap_err(), "Unknown column floors");
+        let missing = read_records(ImportFormat::Csv, b"building_name\nA\n");
+        assert_eq!(missing.unwrap_err(), "Missing column address");
+    }
+
+    #[test]
+    fn test_name_key() {
+        assert_eq!(name_key("  Jean   Tremblay "), "jean tremblay");
+    }
+
+    #[test]
+    fn test_import_deduplicates_owners() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("IMPORT"));
+            User::insert(&conn, &user);
+            let records = read_records(ImportFormat::Csv, CSV.as_bytes()).unwrap();
+
+            let dry_run = import_records(&conn, &user, records.clone(), true).unwrap();
+            assert!(dry_run.errors.is_empty());
+            assert!(!dry_run.committed);
+            assert_eq!((dry_run.buildings_created, dry_run.owners_created, dry_run.owners_reused), (4, 2, 2));
+            assert_eq!(dry_run.coordinates_created, 1);
+            assert!(Building::get_all(&conn).iter().all(|b| b.org_id() != user.org_id()));
+
+            let report = import_records(&conn, &user, records, false).unwrap();
+            assert!(report.committed);
+            assert_eq!(Building::get_all(&conn).iter().filter(|b| b.org_id() == user.org_id()).count(), 4);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_import_is_atomic() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("IMPORT_ERRORS"));
+            User::insert(&conn, &user);
+            let csv = "building_name,address,owner_name,owner_kind,respondant_email
+Tour A,1 rue Principale,Jean Tremblay,,
+,2 rue Principale,Jean Tremblay,landlord,nobody@example.com
+";
+            let records = read_records(ImportFormat::Csv, csv.as_bytes()).unwrap();
+
+            let report = import_records(&conn, &user, records, false).unwrap();
+            assert!(!report.committed);
+            let fields: Vec<(usize, Option<&str>)> = report.errors.iter()
+                .map(|e| (e.row, e.field.as_ref().map(String::as_str)))
+                .collect();
+            assert_eq!(fields, vec![(3, Some("building_name")), (3, Some("respondant_email")), (3, Some("owner_kind"))]);
+            assert!(Building::get_all(&conn).iter().all(|b| b.org_id() != user.org_id()));
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,236 @@
+use serde_derive::{Deserialize, Serialize};
+
+use std::fmt::Write;
+
+/// Page size used when a listing does not ask for one.
+pub const DEFAULT_PAGE_SIZE: i64 = 25;
+
+/// Largest page size accepted by paginated operations.
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Filters a boxed query on the rows following the cursor and orders it by
+/// the sort column, the id breaking ties so every row has a single place.
+macro_rules! keyset_page {
+    ($query:expr, $column:expr, $id:expr, $request:expr) => {{
+        let request = $request;
+        let mut query = $query;
+        let after = request.after.as_ref().map(|c| (c.key.clone(), c.id));
+
+        if request.desc {
+            if let Some((key, id)) = after {
+                query = query.filter($column.lt(key.clone()).or($column.eq(key).and($id.lt(id))));
+            }
+            query.order(($column.desc(), $id.desc())).limit(request.limit + 1)
+        } else {
+            if let Some((key, id)) = after {
+                query = query.filter($column.gt(key.clone()).or($column.eq(key).and($id.gt(id))));
+            }
+            query.order(($column.asc(), $id.asc())).limit(request.limit + 1)
+        }
+    }};
+}
+
+/// Paging parameters of a list operation, as sent in its query string.
+///
+/// `sort` names one of the listing's sort fields, prefixed with `-` for a
+/// descending order. `cursor` is the `next_cursor` of the previous page.
+#[derive(Debug, Default, Deserialize)]
+pub struct ListQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub sort: Option<String>,
+}
+
+/// Position of the last row of a page in its sort order.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct Cursor {
+    pub sort: String,
+    pub key: String,
+    pub id: uuid::Uuid,
+}
+
+impl Cursor {
+    /// Encodes the cursor as an opaque token.
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self)
+            .expect("Error encoding cursor")
+            .bytes()
+            .fold(String::new(), |mut hex, b| {
+                write!(hex, "{:02x}", b).unwrap();
+                hex
+            })
+    }
+
+    pub fn decode(token: &str) -> Option<Cursor> {
+        if token.len() % 2 != 0 || !token.is_ascii() {
+            return None;
+        }
+
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+/// A checked `ListQuery`: the sort field is one the listing allows and the
+/// cursor was made for the same sort.
+#[derive(Debug)]
+pub struct PageRequest {
+    pub field: &'static str,
+    pub desc: bool,
+    pub after: Option<Cursor>,
+    pub limit: i64,
+}
+
+impl ListQuery {
+    /// Checks the query against the sort fields of a listing, the first one
+    /// being its default order.
+    pub fn page_request(&self, fields: &[&'static str]) -> Result<PageRequest, String> {
+        let sort = self.sort.as_ref().map(|s| s.trim()).unwrap_or(fields[0]);
+        let (name, desc) = if sort.starts_with('-') {
+            (&sort[1..], true)
+        } else {
+            (sort, false)
+        };
+        let field = fields.iter()
+            .find(|f| **f == name)
+            .cloned()
+            .ok_or_else(|| format!("Cannot sort by {}", name))?;
+
+        let after = match &self.cursor {
+            Some(token) => Some(Cursor::decode(token)
+                .filter(|c| c.sort == sort)
+                .ok_or_else(|| String::from("Invalid cursor"))?),
+            None => None,
+        };
+
+        Ok(PageRequest {
+            field,
+            desc,
+            after,
+            limit: self.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1).min(MAX_PAGE_SIZE),
+        })
+    }
+}
+
+impl PageRequest {
+    fn sort(&self) -> String {
+        if self.desc {
+            format!("-{}", self.field)
+        } else {
+            self.field.to_owned()
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from rows loaded with `keyset_page!`, which loads one
+    /// row more than the page holds to tell whether another page follows.
+    pub fn new<F>(mut rows: Vec<T>, request: &PageRequest, key: F) -> Page<T>
+        where F: Fn(&T) -> (String, uuid::Uuid)
+    {
+        let next_cursor = if rows.len() as i64 > request.limit {
+            rows.truncate(request.limit as usize);
+            rows.last().map(|last| {
+                let (key, id) = key(last);
+                Cursor { sort: request.sort(), key, id }.encode()
+            })
+        } else {
+            None
+        };
+
+        Page { items: rows, next_cursor }
+    }
+
+    pub fn map<U, F: Fn(&T) -> U>(self, f: F) -> Page<U> {
+        Page {
+            items: self.items.iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// Pattern matching the values containing `substring` with `LIKE`/`ILIKE`.
+pub fn contains_pattern(substring: &str) -> String {
+    let escaped = substring
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contains_pattern, Cursor, ListQuery, Page, MAX_PAGE_SIZE};
+
+    const FIELDS: &[&str] = &["name", "address"];
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor { sort: String::from("-name"), key: String::from("Tour \"A\""), id: uuid::Uuid::new_v4() };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::decode("abc"), None);
+        assert_eq!(Cursor::decode("zz"), None);
+        assert_eq!(Cursor::decode("é1"), None);
+    }
+
+    #[test]
+    fn test_page_request() {
+        let request = ListQuery::default().page_request(FIELDS).unwrap();
+        assert_eq!(request.field, "name");
+        assert!(!request.desc);
+        assert!(request.after.is_none());
+
+        let query = ListQuery { sort: Some(String::from("-address")), limit: Some(1000), cursor: None };
+        let request = query.page_request(FIELDS).unwrap();
+        assert_eq!(request.field, "address");
+        assert!(request.desc);
+        assert_eq!(request.limit, MAX_PAGE_SIZE);
+
+        let query = ListQuery { sort: Some(String::from("password")), ..ListQuery::default() };
+        assert!(query.page_request(FIELDS).is_err());
+    }
+
+    #[test]
+    fn test_cursor_must_match_sort() {
+        let cursor = Cursor { sort: String::from("name"), key: String::from("B"), id: uuid::Uuid::new_v4() };
+        let query = ListQuery { cursor: Some(cursor.encode()), ..ListQuery::default() };
+        assert_eq!(query.page_request(FIELDS).unwrap().after, Some(cursor));
+
+        let query = ListQuery { sort: Some(String::from("-name")), ..query };
+        assert!(query.page_request(FIELDS).is_err());
+    }
+
+    #[test]
+    fn test_page_next_cursor() {
+        let ids: Vec<uuid::Uuid> = (0..3).map(|_| uuid::Uuid::new_v4()).collect();
+        let rows = vec![(String::from("A"), ids[0]), (String::from("B"), ids[1]), (String::from("C"), ids[2])];
+        let query = ListQuery { limit: Some(2), ..ListQuery::default() };
+        let request = query.page_request(FIELDS).unwrap();
+
+        let page = Page::new(rows.clone(), &request, |row| row.clone());
+        assert_eq!(page.items.len(), 2);
+        let next = Cursor::decode(page.next_cursor.as_ref().unwrap()).unwrap();
+        assert_eq!((next.sort.as_str(), next.key.as_str(), next.id), ("name", "B", ids[1]));
+
+        let last_page = Page::new(rows[..2].to_vec(), &request, |row| row.clone());
+        assert_eq!(last_page.items.len(), 2);
+        assert!(last_page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_contains_pattern() {
+        assert_eq!(contains_pattern("rue"), "%rue%");
+        assert_eq!(contains_pattern("50%_off\\"), "%50\\%\\_off\\\\%");
+    }
+}
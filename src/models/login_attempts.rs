@@ -0,0 +1,317 @@
+use crate::schema::{account_lockouts, login_attempts};
+
+use chrono::{Duration, Utc};
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+/// Failed logins in a row after which an account is locked.
+pub const MAX_ACCOUNT_FAILURES: i32 = 5;
+/// Lockout after `MAX_ACCOUNT_FAILURES`, doubled by every further failure.
+pub const BASE_LOCKOUT_SECONDS: i64 = 60;
+pub const MAX_LOCKOUT_SECONDS: i64 = 60 * 60;
+/// Failed logins from an address within `IP_WINDOW_MINUTES` after which
+/// its logins are refused until the window moves on.
+pub const MAX_IP_FAILURES: i64 = 20;
+pub const IP_WINDOW_MINUTES: i64 = 15;
+
+/// Where a login comes from, as recorded in the audit.
+#[derive(Debug, Default)]
+pub struct LoginClient {
+    pub ip_address: String,
+    pub user_agent: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoginOutcome {
+    Success,
+    /// Unknown email or wrong password.
+    BadCredentials,
+    /// Missing or wrong second factor.
+    BadCode,
+    /// Refused without checking the password.
+    Locked,
+    Throttled,
+    /// A sign-in through an identity provider that did not go through.
+    SsoFailed,
+}
+
+impl LoginOutcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            LoginOutcome::Success => "success",
+            LoginOutcome::BadCredentials => "bad_credentials",
+            LoginOutcome::BadCode => "bad_code",
+            LoginOutcome::Locked => "locked",
+            LoginOutcome::Throttled => "throttled",
+            LoginOutcome::SsoFailed => "sso_failed",
+        }
+    }
+}
+
+/// A row of the login audit.
+#[derive(Insertable, Queryable, Debug)]
+pub struct LoginAttempt {
+    id: uuid::Uuid,
+    user_id: Option<uuid::Uuid>,
+    email: String,
+    ip_address: String,
+    user_agent: String,
+    outcome: String,
+    created_at: chrono::NaiveDateTime
+}
+
+impl LoginAttempt {
+    pub fn new(user_id: Option<uuid::Uuid>, email: &str, client: &LoginClient, outcome: LoginOutcome) -> LoginAttempt {
+        LoginAttempt {
+            id: uuid::Uuid::new_v4(),
+            user_id,
+            email: email.to_owned(),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            outcome: outcome.name().to_owned(),
+            created_at: Utc::now().naive_utc()
+        }
+    }
+
+    pub fn outcome(&self) -> &String {
+        &self.outcome
+    }
+
+    pub(super) fn insert(conn: &PgConnection, attempt: &LoginAttempt) -> LoginAttempt {
+        diesel::insert_into(login_attempts::table)
+            .values(attempt)
+            .get_result(conn)
+            .expect("Error saving login attempt")
+    }
+
+    /// Counts the logins refused to an address since the given date. Logins
+    /// refused for throttling count too, so that an address that keeps on
+    /// trying stays throttled.
+    pub(super) fn count_ip_failures(conn: &PgConnection, ip_address: &str, since: NaiveDateTime) -> i64 {
+        login_attempts::table
+            .filter(login_attempts::ip_address.eq(ip_address))
+            .filter(login_attempts::created_at.gt(since))
+            .filter(login_attempts::outcome.ne(LoginOutcome::Success.name()))
+            .count()
+            .get_result(conn)
+            .expect("Error counting login attempts")
+    }
+
+    #[cfg(test)]
+    pub(super) fn get_by_user_id(conn: &PgConnection, user_id: uuid::Uuid) -> Vec<LoginAttempt> {
+        login_attempts::table
+            .filter(login_attempts::user_id.eq(user_id))
+            .order(login_attempts::created_at.asc())
+            .load::<LoginAttempt>(conn)
+            .expect("Error loading login attempts")
+    }
+}
+
+/// How long an account is locked after this many failed logins in a row,
+/// doubling from `BASE_LOCKOUT_SECONDS` up to `MAX_LOCKOUT_SECONDS`.
+pub fn lockout_duration(failures: i32) -> Option<Duration> {
+    if failures < MAX_ACCOUNT_FAILURES {
+        return None;
+    }
+
+    let doublings = (failures - MAX_ACCOUNT_FAILURES).min(16) as u32;
+    Some(Duration::seconds((BASE_LOCKOUT_SECONDS * 2i64.pow(doublings)).min(MAX_LOCKOUT_SECONDS)))
+}
+
+/// The failed logins in a row of an account.
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug)]
+#[primary_key(user_id)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct AccountLockout {
+    user_id: uuid::Uuid,
+    failures: i32,
+    locked_until: Option<chrono::NaiveDateTime>,
+    updated_at: chrono::NaiveDateTime
+}
+
+impl AccountLockout {
+    pub fn failures(&self) -> i32 {
+        self.failures
+    }
+
+    /// The end of the lockout, unless the account is not locked anymore.
+    pub fn locked_until(&self) -> Option<NaiveDateTime> {
+        self.locked_until.filter(|until| *until > Utc::now().naive_utc())
+    }
+
+    pub(super) fn find(conn: &PgConnection, user_id: uuid::Uuid) -> Option<AccountLockout> {
+        account_lockouts::table
+            .find(user_id)
+            .first(conn)
+            .optional()
+            .expect("Error loading account lockout")
+    }
+
+    /// Counts one more failed login, locking the account once there are too
+    /// many.
+    pub(super) fn record_failure(conn: &PgConnection, user_id: uuid::Uuid) -> AccountLockout {
+        let failures = AccountLockout::find(&conn, user_id).map_or(0, |l| l.failures) + 1;
+        let now = Utc::now().naive_utc();
+        let lockout = AccountLockout {
+            user_id,
+            failures,
+            locked_until: lockout_duration(failures).map(|duration| now + duration),
+            updated_at: now
+        };
+
+        diesel::insert_into(account_lockouts::table)
+            .values(&lockout)
+            .on_conflict(account_lockouts::user_id)
+            .do_update()
+            .set(&lockout)
+            .get_result(conn)
+            .expect("Error saving account lockout")
+    }
+
+    /// Forgets the failed logins of the account, after a successful login or
+    /// when an admin unlocks it.
+    pub(super) fn clear(conn: &PgConnection, user_id: uuid::Uuid) -> usize {
+        diesel::delete(account_lockouts::table.find(user_id))
+            .execute(conn)
+            .expect("Error deleting account lockout")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lockout_duration, AccountLockout, LoginAttempt, LoginClient, MAX_ACCOUNT_FAILURES, MAX_IP_FAILURES, MAX_LOCKOUT_SECONDS};
+    use super::super::api::{self, APIError};
+    use super::super::sessions::SESSION_DURATION_HOURS;
+    use super::super::users::{PasswordChange, User, test_functions::*};
+    use crate::db;
+    use chrono::Duration;
+    use diesel::Connection;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_lockout_duration() {
+        assert_eq!(lockout_duration(MAX_ACCOUNT_FAILURES - 1), None);
+        assert_eq!(lockout_duration(MAX_ACCOUNT_FAILURES), Some(Duration::minutes(1)));
+        assert_eq!(lockout_duration(MAX_ACCOUNT_FAILURES + 2), Some(Duration::minutes(4)));
+        assert_eq!(lockout_duration(1000), Some(Duration::seconds(MAX_LOCKOUT_SECONDS)));
+    }
+
+    #[test]
+    fn test_record_failures() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("LOCKOUT"));
+            User::insert(&conn, &user);
+
+            for _ in 1..MAX_ACCOUNT_FAILURES {
+                assert!(AccountLockout::record_failure(&conn, user.id()).locked_until().is_none());
+            }
+            let lockout = AccountLockout::record_failure(&conn, user.id());
+            assert_eq!(lockout.failures(), MAX_ACCOUNT_FAILURES);
+            assert!(lockout.locked_until().is_some());
+
+            AccountLockout::clear(&conn, user.id());
+            assert!(AccountLockout::find(&conn, user.id()).is_none());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_sso_failures() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let client = LoginClient { ip_address: uuid::Uuid::new_v4().to_string(), user_agent: String::from("tests") };
+
+            for _ in 0..MAX_IP_FAILURES {
+                match api::complete_sso(&conn, "code", "unknown state", &client, SESSION_DURATION_HOURS) {
+                    Err(APIError::Unauthorized) => (),
+                    other => panic!("Expected unauthorized, got {:?}", other),
+                }
+            }
+            match api::complete_sso(&conn, "code", "unknown state", &client, SESSION_DURATION_HOURS) {
+                Err(APIError::TooManyAttempts(_)) => (),
+                other => panic!("Expected throttling, got {:?}", other),
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_login_lockout() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("LOGIN_LOCKOUT"));
+            User::insert(&conn, &user);
+            let password = "supersecretpassword LOGIN_LOCKOUT";
+            let client = LoginClient { ip_address: uuid::Uuid::new_v4().to_string(), user_agent: String::from("tests") };
+
+            for _ in 0..MAX_ACCOUNT_FAILURES {
+                match api::login(&conn, user.email(), "wrong password", None, &client, SESSION_DURATION_HOURS) {
+                    Err(APIError::Unauthorized) => (),
+                    other => panic!("Expected unauthorized, got {:?}", other),
+                }
+            }
+            match api::login(&conn, user.email(), password, None, &client, SESSION_DURATION_HOURS) {
+                Err(APIError::TooManyAttempts(seconds)) => assert!(seconds > 0),
+                other => panic!("Expected a lockout, got {:?}", other),
+            }
+
+            AccountLockout::clear(&conn, user.id());
+            assert!(api::login(&conn, user.email(), password, None, &client, SESSION_DURATION_HOURS).is_ok());
+
+            let outcomes: Vec<String> = LoginAttempt::get_by_user_id(&conn, user.id())
+                .iter()
+                .map(|a| a.outcome().clone())
+                .collect();
+            assert_eq!(outcomes.len(), MAX_ACCOUNT_FAILURES as usize + 2);
+            assert_eq!(outcomes[outcomes.len() - 2], "locked");
+            assert_eq!(outcomes[outcomes.len() - 1], "success");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_password_change_lockout() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("CHANGE_LOCKOUT"));
+            User::insert(&conn, &user);
+            let client = LoginClient { ip_address: uuid::Uuid::new_v4().to_string(), user_agent: String::from("tests") };
+            let change = |old_password: &str| PasswordChange {
+                old_password: old_password.to_owned(),
+                new_password: String::from("correct horse battery"),
+            };
+
+            for _ in 0..MAX_ACCOUNT_FAILURES {
+                match api::change_password(&conn, user.id(), change("wrong password"), &client) {
+                    Err(APIError::InvalidInput(_)) => (),
+                    other => panic!("Expected a wrong password, got {:?}", other),
+                }
+            }
+            match api::change_password(&conn, user.id(), change("supersecretpassword CHANGE_LOCKOUT"), &client) {
+                Err(APIError::TooManyAttempts(seconds)) => assert!(seconds > 0),
+                other => panic!("Expected a lockout, got {:?}", other),
+            }
+            assert!(User::find_by_id(&conn, user.id()).unwrap().is_valid_password("supersecretpassword CHANGE_LOCKOUT"));
+
+            let outcomes: Vec<String> = LoginAttempt::get_by_user_id(&conn, user.id())
+                .iter()
+                .map(|a| a.outcome().clone())
+                .collect();
+            assert_eq!(outcomes.len(), MAX_ACCOUNT_FAILURES as usize + 1);
+            assert_eq!(outcomes[0], "bad_credentials");
+            assert_eq!(outcomes[outcomes.len() - 1], "locked");
+
+            Ok(())
+        });
+    }
+}
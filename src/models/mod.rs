@@ -1,11 +1,29 @@
+#[macro_use]
+pub mod list_query;
 
+pub mod admin;
+pub mod api;
+pub mod api_keys;
 pub mod buildings;
 pub mod organizations;
+pub mod password_policy;
 pub mod building_owners;
 pub mod coordinates;
+pub mod dossier;
+pub mod email_verifications;
 pub mod entities_files;
 pub mod entities_history;
 pub mod entities_notes;
+pub mod entities_notes_edits;
+pub mod entities_notes_mentions;
+pub mod export;
 pub mod files;
+pub mod files_notes;
+pub mod import;
+pub mod login_attempts;
+pub mod oidc;
 pub mod registers;
+pub mod sessions;
+pub mod shared_folders;
+pub mod two_factor;
 pub mod users;
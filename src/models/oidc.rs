@@ -0,0 +1,506 @@
+use crate::schema::{oidc_identities, oidc_providers, oidc_states};
+use crate::utils::validators::validate_not_blank;
+use super::users::{PERMISSION_ADMIN, PERMISSION_MEMBER};
+
+use chrono::{Duration, Utc};
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use sha2::{Digest, Sha256};
+
+use validator::Validate;
+
+/// Minutes a user has to sign in at the identity provider.
+pub const SSO_STATE_MINUTES: i64 = 10;
+/// Scopes asked to the identity provider, for the claims of `SsoIdentity`.
+pub const OIDC_SCOPES: &str = "openid email profile";
+
+fn default_groups_claim() -> String {
+    String::from("groups")
+}
+
+/// The identity provider of an organization, as registered by its admin.
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewOidcProvider {
+    #[validate(url)]
+    pub issuer: String,
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub client_id: String,
+    #[validate(length(min = 1, max = 1024))]
+    pub client_secret: String,
+    #[validate(url)]
+    pub authorization_endpoint: String,
+    #[validate(url)]
+    pub token_endpoint: String,
+    /// Where the identity provider sends the user back, with the `code` and
+    /// `state` to pass on to `complete_sso`.
+    #[validate(url)]
+    pub redirect_uri: String,
+    /// The claim of the ID token listing the groups of the user.
+    #[serde(default = "default_groups_claim")]
+    #[validate(length(min = 1, max = 255))]
+    pub groups_claim: String,
+    /// Users in one of these groups are admins.
+    #[serde(default)]
+    pub admin_groups: Vec<String>,
+    /// Users in one of these groups are members, any user when empty.
+    #[serde(default)]
+    pub member_groups: Vec<String>,
+}
+
+/// What the API returns for an identity provider, without the client
+/// secret.
+#[derive(Debug, Serialize)]
+pub struct OidcProviderResponse {
+    pub issuer: String,
+    pub client_id: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub redirect_uri: String,
+    pub groups_claim: String,
+    pub admin_groups: Vec<String>,
+    pub member_groups: Vec<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<&OidcProvider> for OidcProviderResponse {
+    fn from(provider: &OidcProvider) -> Self {
+        OidcProviderResponse {
+            issuer: provider.issuer.clone(),
+            client_id: provider.client_id.clone(),
+            authorization_endpoint: provider.authorization_endpoint.clone(),
+            token_endpoint: provider.token_endpoint.clone(),
+            redirect_uri: provider.redirect_uri.clone(),
+            groups_claim: provider.groups_claim.clone(),
+            admin_groups: provider.admin_groups.clone(),
+            member_groups: provider.member_groups.clone(),
+            updated_at: provider.updated_at,
+        }
+    }
+}
+
+/// Who signed in at the identity provider, from the claims of the ID token.
+#[derive(Debug, PartialEq)]
+pub struct SsoIdentity {
+    pub subject: String,
+    pub email: String,
+    /// Whether the provider verified the email, without which it does not
+    /// prove the account of that email is the user's.
+    pub email_verified: bool,
+    pub full_name: String,
+    pub groups: Vec<String>,
+}
+
+/// The OpenID Connect provider of an organization. The client secret is
+/// kept as is, since it is sent to the provider.
+#[derive(Insertable, Queryable, Identifiable, Debug)]
+pub struct OidcProvider {
+    id: uuid::Uuid,
+    org_id: uuid::Uuid,
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    redirect_uri: String,
+    groups_claim: String,
+    admin_groups: Vec<String>,
+    member_groups: Vec<String>,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime
+}
+
+impl OidcProvider {
+    pub fn new(org_id: uuid::Uuid, fields: NewOidcProvider) -> OidcProvider {
+        OidcProvider {
+            id: uuid::Uuid::new_v4(),
+            org_id,
+            issuer: fields.issuer,
+            client_id: fields.client_id,
+            client_secret: fields.client_secret,
+            authorization_endpoint: fields.authorization_endpoint,
+            token_endpoint: fields.token_endpoint,
+            redirect_uri: fields.redirect_uri,
+            groups_claim: fields.groups_claim,
+            admin_groups: fields.admin_groups,
+            member_groups: fields.member_groups,
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+        }
+    }
+
+    pub fn org_id(&self) -> uuid::Uuid {
+        self.org_id
+    }
+
+    /// The URL of the identity provider where the user signs in, for the
+    /// authorization code flow with PKCE.
+    pub fn authorization_url(&self, state: &SsoState) -> Result<String, String> {
+        let mut url = reqwest::Url::parse(&self.authorization_endpoint).map_err(|e| e.to_string())?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", OIDC_SCOPES)
+            .append_pair("state", &state.state)
+            .append_pair("nonce", &state.nonce)
+            .append_pair("code_challenge", &state.code_challenge())
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.into_string())
+    }
+
+    /// Exchanges the code the user came back with for an ID token.
+    pub fn exchange_code(&self, code: &str, state: &SsoState) -> Result<String, String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            id_token: String,
+        }
+
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("code_verifier", state.code_verifier.as_str()),
+        ];
+        let mut response = reqwest::Client::new()
+            .post(&self.token_endpoint)
+            .form(&params)
+            .send()
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Token endpoint answered {}", response.status()));
+        }
+
+        let token: TokenResponse = response.json().map_err(|e| e.to_string())?;
+        Ok(token.id_token)
+    }
+
+    /// Checks the claims of an ID token meant for this client and returns
+    /// the identity they describe. The token comes straight from the token
+    /// endpoint over TLS, which OpenID Connect accepts in place of checking
+    /// its signature.
+    pub fn identity(&self, id_token: &str, nonce: &str) -> Result<SsoIdentity, String> {
+        let claims = decode_id_token(id_token)?;
+        let claim = |name: &str| claims.get(name).and_then(Value::as_str);
+
+        if claim("iss") != Some(self.issuer.as_str()) {
+            return Err(String::from("Wrong issuer"));
+        }
+        let audience_ok = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == &self.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(self.client_id.as_str())),
+            _ => false,
+        };
+        if !audience_ok {
+            return Err(String::from("Wrong audience"));
+        }
+        if claims.get("exp").and_then(Value::as_i64).map_or(true, |exp| exp <= Utc::now().timestamp()) {
+            return Err(String::from("Expired ID token"));
+        }
+        if claim("nonce") != Some(nonce) {
+            return Err(String::from("Wrong nonce"));
+        }
+        if claims.get("email_verified").and_then(Value::as_bool) == Some(false) {
+            return Err(String::from("Unverified email"));
+        }
+
+        let subject = claim("sub").filter(|sub| !sub.is_empty()).ok_or("Missing subject")?;
+        let email = claim("email").filter(|email| !email.is_empty()).ok_or("Missing email")?;
+        let groups = match claims.get(&self.groups_claim) {
+            Some(Value::Array(groups)) => groups.iter().filter_map(Value::as_str).map(str::to_owned).collect(),
+            Some(Value::String(group)) => vec![group.clone()],
+            _ => Vec::new(),
+        };
+
+        Ok(SsoIdentity {
+            subject: subject.to_owned(),
+            email: email.to_owned(),
+            email_verified: claims.get("email_verified").and_then(Value::as_bool) == Some(true),
+            full_name: claim("name").unwrap_or(email).to_owned(),
+            groups,
+        })
+    }
+
+    /// The permission of a user in these groups, `None` if the user may not
+    /// sign in.
+    pub fn permission_for(&self, groups: &[String]) -> Option<i16> {
+        let in_any = |wanted: &[String]| groups.iter().any(|group| wanted.contains(group));
+
+        if in_any(&self.admin_groups) {
+            Some(PERMISSION_ADMIN)
+        } else if self.member_groups.is_empty() || in_any(&self.member_groups) {
+            Some(PERMISSION_MEMBER)
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn insert(conn: &PgConnection, provider: &OidcProvider) -> OidcProvider {
+        diesel::insert_into(oidc_providers::table)
+            .values(provider)
+            .get_result(conn)
+            .expect("Error saving identity provider")
+    }
+
+    pub(super) fn find_by_org(conn: &PgConnection, org_id: uuid::Uuid) -> Option<OidcProvider> {
+        oidc_providers::table
+            .filter(oidc_providers::org_id.eq(org_id))
+            .filter(oidc_providers::deleted.eq(false))
+            .first(conn)
+            .optional()
+            .expect("Error loading identity provider")
+    }
+
+    pub(super) fn delete_for_org(conn: &PgConnection, org_id: uuid::Uuid) -> usize {
+        diesel::update(oidc_providers::table
+                .filter(oidc_providers::org_id.eq(org_id))
+                .filter(oidc_providers::deleted.eq(false)))
+            .set((oidc_providers::deleted.eq(true), oidc_providers::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error deleting identity provider")
+    }
+}
+
+/// Reads the claims of a JWT without checking its signature.
+fn decode_id_token(id_token: &str) -> Result<Value, String> {
+    let payload = id_token.split('.').nth(1).ok_or("Malformed ID token")?;
+    let json = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).map_err(|e| e.to_string())?;
+
+    serde_json::from_slice(&json).map_err(|e| e.to_string())
+}
+
+/// `BASE64URL(SHA256(verifier))`, the PKCE `S256` challenge of a verifier.
+fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(&Sha256::digest(code_verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+fn random_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4()).replace("-", "")
+}
+
+/// A sign-in started at the identity provider, until the user comes back.
+/// The state is single use, and the verifier never leaves the server until
+/// it is sent to the token endpoint.
+#[derive(Insertable, Queryable, Debug)]
+#[table_name = "oidc_states"]
+pub struct SsoState {
+    state: String,
+    org_id: uuid::Uuid,
+    code_verifier: String,
+    nonce: String,
+    created_at: chrono::NaiveDateTime
+}
+
+impl SsoState {
+    pub fn new(org_id: uuid::Uuid) -> SsoState {
+        SsoState {
+            state: random_token(),
+            org_id,
+            code_verifier: random_token(),
+            nonce: random_token(),
+            created_at: Utc::now().naive_utc()
+        }
+    }
+
+    pub fn org_id(&self) -> uuid::Uuid {
+        self.org_id
+    }
+
+    pub fn nonce(&self) -> &String {
+        &self.nonce
+    }
+
+    pub fn code_challenge(&self) -> String {
+        code_challenge(&self.code_verifier)
+    }
+
+    /// Saves the state, dropping the sign-ins that were never completed.
+    pub(super) fn insert(conn: &PgConnection, state: &SsoState) -> SsoState {
+        let expired = Utc::now().naive_utc() - Duration::minutes(SSO_STATE_MINUTES);
+        diesel::delete(oidc_states::table.filter(oidc_states::created_at.lt(expired)))
+            .execute(conn)
+            .expect("Error deleting sign-in states");
+
+        diesel::insert_into(oidc_states::table)
+            .values(state)
+            .get_result(conn)
+            .expect("Error saving sign-in state")
+    }
+
+    /// Loads and deletes a state that has not expired.
+    pub(super) fn take(conn: &PgConnection, state: &str) -> Option<SsoState> {
+        let expired = Utc::now().naive_utc() - Duration::minutes(SSO_STATE_MINUTES);
+
+        diesel::delete(oidc_states::table
+                .filter(oidc_states::state.eq(state))
+                .filter(oidc_states::created_at.gt(expired)))
+            .get_result(conn)
+            .optional()
+            .expect("Error loading sign-in state")
+    }
+}
+
+/// Links a user to the subject of the identity provider of the user's
+/// organization.
+#[derive(Insertable, Queryable, Debug)]
+#[table_name = "oidc_identities"]
+pub struct OidcIdentity {
+    id: uuid::Uuid,
+    org_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    subject: String,
+    created_at: chrono::NaiveDateTime
+}
+
+impl OidcIdentity {
+    pub fn new(org_id: uuid::Uuid, user_id: uuid::Uuid, subject: &str) -> OidcIdentity {
+        OidcIdentity {
+            id: uuid::Uuid::new_v4(),
+            org_id,
+            user_id,
+            subject: subject.to_owned(),
+            created_at: Utc::now().naive_utc()
+        }
+    }
+
+    pub(super) fn insert(conn: &PgConnection, identity: &OidcIdentity) -> OidcIdentity {
+        diesel::insert_into(oidc_identities::table)
+            .values(identity)
+            .get_result(conn)
+            .expect("Error saving identity")
+    }
+
+    /// The id of the user linked to the subject, deactivated or not.
+    pub(super) fn find_user_id(conn: &PgConnection, org_id: uuid::Uuid, subject: &str) -> Option<uuid::Uuid> {
+        oidc_identities::table
+            .filter(oidc_identities::org_id.eq(org_id))
+            .filter(oidc_identities::subject.eq(subject))
+            .select(oidc_identities::user_id)
+            .first(conn)
+            .optional()
+            .expect("Error loading identity")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{code_challenge, NewOidcProvider, OidcProvider, SsoState, PERMISSION_ADMIN, PERMISSION_MEMBER};
+    use super::super::organizations::{Organization, test_functions::*};
+    use crate::db;
+    use chrono::Utc;
+    use diesel::Connection;
+    use diesel::result::Error;
+    use serde_json::json;
+
+    fn provider(admin_groups: &[&str], member_groups: &[&str]) -> OidcProvider {
+        OidcProvider::new(uuid::Uuid::new_v4(), NewOidcProvider {
+            issuer: String::from("https://idp.example.com"),
+            client_id: String::from("bep"),
+            client_secret: String::from("secret"),
+            authorization_endpoint: String::from("https://idp.example.com/authorize"),
+            token_endpoint: String::from("https://idp.example.com/token"),
+            redirect_uri: String::from("https://bep.example.com/sso"),
+            groups_claim: String::from("groups"),
+            admin_groups: admin_groups.iter().map(|g| g.to_string()).collect(),
+            member_groups: member_groups.iter().map(|g| g.to_string()).collect(),
+        })
+    }
+
+    fn id_token(claims: serde_json::Value) -> String {
+        let encode = |value: serde_json::Value| base64::encode_config(&value.to_string(), base64::URL_SAFE_NO_PAD);
+        format!("{}.{}.", encode(json!({"alg": "none"})), encode(claims))
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // Example of RFC 7636, appendix B
+        assert_eq!(code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn test_authorization_url() {
+        let state = SsoState::new(uuid::Uuid::new_v4());
+        let url = provider(&[], &[]).authorization_url(&state).unwrap();
+
+        assert!(url.starts_with("https://idp.example.com/authorize?response_type=code&client_id=bep"));
+        assert!(url.contains(&format!("code_challenge={}", state.code_challenge())));
+        assert!(url.contains("code_challenge_method=S256"));
+        assert!(url.contains("scope=openid+email+profile"));
+    }
+
+    #[test]
+    fn test_identity() {
+        let provider = provider(&[], &[]);
+        let claims = json!({
+            "iss": "https://idp.example.com",
+            "aud": ["bep", "other"],
+            "exp": Utc::now().timestamp() + 60,
+            "nonce": "n",
+            "sub": "42",
+            "email": "jane@example.com",
+            "groups": ["staff"],
+        });
+
+        let identity = provider.identity(&id_token(claims.clone()), "n").unwrap();
+        assert_eq!(identity.subject, "42");
+        assert_eq!(identity.full_name, "jane@example.com");
+        assert_eq!(identity.groups, vec!["staff"]);
+        assert!(!identity.email_verified);
+
+        let mut verified = claims.clone();
+        verified["email_verified"] = json!(true);
+        assert!(provider.identity(&id_token(verified), "n").unwrap().email_verified);
+        let mut unverified = claims.clone();
+        unverified["email_verified"] = json!(false);
+        assert!(provider.identity(&id_token(unverified), "n").is_err());
+
+        assert!(provider.identity(&id_token(claims.clone()), "other nonce").is_err());
+        let mut expired = claims.clone();
+        expired["exp"] = json!(Utc::now().timestamp() - 1);
+        assert!(provider.identity(&id_token(expired), "n").is_err());
+        let mut other_client = claims;
+        other_client["aud"] = json!("other");
+        assert!(provider.identity(&id_token(other_client), "n").is_err());
+        assert!(provider.identity("not a token", "n").is_err());
+    }
+
+    #[test]
+    fn test_permission_for() {
+        let groups = |names: &[&str]| names.iter().map(|g| g.to_string()).collect::<Vec<String>>();
+
+        let restricted = provider(&["admins"], &["staff"]);
+        assert_eq!(restricted.permission_for(&groups(&["staff", "admins"])), Some(PERMISSION_ADMIN));
+        assert_eq!(restricted.permission_for(&groups(&["staff"])), Some(PERMISSION_MEMBER));
+        assert_eq!(restricted.permission_for(&groups(&["visitors"])), None);
+
+        assert_eq!(provider(&["admins"], &[]).permission_for(&[]), Some(PERMISSION_MEMBER));
+    }
+
+    #[test]
+    fn test_states_are_single_use() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let org = create_test_organization1(&conn);
+            Organization::insert(&conn, &org);
+
+            let state = SsoState::insert(&conn, &SsoState::new(org.id()));
+            assert_eq!(SsoState::take(&conn, &state.state).unwrap().nonce(), state.nonce());
+            assert!(SsoState::take(&conn, &state.state).is_none());
+
+            Ok(())
+        });
+    }
+}
@@ -1,5 +1,9 @@
 use crate::schema::organizations;
 use crate::schema::organizations::dsl;
+use super::password_policy::{PasswordPolicy, MIN_PASSWORD_LENGTH};
+use super::users::NewUser;
+use crate::utils::nullable::deserialize_nullable;
+use crate::utils::validators::validate_not_blank;
 
 use chrono::Utc;
 use chrono::naive::NaiveDateTime;
@@ -11,6 +15,78 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::vec::Vec;
 
+use validator::Validate;
+
+/// A new organization along with its first admin, who invites the other
+/// members once logged in.
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewOrganization {
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub org_name: String,
+    pub admin: NewUser,
+}
+
+/// Organization fields to change, fields left to `None` are not updated.
+#[derive(AsChangeset, Debug, Default, Deserialize, Validate)]
+#[table_name = "organizations"]
+pub struct OrganizationPatch {
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub org_name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub coordinates_id: Option<Option<uuid::Uuid>>,
+    /// Whether every member must log in with a second factor.
+    pub require_two_factor: Option<bool>,
+    #[validate(range(min = 8, max = 1024))]
+    pub password_min_length: Option<i16>,
+    /// Bits of entropy, as `password_policy::estimate_entropy` counts them.
+    #[validate(range(min = 0, max = 256))]
+    pub password_min_entropy: Option<i16>,
+    /// Whether passwords may not contain part of their user's name or email.
+    pub password_reject_personal_info: Option<bool>,
+}
+
+impl OrganizationPatch {
+    pub fn is_empty(&self) -> bool {
+        self.org_name.is_none() &&
+        self.coordinates_id.is_none() &&
+        self.require_two_factor.is_none() &&
+        self.password_min_length.is_none() &&
+        self.password_min_entropy.is_none() &&
+        self.password_reject_personal_info.is_none()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrganizationResponse {
+    pub id: uuid::Uuid,
+    pub org_name: String,
+    pub coordinates_id: Option<uuid::Uuid>,
+    pub require_two_factor: bool,
+    pub password_min_length: i16,
+    pub password_min_entropy: i16,
+    pub password_reject_personal_info: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub version: i32,
+}
+
+impl From<&Organization> for OrganizationResponse {
+    fn from(org: &Organization) -> Self {
+        OrganizationResponse {
+            id: org.id,
+            org_name: org.org_name.clone(),
+            coordinates_id: org.coordinates_id,
+            require_two_factor: org.require_two_factor,
+            password_min_length: org.password_min_length,
+            password_min_entropy: org.password_min_entropy,
+            password_reject_personal_info: org.password_reject_personal_info,
+            created_at: org.created_at,
+            updated_at: org.updated_at,
+            version: org.version,
+        }
+    }
+}
+
 #[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
 #[changeset_options(treat_none_as_null = "true")]
 pub struct Organization {
@@ -21,7 +97,12 @@ pub struct Organization {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    version: i32,
+    require_two_factor: bool,
+    password_min_length: i16,
+    password_min_entropy: i16,
+    password_reject_personal_info: bool
 }
 
 impl PartialEq for Organization {
@@ -33,7 +114,11 @@ impl PartialEq for Organization {
         self.deleted == other.deleted &&
         self.created_at.timestamp() == other.created_at.timestamp() &&
         self.updated_at.timestamp() == other.updated_at.timestamp() &&
-        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.require_two_factor == other.require_two_factor &&
+        self.password_min_length == other.password_min_length &&
+        self.password_min_entropy == other.password_min_entropy &&
+        self.password_reject_personal_info == other.password_reject_personal_info
     }
 }
 
@@ -43,6 +128,8 @@ impl Organization {
         profile_picture: Vec<u8>,
         coordinates_id: Option<uuid::Uuid>,
     ) -> Organization {
+        let policy = PasswordPolicy::default();
+
         Organization {
             id: uuid::Uuid::new_v4(),
             org_name,
@@ -51,7 +138,12 @@ impl Organization {
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            version: 1,
+            require_two_factor: false,
+            password_min_length: policy.min_length as i16,
+            password_min_entropy: policy.min_entropy,
+            password_reject_personal_info: policy.reject_personal_info
         }
     }
 
@@ -59,6 +151,34 @@ impl Organization {
         self.id 
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn org_name(&self) -> &String {
+        &self.org_name
+    }
+
+    pub fn profile_picture(&self) -> &Vec<u8> {
+        &self.profile_picture
+    }
+
+    pub fn coordinates_id(&self) -> Option<uuid::Uuid> {
+        self.coordinates_id
+    }
+
+    pub fn requires_two_factor(&self) -> bool {
+        self.require_two_factor
+    }
+
+    pub fn password_policy(&self) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: (self.password_min_length.max(0) as usize).max(MIN_PASSWORD_LENGTH),
+            min_entropy: self.password_min_entropy,
+            reject_personal_info: self.password_reject_personal_info,
+        }
+    }
+
     pub(super) fn insert(conn: &PgConnection, buildm: &Organization) -> Organization {
         diesel::insert_into(organizations::table)
             .values(buildm)
@@ -66,17 +186,44 @@ impl Organization {
             .expect("Error saving new user")
     }
 
-    fn update(conn: &PgConnection, org: &Organization) -> Organization {
-        diesel::update(organizations::table)
+    fn update(conn: &PgConnection, org: &Organization) -> Option<Organization> {
+        diesel::update(dsl::organizations
+                .filter(dsl::id.eq(org.id))
+                .filter(dsl::version.eq(org.version)))
             .set(org)
             .get_result(conn)
+            .optional()
             .expect("Error saving the building manager")
     }
 
-    fn get_one_by_id(conn: &PgConnection, org_id: uuid::Uuid) -> Organization {
+    pub(super) fn get_one_by_id(conn: &PgConnection, org_id: uuid::Uuid) -> Organization {
         dsl::organizations.filter(dsl::id.eq(org_id)).first(conn)
             .expect("Could not find building manager")
     }
+
+    pub(super) fn patch(conn: &PgConnection, org_id: uuid::Uuid, version: i32, patch: &OrganizationPatch) -> Option<Organization> {
+        diesel::update(dsl::organizations
+                .filter(dsl::id.eq(org_id))
+                .filter(dsl::version.eq(version)))
+            .set(patch)
+            .get_result(conn)
+            .optional()
+            .expect("Error saving the organization")
+    }
+
+    pub(super) fn get_all(conn: &PgConnection) -> Vec<Organization> {
+        dsl::organizations
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::org_name.asc())
+            .load(conn)
+            .expect("Error loading organizations")
+    }
+
+    pub(super) fn find_by_id(conn: &PgConnection, org_id: uuid::Uuid) -> Option<Organization> {
+        dsl::organizations.filter(dsl::id.eq(org_id)).first(conn)
+            .optional()
+            .expect("Error loading organization")
+    }
 }
 
 #[cfg(test)]
@@ -111,7 +258,7 @@ pub mod test_functions {
 
 #[cfg(test)]
 mod tests {
-    use super::{Organization, Connection};
+    use super::{Organization, OrganizationPatch, Connection};
     use super::test_functions::*;
     use crate::db;
     use diesel::result::Error;
@@ -154,4 +301,32 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn test_patch_organization() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let org = create_test_organization1(&conn);
+            Organization::insert(&conn, &org);
+
+            let rename = OrganizationPatch {
+                org_name: Some(String::from("NEW NAME")),
+                ..OrganizationPatch::default()
+            };
+            let patched = Organization::patch(&conn, org.id, org.version, &rename).unwrap();
+            assert_eq!(patched.org_name, "NEW NAME");
+            assert_eq!(patched.coordinates_id, org.coordinates_id);
+
+            let clear_coordinates = OrganizationPatch {
+                coordinates_id: Some(None),
+                ..OrganizationPatch::default()
+            };
+            let patched = Organization::patch(&conn, org.id, patched.version, &clear_coordinates).unwrap();
+            assert_eq!(patched.org_name, "NEW NAME");
+            assert_eq!(patched.coordinates_id, None);
+
+            Ok(())
+        });
+    }
 }
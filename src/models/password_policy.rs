@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::RwLock;
+
+use validator::{ValidationError, ValidationErrors};
+
+/// Shortest password any policy accepts, as `NewUser` validates.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+/// Bits of entropy required unless the organization sets otherwise.
+pub const DEFAULT_MIN_ENTROPY: i16 = 30;
+/// Parts of a name or email shorter than this are not looked for in
+/// passwords.
+const MIN_PERSONAL_PART: usize = 3;
+
+lazy_static! {
+    /// The bundled common passwords, along with those of the file given to
+    /// `load_compromised_list`, lowercased.
+    static ref COMPROMISED_PASSWORDS: RwLock<HashSet<String>> =
+        RwLock::new(parse_compromised_passwords(include_str!("compromised_passwords.txt")));
+}
+
+fn parse_compromised_passwords(list: &str) -> HashSet<String> {
+    list.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_lowercase())
+        .collect()
+}
+
+/// Adds the passwords of a file, one a line, to the bundled ones. Called at
+/// startup with `passwords.compromised_list` of the configuration, so that a
+/// missing file stops the server from starting.
+pub fn load_compromised_list(path: &Path) -> io::Result<()> {
+    let extra = parse_compromised_passwords(&fs::read_to_string(path)?);
+    COMPROMISED_PASSWORDS.write().unwrap().extend(extra);
+
+    Ok(())
+}
+
+pub fn is_compromised(password: &str) -> bool {
+    COMPROMISED_PASSWORDS.read().unwrap().contains(&password.to_lowercase())
+}
+
+/// Estimates the bits of entropy of a password from the kinds of characters
+/// it uses and its length. Characters repeating or continuing a sequence of
+/// the previous one, as in `aaaa`, `1234` or `dcba`, do not count.
+pub fn estimate_entropy(password: &str) -> f64 {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') {
+        pool += 33;
+    }
+    if password.chars().any(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let mut previous: Option<char> = None;
+    let mut length = 0;
+    for c in password.chars() {
+        let continues = previous.map_or(false, |p| (i64::from(u32::from(c)) - i64::from(u32::from(p))).abs() <= 1);
+        if !continues {
+            length += 1;
+        }
+        previous = Some(c);
+    }
+
+    f64::from(length) * f64::from(pool).log2()
+}
+
+/// Tells whether a password contains part of a name or email, such as
+/// `landry` for Bernard Landry. Only the part of emails before the `@` is
+/// looked for.
+pub fn contains_personal_info(password: &str, personal_info: &[&str]) -> bool {
+    let password = password.to_lowercase();
+
+    personal_info.iter()
+        .map(|info| info.split('@').next().unwrap_or_default().to_lowercase())
+        .any(|info| info
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| part.chars().count() >= MIN_PERSONAL_PART)
+            .any(|part| password.contains(part)))
+}
+
+/// The rules the passwords of an organization's users follow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// Bits, as `estimate_entropy` counts them.
+    pub min_entropy: i16,
+    /// Refuses passwords containing part of the user's name or email.
+    pub reject_personal_info: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: MIN_PASSWORD_LENGTH,
+            min_entropy: DEFAULT_MIN_ENTROPY,
+            reject_personal_info: true,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Checks a password of the user with this name and email, reporting
+    /// the rules it breaks as errors of `field`: `length`, `entropy`,
+    /// `personal_info` and `compromised`. Compromised passwords are refused
+    /// whatever the policy.
+    pub fn check(&self, field: &'static str, password: &str, personal_info: &[&str]) -> Result<(), ValidationErrors> {
+        let mut failed = Vec::new();
+        if password.chars().count() < self.min_length.max(MIN_PASSWORD_LENGTH) {
+            failed.push("length");
+        }
+        if estimate_entropy(password) < f64::from(self.min_entropy) {
+            failed.push("entropy");
+        }
+        if self.reject_personal_info && contains_personal_info(password, personal_info) {
+            failed.push("personal_info");
+        }
+        if is_compromised(password) {
+            failed.push("compromised");
+        }
+
+        if failed.is_empty() {
+            return Ok(());
+        }
+
+        let mut errors = ValidationErrors::new();
+        for code in failed {
+            errors.add(field, ValidationError::new(code));
+        }
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contains_personal_info, estimate_entropy, is_compromised, PasswordPolicy};
+
+    #[test]
+    fn test_estimate_entropy() {
+        assert!(estimate_entropy("") < 1.0);
+        assert!(estimate_entropy("aaaaaaaaaaaa") < 5.0);
+        assert!(estimate_entropy("12345678") < 4.0);
+        assert!(estimate_entropy("zyxwvuts") < 5.0);
+        assert!(estimate_entropy("tnkzqgmw") > 30.0);
+        assert!(estimate_entropy("correct horse battery") > estimate_entropy("correcthorsebattery"));
+    }
+
+    #[test]
+    fn test_contains_personal_info() {
+        let info = ["Bernard Landry", "b.landry@gmail.com"];
+        assert!(contains_personal_info("MonsieurLandry2019", &info));
+        assert!(contains_personal_info("bernard!!", &info));
+        assert!(!contains_personal_info("gmail account", &info));
+        assert!(!contains_personal_info("correct horse battery", &info));
+    }
+
+    #[test]
+    fn test_compromised_passwords() {
+        assert!(is_compromised("password123"));
+        assert!(is_compromised("MotDePasse"));
+        assert!(!is_compromised("correct horse battery"));
+    }
+
+    #[test]
+    fn test_check_password() {
+        let policy = PasswordPolicy::default();
+        let info = ["Bernard Landry", "blandry@gmail.com"];
+        assert!(policy.check("password", "correct horse battery", &info).is_ok());
+
+        let errors = policy.check("password", "landry", &info).unwrap_err();
+        let codes: Vec<String> = errors.field_errors()["password"].iter().map(|e| e.code.to_string()).collect();
+        assert_eq!(codes, vec!["length", "entropy", "personal_info"]);
+
+        let errors = policy.check("new_password", "qwertyuiop", &info).unwrap_err();
+        let codes: Vec<String> = errors.field_errors()["new_password"].iter().map(|e| e.code.to_string()).collect();
+        assert_eq!(codes, vec!["compromised"]);
+
+        let lenient = PasswordPolicy { min_entropy: 0, reject_personal_info: false, ..policy };
+        assert!(lenient.check("password", "landry1789", &info).is_ok());
+        let strict = PasswordPolicy { min_length: 24, ..policy };
+        assert!(strict.check("password", "correct horse battery", &info).is_err());
+    }
+}
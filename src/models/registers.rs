@@ -1,5 +1,8 @@
-use crate::schema::registers;
+use crate::schema::{buildings, registers};
 use crate::schema::registers::dsl;
+use crate::utils::validators::validate_not_blank;
+use super::buildings::Building;
+use super::list_query::{contains_pattern, PageRequest};
 
 use chrono::Utc;
 use chrono::naive::NaiveDateTime;
@@ -11,7 +14,63 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::vec::Vec;
 
-#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewRegister {
+    pub building_id: uuid::Uuid,
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub name: String,
+}
+/// Register fields to change, fields left to `None` are not updated.
+#[derive(AsChangeset, Debug, Default, Deserialize, Validate)]
+#[table_name = "registers"]
+pub struct RegisterPatch {
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub name: Option<String>,
+}
+
+impl RegisterPatch {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+    }
+}
+
+/// Fields the registers listing can be sorted by.
+pub const REGISTER_SORT_FIELDS: &[&str] = &["name"];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RegisterFilter {
+    pub building_id: Option<uuid::Uuid>,
+    /// Part of the name, case insensitive.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegisterResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub building_id: uuid::Uuid,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub version: i32,
+}
+
+impl From<&Register> for RegisterResponse {
+    fn from(register: &Register) -> Self {
+        RegisterResponse {
+            id: register.id,
+            name: register.name.clone(),
+            building_id: register.building_id,
+            created_at: register.created_at,
+            updated_at: register.updated_at,
+            version: register.version,
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Identifiable, Associations, AsChangeset, Debug, Serialize, Deserialize)]
+#[belongs_to(Building)]
 #[changeset_options(treat_none_as_null = "true")]
 pub struct Register {
     id: uuid::Uuid,
@@ -20,7 +79,8 @@ pub struct Register {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    version: i32
 }
 
 impl PartialEq for Register {
@@ -47,7 +107,8 @@ impl Register {
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            version: 1
         }
     }
 
@@ -55,6 +116,26 @@ impl Register {
         self.id
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn building_id(&self) -> uuid::Uuid {
+        self.building_id
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    pub fn sort_key(&self, _field: &str) -> (String, uuid::Uuid) {
+        (self.name.clone(), self.id)
+    }
+
     pub(super) fn insert(conn: &PgConnection, register: &Register) -> Register {
         diesel::insert_into(registers::table)
             .values(register)
@@ -62,10 +143,13 @@ impl Register {
             .expect("Error saving new register")
     }
 
-    fn update(conn: &PgConnection, register: &Register) -> Register {
-        diesel::update(registers::table)
+    fn update(conn: &PgConnection, register: &Register) -> Option<Register> {
+        diesel::update(dsl::registers
+                .filter(dsl::id.eq(register.id))
+                .filter(dsl::version.eq(register.version)))
             .set(register)
             .get_result(conn)
+            .optional()
             .expect("Error saving the register")
     }
 
@@ -74,6 +158,60 @@ impl Register {
             .expect("Could not find register")
     }
 
+    pub(super) fn patch(conn: &PgConnection, register_id: uuid::Uuid, version: i32, patch: &RegisterPatch) -> Option<Register> {
+        diesel::update(dsl::registers
+                .filter(dsl::id.eq(register_id))
+                .filter(dsl::version.eq(version)))
+            .set(patch)
+            .get_result(conn)
+            .optional()
+            .expect("Error saving the register")
+    }
+
+    pub(super) fn find_by_id(conn: &PgConnection, register_id: uuid::Uuid) -> Option<Register> {
+        dsl::registers.filter(dsl::id.eq(register_id)).first(conn)
+            .optional()
+            .expect("Error loading register")
+    }
+
+    /// Loads the registers of every given building in a single query,
+    /// grouped in the same order as `buildings`.
+    pub(super) fn get_by_buildings(conn: &PgConnection, buildings: &[Building]) -> Vec<Vec<Register>> {
+        Register::belonging_to(buildings)
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::name.asc())
+            .load::<Register>(conn)
+            .expect("Error loading registers")
+            .grouped_by(buildings)
+    }
+
+    /// Loads a page of the registers of the organization's buildings, along
+    /// with the first register of the next page if there is one.
+    pub(super) fn list(
+        conn: &PgConnection,
+        org_id: uuid::Uuid,
+        filter: &RegisterFilter,
+        request: &PageRequest
+    ) -> Vec<Register> {
+        let mut query = registers::table
+            .inner_join(buildings::table)
+            .filter(buildings::org_id.eq(org_id))
+            .filter(buildings::deleted.eq(false))
+            .filter(dsl::deleted.eq(false))
+            .select(registers::all_columns)
+            .into_boxed();
+
+        if let Some(building_id) = filter.building_id {
+            query = query.filter(dsl::building_id.eq(building_id));
+        }
+        if let Some(name) = &filter.name {
+            query = query.filter(dsl::name.ilike(contains_pattern(name)));
+        }
+
+        keyset_page!(query, dsl::name, dsl::id, request)
+            .load::<Register>(conn)
+            .expect("Error loading registers")
+    }
 }
 
 #[cfg(test)]
@@ -93,7 +231,8 @@ pub mod test_functions {
 
 #[cfg(test)]
 mod tests {
-    use super::{Register, Connection};
+    use super::{Register, NewRegister, RegisterFilter, RegisterPatch, Building, Connection, Validate, REGISTER_SORT_FIELDS};
+    use super::super::list_query::ListQuery;
     use super::test_functions::*;
     use crate::db;
     use diesel::result::Error;
@@ -128,5 +267,79 @@ mod tests {
             Ok(())
         });
     }
-}
 
+    #[test]
+    fn test_get_registers_by_buildings() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let register = create_test_register(&conn);
+            Register::insert(&conn, &register);
+
+            let buildings = vec![Building::get_one_by_id(&conn, register.building_id)];
+            let registers = Register::get_by_buildings(&conn, &buildings);
+
+            assert_eq!(registers, vec![vec![register]]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_validate_new_register() {
+        let register = NewRegister {
+            building_id: uuid::Uuid::new_v4(),
+            name: String::from("Registre des inspections"),
+        };
+        assert!(register.validate().is_ok());
+
+        let long_name = "a".repeat(256);
+        for name in vec!["", "   ", long_name.as_str()] {
+            let invalid = NewRegister { building_id: register.building_id, name: name.to_string() };
+            assert!(invalid.validate().unwrap_err().field_errors().contains_key("name"));
+        }
+    }
+
+    #[test]
+    fn test_patch_register() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let register = create_test_register(&conn);
+            Register::insert(&conn, &register);
+
+            let patch = RegisterPatch { name: Some(String::from("CHANGED NAME")) };
+            let patched = Register::patch(&conn, register.id(), register.version(), &patch).unwrap();
+            assert_eq!(patched.name, "CHANGED NAME");
+            assert_eq!(patched.building_id, register.building_id);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_list_registers() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let register = create_test_register(&conn);
+            Register::insert(&conn, &register);
+            let archives = Register::new(String::from("ARCHIVES"), register.building_id());
+            Register::insert(&conn, &archives);
+            let building = Building::get_one_by_id(&conn, register.building_id());
+
+            let request = ListQuery::default().page_request(REGISTER_SORT_FIELDS).unwrap();
+            let registers = Register::list(&conn, building.org_id(), &RegisterFilter::default(), &request);
+            assert_eq!(registers, vec![archives, register]);
+
+            let filter = RegisterFilter { name: Some(String::from("test")), ..RegisterFilter::default() };
+            let registers = Register::list(&conn, building.org_id(), &filter, &request);
+            assert_eq!(registers.len(), 1);
+            assert_eq!(registers[0].name(), "TEST REGISTER");
+
+            assert!(Register::list(&conn, uuid::Uuid::new_v4(), &RegisterFilter::default(), &request).is_empty());
+
+            Ok(())
+        });
+    }
+}
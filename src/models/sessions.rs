@@ -0,0 +1,328 @@
+use super::login_attempts::LoginClient;
+use crate::schema::sessions;
+use crate::schema::sessions::dsl;
+use crate::utils::{constant_time_eq, token_digest};
+
+use chrono::{Duration, Utc};
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde_derive::Serialize;
+
+/// How long a session stays valid after login, unless `tokens.session_hours`
+/// of the configuration says otherwise.
+pub const SESSION_DURATION_HOURS: i64 = 24 * 14;
+/// How often the last use of a session is saved, sparing a write on every
+/// request.
+pub const LAST_SEEN_INTERVAL_SECONDS: i64 = 60;
+
+/// Names the browser and system of a user agent, such as `Firefox on
+/// Windows`, for users to recognize their sessions.
+pub fn describe_device(user_agent: &str) -> String {
+    // Checked in order, as Edge and Opera also claim to be Chrome, and
+    // Chrome to be Safari
+    const BROWSERS: &[(&str, &str)] = &[
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ];
+    const SYSTEMS: &[(&str, &str)] = &[
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "Chrome OS"),
+        ("Linux", "Linux"),
+    ];
+
+    let find = |names: &[(&str, &'static str)]| -> Option<&'static str> {
+        names.iter()
+            .find(|(marker, _)| user_agent.contains(*marker))
+            .map(|(_, name)| *name)
+    };
+
+    match (find(BROWSERS), find(SYSTEMS)) {
+        (Some(browser), Some(system)) => format!("{} on {}", browser, system),
+        (Some(name), None) | (None, Some(name)) => name.to_owned(),
+        (None, None) => String::from("Unknown device"),
+    }
+}
+
+/// A logged in user. The client holds the session id and a random secret,
+/// only a digest of the secret is stored.
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug)]
+pub struct Session {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    secret: String,
+    expires_at: chrono::NaiveDateTime,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime,
+    ip_address: String,
+    user_agent: String,
+    last_seen_at: chrono::NaiveDateTime
+}
+
+impl PartialEq for Session {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id &&
+        self.user_id == other.user_id &&
+        self.secret == other.secret &&
+        self.expires_at.timestamp() == other.expires_at.timestamp() &&
+        self.deleted == other.deleted &&
+        self.created_at.timestamp() == other.created_at.timestamp() &&
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.ip_address == other.ip_address &&
+        self.user_agent == other.user_agent &&
+        self.last_seen_at.timestamp() == other.last_seen_at.timestamp()
+    }
+}
+
+impl Session {
+    /// Opens a session for the user logging in from this client, returning
+    /// it along with the token to hand to the client, `<session id>.<secret>`.
+    /// The session expires after `hours`.
+    pub fn new(user_id: uuid::Uuid, client: &LoginClient, hours: i64) -> (Session, String) {
+        let id = uuid::Uuid::new_v4();
+        let secret = format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4()).replace("-", "");
+
+        let session = Session {
+            id,
+            user_id,
+            secret: token_digest(&secret),
+            expires_at: Utc::now().naive_utc() + Duration::hours(hours),
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            last_seen_at: Utc::now().naive_utc()
+        };
+
+        (session, format!("{}.{}", id, secret))
+    }
+
+    /// Splits a token into its session id and secret.
+    pub fn parse_token(token: &str) -> Option<(uuid::Uuid, &str)> {
+        let mut parts = token.splitn(2, '.');
+        let id = parts.next().and_then(|id| uuid::Uuid::parse_str(id).ok())?;
+        let secret = parts.next().filter(|secret| !secret.is_empty())?;
+
+        Some((id, secret))
+    }
+
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    pub fn user_id(&self) -> uuid::Uuid {
+        self.user_id
+    }
+
+    pub fn is_valid_secret(&self, secret: &str) -> bool {
+        constant_time_eq(self.secret.as_bytes(), token_digest(secret).as_bytes())
+    }
+
+    pub(super) fn insert(conn: &PgConnection, session: &Session) -> Session {
+        diesel::insert_into(sessions::table)
+            .values(session)
+            .get_result(conn)
+            .expect("Error saving session")
+    }
+
+    /// Loads a session that was neither closed nor expired.
+    pub(super) fn find_active(conn: &PgConnection, session_id: uuid::Uuid) -> Option<Session> {
+        dsl::sessions
+            .filter(dsl::id.eq(session_id))
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::expires_at.gt(Utc::now().naive_utc()))
+            .first(conn)
+            .optional()
+            .expect("Error loading session")
+    }
+
+    /// Lists the sessions of the user that were neither closed nor expired,
+    /// last used first.
+    pub(super) fn get_active_for_user(conn: &PgConnection, user_id: uuid::Uuid) -> Vec<Session> {
+        dsl::sessions
+            .filter(dsl::user_id.eq(user_id))
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::expires_at.gt(Utc::now().naive_utc()))
+            .order(dsl::last_seen_at.desc())
+            .load::<Session>(conn)
+            .expect("Error loading sessions")
+    }
+
+    /// Saves that the session is being used, unless it was saved lately.
+    pub(super) fn touch(conn: &PgConnection, session_id: uuid::Uuid) -> usize {
+        let now = Utc::now().naive_utc();
+
+        diesel::update(dsl::sessions
+                .filter(dsl::id.eq(session_id))
+                .filter(dsl::last_seen_at.lt(now - Duration::seconds(LAST_SEEN_INTERVAL_SECONDS))))
+            .set(dsl::last_seen_at.eq(now))
+            .execute(conn)
+            .expect("Error saving session")
+    }
+
+    /// Closes every open session of the user.
+    pub(super) fn close_all_for_user(conn: &PgConnection, user_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::sessions
+                .filter(dsl::user_id.eq(user_id))
+                .filter(dsl::deleted.eq(false)))
+            .set((dsl::deleted.eq(true), dsl::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error closing sessions")
+    }
+
+    /// Closes the open sessions of the user but one, such as the one asking.
+    pub(super) fn close_others_for_user(conn: &PgConnection, user_id: uuid::Uuid, kept_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::sessions
+                .filter(dsl::user_id.eq(user_id))
+                .filter(dsl::id.ne(kept_id))
+                .filter(dsl::deleted.eq(false)))
+            .set((dsl::deleted.eq(true), dsl::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error closing sessions")
+    }
+
+    /// Closes a session of the user, returning the number of sessions
+    /// closed.
+    pub(super) fn close_for_user(conn: &PgConnection, user_id: uuid::Uuid, session_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::sessions
+                .filter(dsl::id.eq(session_id))
+                .filter(dsl::user_id.eq(user_id))
+                .filter(dsl::deleted.eq(false)))
+            .set((dsl::deleted.eq(true), dsl::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error closing session")
+    }
+
+    pub(super) fn close(conn: &PgConnection, session_id: uuid::Uuid) -> usize {
+        diesel::update(dsl::sessions
+                .filter(dsl::id.eq(session_id))
+                .filter(dsl::deleted.eq(false)))
+            .set((dsl::deleted.eq(true), dsl::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error closing session")
+    }
+}
+
+/// What the API returns for a session, without its secret.
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: uuid::Uuid,
+    /// The browser and system of the user agent, see `describe_device`.
+    pub device: String,
+    pub ip_address: String,
+    pub user_agent: String,
+    /// Whether this is the session making the request.
+    pub current: bool,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+impl SessionResponse {
+    pub fn new(session: &Session, current: bool) -> Self {
+        SessionResponse {
+            id: session.id,
+            device: describe_device(&session.user_agent),
+            ip_address: session.ip_address.clone(),
+            user_agent: session.user_agent.clone(),
+            current,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_device, Session, Connection, SESSION_DURATION_HOURS};
+    use super::super::login_attempts::LoginClient;
+    use super::super::users::{User, test_functions::*};
+    use crate::db;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_parse_token() {
+        let id = uuid::Uuid::new_v4();
+        assert_eq!(Session::parse_token(&format!("{}.abc.def", id)), Some((id, "abc.def")));
+        assert_eq!(Session::parse_token(&format!("{}.", id)), None);
+        assert_eq!(Session::parse_token(&id.to_string()), None);
+        assert_eq!(Session::parse_token("not-a-session.abc"), None);
+    }
+
+    #[test]
+    fn test_open_and_close_session() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("SESSIONS"));
+            User::insert(&conn, &user);
+
+            let (session, token) = Session::new(user.id(), &LoginClient::default(), SESSION_DURATION_HOURS);
+            Session::insert(&conn, &session);
+
+            let (id, secret) = Session::parse_token(&token).unwrap();
+            let stored = Session::find_active(&conn, id).unwrap();
+            assert_eq!(stored, session);
+            assert!(stored.is_valid_secret(secret));
+            assert!(!stored.is_valid_secret("guess"));
+
+            assert_eq!(Session::close(&conn, id), 1);
+            assert!(Session::find_active(&conn, id).is_none());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_describe_device() {
+        let firefox = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:69.0) Gecko/20100101 Firefox/69.0";
+        assert_eq!(describe_device(firefox), "Firefox on Windows");
+        let safari = "Mozilla/5.0 (iPhone; CPU iPhone OS 13_1 like Mac OS X) AppleWebKit/605.1.15 \
+            (KHTML, like Gecko) Version/13.0.1 Mobile/15E148 Safari/604.1";
+        assert_eq!(describe_device(safari), "Safari on iOS");
+        let chrome = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_14_6) AppleWebKit/537.36 \
+            (KHTML, like Gecko) Chrome/77.0.3865.90 Safari/537.36";
+        assert_eq!(describe_device(chrome), "Chrome on macOS");
+        assert_eq!(describe_device("curl/7.65.3"), "Unknown device");
+    }
+
+    #[test]
+    fn test_close_other_sessions() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("OTHER_SESSIONS"));
+            User::insert(&conn, &user);
+
+            let client = LoginClient { ip_address: String::from("192.0.2.10"), user_agent: String::from("Firefox/69.0") };
+            let sessions: Vec<Session> = (0..3)
+                .map(|_| Session::insert(&conn, &Session::new(user.id(), &client, SESSION_DURATION_HOURS).0))
+                .collect();
+            assert_eq!(Session::get_active_for_user(&conn, user.id()).len(), 3);
+
+            assert_eq!(Session::close_for_user(&conn, uuid::Uuid::new_v4(), sessions[0].id()), 0);
+            assert_eq!(Session::close_for_user(&conn, user.id(), sessions[0].id()), 1);
+            assert_eq!(Session::close_others_for_user(&conn, user.id(), sessions[1].id()), 1);
+
+            let active = Session::get_active_for_user(&conn, user.id());
+            assert_eq!(active, vec![sessions.into_iter().nth(1).unwrap()]);
+            assert_eq!(active[0].ip_address, "192.0.2.10");
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,119 @@
+use crate::schema::shared_folders;
+use crate::schema::shared_folders::dsl;
+
+use chrono::Utc;
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::vec::Vec;
+
+/// A folder of a building's files, and of its registers' files, that the
+/// managing organization shares with the building owner.
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+#[primary_key(building_id, folder)]
+pub struct SharedFolder {
+    building_id: uuid::Uuid,
+    folder: String,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime
+}
+
+impl PartialEq for SharedFolder {
+    fn eq(&self, other: &Self) -> bool {
+        self.building_id == other.building_id &&
+        self.folder == other.folder &&
+        self.deleted == other.deleted &&
+        self.created_at.timestamp() == other.created_at.timestamp() &&
+        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+    }
+}
+
+impl SharedFolder {
+    pub fn new(
+        building_id: uuid::Uuid,
+        folder: String
+    ) -> SharedFolder {
+        SharedFolder {
+            building_id,
+            folder,
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+        }
+    }
+
+    pub fn building_id(&self) -> uuid::Uuid {
+        self.building_id
+    }
+
+    pub fn folder(&self) -> &String {
+        &self.folder
+    }
+
+    /// Shares a folder, sharing it again if it was unshared before.
+    pub(super) fn insert(conn: &PgConnection, shared: &SharedFolder) -> SharedFolder {
+        diesel::insert_into(shared_folders::table)
+            .values(shared)
+            .on_conflict((dsl::building_id, dsl::folder))
+            .do_update()
+            .set((dsl::deleted.eq(false), dsl::deleted_at.eq(shared.deleted_at)))
+            .get_result(conn)
+            .expect("Error saving shared folder")
+    }
+
+    pub(super) fn unshare(conn: &PgConnection, building_id: uuid::Uuid, folder: &str) -> usize {
+        diesel::update(dsl::shared_folders
+                .filter(dsl::building_id.eq(building_id))
+                .filter(dsl::folder.eq(folder))
+                .filter(dsl::deleted.eq(false)))
+            .set((dsl::deleted.eq(true), dsl::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error unsharing folder")
+    }
+
+    pub(super) fn get_by_building_ids(conn: &PgConnection, building_ids: &[uuid::Uuid]) -> Vec<SharedFolder> {
+        dsl::shared_folders
+            .filter(dsl::building_id.eq_any(building_ids))
+            .filter(dsl::deleted.eq(false))
+            .order(dsl::folder.asc())
+            .load::<SharedFolder>(conn)
+            .expect("Error loading shared folders")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SharedFolder, Connection};
+    use super::super::buildings::{Building, test_functions::*};
+    use crate::db;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_share_and_unshare_folder() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let building = create_test_building1(&conn);
+            Building::insert(&conn, &building);
+
+            let shared = SharedFolder::new(building.id(), String::from("Inspections"));
+            SharedFolder::insert(&conn, &shared);
+            assert_eq!(SharedFolder::get_by_building_ids(&conn, &[building.id()]), vec![shared]);
+
+            assert_eq!(SharedFolder::unshare(&conn, building.id(), "Inspections"), 1);
+            assert!(SharedFolder::get_by_building_ids(&conn, &[building.id()]).is_empty());
+
+            SharedFolder::insert(&conn, &SharedFolder::new(building.id(), String::from("Inspections")));
+            assert_eq!(SharedFolder::get_by_building_ids(&conn, &[building.id()]).len(), 1);
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,341 @@
+use crate::schema::{recovery_codes, users_two_factor};
+use crate::utils::HASHER;
+
+use chrono::Utc;
+use chrono::naive::NaiveDateTime;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use libreauth::key::KeyBuilder;
+use libreauth::oath::{TOTPBuilder, TOTP};
+use libreauth::pass::HashBuilder;
+
+use qrcode::QrCode;
+use qrcode::render::svg;
+
+use serde_derive::Serialize;
+
+/// Issuer shown by authenticator apps next to the account.
+pub const TOTP_ISSUER: &str = "BEP";
+/// Recovery codes handed to a user when enabling two-factor authentication.
+pub const RECOVERY_CODES: usize = 10;
+/// Bytes of a TOTP secret, the 160 bits recommended by RFC 4226.
+const SECRET_SIZE: usize = 20;
+/// Seconds a TOTP code is valid for.
+const TOTP_PERIOD: i64 = 30;
+/// Periods before and after the current one whose codes are accepted, to
+/// allow for clock drift.
+const TOTP_TOLERANCE: i64 = 1;
+
+/// What a user needs to add the account to an authenticator app: the
+/// secret to type in, or its URI to scan as a QR code.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+    /// The provisioning URI as an SVG QR code.
+    pub qr_code_svg: String,
+}
+
+/// The TOTP secret of a user. It is pending until the user proves the
+/// authenticator app was set up by sending a first code.
+#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug)]
+#[table_name = "users_two_factor"]
+pub struct TwoFactor {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    secret: String,
+    enabled: bool,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime,
+    /// The time step of the last code accepted, codes of this step or of an
+    /// earlier one are refused.
+    last_used_step: i64
+}
+
+impl TwoFactor {
+    /// Draws a new secret for the user, pending confirmation.
+    pub fn new(user_id: uuid::Uuid) -> TwoFactor {
+        TwoFactor {
+            id: uuid::Uuid::new_v4(),
+            user_id,
+            secret: KeyBuilder::new().size(SECRET_SIZE).generate().as_base32(),
+            enabled: false,
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            last_used_step: 0
+        }
+    }
+
+    pub fn secret(&self) -> &String {
+        &self.secret
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    /// The generator of the codes of the current period.
+    fn totp(&self) -> TOTP {
+        self.totp_at(Utc::now().timestamp() / TOTP_PERIOD)
+    }
+
+    /// The generator of the code of a time step only.
+    fn totp_at(&self, step: i64) -> TOTP {
+        TOTPBuilder::new()
+            .base32_key(&self.secret)
+            .period(TOTP_PERIOD as u32)
+            .timestamp(step * TOTP_PERIOD)
+            .tolerance(0)
+            .finalize()
+            .expect("Invalid TOTP secret")
+    }
+
+    /// The time step of a code accepted now and not used yet, if it is one.
+    fn unused_step(&self, code: &str) -> Option<i64> {
+        let current = Utc::now().timestamp() / TOTP_PERIOD;
+
+        (current - TOTP_TOLERANCE..=current + TOTP_TOLERANCE)
+            .filter(|step| *step > self.last_used_step)
+            .find(|step| self.totp_at(*step).is_valid(code.trim()))
+    }
+
+    /// Checks a code of the authenticator app and marks its time step used,
+    /// telling whether it was valid. A code is accepted once, and so are the
+    /// codes older than the last one accepted, which would let a code seen
+    /// over a shoulder be replayed.
+    pub(super) fn redeem_code(&mut self, conn: &PgConnection, code: &str) -> bool {
+        let step = match self.unused_step(code) {
+            Some(step) => step,
+            None => return false,
+        };
+
+        // Guarded by the stored step, a code sent twice at once only passes
+        // once
+        let updated = diesel::update(users_two_factor::table
+                .filter(users_two_factor::id.eq(self.id))
+                .filter(users_two_factor::last_used_step.lt(step)))
+            .set(users_two_factor::last_used_step.eq(step))
+            .execute(conn)
+            .expect("Error saving two-factor secret");
+        self.last_used_step = step;
+
+        updated == 1
+    }
+
+    /// The `otpauth://` URI that authenticator apps scan to add the account.
+    pub fn provisioning_uri(&self, account: &str) -> String {
+        self.totp().key_uri_format(TOTP_ISSUER, account).finalize()
+    }
+
+    pub fn enrollment(&self, account: &str) -> TwoFactorEnrollment {
+        let provisioning_uri = self.provisioning_uri(account);
+        let qr_code_svg = QrCode::new(provisioning_uri.as_bytes())
+            .expect("Provisioning URI too long for a QR code")
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build();
+
+        TwoFactorEnrollment { secret: self.secret.clone(), provisioning_uri, qr_code_svg }
+    }
+
+    pub(super) fn insert(conn: &PgConnection, two_factor: &TwoFactor) -> TwoFactor {
+        diesel::insert_into(users_two_factor::table)
+            .values(two_factor)
+            .get_result(conn)
+            .expect("Error saving two-factor secret")
+    }
+
+    pub(super) fn update(conn: &PgConnection, two_factor: &TwoFactor) -> TwoFactor {
+        diesel::update(two_factor)
+            .set(two_factor)
+            .get_result(conn)
+            .expect("Error saving two-factor secret")
+    }
+
+    /// Loads the secret of the user, pending or enabled.
+    pub(super) fn find_by_user_id(conn: &PgConnection, user_id: uuid::Uuid) -> Option<TwoFactor> {
+        users_two_factor::table
+            .filter(users_two_factor::user_id.eq(user_id))
+            .filter(users_two_factor::deleted.eq(false))
+            .first(conn)
+            .optional()
+            .expect("Error loading two-factor secret")
+    }
+
+    /// Tells whether the user logs in with a second factor.
+    pub(super) fn is_enabled_for_user(conn: &PgConnection, user_id: uuid::Uuid) -> bool {
+        TwoFactor::find_by_user_id(&conn, user_id).map_or(false, |t| t.enabled)
+    }
+
+    /// Drops the secret of the user, pending or enabled.
+    pub(super) fn delete_for_user(conn: &PgConnection, user_id: uuid::Uuid) -> usize {
+        diesel::update(users_two_factor::table
+                .filter(users_two_factor::user_id.eq(user_id))
+                .filter(users_two_factor::deleted.eq(false)))
+            .set((users_two_factor::deleted.eq(true), users_two_factor::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error deleting two-factor secret")
+    }
+}
+
+/// A single-use code replacing the authenticator app, for instance when the
+/// phone is lost. Only a hash of the code is stored.
+#[derive(Insertable, Queryable, Identifiable, Debug)]
+pub struct RecoveryCode {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    code: String,
+    deleted: bool,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    deleted_at: chrono::NaiveDateTime
+}
+
+impl RecoveryCode {
+    /// Draws a new code for the user, returning it along with the code to
+    /// hand to the user.
+    pub fn new(user_id: uuid::Uuid) -> (RecoveryCode, String) {
+        let code = uuid::Uuid::new_v4().to_string().replace("-", "")[..10].to_owned();
+
+        let recovery_code = RecoveryCode {
+            id: uuid::Uuid::new_v4(),
+            user_id,
+            code: HASHER.hash(&code).unwrap(),
+            deleted: false,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+        };
+
+        (recovery_code, code)
+    }
+
+    pub fn is_valid_code(&self, code: &str) -> bool {
+        HashBuilder::from_phc(&self.code)
+            .map(|checker| checker.is_valid(code.trim()))
+            .unwrap_or(false)
+    }
+
+    /// Replaces the codes of the user by new ones, returning the codes to
+    /// hand to the user.
+    pub(super) fn regenerate(conn: &PgConnection, user_id: uuid::Uuid) -> Vec<String> {
+        RecoveryCode::delete_all_for_user(&conn, user_id);
+
+        let (codes, plain): (Vec<RecoveryCode>, Vec<String>) = (0..RECOVERY_CODES)
+            .map(|_| RecoveryCode::new(user_id))
+            .unzip();
+        diesel::insert_into(recovery_codes::table)
+            .values(&codes)
+            .execute(conn)
+            .expect("Error saving recovery codes");
+
+        plain
+    }
+
+    /// Checks a code of the user and marks it used, telling whether it was
+    /// valid.
+    pub(super) fn redeem(conn: &PgConnection, user_id: uuid::Uuid, code: &str) -> bool {
+        let codes = recovery_codes::table
+            .filter(recovery_codes::user_id.eq(user_id))
+            .filter(recovery_codes::deleted.eq(false))
+            .load::<RecoveryCode>(conn)
+            .expect("Error loading recovery codes");
+
+        match codes.iter().find(|c| c.is_valid_code(code)) {
+            Some(used) => {
+                diesel::update(used)
+                    .set((recovery_codes::deleted.eq(true), recovery_codes::deleted_at.eq(Utc::now().naive_utc())))
+                    .execute(conn)
+                    .expect("Error saving recovery code");
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(super) fn delete_all_for_user(conn: &PgConnection, user_id: uuid::Uuid) -> usize {
+        diesel::update(recovery_codes::table
+                .filter(recovery_codes::user_id.eq(user_id))
+                .filter(recovery_codes::deleted.eq(false)))
+            .set((recovery_codes::deleted.eq(true), recovery_codes::deleted_at.eq(Utc::now().naive_utc())))
+            .execute(conn)
+            .expect("Error deleting recovery codes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecoveryCode, TwoFactor, RECOVERY_CODES};
+    use super::super::users::{User, test_functions::*};
+    use crate::db;
+    use diesel::Connection;
+    use diesel::result::Error;
+
+    #[test]
+    fn test_totp_codes() {
+        let two_factor = TwoFactor::new(uuid::Uuid::new_v4());
+        let code = two_factor.totp().generate();
+
+        assert!(two_factor.unused_step(&code).is_some());
+        assert!(two_factor.unused_step("not a code").is_none());
+        assert!(two_factor.provisioning_uri("jane@example.com").starts_with("otpauth://totp/"));
+    }
+
+    #[test]
+    fn test_totp_codes_are_single_use() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("TOTP REPLAY"));
+            User::insert(&conn, &user);
+            let mut two_factor = TwoFactor::new(user.id());
+            TwoFactor::insert(&conn, &two_factor);
+
+            let current = chrono::Utc::now().timestamp() / super::TOTP_PERIOD;
+            let previous = two_factor.totp_at(current - 1).generate();
+            let code = two_factor.totp().generate();
+
+            assert!(two_factor.redeem_code(&conn, &code));
+            assert!(!two_factor.redeem_code(&conn, &code));
+            // Older than the code used, though still within the tolerance
+            assert!(!two_factor.redeem_code(&conn, &previous));
+
+            let mut stored = TwoFactor::find_by_user_id(&conn, user.id()).unwrap();
+            assert!(!stored.redeem_code(&conn, &code));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_recovery_codes_are_single_use() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("RECOVERY"));
+            User::insert(&conn, &user);
+
+            let codes = RecoveryCode::regenerate(&conn, user.id());
+            assert_eq!(codes.len(), RECOVERY_CODES);
+
+            assert!(RecoveryCode::redeem(&conn, user.id(), &codes[0]));
+            assert!(!RecoveryCode::redeem(&conn, user.id(), &codes[0]));
+            assert!(!RecoveryCode::redeem(&conn, user.id(), "0000000000"));
+
+            RecoveryCode::regenerate(&conn, user.id());
+            assert!(!RecoveryCode::redeem(&conn, user.id(), &codes[1]));
+
+            Ok(())
+        });
+    }
+}
@@ -1,6 +1,12 @@
 use crate::schema::users;
 use crate::schema::users::dsl;
 use crate::utils::{HASHER, PWD_SCHEME_VERSION};
+use crate::utils::validators::validate_not_blank;
+use super::buildings::Building;
+use super::list_query::PageRequest;
+use super::organizations::Organization;
+use super::password_policy::PasswordPolicy;
+use super::sessions::Session;
 
 use chrono::Utc;
 use chrono::naive::NaiveDateTime;
@@ -12,9 +18,161 @@ use libreauth::pass::HashBuilder;
 
 use serde_derive::{Deserialize, Serialize};
 
+use std::collections::{HashMap, HashSet};
 use std::vec::Vec;
 
-#[derive(Insertable, Queryable, Identifiable, AsChangeset, Debug, Serialize, Deserialize)]
+use validator::{Validate, ValidationErrors};
+
+pub const PERMISSION_MEMBER: i16 = 1;
+pub const PERMISSION_ADMIN: i16 = 2;
+/// Building owners with a user account, limited to read-only views of the
+/// buildings they own.
+pub const PERMISSION_OWNER: i16 = 3;
+
+sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewUser {
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub full_name: String,
+    #[validate(email, length(max = 254))]
+    pub email: String,
+    #[validate(length(min = 8, max = 1024))]
+    pub password: String,
+    #[validate(length(max = 255))]
+    #[serde(default)]
+    pub job_title: String,
+    #[validate(length(max = 2048))]
+    #[serde(default)]
+    pub profile_picture: String,
+    /// `member`, by default, or `owner` for the account of a building owner.
+    /// Only read by `create_new_user`, the admin of a new organization being
+    /// an admin.
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+impl NewUser {
+    /// The permission of the role an admin gives the new user.
+    pub fn permission(&self) -> Result<i16, String> {
+        match self.role.as_ref().map(|r| r.as_str()) {
+            None | Some("member") => Ok(PERMISSION_MEMBER),
+            Some("owner") => Ok(PERMISSION_OWNER),
+            Some(role) => Err(format!("Unknown role {}", role)),
+        }
+    }
+}
+
+/// A new password for the user, given the current one. The organization's
+/// password policy tells the shortest.
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasswordChange {
+    pub old_password: String,
+    #[validate(length(max = 1024))]
+    pub new_password: String,
+}
+
+/// Profile fields a user may change, fields left to `None` are not updated.
+#[derive(AsChangeset, Debug, Default, Deserialize, Validate)]
+#[table_name = "users"]
+pub struct UserPatch {
+    #[validate(length(min = 1, max = 255), custom = "validate_not_blank")]
+    pub full_name: Option<String>,
+    #[validate(email, length(max = 254))]
+    pub email: Option<String>,
+    #[validate(length(max = 255))]
+    pub job_title: Option<String>,
+    #[validate(length(max = 2048))]
+    pub profile_picture: Option<String>,
+}
+
+impl UserPatch {
+    pub fn is_empty(&self) -> bool {
+        self.full_name.is_none() &&
+        self.email.is_none() &&
+        self.job_title.is_none() &&
+        self.profile_picture.is_none()
+    }
+}
+
+/// The user taking over from a user who leaves.
+#[derive(Debug, Deserialize)]
+pub struct Handover {
+    pub successor_id: uuid::Uuid,
+}
+
+/// What a handover moved to the successor.
+#[derive(Debug, Serialize)]
+pub struct HandoverReport {
+    /// Buildings the successor is now the respondant of.
+    pub buildings: usize,
+    /// Mentions in notes, which call on the user to act.
+    pub mentions: usize,
+}
+
+/// Fields the users listing can be sorted by, the first being the default.
+pub const USER_SORT_FIELDS: &[&str] = &["full_name", "email"];
+
+/// Filters of the users listing, `role` being `member`, `admin` or `owner`.
+#[derive(Debug, Default, Deserialize)]
+pub struct UserFilter {
+    pub role: Option<String>,
+}
+
+impl UserFilter {
+    pub fn permission(&self) -> Result<Option<i16>, String> {
+        match self.role.as_ref().map(|r| r.as_str()) {
+            None => Ok(None),
+            Some("member") => Ok(Some(PERMISSION_MEMBER)),
+            Some("admin") => Ok(Some(PERMISSION_ADMIN)),
+            Some("owner") => Ok(Some(PERMISSION_OWNER)),
+            Some(role) => Err(format!("Unknown role {}", role)),
+        }
+    }
+}
+
+/// What the API returns for a user, without the password hash.
+#[derive(Debug, Serialize)]
+pub struct UserResponse {
+    pub id: uuid::Uuid,
+    pub org_id: uuid::Uuid,
+    pub permission: i16,
+    pub full_name: String,
+    pub email: String,
+    pub job_title: String,
+    pub profile_picture: String,
+    pub email_verified: bool,
+    /// The address the user is changing to, until they confirm it. Only
+    /// given in the user's own profile.
+    pub pending_email: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub version: i32,
+}
+
+impl From<&User> for UserResponse {
+    fn from(user: &User) -> Self {
+        UserResponse {
+            id: user.id,
+            org_id: user.org_id,
+            permission: user.permission,
+            full_name: user.full_name.clone(),
+            email: user.email.clone(),
+            job_title: user.job_title.clone(),
+            profile_picture: user.profile_picture.clone(),
+            email_verified: user.email_verified,
+            pending_email: None,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            version: user.version,
+        }
+    }
+}
+
+// Not serializable on purpose: the API returns `UserResponse` so the
+// password hash never leaves the server.
+#[derive(Insertable, Queryable, Identifiable, Associations, AsChangeset, Debug)]
+#[belongs_to(Organization, foreign_key = "org_id")]
 pub struct User {
     id: uuid::Uuid,
     org_id: uuid::Uuid,
@@ -27,7 +185,12 @@ pub struct User {
     deleted: bool,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    deleted_at: chrono::NaiveDateTime
+    deleted_at: chrono::NaiveDateTime,
+    version: i32,
+    email_verified: bool,
+    successor_id: Option<uuid::Uuid>,
+    deactivated: bool,
+    deactivated_at: Option<chrono::NaiveDateTime>
 }
 
 impl PartialEq for User {
@@ -43,12 +206,42 @@ impl PartialEq for User {
         self.deleted == other.deleted &&
         self.created_at.timestamp() == other.created_at.timestamp() &&
         self.updated_at.timestamp() == other.updated_at.timestamp() &&
-        self.deleted_at.timestamp() == other.deleted_at.timestamp()
+        self.deleted_at.timestamp() == other.deleted_at.timestamp() &&
+        self.email_verified == other.email_verified &&
+        self.successor_id == other.successor_id &&
+        self.deactivated == other.deactivated &&
+        self.deactivated_at.map(|d| d.timestamp()) == other.deactivated_at.map(|d| d.timestamp())
     }
 }
 
+/// Why a user's password could not be changed.
+#[derive(Debug)]
+pub enum PasswordError {
+    /// The current password given is wrong.
+    WrongPassword,
+    /// The new password breaks the organization's password policy.
+    Policy(ValidationErrors),
+}
+
 impl User {
-    pub fn new(
+    /// A user whose password follows the policy of their organization.
+    pub fn new(org_id: uuid::Uuid, permission: i16, fields: NewUser, policy: &PasswordPolicy) -> Result<User, ValidationErrors> {
+        policy.check("password", &fields.password, &[fields.full_name.as_str(), fields.email.as_str()])?;
+        let password = HASHER.hash(&fields.password).unwrap();
+
+        Ok(User::with_password(org_id, permission, fields.full_name, fields.email, password, fields.job_title, fields.profile_picture))
+    }
+
+    /// A user signing in through the identity provider of their
+    /// organization. The random password only matters once an admin resets
+    /// it.
+    pub fn new_federated(org_id: uuid::Uuid, permission: i16, full_name: String, email: String) -> User {
+        let password = HASHER.hash(&uuid::Uuid::new_v4().to_string()).unwrap();
+
+        User::with_password(org_id, permission, full_name, email, password, String::new(), String::new())
+    }
+
+    fn with_password(
         org_id: uuid::Uuid,
         permission: i16,
         full_name: String,
@@ -63,13 +256,18 @@ impl User {
             permission,
             full_name,
             email,
-            password: HASHER.hash(&password).unwrap(),
+            password,
             job_title,
             profile_picture,
             deleted: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
-            deleted_at: NaiveDateTime::from_timestamp(0, 0)
+            deleted_at: NaiveDateTime::from_timestamp(0, 0),
+            version: 1,
+            email_verified: false,
+            successor_id: None,
+            deactivated: false,
+            deactivated_at: None
         }
     }
 
@@ -77,34 +275,157 @@ impl User {
         &self.password
     }
 
-    pub fn change_password(&mut self, old_password_raw: String, new_password_raw: String) -> Result<&User, String> {
+    /// Replaces the password given the current one. The new password
+    /// follows the policy of the user's organization. See `save_password`.
+    pub(super) fn change_password(
+        &mut self,
+        conn: &PgConnection,
+        old_password_raw: &str,
+        new_password_raw: &str,
+        policy: &PasswordPolicy
+    ) -> Result<(), PasswordError> {
+        if !self.is_valid_password(old_password_raw) {
+            return Err(PasswordError::WrongPassword);
+        }
+        policy.check("new_password", new_password_raw, &[self.full_name.as_str(), self.email.as_str()])
+            .map_err(PasswordError::Policy)?;
 
-        fn new_pass(user: &User, old_password_raw: String, new_password_raw: String) -> Result<String, libreauth::pass::ErrorCode> {
-            let checker = HashBuilder::from_phc(&user.password)?;
+        self.save_password(conn, new_password_raw);
+        Ok(())
+    }
 
-            if checker.is_valid(&old_password_raw) {
-                let hashed_password = HASHER.hash(&new_password_raw)?;
-                Ok(hashed_password)
-            } else {
-                Err(libreauth::pass::ErrorCode::InvalidPasswordFormat)
-            }
+    /// Replaces the password without checking the old one, for operators
+    /// resetting a forgotten password. The new password follows the policy
+    /// of the user's organization. See `save_password`.
+    pub(super) fn reset_password(
+        &mut self,
+        conn: &PgConnection,
+        new_password_raw: &str,
+        policy: &PasswordPolicy
+    ) -> Result<(), ValidationErrors> {
+        policy.check("password", new_password_raw, &[self.full_name.as_str(), self.email.as_str()])?;
+
+        self.save_password(conn, new_password_raw);
+        Ok(())
+    }
 
-        };
+    /// Saves a new password and closes every session of the user, so that
+    /// whoever knew the old password is logged out everywhere.
+    fn save_password(&mut self, conn: &PgConnection, new_password_raw: &str) {
+        let password = HASHER.hash(new_password_raw).unwrap();
+        let user_id = self.id;
+
+        *self = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let user = diesel::update(dsl::users.find(user_id))
+                .set(dsl::password.eq(password))
+                .get_result(conn)?;
+            Session::close_all_for_user(conn, user_id);
+            Ok(user)
+        }).expect("Error saving password");
+    }
 
-        match new_pass(&self, old_password_raw, new_password_raw) {
-            Ok(encrypted_pass) => {
-                self.password = encrypted_pass;
-                Ok(self)
-            },
-            Err(_e) => Err(String::from("Wrong password entered"))
+    /// Deactivates the account and closes the user's sessions: the user can
+    /// no longer log in. Refused while the user is the respondant of
+    /// buildings, which must be handed over first. Unlike a deleted user, a
+    /// deactivated one is never purged, as notes, files and handovers still
+    /// refer to the user.
+    pub(super) fn deactivate(&mut self, conn: &PgConnection) -> Result<(), String> {
+        if Building::has_respondant(conn, self.id) {
+            return Err(String::from("User is still the respondant of buildings, hand them over first"));
         }
+        let user_id = self.id;
+
+        *self = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let user = diesel::update(dsl::users.find(user_id))
+                .set((dsl::deactivated.eq(true), dsl::deactivated_at.eq(Utc::now().naive_utc())))
+                .get_result(conn)?;
+            Session::close_all_for_user(conn, user_id);
+            Ok(user)
+        }).expect("Error deactivating user");
+        Ok(())
+    }
+
+    /// Records who took over from the user.
+    pub fn hand_over_to(&mut self, successor_id: uuid::Uuid) {
+        self.successor_id = Some(successor_id);
+    }
+
+    pub fn successor_id(&self) -> Option<uuid::Uuid> {
+        self.successor_id
+    }
+
+    pub fn permission(&self) -> i16 {
+        self.permission
+    }
+
+    pub fn set_permission(&mut self, permission: i16) {
+        self.permission = permission;
+    }
+
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified
+    }
 
+    /// Sets the email the user proved to own.
+    pub fn confirm_email(&mut self, email: String) {
+        self.email = email;
+        self.email_verified = true;
+    }
+
+    /// Tells whether an email is the user's, whatever its case.
+    pub fn has_email(&self, email: &str) -> bool {
+        self.email.to_lowercase() == email.to_lowercase()
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.deleted && !self.deactivated
+    }
+
+    pub fn is_valid_password(&self, password_raw: &str) -> bool {
+        HashBuilder::from_phc(&self.password)
+            .map(|checker| checker.is_valid(password_raw))
+            .unwrap_or(false)
     }
 
     pub fn id(&self) -> uuid::Uuid {
         self.id
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn org_id(&self) -> uuid::Uuid {
+        self.org_id
+    }
+
+    pub fn full_name(&self) -> &String {
+        &self.full_name
+    }
+
+    pub fn email(&self) -> &String {
+        &self.email
+    }
+
+    pub fn job_title(&self) -> &String {
+        &self.job_title
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.permission == PERMISSION_ADMIN
+    }
+
+    pub fn is_owner(&self) -> bool {
+        self.permission == PERMISSION_OWNER
+    }
+
+    pub fn sort_key(&self, field: &str) -> (String, uuid::Uuid) {
+        match field {
+            "email" => (self.email.clone(), self.id),
+            _ => (self.full_name.clone(), self.id),
+        }
+    }
+
     pub(super) fn insert(conn: &PgConnection, user: &User) -> User {
         diesel::insert_into(users::table)
             .values(user)
@@ -112,10 +433,13 @@ impl User {
             .expect("Error saving new user")
     }
 
-    fn update(conn: &PgConnection, user: &User) -> User {
-        diesel::update(users::table)
+    pub(super) fn update(conn: &PgConnection, user: &User) -> Option<User> {
+        diesel::update(dsl::users
+                .filter(dsl::id.eq(user.id))
+                .filter(dsl::version.eq(user.version)))
             .set(user)
             .get_result(conn)
+            .optional()
             .expect("Error saving user")
     }
 
@@ -124,16 +448,154 @@ impl User {
             .expect("Could not find user")
     }
 
-    fn get_all(conn: &PgConnection) -> Vec<User> {
-        users::table.load::<User>(conn)
+    pub(super) fn patch(conn: &PgConnection, user_id: uuid::Uuid, version: i32, patch: &UserPatch) -> Option<User> {
+        diesel::update(dsl::users
+                .filter(dsl::id.eq(user_id))
+                .filter(dsl::version.eq(version)))
+            .set(patch)
+            .get_result(conn)
+            .optional()
+            .expect("Error saving user")
+    }
+
+    pub(super) fn find_by_id(conn: &PgConnection, user_id: uuid::Uuid) -> Option<User> {
+        dsl::users.filter(dsl::id.eq(user_id)).first(conn)
+            .optional()
+            .expect("Error loading user")
+    }
+
+    pub(super) fn find_by_email(conn: &PgConnection, email: &str) -> Option<User> {
+        dsl::users
+            .filter(lower(dsl::email).eq(email.to_lowercase()))
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::deactivated.eq(false))
+            .first(conn)
+            .optional()
+            .expect("Error loading user")
+    }
+
+    /// Tells whether a user, deleted or not, already has this email, whatever
+    /// its case.
+    pub(super) fn email_taken(conn: &PgConnection, email: &str) -> bool {
+        diesel::select(diesel::dsl::exists(dsl::users.filter(lower(dsl::email).eq(email.to_lowercase()))))
+            .get_result(conn)
+            .expect("Error loading user")
+    }
+
+    pub(super) fn get_by_emails_in_org(conn: &PgConnection, org_id: uuid::Uuid, emails: &[String]) -> Vec<User> {
+        let emails: Vec<String> = emails.iter().map(|e| e.to_lowercase()).collect();
+        dsl::users
+            .filter(dsl::org_id.eq(org_id))
+            .filter(lower(dsl::email).eq_any(emails))
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::deactivated.eq(false))
+            .load::<User>(conn)
+            .expect("Error loading users")
+    }
+
+    pub(super) fn get_by_ids(conn: &PgConnection, user_ids: &[uuid::Uuid]) -> Vec<User> {
+        dsl::users
+            .filter(dsl::id.eq_any(user_ids))
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::deactivated.eq(false))
+            .order(dsl::full_name.asc())
+            .load::<User>(conn)
+            .expect("Error loading users")
+    }
+
+    /// Maps the deactivated users among the given ones to the users who took
+    /// over from them. A successor deactivated in turn is replaced by the
+    /// user who took over from them, down the chain of handovers.
+    pub(super) fn get_successors(conn: &PgConnection, user_ids: &[uuid::Uuid]) -> Vec<(uuid::Uuid, uuid::Uuid)> {
+        let mut handovers: HashMap<uuid::Uuid, uuid::Uuid> = HashMap::new();
+        let mut loaded: HashSet<uuid::Uuid> = HashSet::new();
+        let mut pending: Vec<uuid::Uuid> = user_ids.to_vec();
+        while !pending.is_empty() {
+            loaded.extend(pending.iter().cloned());
+            handovers.extend(User::get_handovers(conn, &pending));
+            pending = handovers.values()
+                .filter(|id| !loaded.contains(id))
+                .cloned()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+        }
+
+        user_ids.iter()
+            .filter_map(|user_id| {
+                let mut successor = *handovers.get(user_id)?;
+                let mut visited: HashSet<uuid::Uuid> = [*user_id].iter().cloned().collect();
+                while let Some(next) = handovers.get(&successor) {
+                    if !visited.insert(successor) {
+                        break;
+                    }
+                    successor = *next;
+                }
+                Some((*user_id, successor))
+            })
+            .collect()
+    }
+
+    /// Maps the deactivated users among the given ones to the users they
+    /// handed over to.
+    fn get_handovers(conn: &PgConnection, user_ids: &[uuid::Uuid]) -> Vec<(uuid::Uuid, uuid::Uuid)> {
+        dsl::users
+            .filter(dsl::id.eq_any(user_ids))
+            .filter(dsl::deactivated.eq(true))
+            .filter(dsl::successor_id.is_not_null())
+            .select((dsl::id, dsl::successor_id))
+            .load::<(uuid::Uuid, Option<uuid::Uuid>)>(conn)
+            .expect("Error loading users")
+            .into_iter()
+            .filter_map(|(id, successor_id)| successor_id.map(|successor_id| (id, successor_id)))
+            .collect()
+    }
+
+    /// Loads the users of every given organization in a single query,
+    /// grouped in the same order as `orgs`.
+    pub(super) fn get_by_organizations(conn: &PgConnection, orgs: &[Organization]) -> Vec<Vec<User>> {
+        User::belonging_to(orgs)
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::deactivated.eq(false))
+            .order(dsl::full_name.asc())
+            .load::<User>(conn)
+            .expect("Error loading users")
+            .grouped_by(orgs)
+    }
+
+    /// Loads a page of the organization's users, along with the first user
+    /// of the next page if there is one.
+    pub(super) fn list(
+        conn: &PgConnection,
+        org_id: uuid::Uuid,
+        permission: Option<i16>,
+        request: &PageRequest
+    ) -> Vec<User> {
+        let mut query = dsl::users
+            .filter(dsl::org_id.eq(org_id))
+            .filter(dsl::deleted.eq(false))
+            .filter(dsl::deactivated.eq(false))
+            .into_boxed();
+
+        if let Some(permission) = permission {
+            query = query.filter(dsl::permission.eq(permission));
+        }
+
+        let query = match request.field {
+            "email" => keyset_page!(query, dsl::email, dsl::id, request),
+            _ => keyset_page!(query, dsl::full_name, dsl::id, request),
+        };
+
+        query.load::<User>(conn)
             .expect("Error loading users")
     }
 }
 
 #[cfg(test)]
 pub mod test_functions {
-    use super::User;
+    use super::{NewUser, User};
     use super::super::organizations::{Organization, test_functions::*};
+    use super::super::password_policy::PasswordPolicy;
 
     use diesel::PgConnection;
 
@@ -141,22 +603,28 @@ pub mod test_functions {
         let test_org = create_test_organization1(&conn);
         Organization::insert(&conn, &test_org);
 
-        User::new(
-            test_org.id(),
-            1, 
-            format!("USER NAME {}", unique), 
-            format!("email@gmail.com {}", unique), 
-            format!("supersecretpassword {}", unique), 
-            format!("Gestionnaire {}", unique), 
-            format!("test.png {}", unique)
-        )
+        // The password contains the unique part of the name
+        let policy = PasswordPolicy { reject_personal_info: false, ..PasswordPolicy::default() };
+        User::new(test_org.id(), 1, NewUser {
+            full_name: format!("USER NAME {}", unique),
+            email: format!("email@gmail.com {}", unique),
+            password: format!("supersecretpassword {}", unique),
+            job_title: format!("Gestionnaire {}", unique),
+            profile_picture: format!("test.png {}", unique),
+            role: None
+        }, &policy).unwrap()
     }
 
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{User, Connection};
+    use super::{User, NewUser, PasswordError, UserFilter, UserPatch, UserResponse, Organization, Connection, Validate};
+    use super::{PERMISSION_ADMIN, PERMISSION_MEMBER, PERMISSION_OWNER, USER_SORT_FIELDS};
+    use super::super::list_query::ListQuery;
+    use super::super::login_attempts::LoginClient;
+    use super::super::password_policy::PasswordPolicy;
+    use super::super::sessions::{Session, SESSION_DURATION_HOURS};
     use super::test_functions::*;
     use crate::db;
     use diesel::result::Error;
@@ -184,11 +652,12 @@ mod tests {
         conn.test_transaction::<_, Error, _>(|| {
             let mut user = create_test_user(&conn, String::from("TEST"));
             User::insert(&conn, &user);
+            user.change_password(&conn, "supersecretpassword TEST", "changedpassword", &PasswordPolicy::default())
+                .expect("Should not happen.");
 
             user.full_name = String::from("Bernard Landry");
             user.permission = 1;
             user.email = String::from("blandry@gmail.com");
-            user.change_password("supersecretpassword TEST".to_owned(), "changedpassword".to_owned()).expect("Should not happen.");
             user.job_title = String::from("Coordinateur");
             user.profile_picture = String::from("new_picture.png");
             user.deleted = true;
@@ -214,4 +683,266 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn test_get_users_by_organizations() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user1 = create_test_user(&conn, String::from("ORG1"));
+            let user2 = create_test_user(&conn, String::from("ORG2"));
+            User::insert(&conn, &user1);
+            User::insert(&conn, &user2);
+
+            let orgs = vec![
+                Organization::get_one_by_id(&conn, user2.org_id),
+                Organization::get_one_by_id(&conn, user1.org_id),
+            ];
+            let users = User::get_by_organizations(&conn, &orgs);
+
+            assert_eq!(users, vec![vec![user2], vec![user1]]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_get_users_by_emails_in_org() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("MENTION"));
+            User::insert(&conn, &user);
+
+            let emails = vec![user.email.clone(), String::from("unknown@gmail.com")];
+            assert_eq!(User::get_by_emails_in_org(&conn, user.org_id, &emails), vec![user]);
+            assert!(User::get_by_emails_in_org(&conn, uuid::Uuid::new_v4(), &emails).is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_emails_ignore_case() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let mut user = create_test_user(&conn, String::from("CASE"));
+            user.email = String::from("Bernard.Landry@Gmail.com");
+            User::insert(&conn, &user);
+
+            assert_eq!(User::find_by_email(&conn, "bernard.landry@gmail.com"), Some(user));
+            assert!(User::email_taken(&conn, "BERNARD.LANDRY@GMAIL.COM"));
+            assert!(!User::email_taken(&conn, "jacques.parizeau@gmail.com"));
+
+            Ok(())
+        });
+    }
+
+    fn new_user() -> NewUser {
+        NewUser {
+            full_name: String::from("Bernard Landry"),
+            email: String::from("blandry@gmail.com"),
+            password: String::from("supersecretpassword"),
+            job_title: String::from("Coordinateur"),
+            profile_picture: String::new(),
+            role: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_new_user() {
+        assert!(new_user().validate().is_ok());
+
+        let invalid = NewUser {
+            full_name: String::from("   "),
+            email: String::from("blandry.gmail.com"),
+            password: String::from("short"),
+            ..new_user()
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields = errors.field_errors();
+
+        assert_eq!(fields.len(), 3);
+        assert!(fields.contains_key("full_name"));
+        assert!(fields.contains_key("email"));
+        assert!(fields.contains_key("password"));
+    }
+
+    #[test]
+    fn test_new_user_role() {
+        assert_eq!(new_user().permission(), Ok(PERMISSION_MEMBER));
+        assert_eq!(NewUser { role: Some(String::from("owner")), ..new_user() }.permission(), Ok(PERMISSION_OWNER));
+        assert!(NewUser { role: Some(String::from("admin")), ..new_user() }.permission().is_err());
+    }
+
+    #[test]
+    fn test_validate_new_user_lengths() {
+        let too_long = NewUser {
+            full_name: "a".repeat(256),
+            email: format!("{}@gmail.com", "a".repeat(250)),
+            job_title: "a".repeat(256),
+            ..new_user()
+        };
+        let errors = too_long.validate().unwrap_err();
+        let fields = errors.field_errors();
+
+        assert!(fields.contains_key("full_name"));
+        assert!(fields.contains_key("email"));
+        assert!(fields.contains_key("job_title"));
+        assert!(!fields.contains_key("password"));
+
+        let empty_name = NewUser { full_name: String::new(), ..new_user() };
+        assert!(empty_name.validate().unwrap_err().field_errors().contains_key("full_name"));
+    }
+
+    #[test]
+    fn test_patch_user() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let user = create_test_user(&conn, String::from("PATCH"));
+            User::insert(&conn, &user);
+
+            let patch = UserPatch {
+                job_title: Some(String::from("Coordinateur")),
+                ..UserPatch::default()
+            };
+            assert!(!patch.is_empty());
+            assert!(UserPatch::default().is_empty());
+
+            let patched = User::patch(&conn, user.id, user.version, &patch).unwrap();
+            assert_eq!(patched.job_title, "Coordinateur");
+            assert_eq!(patched.full_name, user.full_name);
+            assert_eq!(patched.email, user.email);
+            assert_eq!(patched.password, user.password);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_validate_user_patch() {
+        assert!(UserPatch::default().validate().is_ok());
+
+        let patch = UserPatch {
+            full_name: Some(String::from(" ")),
+            email: Some(String::from("not an email")),
+            ..UserPatch::default()
+        };
+        let errors = patch.validate().unwrap_err();
+        let fields = errors.field_errors();
+        assert_eq!(fields.len(), 2);
+        assert!(fields.contains_key("full_name"));
+        assert!(fields.contains_key("email"));
+    }
+
+    #[test]
+    fn test_password_policy() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let policy = PasswordPolicy::default();
+            let weak = NewUser { password: String::from("landry1789"), ..new_user() };
+            let errors = User::new(uuid::Uuid::new_v4(), 1, weak, &policy).unwrap_err();
+            assert!(errors.field_errors().contains_key("password"));
+
+            let mut user = create_test_user(&conn, String::from("POLICY"));
+            User::insert(&conn, &user);
+            let (session, _) = Session::new(user.id, &LoginClient::default(), SESSION_DURATION_HOURS);
+            Session::insert(&conn, &session);
+
+            match user.change_password(&conn, "wrong password", "correct horse battery", &policy) {
+                Err(PasswordError::WrongPassword) => (),
+                other => panic!("Expected a wrong password, got {:?}", other),
+            }
+            match user.change_password(&conn, "supersecretpassword POLICY", "qwertyuiop", &policy) {
+                Err(PasswordError::Policy(errors)) => assert!(errors.field_errors().contains_key("new_password")),
+                other => panic!("Expected a policy error, got {:?}", other),
+            }
+            assert!(Session::find_active(&conn, session.id()).is_some());
+
+            user.change_password(&conn, "supersecretpassword POLICY", "correct horse battery", &policy).unwrap();
+            assert!(User::get_one_by_id(&conn, user.id).is_valid_password("correct horse battery"));
+            assert!(Session::find_active(&conn, session.id()).is_none());
+
+            assert!(user.reset_password(&conn, "blandry", &policy).is_err());
+            assert!(User::get_one_by_id(&conn, user.id).is_valid_password("correct horse battery"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_user_response_has_no_password() {
+        let user = User::new(uuid::Uuid::new_v4(), 1, new_user(), &PasswordPolicy::default()).unwrap();
+        let json = serde_json::to_value(UserResponse::from(&user)).unwrap();
+
+        assert_eq!(json["email"], "blandry@gmail.com");
+        assert!(json.get("password").is_none());
+    }
+
+    #[test]
+    fn test_successors_chain() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let mut first = create_test_user(&conn, String::from("FIRST"));
+            let mut second = create_test_user(&conn, String::from("SECOND"));
+            let third = create_test_user(&conn, String::from("THIRD"));
+            for user in &[&first, &second, &third] {
+                User::insert(&conn, user);
+            }
+
+            first.hand_over_to(second.id);
+            User::update(&conn, &first).unwrap();
+            first.deactivate(&conn).unwrap();
+            assert_eq!(User::get_successors(&conn, &[first.id, second.id]), vec![(first.id, second.id)]);
+
+            // The successor left in turn
+            second.hand_over_to(third.id);
+            User::update(&conn, &second).unwrap();
+            second.deactivate(&conn).unwrap();
+            let mut successors = User::get_successors(&conn, &[first.id, second.id, third.id]);
+            successors.sort();
+            let mut expected = vec![(first.id, third.id), (second.id, third.id)];
+            expected.sort();
+            assert_eq!(successors, expected);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_list_users() {
+        let conn = db::connection::establish_connection();
+
+        conn.test_transaction::<_, Error, _>(|| {
+            let member = create_test_user(&conn, String::from("LIST"));
+            User::insert(&conn, &member);
+            let admin = User::new(member.org_id, PERMISSION_ADMIN, NewUser {
+                full_name: String::from("ADMIN LIST"),
+                email: String::from("admin.list@gmail.com"),
+                password: String::from("supersecretpassword"),
+                job_title: String::new(),
+                profile_picture: String::new(),
+                role: None,
+            }, &PasswordPolicy::default()).unwrap();
+            User::insert(&conn, &admin);
+
+            let request = ListQuery::default().page_request(USER_SORT_FIELDS).unwrap();
+            let users = User::list(&conn, member.org_id, None, &request);
+            let ids: Vec<uuid::Uuid> = users.iter().map(|u| u.id).collect();
+            assert_eq!(ids, vec![admin.id, member.id]);
+
+            let filter = UserFilter { role: Some(String::from("admin")) };
+            let users = User::list(&conn, admin.org_id, filter.permission().unwrap(), &request);
+            assert_eq!(users.len(), 1);
+            assert_eq!(users[0].id, admin.id);
+
+            assert!(UserFilter { role: Some(String::from("root")) }.permission().is_err());
+
+            Ok(())
+        });
+    }
 }
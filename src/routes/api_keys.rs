@@ -0,0 +1,25 @@
+use super::auth::AuthUser;
+use crate::models::api::{self, APIError};
+use crate::models::api_keys::NewApiKey;
+
+use actix_web::{web, HttpResponse};
+
+use serde_json::json;
+
+pub fn get_api_keys(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    let keys = api::get_api_keys(&auth.conn, auth.user_id)?;
+
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+pub fn create_api_key(auth: AuthUser, fields: web::Json<NewApiKey>) -> Result<HttpResponse, APIError> {
+    let (id, key) = api::create_api_key(&auth.conn, auth.user_id, fields.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id, "key": key})))
+}
+
+pub fn revoke_api_key(auth: AuthUser, key_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    api::revoke_api_key(&auth.conn, auth.user_id, key_id.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,256 @@
+use crate::config::Config;
+use crate::db::connection::{self, Pool, PooledConnection};
+use crate::logging;
+use crate::models::api::{self, APIError};
+use crate::models::api_keys::{ApiKey, ApiScope};
+use crate::models::login_attempts::LoginClient;
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest, HttpResponse};
+
+use diesel::pg::PgConnection;
+
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use std::net::IpAddr;
+use std::ops::Deref;
+
+/// A database connection taken from the pool for the time of a request.
+pub struct DbConn(PooledConnection);
+
+impl Deref for DbConn {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        &self.0
+    }
+}
+
+impl FromRequest for DbConn {
+    type Config = ();
+    type Error = APIError;
+    type Future = Result<Self, Self::Error>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let pool = web::Data::<Pool>::extract(req).map_err(|_| APIError::Unavailable)?;
+        let conn = pool.get().map_err(|e| {
+            warn!("No database connection: {}", e);
+            APIError::Unavailable
+        })?;
+        if let Some(request_id) = logging::current_request_id() {
+            connection::tag_request(&conn, &request_id).map_err(|_| APIError::Unavailable)?;
+        }
+
+        Ok(DbConn(conn))
+    }
+}
+
+/// The user of the session token sent as `Authorization: Bearer <token>`,
+/// along with the connection used to check it. The token may also be an API
+/// key on the routes given the `ApiScope` it needs, the user then being the
+/// admin who created the key.
+pub struct AuthUser {
+    pub conn: DbConn,
+    pub user_id: uuid::Uuid,
+    pub token: String,
+}
+
+/// Like `AuthUser`, but also accepting users who have yet to enable the
+/// two-factor authentication their organization requires, so that they
+/// can enroll. API keys are refused.
+pub struct SessionUser {
+    pub conn: DbConn,
+    pub user_id: uuid::Uuid,
+    pub token: String,
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| value.starts_with("Bearer "))
+        .map(|value| value["Bearer ".len()..].trim().to_owned())
+}
+
+impl FromRequest for SessionUser {
+    type Config = ();
+    type Error = APIError;
+    type Future = Result<Self, Self::Error>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let token = bearer_token(req).ok_or(APIError::Unauthorized)?;
+        let conn = DbConn::from_request(req, payload)?;
+        let user_id = api::authenticate(&conn, &token)?;
+        logging::record_user(user_id, api::get_user_org_id(&conn, user_id));
+
+        Ok(SessionUser { conn, user_id, token })
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Config = ();
+    type Error = APIError;
+    type Future = Result<Self, Self::Error>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let token = bearer_token(req).ok_or(APIError::Unauthorized)?;
+        if ApiKey::is_key_token(&token) {
+            let conn = DbConn::from_request(req, payload)?;
+            let scope = req.route_data::<ApiScope>().map(|scope| **scope);
+            let user_id = api::authenticate_api_key(&conn, &token, scope)?;
+            logging::record_user(user_id, api::get_user_org_id(&conn, user_id));
+
+            return Ok(AuthUser { conn, user_id, token });
+        }
+
+        let SessionUser { conn, user_id, token } = SessionUser::from_request(req, payload)?;
+        api::ensure_two_factor_enrolled(&conn, user_id)?;
+
+        Ok(AuthUser { conn, user_id, token })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+    /// A code of the authenticator app or a recovery code, for users with
+    /// two-factor authentication.
+    pub code: Option<String>,
+}
+
+/// A code of the authenticator app, or a recovery code where accepted.
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+/// The address of the client: the peer, unless it is a trusted proxy. The
+/// addresses a trusted proxy forwards are then read from the right, each
+/// one added by the proxy before, up to the first that is not a trusted
+/// proxy. Anything to its left could have been sent by the client.
+fn client_ip(peer: Option<IpAddr>, forwarded_for: Option<&str>, trusted_proxies: &[IpAddr]) -> String {
+    let peer = match peer {
+        Some(peer) => peer,
+        None => return String::new(),
+    };
+    if !trusted_proxies.contains(&peer) {
+        return peer.to_string();
+    }
+
+    let mut client = peer;
+    for forwarded in forwarded_for.unwrap_or_default().rsplit(',') {
+        match forwarded.trim().parse::<IpAddr>() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+    }
+
+    client.to_string()
+}
+
+/// Where the request comes from, see `client_ip`, and the user agent.
+pub(super) fn login_client(req: &HttpRequest, config: &Config) -> LoginClient {
+    let forwarded_for = req.headers()
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok());
+    let ip_address = client_ip(req.peer_addr().map(|addr| addr.ip()), forwarded_for, &config.server.trusted_proxies);
+    let user_agent = req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+
+    LoginClient { ip_address, user_agent }
+}
+
+pub fn login(
+    req: HttpRequest,
+    conn: DbConn,
+    config: web::Data<Config>,
+    credentials: web::Json<Credentials>
+) -> Result<HttpResponse, APIError> {
+    let code = credentials.code.as_ref().map(String::as_str);
+    let client = login_client(&req, &config);
+    let token = api::login(&conn, &credentials.email, &credentials.password, code, &client, config.tokens.session_hours)?;
+
+    Ok(HttpResponse::Ok().json(json!({"token": token})))
+}
+
+/// The code and state an identity provider sent the user back with.
+#[derive(Debug, Deserialize)]
+pub struct SsoCallback {
+    pub code: String,
+    pub state: String,
+}
+
+pub fn begin_sso(conn: DbConn, org_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let url = api::begin_sso(&conn, org_id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(json!({"authorization_url": url})))
+}
+
+pub fn complete_sso(
+    req: HttpRequest,
+    conn: DbConn,
+    config: web::Data<Config>,
+    callback: web::Json<SsoCallback>
+) -> Result<HttpResponse, APIError> {
+    let token = api::complete_sso(&conn, &callback.code, &callback.state, &login_client(&req, &config), config.tokens.session_hours)?;
+
+    Ok(HttpResponse::Ok().json(json!({"token": token})))
+}
+
+pub fn logout(session: SessionUser) -> Result<HttpResponse, APIError> {
+    api::logout(&session.conn, &session.token)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn begin_two_factor(session: SessionUser) -> Result<HttpResponse, APIError> {
+    let enrollment = api::begin_two_factor(&session.conn, session.user_id)?;
+
+    Ok(HttpResponse::Ok().json(enrollment))
+}
+
+pub fn confirm_two_factor(session: SessionUser, body: web::Json<TwoFactorCode>) -> Result<HttpResponse, APIError> {
+    let codes = api::confirm_two_factor(&session.conn, session.user_id, &body.code)?;
+
+    Ok(HttpResponse::Ok().json(json!({"recovery_codes": codes})))
+}
+
+pub fn regenerate_recovery_codes(auth: AuthUser, body: web::Json<TwoFactorCode>) -> Result<HttpResponse, APIError> {
+    let codes = api::regenerate_recovery_codes(&auth.conn, auth.user_id, &body.code)?;
+
+    Ok(HttpResponse::Ok().json(json!({"recovery_codes": codes})))
+}
+
+pub fn disable_two_factor(auth: AuthUser, body: web::Json<TwoFactorCode>) -> Result<HttpResponse, APIError> {
+    api::disable_two_factor(&auth.conn, auth.user_id, &body.code)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::client_ip;
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_client_ip() {
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+        let proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        // A client cannot claim another address without a trusted proxy
+        assert_eq!(client_ip(Some(ip("203.0.113.7")), Some("198.51.100.1"), &proxies), "203.0.113.7");
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), None, &proxies), "10.0.0.1");
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), Some("203.0.113.7"), &proxies), "203.0.113.7");
+        // Only the addresses added by trusted proxies are believed
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), Some("198.51.100.1, 203.0.113.7, 10.0.0.2"), &proxies), "203.0.113.7");
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), Some("forged, 10.0.0.2"), &proxies), "10.0.0.2");
+        assert_eq!(client_ip(None, Some("203.0.113.7"), &proxies), "");
+    }
+}
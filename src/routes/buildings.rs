@@ -0,0 +1,80 @@
+use super::auth::AuthUser;
+use super::versions::{versioned, IfMatch};
+use crate::models::api::{self, APIError};
+use crate::models::buildings::{BuildingFilter, BuildingPatch, NewBuilding};
+use crate::models::list_query::ListQuery;
+use crate::models::registers::{NewRegister, RegisterFilter, RegisterPatch};
+
+use actix_web::{http::header, web, HttpResponse};
+
+use serde_json::json;
+
+pub fn get_buildings(
+    auth: AuthUser,
+    filter: web::Query<BuildingFilter>,
+    query: web::Query<ListQuery>
+) -> Result<HttpResponse, APIError> {
+    let page = api::get_buildings(&auth.conn, auth.user_id, filter.into_inner(), query.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub fn create_new_building(auth: AuthUser, fields: web::Json<NewBuilding>) -> Result<HttpResponse, APIError> {
+    let id = api::create_new_building(&auth.conn, auth.user_id, fields.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id})))
+}
+
+pub fn get_building_information(auth: AuthUser, building_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let info = api::get_building_information(&auth.conn, auth.user_id, building_id.into_inner())?;
+
+    Ok(versioned(info.building.version, &info))
+}
+
+pub fn get_building_dossier(auth: AuthUser, building_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let building_id = building_id.into_inner();
+    let pdf = api::get_building_dossier(&auth.conn, auth.user_id, building_id)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"dossier-{}.pdf\"", building_id))
+        .body(pdf))
+}
+
+pub fn modify_building(
+    auth: AuthUser,
+    building_id: web::Path<uuid::Uuid>,
+    if_match: IfMatch,
+    patch: web::Json<BuildingPatch>
+) -> Result<HttpResponse, APIError> {
+    let building = api::modify_building(&auth.conn, auth.user_id, building_id.into_inner(), if_match.0, patch.into_inner())?;
+
+    Ok(versioned(building.version, &building))
+}
+
+pub fn get_registers(
+    auth: AuthUser,
+    filter: web::Query<RegisterFilter>,
+    query: web::Query<ListQuery>
+) -> Result<HttpResponse, APIError> {
+    let page = api::get_registers(&auth.conn, auth.user_id, filter.into_inner(), query.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub fn create_new_register(auth: AuthUser, fields: web::Json<NewRegister>) -> Result<HttpResponse, APIError> {
+    let id = api::create_new_register(&auth.conn, auth.user_id, fields.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id})))
+}
+
+pub fn modify_register(
+    auth: AuthUser,
+    register_id: web::Path<uuid::Uuid>,
+    if_match: IfMatch,
+    patch: web::Json<RegisterPatch>
+) -> Result<HttpResponse, APIError> {
+    let register = api::modify_register(&auth.conn, auth.user_id, register_id.into_inner(), if_match.0, patch.into_inner())?;
+
+    Ok(versioned(register.version, &register))
+}
@@ -0,0 +1,90 @@
+use super::auth::AuthUser;
+use crate::models::api::{self, APIError};
+use crate::models::entities_notes::NoteText;
+use crate::models::list_query::DEFAULT_PAGE_SIZE;
+
+use actix_web::{web, HttpResponse};
+
+use serde_derive::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotesQuery {
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+pub fn get_entity_files(auth: AuthUser, entity_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let files = api::get_entity_files(&auth.conn, auth.user_id, entity_id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(files))
+}
+
+pub fn create_note_on_file(
+    auth: AuthUser,
+    path: web::Path<(uuid::Uuid, uuid::Uuid)>,
+    body: web::Json<NoteText>
+) -> Result<HttpResponse, APIError> {
+    let (entity_id, file_id) = path.into_inner();
+    api::create_note_on_file(&auth.conn, auth.user_id, entity_id, file_id, body.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn get_entity_notes(
+    auth: AuthUser,
+    entity_id: web::Path<uuid::Uuid>,
+    query: web::Query<NotesQuery>
+) -> Result<HttpResponse, APIError> {
+    let notes = api::get_entity_notes(
+        &auth.conn,
+        auth.user_id,
+        entity_id.into_inner(),
+        query.page.unwrap_or(0),
+        query.page_size.unwrap_or(DEFAULT_PAGE_SIZE)
+    )?;
+
+    Ok(HttpResponse::Ok().json(notes))
+}
+
+pub fn create_entity_note(
+    auth: AuthUser,
+    entity_id: web::Path<uuid::Uuid>,
+    body: web::Json<NoteText>
+) -> Result<HttpResponse, APIError> {
+    let id = api::create_entity_note(&auth.conn, auth.user_id, entity_id.into_inner(), body.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id})))
+}
+
+pub fn reply_to_entity_note(
+    auth: AuthUser,
+    note_id: web::Path<uuid::Uuid>,
+    body: web::Json<NoteText>
+) -> Result<HttpResponse, APIError> {
+    let id = api::reply_to_entity_note(&auth.conn, auth.user_id, note_id.into_inner(), body.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id})))
+}
+
+pub fn edit_entity_note(
+    auth: AuthUser,
+    note_id: web::Path<uuid::Uuid>,
+    body: web::Json<NoteText>
+) -> Result<HttpResponse, APIError> {
+    api::edit_entity_note(&auth.conn, auth.user_id, note_id.into_inner(), body.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn delete_entity_note(auth: AuthUser, note_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    api::delete_entity_note(&auth.conn, auth.user_id, note_id.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn get_entity_note_edits(auth: AuthUser, note_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let edits = api::get_entity_note_edits(&auth.conn, auth.user_id, note_id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(edits))
+}
@@ -0,0 +1,22 @@
+use super::auth::AuthUser;
+use crate::models::api::{self, APIError};
+
+use actix_web::{http::header, web, HttpResponse};
+
+use serde_derive::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: String,
+    pub owner_id: Option<uuid::Uuid>,
+}
+
+/// Sends the portfolio as a file to download.
+pub fn export_buildings(auth: AuthUser, query: web::Query<ExportQuery>) -> Result<HttpResponse, APIError> {
+    let (format, data) = api::export_buildings(&auth.conn, auth.user_id, &query.format, query.owner_id)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.media_type())
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"buildings.{}\"", format.extension()))
+        .body(data))
+}
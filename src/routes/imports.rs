@@ -0,0 +1,25 @@
+use super::auth::AuthUser;
+use crate::models::api::{self, APIError};
+
+use actix_web::{web, HttpResponse};
+
+use serde_derive::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub format: String,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Imports the file sent as the body, answering with the report of every
+/// row whether or not it was saved.
+pub fn import_buildings(
+    auth: AuthUser,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes
+) -> Result<HttpResponse, APIError> {
+    let report = api::import_buildings(&auth.conn, auth.user_id, &query.format, &body, query.dry_run)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
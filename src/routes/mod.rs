@@ -0,0 +1,105 @@
+pub mod api_keys;
+pub mod auth;
+pub mod buildings;
+pub mod entities;
+pub mod exports;
+pub mod imports;
+pub mod openapi;
+pub mod organisations;
+pub mod owners;
+pub mod portal;
+pub mod tracing;
+pub mod users;
+pub mod versions;
+
+use crate::config::Config;
+use crate::models::api_keys::ApiScope;
+
+use actix_web::web;
+
+/// Declares every route once, for the router and for `ROUTES`, which the
+/// OpenAPI document is checked against. A route may be given the config of
+/// its extractors after its handler, such as the `ApiScope` an API key needs
+/// to call it. The config may depend on the settings of the application,
+/// named by the ident given first.
+macro_rules! api_routes {
+    ($settings:ident; $($method:ident $path:expr => $handler:expr $(, $config:expr)*;)*) => {
+        /// Method and path of every route, relative to `/api/v1`.
+        pub const ROUTES: &[(&str, &str)] = &[$((stringify!($method), $path)),*];
+
+        /// Registers the API routes, mounted under `/api/v1`.
+        pub fn config(cfg: &mut web::ServiceConfig, $settings: &Config) {
+            cfg.service(web::scope("/api/v1")
+                .wrap(tracing::RequestTracing)
+                $(.route($path, web::$method().to($handler)$(.data($config))*))*
+            );
+        }
+    };
+}
+
+api_routes! {
+    settings;
+    get "/openapi.json" => openapi::get_openapi;
+    post "/organisations" => organisations::register_new_organisation;
+    post "/login" => auth::login;
+    post "/logout" => auth::logout;
+    get "/sso/{org_id}" => auth::begin_sso;
+    post "/sso/login" => auth::complete_sso;
+    post "/two_factor" => auth::begin_two_factor;
+    delete "/two_factor" => auth::disable_two_factor;
+    post "/two_factor/confirm" => auth::confirm_two_factor;
+    post "/two_factor/recovery_codes" => auth::regenerate_recovery_codes;
+    get "/users" => users::get_users;
+    post "/users" => users::create_new_user;
+    post "/users/{user_id}/unlock" => users::unlock_user;
+    post "/users/{user_id}/deactivate" => users::deactivate_user;
+    post "/users/{user_id}/handover" => users::hand_over_user;
+    get "/users/{user_id}/sessions" => users::get_user_sessions;
+    delete "/users/{user_id}/sessions" => users::revoke_user_sessions;
+    delete "/users/{user_id}/sessions/{session_id}" => users::revoke_user_session;
+    get "/profile" => users::get_user_profile;
+    patch "/profile" => users::modify_user_profile;
+    post "/profile/password" => users::change_password;
+    get "/profile/sessions" => users::get_sessions;
+    delete "/profile/sessions" => users::revoke_other_sessions;
+    delete "/profile/sessions/{session_id}" => users::revoke_session;
+    post "/profile/email_verification" => users::resend_email_verification;
+    post "/verify_email" => users::verify_email;
+    get "/api_keys" => api_keys::get_api_keys;
+    post "/api_keys" => api_keys::create_api_key;
+    delete "/api_keys/{key_id}" => api_keys::revoke_api_key;
+    get "/organisation" => organisations::get_organisation_info;
+    patch "/organisation" => organisations::modify_organisation_info;
+    get "/organisation/sso" => organisations::get_sso_config;
+    put "/organisation/sso" => organisations::set_sso_config;
+    delete "/organisation/sso" => organisations::delete_sso_config;
+    post "/coordinates" => organisations::create_coordinate;
+    patch "/coordinates/{coordinates_id}" => organisations::modify_coordinate;
+    get "/owners" => owners::get_owners, ApiScope::ReadBuildings;
+    post "/owners" => owners::create_new_owner;
+    get "/owners/{owner_id}" => owners::get_owner_info, ApiScope::ReadBuildings;
+    patch "/owners/{owner_id}" => owners::modify_owner_info;
+    get "/buildings" => buildings::get_buildings, ApiScope::ReadBuildings;
+    post "/buildings" => buildings::create_new_building;
+    get "/buildings/{building_id}" => buildings::get_building_information, ApiScope::ReadBuildings;
+    patch "/buildings/{building_id}" => buildings::modify_building;
+    get "/buildings/{building_id}/dossier" => buildings::get_building_dossier, ApiScope::ReadBuildings;
+    get "/buildings/{building_id}/shared_folders" => portal::get_shared_folders;
+    post "/buildings/{building_id}/shared_folders" => portal::share_folder_with_owner;
+    delete "/buildings/{building_id}/shared_folders" => portal::unshare_folder_with_owner;
+    get "/registers" => buildings::get_registers, ApiScope::ReadBuildings;
+    post "/registers" => buildings::create_new_register, ApiScope::WriteRegisters;
+    post "/imports" => imports::import_buildings, web::PayloadConfig::new(settings.uploads.max_import_size), ApiScope::UploadFiles;
+    get "/exports/buildings" => exports::export_buildings, ApiScope::ReadBuildings;
+    patch "/registers/{register_id}" => buildings::modify_register, ApiScope::WriteRegisters;
+    get "/entities/{entity_id}/files" => entities::get_entity_files, ApiScope::ReadBuildings;
+    post "/entities/{entity_id}/files/{file_id}/notes" => entities::create_note_on_file;
+    get "/entities/{entity_id}/notes" => entities::get_entity_notes, ApiScope::ReadBuildings;
+    post "/entities/{entity_id}/notes" => entities::create_entity_note;
+    patch "/notes/{note_id}" => entities::edit_entity_note;
+    delete "/notes/{note_id}" => entities::delete_entity_note;
+    post "/notes/{note_id}/replies" => entities::reply_to_entity_note;
+    get "/notes/{note_id}/edits" => entities::get_entity_note_edits;
+    get "/portal/buildings" => portal::get_owner_buildings;
+    get "/portal/buildings/{building_id}" => portal::get_owner_building;
+}
@@ -0,0 +1,1197 @@
+use crate::models::api_keys::ApiScope;
+use crate::models::list_query::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+use actix_web::HttpResponse;
+
+use serde_json::{json, Map, Value};
+
+lazy_static! {
+    static ref SPEC: Value = spec();
+}
+
+/// Serves the OpenAPI 3 document of the API.
+pub fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(&*SPEC)
+}
+
+/// What a successful call answers.
+enum Reply {
+    /// 200 with a body of this schema.
+    Json(Value),
+    /// 200 with an entity of this schema and its version as `ETag`.
+    Versioned(Value),
+    /// 201 with the id of the created entity.
+    Created,
+    /// 201 with a body of this schema.
+    CreatedWith(Value),
+    /// 204 without a body.
+    Empty,
+    /// 200 with a file to download, the schema of each media type.
+    File(Vec<(&'static str, Value)>),
+}
+
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    id: &'static str,
+    summary: &'static str,
+    auth: bool,
+    query: Vec<Value>,
+    body: Option<Value>,
+    /// Media types of the body, JSON unless it is an uploaded file.
+    media_types: &'static [&'static str],
+    /// Error statuses beyond those the other fields imply.
+    errors: &'static [&'static str],
+    /// The scope of the API keys accepted, which are refused otherwise.
+    scope: Option<ApiScope>,
+    reply: Reply,
+}
+
+impl Operation {
+    fn new(method: &'static str, path: &'static str, id: &'static str, summary: &'static str) -> Operation {
+        Operation { method, path, id, summary, auth: true, query: Vec::new(), body: None, media_types: &["application/json"], errors: &[], scope: None, reply: Reply::Empty }
+    }
+
+    fn public(self) -> Operation {
+        Operation { auth: false, ..self }
+    }
+
+    fn query(mut self, params: Vec<Value>) -> Operation {
+        self.query.extend(params);
+        self
+    }
+
+    fn body(self, schema: Value) -> Operation {
+        Operation { body: Some(schema), ..self }
+    }
+
+    /// A file sent as the raw body, of one of the media types.
+    fn upload(self, media_types: &'static [&'static str]) -> Operation {
+        Operation { body: Some(json!({"type": "string", "format": "binary"})), media_types, ..self }
+    }
+
+    fn errors(self, errors: &'static [&'static str]) -> Operation {
+        Operation { errors, ..self }
+    }
+
+    fn scope(self, scope: ApiScope) -> Operation {
+        Operation { scope: Some(scope), ..self }
+    }
+
+    fn reply(self, reply: Reply) -> Operation {
+        Operation { reply, ..self }
+    }
+
+    /// Names of the `{param}` segments of the path.
+    fn path_params(&self) -> Vec<&'static str> {
+        self.path.split('/')
+            .filter(|s| s.starts_with('{') && s.ends_with('}'))
+            .map(|s| &s[1..s.len() - 1])
+            .collect()
+    }
+
+    fn to_json(&self) -> Value {
+        let mut params: Vec<Value> = self.path_params().into_iter()
+            .map(|name| json!({"name": name, "in": "path", "required": true, "schema": uuid()}))
+            .collect();
+        params.extend(self.query.iter().cloned());
+        if self.method == "patch" {
+            params.push(json!({"$ref": "#/components/parameters/IfMatch"}));
+        }
+
+        let mut responses = Map::new();
+        match &self.reply {
+            Reply::Json(schema) => {
+                responses.insert("200".into(), json!({
+                    "description": "OK",
+                    "content": {"application/json": {"schema": schema}},
+                }));
+            }
+            Reply::Versioned(schema) => {
+                responses.insert("200".into(), json!({
+                    "description": "OK",
+                    "headers": {"ETag": {"$ref": "#/components/headers/ETag"}},
+                    "content": {"application/json": {"schema": schema}},
+                }));
+            }
+            Reply::Created => {
+                responses.insert("201".into(), json!({
+                    "description": "Created",
+                    "content": {"application/json": {"schema": reference("Created")}},
+                }));
+            }
+            Reply::CreatedWith(schema) => {
+                responses.insert("201".into(), json!({
+                    "description": "Created",
+                    "content": {"application/json": {"schema": schema}},
+                }));
+            }
+            Reply::Empty => {
+                responses.insert("204".into(), json!({"description": "No content"}));
+            }
+            Reply::File(contents) => {
+                let content: Map<String, Value> = contents.iter()
+                    .map(|(media_type, schema)| (media_type.to_string(), json!({"schema": schema})))
+                    .collect();
+                responses.insert("200".into(), json!({
+                    "description": "OK",
+                    "headers": {"Content-Disposition": {"schema": string(), "description": "attachment; filename=..."}},
+                    "content": content,
+                }));
+            }
+        }
+
+        let mut errors = vec!["400"];
+        if self.auth {
+            errors.extend(&["401", "403"]);
+        }
+        if !self.path_params().is_empty() {
+            errors.push("404");
+        }
+        if self.method == "patch" {
+            errors.push("409");
+        }
+        if self.body.is_some() && self.media_types == ["application/json"] {
+            errors.push("422");
+        }
+        errors.extend(self.errors);
+        errors.extend(&["500", "503"]);
+        for status in errors {
+            responses.insert(status.into(), json!({"$ref": format!("#/components/responses/{}", status)}));
+        }
+
+        let mut operation = json!({
+            "operationId": self.id,
+            "summary": self.summary,
+            "parameters": params,
+            "responses": responses,
+        });
+        if let Some(schema) = &self.body {
+            let content: Map<String, Value> = self.media_types.iter()
+                .map(|media_type| (media_type.to_string(), json!({"schema": schema})))
+                .collect();
+            operation["requestBody"] = json!({"required": true, "content": content});
+        }
+        if !self.auth {
+            operation["security"] = json!([]);
+        }
+        if let Some(scope) = self.scope {
+            operation["x-api-key-scope"] = json!(scope.name());
+        }
+
+        operation
+    }
+}
+
+fn reference(name: &str) -> Value {
+    json!({"$ref": format!("#/components/schemas/{}", name)})
+}
+
+fn uuid() -> Value {
+    json!({"type": "string", "format": "uuid"})
+}
+
+fn string() -> Value {
+    json!({"type": "string"})
+}
+
+/// `NaiveDateTime` values, in UTC without an offset.
+fn timestamp() -> Value {
+    json!({"type": "string", "example": "2019-09-17T14:12:05.123456"})
+}
+
+fn integer() -> Value {
+    json!({"type": "integer"})
+}
+
+fn boolean() -> Value {
+    json!({"type": "boolean"})
+}
+
+fn nullable(mut schema: Value) -> Value {
+    schema["nullable"] = json!(true);
+    schema
+}
+
+fn array(items: Value) -> Value {
+    json!({"type": "array", "items": items})
+}
+
+fn object(properties: Vec<(&str, Value)>, required: &[&str]) -> Value {
+    let properties: Map<String, Value> = properties.into_iter()
+        .map(|(name, schema)| (name.to_owned(), schema))
+        .collect();
+    let mut schema = json!({"type": "object", "properties": properties});
+    if !required.is_empty() {
+        schema["required"] = json!(required);
+    }
+
+    schema
+}
+
+/// A response object, every field being present.
+fn response(properties: Vec<(&str, Value)>) -> Value {
+    let required: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+    object(properties, &required)
+}
+
+fn page_of(item: &str) -> Value {
+    response(vec![
+        ("items", array(reference(item))),
+        ("next_cursor", nullable(string())),
+    ])
+}
+
+fn query_param(name: &str, schema: Value, description: &str) -> Value {
+    json!({"name": name, "in": "query", "required": false, "schema": schema, "description": description})
+}
+
+fn list_params(sort_fields: &[&str]) -> Vec<Value> {
+    let sorts: Vec<String> = sort_fields.iter()
+        .flat_map(|f| vec![f.to_string(), format!("-{}", f)])
+        .collect();
+
+    vec![
+        query_param("sort", json!({"type": "string", "enum": sorts}), "Sort field, `-field` for descending order"),
+        query_param("limit", json!({"type": "integer", "minimum": 1, "maximum": MAX_PAGE_SIZE, "default": DEFAULT_PAGE_SIZE}), "Page size"),
+        query_param("cursor", string(), "next_cursor of the previous page"),
+    ]
+}
+
+fn schemas() -> Value {
+    let note = object(vec![("note", json!({"type": "string", "minLength": 1, "maxLength": 10000}))], &["note"]);
+    let folder = object(vec![("folder", string())], &["folder"]);
+    // Broken rules of the organization's password policy come as the
+    // `length`, `entropy`, `personal_info` and `compromised` field errors
+    let password = json!({
+        "type": "string",
+        "minLength": 8,
+        "maxLength": 1024,
+        "description": "Follows the organization's password policy",
+    });
+    let new_user = object(vec![
+        ("full_name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+        ("email", json!({"type": "string", "format": "email", "maxLength": 254})),
+        ("password", password.clone()),
+        ("job_title", json!({"type": "string", "maxLength": 255})),
+        ("profile_picture", json!({"type": "string", "maxLength": 2048})),
+        ("role", json!({"type": "string", "enum": ["member", "owner"], "description": "member by default, read by create_new_user only"})),
+    ], &["full_name", "email", "password"]);
+    let coordinate_fields = || vec![
+        ("address", json!({"type": "string", "maxLength": 500})),
+        ("telephone_no", string()),
+        ("fax", string()),
+        ("cellphone_no", string()),
+        ("email", json!({"type": "string", "format": "email", "maxLength": 254})),
+        ("company_name", json!({"type": "string", "maxLength": 255})),
+        ("company_number", json!({"type": "string", "maxLength": 50})),
+    ];
+    let owner_kind = json!({"type": "integer", "enum": [0, 1, 2], "description": "0 individual, 1 company, 2 manager"});
+
+    let schemas = vec![
+        ("Error", response(vec![("error", string()), ("request_id", string())])),
+        ("ValidationError", response(vec![
+            ("error", string()),
+            ("request_id", string()),
+            ("fields", json!({"type": "object", "additionalProperties": array(string())})),
+        ])),
+        ("Created", response(vec![("id", uuid())])),
+        ("Credentials", object(vec![
+            ("email", string()),
+            ("password", string()),
+            ("code", string()),
+        ], &["email", "password"])),
+        ("Token", response(vec![("token", string())])),
+        ("SsoAuthorization", response(vec![("authorization_url", string())])),
+        ("SsoCallback", object(vec![("code", string()), ("state", string())], &["code", "state"])),
+        ("NewOidcProvider", object(vec![
+            ("issuer", json!({"type": "string", "format": "uri"})),
+            ("client_id", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("client_secret", json!({"type": "string", "minLength": 1, "maxLength": 1024})),
+            ("authorization_endpoint", json!({"type": "string", "format": "uri"})),
+            ("token_endpoint", json!({"type": "string", "format": "uri"})),
+            ("redirect_uri", json!({"type": "string", "format": "uri"})),
+            ("groups_claim", json!({"type": "string", "default": "groups"})),
+            ("admin_groups", array(string())),
+            ("member_groups", array(string())),
+        ], &["issuer", "client_id", "client_secret", "authorization_endpoint", "token_endpoint", "redirect_uri"])),
+        ("OidcProviderResponse", response(vec![
+            ("issuer", string()),
+            ("client_id", string()),
+            ("authorization_endpoint", string()),
+            ("token_endpoint", string()),
+            ("redirect_uri", string()),
+            ("groups_claim", string()),
+            ("admin_groups", array(string())),
+            ("member_groups", array(string())),
+            ("updated_at", timestamp()),
+        ])),
+        ("TwoFactorCode", object(vec![("code", string())], &["code"])),
+        ("TwoFactorEnrollment", response(vec![
+            ("secret", string()),
+            ("provisioning_uri", string()),
+            ("qr_code_svg", string()),
+        ])),
+        ("RecoveryCodes", response(vec![("recovery_codes", array(string()))])),
+        ("NewApiKey", object(vec![
+            ("name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("scopes", array(json!({"type": "string", "enum": ApiScope::ALL.iter().map(|s| s.name()).collect::<Vec<_>>()}))),
+            ("expires_at", nullable(timestamp())),
+        ], &["name", "scopes"])),
+        ("ApiKeyResponse", response(vec![
+            ("id", uuid()),
+            ("name", string()),
+            ("scopes", array(string())),
+            ("created_by", uuid()),
+            ("expires_at", nullable(timestamp())),
+            ("last_used_at", nullable(timestamp())),
+            ("created_at", timestamp()),
+        ])),
+        ("CreatedApiKey", response(vec![("id", uuid()), ("key", string())])),
+        ("SessionResponse", response(vec![
+            ("id", uuid()),
+            ("device", json!({"type": "string", "description": "Browser and system of the user agent, such as Firefox on Windows"})),
+            ("ip_address", string()),
+            ("user_agent", string()),
+            ("current", json!({"type": "boolean", "description": "Whether the request comes from this session"})),
+            ("created_at", timestamp()),
+            ("last_seen_at", timestamp()),
+            ("expires_at", timestamp()),
+        ])),
+        ("NoteText", note),
+        ("Folder", folder),
+        ("NewOrganization", object(vec![
+            ("org_name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("admin", reference("NewUser")),
+        ], &["org_name", "admin"])),
+        ("Registered", response(vec![("user_id", uuid())])),
+        ("OrganizationPatch", object(vec![
+            ("org_name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("coordinates_id", nullable(uuid())),
+            ("require_two_factor", boolean()),
+            ("password_min_length", json!({"type": "integer", "minimum": 8, "maximum": 1024})),
+            ("password_min_entropy", json!({"type": "integer", "minimum": 0, "maximum": 256, "description": "Estimated bits"})),
+            ("password_reject_personal_info", boolean()),
+        ], &[])),
+        ("OrganizationResponse", response(vec![
+            ("id", uuid()),
+            ("org_name", string()),
+            ("coordinates_id", nullable(uuid())),
+            ("require_two_factor", boolean()),
+            ("password_min_length", integer()),
+            ("password_min_entropy", integer()),
+            ("password_reject_personal_info", boolean()),
+            ("created_at", timestamp()),
+            ("updated_at", timestamp()),
+            ("version", integer()),
+        ])),
+        ("NewUser", new_user),
+        ("UserPatch", object(vec![
+            ("full_name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("email", json!({"type": "string", "format": "email", "maxLength": 254})),
+            ("job_title", json!({"type": "string", "maxLength": 255})),
+            ("profile_picture", json!({"type": "string", "maxLength": 2048})),
+        ], &[])),
+        ("UserResponse", response(vec![
+            ("id", uuid()),
+            ("org_id", uuid()),
+            ("permission", json!({"type": "integer", "enum": [1, 2, 3], "description": "1 member, 2 admin, 3 owner"})),
+            ("full_name", string()),
+            ("email", string()),
+            ("job_title", string()),
+            ("profile_picture", string()),
+            ("email_verified", boolean()),
+            ("pending_email", nullable(json!({
+                "type": "string",
+                "description": "The address the user is changing to until confirmed, only in the user's own profile",
+            }))),
+            ("created_at", timestamp()),
+            ("updated_at", timestamp()),
+            ("version", integer()),
+        ])),
+        ("PasswordChange", object(vec![
+            ("old_password", string()),
+            ("new_password", password),
+        ], &["old_password", "new_password"])),
+        ("Handover", object(vec![("successor_id", uuid())], &["successor_id"])),
+        ("HandoverReport", response(vec![
+            ("buildings", json!({"type": "integer", "description": "Buildings the successor is now the respondant of"})),
+            ("mentions", json!({"type": "integer", "description": "Mentions in notes moved to the successor"})),
+        ])),
+        ("EmailVerificationToken", object(vec![("token", string())], &["token"])),
+        ("NewCoordinate", object(coordinate_fields(), &[])),
+        ("CoordinatePatch", object(coordinate_fields(), &[])),
+        ("CoordinateResponse", response(vec![
+            ("id", uuid()),
+            ("address", string()),
+            ("telephone_no", string()),
+            ("fax", string()),
+            ("cellphone_no", string()),
+            ("email", string()),
+            ("company_name", string()),
+            ("company_number", string()),
+            ("updated_at", timestamp()),
+            ("version", integer()),
+        ])),
+        ("NewBuildingOwner", object(vec![
+            ("full_name", json!({"type": "string", "maxLength": 255})),
+            ("same_as_manager", boolean()),
+            ("kind", owner_kind.clone()),
+            ("linked_user_id", nullable(uuid())),
+            ("coordinates_id", nullable(uuid())),
+        ], &["same_as_manager", "kind"])),
+        ("BuildingOwnerPatch", object(vec![
+            ("full_name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("same_as_manager", boolean()),
+            ("kind", owner_kind.clone()),
+            ("linked_user_id", nullable(uuid())),
+            ("coordinates_id", nullable(uuid())),
+        ], &[])),
+        ("BuildingOwnerResponse", response(vec![
+            ("id", uuid()),
+            ("full_name", string()),
+            ("kind", owner_kind),
+            ("is_manager", boolean()),
+            ("org_id", nullable(uuid())),
+            ("linked_user_id", nullable(uuid())),
+            ("coordinates_id", nullable(uuid())),
+            ("created_at", timestamp()),
+            ("updated_at", timestamp()),
+            ("version", integer()),
+        ])),
+        ("OwnerInfo", response(vec![
+            ("profile", reference("BuildingOwnerResponse")),
+            ("coordinates", nullable(reference("CoordinateResponse"))),
+            ("buildings", array(reference("BuildingInfo"))),
+            ("users", array(reference("UserResponse"))),
+        ])),
+        ("NewBuilding", object(vec![
+            ("owner_id", uuid()),
+            ("respondant_id", uuid()),
+            ("name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("address", json!({"type": "string", "minLength": 1, "maxLength": 500})),
+        ], &["owner_id", "respondant_id", "name", "address"])),
+        ("BuildingPatch", object(vec![
+            ("owner_id", uuid()),
+            ("respondant_id", uuid()),
+            ("name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+            ("address", json!({"type": "string", "minLength": 1, "maxLength": 500})),
+        ], &[])),
+        ("BuildingResponse", response(vec![
+            ("id", uuid()),
+            ("owner_id", uuid()),
+            ("org_id", uuid()),
+            ("respondant_id", uuid()),
+            ("name", string()),
+            ("address", string()),
+            ("created_at", timestamp()),
+            ("updated_at", timestamp()),
+            ("version", integer()),
+        ])),
+        ("BuildingInfo", json!({
+            "allOf": [
+                reference("BuildingResponse"),
+                response(vec![("registers", array(reference("RegisterResponse")))]),
+            ],
+        })),
+        ("NewRegister", object(vec![
+            ("building_id", uuid()),
+            ("name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+        ], &["building_id", "name"])),
+        ("RegisterPatch", object(vec![
+            ("name", json!({"type": "string", "minLength": 1, "maxLength": 255})),
+        ], &[])),
+        ("RegisterResponse", response(vec![
+            ("id", uuid()),
+            ("name", string()),
+            ("building_id", uuid()),
+            ("created_at", timestamp()),
+            ("updated_at", timestamp()),
+            ("version", integer()),
+        ])),
+        ("RowError", response(vec![
+            ("row", integer()),
+            ("field", nullable(string())),
+            ("message", string()),
+        ])),
+        ("ImportReport", response(vec![
+            ("dry_run", boolean()),
+            ("committed", boolean()),
+            ("rows", integer()),
+            ("buildings_created", integer()),
+            ("owners_created", integer()),
+            ("owners_reused", integer()),
+            ("coordinates_created", integer()),
+            ("errors", array(reference("RowError"))),
+        ])),
+        ("ExportRow", response(vec![
+            ("building_id", uuid()),
+            ("building_name", string()),
+            ("address", string()),
+            ("owner_name", string()),
+            ("owner_kind", json!({"type": "string", "enum": ["individual", "company", "manager", ""]})),
+            ("owner_company_name", string()),
+            ("owner_company_number", string()),
+            ("owner_address", string()),
+            ("owner_telephone_no", string()),
+            ("owner_email", string()),
+            ("respondant_name", string()),
+            ("respondant_email", string()),
+            ("registers", integer()),
+            ("register_names", array(string())),
+            ("last_activity", nullable(timestamp())),
+        ])),
+        ("FileNoteInfo", response(vec![
+            ("note_id", uuid()),
+            ("user_id", uuid()),
+            ("user_name", string()),
+            ("handed_over_to", nullable(uuid())),
+            ("date", timestamp()),
+            ("note", string()),
+        ])),
+        ("EntityFileInfo", response(vec![
+            ("file_id", uuid()),
+            ("filename", string()),
+            ("notes", array(reference("FileNoteInfo"))),
+            ("metadata", json!({"type": "object"})),
+            ("url", string()),
+        ])),
+        ("ActivityInfo", response(vec![
+            ("date", timestamp()),
+            ("action_id", integer()),
+            ("file_id", uuid()),
+            ("filename", string()),
+            ("url", string()),
+            ("user_id", uuid()),
+            ("user_name", string()),
+        ])),
+        ("EntityFiles", response(vec![
+            ("files", array(reference("EntityFileInfo"))),
+            ("activity_feed", array(reference("ActivityInfo"))),
+        ])),
+        ("EntityNoteInfo", response(vec![
+            ("user_name", string()),
+            ("user_id", uuid()),
+            ("handed_over_to", nullable(uuid())),
+            ("date", timestamp()),
+            ("note", string()),
+            ("note_id", uuid()),
+            ("edited", boolean()),
+            ("mentions", array(uuid())),
+            ("replies", array(reference("EntityNoteInfo"))),
+        ])),
+        ("NoteEditInfo", response(vec![
+            ("user_name", string()),
+            ("user_id", uuid()),
+            ("date", timestamp()),
+            ("previous_note", string()),
+        ])),
+        ("SharedFileInfo", response(vec![
+            ("file_id", uuid()),
+            ("entity_id", uuid()),
+            ("folder", string()),
+            ("filename", string()),
+            ("url", string()),
+        ])),
+        ("OwnerRegisterInfo", response(vec![("register_id", uuid()), ("name", string())])),
+        ("OwnerBuildingInfo", response(vec![
+            ("building_id", uuid()),
+            ("name", string()),
+            ("address", string()),
+            ("registers", array(reference("OwnerRegisterInfo"))),
+            ("files", array(reference("SharedFileInfo"))),
+        ])),
+    ];
+
+    Value::Object(schemas.into_iter().map(|(name, schema)| (name.to_owned(), schema)).collect())
+}
+
+fn operations() -> Vec<Operation> {
+    use self::Reply::*;
+
+    vec![
+        Operation::new("get", "/openapi.json", "get_openapi", "This document")
+            .public()
+            .reply(Json(json!({"type": "object"}))),
+        Operation::new("post", "/organisations", "register_new_organisation", "Creates an organization and its first admin")
+            .public()
+            .body(reference("NewOrganization"))
+            .reply(Json(reference("Registered"))),
+        Operation::new("post", "/login", "login", "Opens a session")
+            .public()
+            .body(reference("Credentials"))
+            .errors(&["401", "429"])
+            .reply(Json(reference("Token"))),
+        Operation::new("post", "/logout", "logout", "Closes the session"),
+        Operation::new("get", "/sso/{org_id}", "begin_sso", "Starts signing in through the organization's identity provider")
+            .public()
+            .reply(Json(reference("SsoAuthorization"))),
+        Operation::new("post", "/sso/login", "complete_sso", "Opens a session given the code and state of the identity provider")
+            .public()
+            .body(reference("SsoCallback"))
+            .errors(&["401", "403"])
+            .reply(Json(reference("Token"))),
+        Operation::new("post", "/two_factor", "begin_two_factor", "Draws a TOTP secret to add to an authenticator app")
+            .reply(Json(reference("TwoFactorEnrollment"))),
+        Operation::new("delete", "/two_factor", "disable_two_factor", "Turns two-factor authentication off")
+            .body(reference("TwoFactorCode")),
+        Operation::new("post", "/two_factor/confirm", "confirm_two_factor", "Enables the TOTP secret given a first code")
+            .body(reference("TwoFactorCode"))
+            .reply(Json(reference("RecoveryCodes"))),
+        Operation::new("post", "/two_factor/recovery_codes", "regenerate_recovery_codes", "Replaces the recovery codes")
+            .body(reference("TwoFactorCode"))
+            .reply(Json(reference("RecoveryCodes"))),
+        Operation::new("get", "/users", "get_users", "Lists the users of the organization")
+            .query(vec![query_param("role", json!({"type": "string", "enum": ["member", "admin", "owner"]}), "Role of the users")])
+            .query(list_params(crate::models::users::USER_SORT_FIELDS))
+            .reply(Json(page_of("UserResponse"))),
+        Operation::new("post", "/users", "create_new_user", "Creates a member of the admin's organization")
+            .body(reference("NewUser"))
+            .reply(Created),
+        Operation::new("post", "/users/{user_id}/unlock", "unlock_user", "Lifts the lockout of a user after failed logins"),
+        Operation::new("post", "/users/{user_id}/deactivate", "deactivate_user", "Deactivates a user, once their buildings are handed over"),
+        Operation::new("post", "/users/{user_id}/handover", "hand_over_user", "Hands a user's buildings and mentions over to another user")
+            .body(reference("Handover"))
+            .reply(Json(reference("HandoverReport"))),
+        Operation::new("get", "/users/{user_id}/sessions", "get_user_sessions", "Lists the open sessions of a user")
+            .reply(Json(array(reference("SessionResponse")))),
+        Operation::new("delete", "/users/{user_id}/sessions", "revoke_user_sessions", "Closes every session of a user"),
+        Operation::new("delete", "/users/{user_id}/sessions/{session_id}", "revoke_user_session", "Closes a session of a user"),
+        Operation::new("get", "/profile", "get_user_profile", "Returns the user's profile")
+            .reply(Versioned(reference("UserResponse"))),
+        Operation::new("patch", "/profile", "modify_user_profile", "Changes the user's profile, a new email once confirmed")
+            .body(reference("UserPatch"))
+            .reply(Versioned(reference("UserResponse"))),
+        Operation::new("post", "/profile/password", "change_password", "Changes the user's password given the current one")
+            .body(reference("PasswordChange")),
+        Operation::new("get", "/profile/sessions", "get_sessions", "Lists the user's open sessions")
+            .reply(Json(array(reference("SessionResponse")))),
+        Operation::new("delete", "/profile/sessions", "revoke_other_sessions", "Closes the user's sessions but the current one"),
+        Operation::new("delete", "/profile/sessions/{session_id}", "revoke_session", "Closes a session of the user"),
+        Operation::new("post", "/profile/email_verification", "resend_email_verification", "Mails the link verifying the user's email again"),
+        Operation::new("post", "/verify_email", "verify_email", "Verifies an email with the token of the link mailed to it")
+            .public()
+            .body(reference("EmailVerificationToken"))
+            .errors(&["404", "409"]),
+        Operation::new("get", "/api_keys", "get_api_keys", "Lists the API keys of the admin's organization")
+            .reply(Json(array(reference("ApiKeyResponse")))),
+        Operation::new("post", "/api_keys", "create_api_key", "Creates an API key, whose token is only shown once")
+            .body(reference("NewApiKey"))
+            .reply(CreatedWith(reference("CreatedApiKey"))),
+        Operation::new("delete", "/api_keys/{key_id}", "revoke_api_key", "Revokes an API key"),
+        Operation::new("get", "/organisation", "get_organisation_info", "Returns the user's organization")
+            .reply(Versioned(reference("OrganizationResponse"))),
+        Operation::new("patch", "/organisation", "modify_organisation_info", "Changes the admin's organization")
+            .body(reference("OrganizationPatch"))
+            .reply(Versioned(reference("OrganizationResponse"))),
+        Operation::new("get", "/organisation/sso", "get_sso_config", "Returns the identity provider of the admin's organization")
+            .reply(Json(reference("OidcProviderResponse"))),
+        Operation::new("put", "/organisation/sso", "set_sso_config", "Sets the identity provider of the admin's organization")
+            .body(reference("NewOidcProvider"))
+            .reply(Json(reference("OidcProviderResponse"))),
+        Operation::new("delete", "/organisation/sso", "delete_sso_config", "Turns single sign-on off"),
+        Operation::new("post", "/coordinates", "create_coordinate", "Creates coordinates")
+            .body(reference("NewCoordinate"))
+            .reply(Created),
+        Operation::new("patch", "/coordinates/{coordinates_id}", "modify_coordinate", "Changes coordinates")
+            .body(reference("CoordinatePatch"))
+            .reply(Versioned(reference("CoordinateResponse"))),
+        Operation::new("get", "/owners", "get_owners", "Lists the owners of the organization")
+            .scope(ApiScope::ReadBuildings)
+            .query(vec![
+                query_param("kind", json!({"type": "integer", "enum": [0, 1, 2]}), "Owner kind"),
+                query_param("full_name", string(), "Part of the name"),
+            ])
+            .query(list_params(crate::models::building_owners::OWNER_SORT_FIELDS))
+            .reply(Json(page_of("BuildingOwnerResponse"))),
+        Operation::new("post", "/owners", "create_new_owner", "Creates an owner")
+            .body(reference("NewBuildingOwner"))
+            .reply(Created),
+        Operation::new("get", "/owners/{owner_id}", "get_owner_info", "Returns an owner with its buildings and users")
+            .scope(ApiScope::ReadBuildings)
+            .reply(Versioned(reference("OwnerInfo"))),
+        Operation::new("patch", "/owners/{owner_id}", "modify_owner_info", "Changes an owner")
+            .body(reference("BuildingOwnerPatch"))
+            .reply(Versioned(reference("BuildingOwnerResponse"))),
+        Operation::new("get", "/buildings", "get_buildings", "Lists the buildings managed by the organization")
+            .scope(ApiScope::ReadBuildings)
+            .query(vec![
+                query_param("owner_id", uuid(), "Owner of the buildings"),
+                query_param("respondant_id", uuid(), "Respondant of the buildings"),
+                query_param("address", string(), "Part of the address"),
+            ])
+            .query(list_params(crate::models::buildings::BUILDING_SORT_FIELDS))
+            .reply(Json(page_of("BuildingResponse"))),
+        Operation::new("post", "/buildings", "create_new_building", "Creates a building")
+            .body(reference("NewBuilding"))
+            .reply(Created),
+        Operation::new("get", "/buildings/{building_id}", "get_building_information", "Returns a building and its registers")
+            .scope(ApiScope::ReadBuildings)
+            .reply(Versioned(reference("BuildingInfo"))),
+        Operation::new("patch", "/buildings/{building_id}", "modify_building", "Changes a building")
+            .body(reference("BuildingPatch"))
+            .reply(Versioned(reference("BuildingResponse"))),
+        Operation::new("get", "/buildings/{building_id}/dossier", "get_building_dossier", "Returns the printable dossier of a building")
+            .scope(ApiScope::ReadBuildings)
+            .reply(File(vec![("application/pdf", json!({"type": "string", "format": "binary"}))])),
+        Operation::new("get", "/buildings/{building_id}/shared_folders", "get_shared_folders", "Lists the folders shared with the owner")
+            .reply(Json(array(string()))),
+        Operation::new("post", "/buildings/{building_id}/shared_folders", "share_folder_with_owner", "Shares a folder with the owner")
+            .body(reference("Folder")),
+        Operation::new("delete", "/buildings/{building_id}/shared_folders", "unshare_folder_with_owner", "Stops sharing a folder with the owner")
+            .query(vec![json!({"name": "folder", "in": "query", "required": true, "schema": string()})]),
+        Operation::new("get", "/registers", "get_registers", "Lists the registers of the organization's buildings")
+            .scope(ApiScope::ReadBuildings)
+            .query(vec![
+                query_param("building_id", uuid(), "Building of the registers"),
+                query_param("name", string(), "Part of the name"),
+            ])
+            .query(list_params(crate::models::registers::REGISTER_SORT_FIELDS))
+            .reply(Json(page_of("RegisterResponse"))),
+        Operation::new("post", "/registers", "create_new_register", "Creates a register")
+            .scope(ApiScope::WriteRegisters)
+            .body(reference("NewRegister"))
+            .reply(Created),
+        Operation::new("patch", "/registers/{register_id}", "modify_register", "Changes a register")
+            .scope(ApiScope::WriteRegisters)
+            .body(reference("RegisterPatch"))
+            .reply(Versioned(reference("RegisterResponse"))),
+        Operation::new("post", "/imports", "import_buildings", "Imports buildings and their owners from a file")
+            .scope(ApiScope::UploadFiles)
+            .query(vec![
+                json!({"name": "format", "in": "query", "required": true, "schema": {"type": "string", "enum": ["csv", "xlsx"]}}),
+                query_param("dry_run", json!({"type": "boolean", "default": false}), "Checks the rows without saving them"),
+            ])
+            .upload(&["text/csv", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"])
+            .reply(Json(reference("ImportReport"))),
+        Operation::new("get", "/exports/buildings", "export_buildings", "Exports the organization's buildings as a file")
+            .scope(ApiScope::ReadBuildings)
+            .query(vec![
+                json!({"name": "format", "in": "query", "required": true, "schema": {"type": "string", "enum": ["csv", "xlsx", "json"]}}),
+                query_param("owner_id", uuid(), "Owner of the buildings"),
+            ])
+            .reply(File(vec![
+                ("text/csv", json!({"type": "string", "format": "binary"})),
+                ("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", json!({"type": "string", "format": "binary"})),
+                ("application/json", array(reference("ExportRow"))),
+            ])),
+        Operation::new("get", "/entities/{entity_id}/files", "get_entity_files", "Returns the files and activity of a building or register")
+            .scope(ApiScope::ReadBuildings)
+            .reply(Json(reference("EntityFiles"))),
+        Operation::new("post", "/entities/{entity_id}/files/{file_id}/notes", "create_note_on_file", "Adds a note to a file")
+            .body(reference("NoteText")),
+        Operation::new("get", "/entities/{entity_id}/notes", "get_entity_notes", "Returns a page of note threads, newest first")
+            .scope(ApiScope::ReadBuildings)
+            .query(vec![
+                query_param("page", json!({"type": "integer", "minimum": 0, "default": 0}), "Page number"),
+                query_param("page_size", json!({"type": "integer", "minimum": 1, "maximum": MAX_PAGE_SIZE, "default": DEFAULT_PAGE_SIZE}), "Threads per page"),
+            ])
+            .reply(Json(array(reference("EntityNoteInfo")))),
+        Operation::new("post", "/entities/{entity_id}/notes", "create_entity_note", "Starts a note thread")
+            .body(reference("NoteText"))
+            .reply(Created),
+        Operation::new("patch", "/notes/{note_id}", "edit_entity_note", "Changes the text of the author's note")
+            .body(reference("NoteText")),
+        Operation::new("delete", "/notes/{note_id}", "delete_entity_note", "Deletes a note"),
+        Operation::new("post", "/notes/{note_id}/replies", "reply_to_entity_note", "Replies to a note")
+            .body(reference("NoteText"))
+            .reply(Created),
+        Operation::new("get", "/notes/{note_id}/edits", "get_entity_note_edits", "Returns the previous texts of a note")
+            .reply(Json(array(reference("NoteEditInfo")))),
+        Operation::new("get", "/portal/buildings", "get_owner_buildings", "Lists the buildings of an owner-role user")
+            .reply(Json(array(reference("OwnerBuildingInfo")))),
+        Operation::new("get", "/portal/buildings/{building_id}", "get_owner_building", "Returns a building of an owner-role user")
+            .reply(Json(reference("OwnerBuildingInfo"))),
+    ]
+}
+
+fn error_response(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "headers": {"X-Request-Id": {"$ref": "#/components/headers/XRequestId"}},
+        "content": {"application/json": {"schema": reference(schema)}},
+    })
+}
+
+/// Builds the OpenAPI document of the routes registered by `config`.
+pub fn spec() -> Value {
+    let mut paths = Map::new();
+    for operation in operations() {
+        let path = paths.entry(format!("/api/v1{}", operation.path)).or_insert_with(|| json!({}));
+        path[operation.method] = operation.to_json();
+    }
+
+    json!({
+        "openapi": "3.0.2",
+        "info": {
+            "title": "bep",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "security": [{"bearer": []}],
+        "components": {
+            "securitySchemes": {
+                "bearer": {"type": "http", "scheme": "bearer", "description": "Token returned by login, or an API key on the operations with an x-api-key-scope it was granted"},
+            },
+            "parameters": {
+                "IfMatch": {
+                    "name": "If-Match",
+                    "in": "header",
+                    "required": false,
+                    "schema": string(),
+                    "description": "ETag of the version the change applies to, fails with 409 if saved since",
+                },
+            },
+            "headers": {
+                "ETag": {"schema": string(), "description": "Version of the entity, for If-Match"},
+                "XRequestId": {
+                    "schema": string(),
+                    "description": "ID of the request in the server logs, the one sent by the client if valid; sent with every response",
+                },
+            },
+            "responses": {
+                "400": error_response("Request contradicts the stored data", "Error"),
+                "401": error_response("Missing, closed or expired session token", "Error"),
+                "403": error_response("Not allowed for the authenticated user", "Error"),
+                "404": error_response("Unknown entity", "Error"),
+                "409": error_response("Modified since the client loaded it", "Error"),
+                "422": error_response("Invalid fields", "ValidationError"),
+                "429": {
+                    "description": "Too many failed logins",
+                    "headers": {
+                        "Retry-After": {"schema": integer(), "description": "Seconds to wait"},
+                        "X-Request-Id": {"$ref": "#/components/headers/XRequestId"},
+                    },
+                    "content": {"application/json": {"schema": reference("Error")}},
+                },
+                "500": error_response("Internal server error", "Error"),
+                "503": error_response("Service unavailable", "Error"),
+            },
+            "schemas": schemas(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{page_of, reference, spec, json, Map, Value};
+    use crate::config::Config;
+    use crate::models::api::{ActivityInfo, BuildingInfo, EntityFileInfo, EntityFiles, EntityNoteInfo, FileNoteInfo, NoteEditInfo};
+    use crate::models::api::{OwnerBuildingInfo, OwnerInfo, OwnerRegisterInfo, SharedFileInfo};
+    use crate::models::api_keys::{ApiKeyResponse, NewApiKey};
+    use crate::models::building_owners::{BuildingOwnerPatch, BuildingOwnerResponse, NewBuildingOwner};
+    use crate::models::buildings::{BuildingPatch, BuildingResponse, NewBuilding};
+    use crate::models::coordinates::{CoordinatePatch, CoordinateResponse, NewCoordinate};
+    use crate::models::entities_notes::NoteText;
+    use crate::models::export::ExportRow;
+    use crate::models::import::{ImportReport, RowError};
+    use crate::models::list_query::Page;
+    use crate::models::oidc::{NewOidcProvider, OidcProviderResponse};
+    use crate::models::organizations::{NewOrganization, OrganizationPatch, OrganizationResponse};
+    use crate::models::registers::{NewRegister, RegisterPatch, RegisterResponse};
+    use crate::models::sessions::SessionResponse;
+    use crate::models::two_factor::TwoFactorEnrollment;
+    use crate::models::users::{Handover, HandoverReport, NewUser, PasswordChange, UserPatch, UserResponse};
+    use crate::routes::auth::{Credentials, SsoCallback, TwoFactorCode};
+    use crate::routes::portal::Folder;
+    use crate::routes::users::EmailVerificationToken;
+    use crate::routes::{config, ROUTES};
+    use actix_web::{http::StatusCode, test, App};
+    use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+    use serde::forward_to_deserialize_any;
+
+    #[test]
+    fn test_spec_matches_router() {
+        let spec = spec();
+        let mut documented: Vec<(String, String)> = spec["paths"].as_object().unwrap()
+            .iter()
+            .flat_map(|(path, methods)| methods.as_object().unwrap().keys()
+                .map(move |method| (method.clone(), path.clone())))
+            .collect();
+        let mut routed: Vec<(String, String)> = ROUTES.iter()
+            .map(|(method, path)| (method.to_string(), format!("/api/v1{}", path)))
+            .collect();
+
+        documented.sort();
+        routed.sort();
+        assert_eq!(documented, routed);
+    }
+
+    #[test]
+    fn test_spec_references_exist() {
+        fn references(value: &Value, found: &mut Vec<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(r)) = map.get("$ref") {
+                        found.push(r.clone());
+                    }
+                    map.values().for_each(|v| references(v, found));
+                }
+                Value::Array(items) => items.iter().for_each(|v| references(v, found)),
+                _ => (),
+            }
+        }
+
+        let spec = spec();
+        let mut found = Vec::new();
+        references(&spec, &mut found);
+
+        for reference in found {
+            let pointer = reference.trim_start_matches('#');
+            assert!(spec.pointer(pointer).is_some(), "Dangling reference {}", reference);
+        }
+    }
+
+    /// Checks that a value has the properties of its schema, no more and no
+    /// less, down to the objects it holds.
+    fn check_keys(spec: &Value, schema: &Value, value: &Value, path: &str) {
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            let schema = spec.pointer(reference.trim_start_matches('#')).unwrap();
+            return check_keys(spec, schema, value, path);
+        }
+        if value.is_null() {
+            return;
+        }
+
+        let properties = match schema.get("allOf") {
+            Some(Value::Array(parts)) => parts.iter()
+                .map(|part| match part.get("$ref") {
+                    Some(Value::String(r)) => spec.pointer(r.trim_start_matches('#')).unwrap(),
+                    _ => part,
+                })
+                .flat_map(|part| part["properties"].as_object().unwrap().clone())
+                .collect(),
+            _ => match schema.get("properties") {
+                Some(Value::Object(properties)) => properties.clone(),
+                _ => Map::new(),
+            },
+        };
+
+        match value {
+            Value::Object(fields) if !properties.is_empty() => {
+                let mut documented: Vec<&String> = properties.keys().collect();
+                let mut serialized: Vec<&String> = fields.keys().collect();
+                documented.sort();
+                serialized.sort();
+                assert_eq!(serialized, documented, "Keys of {} differ from its schema", path);
+
+                for (name, field) in fields {
+                    check_keys(spec, &properties[name], field, &format!("{}.{}", path, name));
+                }
+            }
+            Value::Array(items) => items.iter()
+                .for_each(|item| check_keys(spec, &schema["items"], item, &format!("{}[]", path))),
+            _ => (),
+        }
+    }
+
+    #[test]
+    fn test_spec_matches_responses() {
+        let now = chrono::Utc::now().naive_utc();
+        let id = uuid::Uuid::new_v4();
+
+        let coordinates = CoordinateResponse {
+            id, address: String::new(), telephone_no: String::new(), fax: String::new(), cellphone_no: String::new(),
+            email: String::new(), company_name: String::new(), company_number: String::new(), updated_at: now, version: 1,
+        };
+        let owner = BuildingOwnerResponse {
+            id, full_name: String::new(), kind: 0, is_manager: false, org_id: Some(id), linked_user_id: Some(id),
+            coordinates_id: Some(id), created_at: now, updated_at: now, version: 1,
+        };
+        let user = || UserResponse {
+            id, org_id: id, permission: 1, full_name: String::new(), email: String::new(), job_title: String::new(),
+            profile_picture: String::new(), email_verified: true, pending_email: Some(String::new()),
+            created_at: now, updated_at: now, version: 1,
+        };
+        let building = || BuildingResponse {
+            id, owner_id: id, org_id: id, respondant_id: id, name: String::new(), address: String::new(),
+            created_at: now, updated_at: now, version: 1,
+        };
+        let register = || RegisterResponse { id, name: String::new(), building_id: id, created_at: now, updated_at: now, version: 1 };
+        let file_note = FileNoteInfo { note_id: id, user_id: id, user_name: String::new(), handed_over_to: Some(id), date: now, note: String::new() };
+        let entity_note = || EntityNoteInfo {
+            user_name: String::new(), user_id: id, handed_over_to: Some(id), date: now, note: String::new(), note_id: id,
+            edited: false, mentions: vec![id], replies: Vec::new(),
+        };
+
+        let responses: Vec<(Value, Value)> = vec![
+            (reference("OidcProviderResponse"), json!(OidcProviderResponse {
+                issuer: String::new(), client_id: String::new(), authorization_endpoint: String::new(),
+                token_endpoint: String::new(), redirect_uri: String::new(), groups_claim: String::new(),
+                admin_groups: vec![String::new()], member_groups: vec![String::new()], updated_at: now,
+            })),
+            (reference("TwoFactorEnrollment"), json!(TwoFactorEnrollment {
+                secret: String::new(), provisioning_uri: String::new(), qr_code_svg: String::new(),
+            })),
+            (reference("ApiKeyResponse"), json!(ApiKeyResponse {
+                id, name: String::new(), scopes: vec![String::new()], created_by: id, expires_at: Some(now),
+                last_used_at: Some(now), created_at: now,
+            })),
+            (reference("SessionResponse"), json!(SessionResponse {
+                id, device: String::new(), ip_address: String::new(), user_agent: String::new(), current: true,
+                created_at: now, last_seen_at: now, expires_at: now,
+            })),
+            (reference("OrganizationResponse"), json!(OrganizationResponse {
+                id, org_name: String::new(), coordinates_id: Some(id), require_two_factor: false, password_min_length: 8,
+                password_min_entropy: 0, password_reject_personal_info: false, created_at: now, updated_at: now, version: 1,
+            })),
+            (reference("UserResponse"), json!(user())),
+            (reference("HandoverReport"), json!(HandoverReport { buildings: 1, mentions: 1 })),
+            (reference("OwnerInfo"), json!(OwnerInfo {
+                profile: owner,
+                coordinates: Some(coordinates),
+                buildings: vec![BuildingInfo { building: building(), registers: vec![register()] }],
+                users: vec![user()],
+            })),
+            (page_of("BuildingResponse"), json!(Page { items: vec![building()], next_cursor: None })),
+            (reference("ImportReport"), json!(ImportReport {
+                errors: vec![RowError { row: 2, field: Some(String::new()), message: String::new() }],
+                ..ImportReport::default()
+            })),
+            (reference("ExportRow"), json!(ExportRow {
+                building_id: id, building_name: String::new(), address: String::new(), owner_name: String::new(),
+                owner_kind: String::new(), owner_company_name: String::new(), owner_company_number: String::new(),
+                owner_address: String::new(), owner_telephone_no: String::new(), owner_email: String::new(),
+                respondant_name: String::new(), respondant_email: String::new(), registers: 1,
+                register_names: vec![String::new()], last_activity: Some(now),
+            })),
+            (reference("EntityFiles"), json!(EntityFiles {
+                files: vec![EntityFileInfo { file_id: id, filename: String::new(), notes: vec![file_note], metadata: json!({}), url: String::new() }],
+                activity_feed: vec![ActivityInfo {
+                    date: now, action_id: 1, file_id: id, filename: String::new(), url: String::new(), user_id: id, user_name: String::new(),
+                }],
+            })),
+            (reference("EntityNoteInfo"), json!(EntityNoteInfo { replies: vec![entity_note()], ..entity_note() })),
+            (reference("NoteEditInfo"), json!(NoteEditInfo { user_name: String::new(), user_id: id, date: now, previous_note: String::new() })),
+            (reference("OwnerBuildingInfo"), json!(OwnerBuildingInfo {
+                building_id: id, name: String::new(), address: String::new(),
+                registers: vec![OwnerRegisterInfo { register_id: id, name: String::new() }],
+                files: vec![SharedFileInfo { file_id: id, entity_id: id, folder: String::new(), filename: String::new(), url: String::new() }],
+            })),
+        ];
+
+        let spec = spec();
+        for (schema, value) in responses {
+            check_keys(&spec, &schema, &value, &schema.to_string());
+        }
+    }
+
+    /// Names of the fields a struct deriving `Deserialize` reads, which it
+    /// hands to `deserialize_struct` before reading any.
+    fn field_names<T: DeserializeOwned>() -> Vec<&'static str> {
+        struct FieldNames<'a>(&'a mut Vec<&'static str>);
+
+        impl<'de, 'a> Deserializer<'de> for FieldNames<'a> {
+            type Error = de::value::Error;
+
+            fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+                Err(de::Error::custom("not a struct"))
+            }
+
+            fn deserialize_struct<V: Visitor<'de>>(
+                self,
+                _name: &'static str,
+                fields: &'static [&'static str],
+                _visitor: V
+            ) -> Result<V::Value, Self::Error> {
+                self.0.extend(fields);
+                Err(de::Error::custom("fields read"))
+            }
+
+            forward_to_deserialize_any! {
+                bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+                unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+            }
+        }
+
+        let mut names = Vec::new();
+        assert!(T::deserialize(FieldNames(&mut names)).is_err());
+        names
+    }
+
+    #[test]
+    fn test_spec_matches_request_bodies() {
+        let bodies: Vec<(&str, Vec<&str>)> = vec![
+            ("create_api_key", field_names::<NewApiKey>()),
+            ("login", field_names::<Credentials>()),
+            ("complete_sso", field_names::<SsoCallback>()),
+            ("confirm_two_factor", field_names::<TwoFactorCode>()),
+            ("regenerate_recovery_codes", field_names::<TwoFactorCode>()),
+            ("disable_two_factor", field_names::<TwoFactorCode>()),
+            ("create_new_building", field_names::<NewBuilding>()),
+            ("modify_building", field_names::<BuildingPatch>()),
+            ("create_new_register", field_names::<NewRegister>()),
+            ("modify_register", field_names::<RegisterPatch>()),
+            ("create_note_on_file", field_names::<NoteText>()),
+            ("create_entity_note", field_names::<NoteText>()),
+            ("reply_to_entity_note", field_names::<NoteText>()),
+            ("edit_entity_note", field_names::<NoteText>()),
+            ("register_new_organisation", field_names::<NewOrganization>()),
+            ("modify_organisation_info", field_names::<OrganizationPatch>()),
+            ("set_sso_config", field_names::<NewOidcProvider>()),
+            ("create_coordinate", field_names::<NewCoordinate>()),
+            ("modify_coordinate", field_names::<CoordinatePatch>()),
+            ("create_new_owner", field_names::<NewBuildingOwner>()),
+            ("modify_owner_info", field_names::<BuildingOwnerPatch>()),
+            ("share_folder_with_owner", field_names::<Folder>()),
+            ("create_new_user", field_names::<NewUser>()),
+            ("hand_over_user", field_names::<Handover>()),
+            ("modify_user_profile", field_names::<UserPatch>()),
+            ("change_password", field_names::<PasswordChange>()),
+            ("verify_email", field_names::<EmailVerificationToken>()),
+        ];
+
+        // Every handler taking a JSON body is listed above
+        let sources = [
+            include_str!("api_keys.rs"), include_str!("auth.rs"), include_str!("buildings.rs"),
+            include_str!("entities.rs"), include_str!("exports.rs"), include_str!("imports.rs"),
+            include_str!("organisations.rs"), include_str!("owners.rs"), include_str!("portal.rs"),
+            include_str!("tracing.rs"), include_str!("users.rs"), include_str!("versions.rs"),
+        ];
+        let mut handlers: Vec<&str> = sources.iter()
+            .flat_map(|source| source.split("\npub fn ").skip(1))
+            .filter(|item| item[..item.find('{').unwrap()].contains("web::Json<"))
+            .map(|item| &item[..item.find('(').unwrap()])
+            .collect();
+        let mut listed: Vec<&str> = bodies.iter().map(|(id, _)| *id).collect();
+        handlers.sort();
+        listed.sort();
+        assert_eq!(handlers, listed);
+
+        // And documented with the fields it reads
+        let spec = spec();
+        let mut documented = Vec::new();
+        for methods in spec["paths"].as_object().unwrap().values() {
+            for operation in methods.as_object().unwrap().values() {
+                if let Some(schema) = operation.pointer("/requestBody/content/application~1json/schema") {
+                    let schema = match schema.get("$ref") {
+                        Some(Value::String(r)) => spec.pointer(r.trim_start_matches('#')).unwrap(),
+                        _ => schema,
+                    };
+                    let mut properties: Vec<&str> = schema["properties"].as_object().unwrap().keys().map(String::as_str).collect();
+                    properties.sort();
+                    documented.push((operation["operationId"].as_str().unwrap(), properties));
+                }
+            }
+        }
+        documented.sort();
+
+        let mut read: Vec<(&str, Vec<&str>)> = bodies.into_iter()
+            .map(|(id, mut fields)| {
+                fields.sort();
+                (id, fields)
+            })
+            .collect();
+        read.sort();
+        assert_eq!(documented, read);
+    }
+
+    #[test]
+    fn test_openapi_route() {
+        let mut app = test::init_service(App::new().configure(|cfg| config(cfg, &Config::default())));
+        let req = test::TestRequest::get().uri("/api/v1/openapi.json").to_request();
+        let resp = test::call_service(&mut app, req);
+
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,79 @@
+use super::auth::{AuthUser, DbConn};
+use super::versions::{versioned, IfMatch};
+use crate::mail::Mailer;
+use crate::models::api::{self, APIError};
+use crate::models::coordinates::{CoordinatePatch, NewCoordinate};
+use crate::models::oidc::NewOidcProvider;
+use crate::models::organizations::{NewOrganization, OrganizationPatch};
+
+use actix_web::{web, HttpResponse};
+
+use serde_json::json;
+
+/// Signs up a new organization, the only operation open without a session.
+pub fn register_new_organisation(
+    conn: DbConn,
+    mailer: web::Data<Mailer>,
+    fields: web::Json<NewOrganization>
+) -> Result<HttpResponse, APIError> {
+    let user_id = api::register_new_organisation(&conn, &mailer, fields.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"user_id": user_id})))
+}
+
+pub fn get_organisation_info(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    let org = api::get_organisation_info(&auth.conn, auth.user_id)?;
+
+    Ok(versioned(org.version, &org))
+}
+
+pub fn modify_organisation_info(
+    auth: AuthUser,
+    if_match: IfMatch,
+    patch: web::Json<OrganizationPatch>
+) -> Result<HttpResponse, APIError> {
+    let org = api::modify_organisation_info(&auth.conn, auth.user_id, if_match.0, patch.into_inner())?;
+
+    Ok(versioned(org.version, &org))
+}
+
+pub fn get_sso_config(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    let config = api::get_sso_config(&auth.conn, auth.user_id)?;
+
+    Ok(HttpResponse::Ok().json(config))
+}
+
+pub fn set_sso_config(auth: AuthUser, fields: web::Json<NewOidcProvider>) -> Result<HttpResponse, APIError> {
+    let config = api::set_sso_config(&auth.conn, auth.user_id, fields.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(config))
+}
+
+pub fn delete_sso_config(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    api::delete_sso_config(&auth.conn, auth.user_id)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn create_coordinate(auth: AuthUser, fields: web::Json<NewCoordinate>) -> Result<HttpResponse, APIError> {
+    let id = api::create_coordinate(&auth.conn, auth.user_id, fields.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id})))
+}
+
+pub fn modify_coordinate(
+    auth: AuthUser,
+    coordinates_id: web::Path<uuid::Uuid>,
+    if_match: IfMatch,
+    patch: web::Json<CoordinatePatch>
+) -> Result<HttpResponse, APIError> {
+    let coordinate = api::modify_coordinate(
+        &auth.conn,
+        auth.user_id,
+        coordinates_id.into_inner(),
+        if_match.0,
+        patch.into_inner()
+    )?;
+
+    Ok(versioned(coordinate.version, &coordinate))
+}
@@ -0,0 +1,42 @@
+use super::auth::AuthUser;
+use super::versions::{versioned, IfMatch};
+use crate::models::api::{self, APIError};
+use crate::models::building_owners::{BuildingOwnerPatch, NewBuildingOwner, OwnerFilter};
+use crate::models::list_query::ListQuery;
+
+use actix_web::{web, HttpResponse};
+
+use serde_json::json;
+
+pub fn get_owners(
+    auth: AuthUser,
+    filter: web::Query<OwnerFilter>,
+    query: web::Query<ListQuery>
+) -> Result<HttpResponse, APIError> {
+    let page = api::get_owners(&auth.conn, auth.user_id, filter.into_inner(), query.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub fn create_new_owner(auth: AuthUser, fields: web::Json<NewBuildingOwner>) -> Result<HttpResponse, APIError> {
+    let id = api::create_new_owner(&auth.conn, auth.user_id, fields.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id})))
+}
+
+pub fn get_owner_info(auth: AuthUser, owner_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let info = api::get_owner_info(&auth.conn, auth.user_id, owner_id.into_inner())?;
+
+    Ok(versioned(info.profile.version, &info))
+}
+
+pub fn modify_owner_info(
+    auth: AuthUser,
+    owner_id: web::Path<uuid::Uuid>,
+    if_match: IfMatch,
+    patch: web::Json<BuildingOwnerPatch>
+) -> Result<HttpResponse, APIError> {
+    let owner = api::modify_owner_info(&auth.conn, auth.user_id, owner_id.into_inner(), if_match.0, patch.into_inner())?;
+
+    Ok(versioned(owner.version, &owner))
+}
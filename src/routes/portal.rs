@@ -0,0 +1,51 @@
+use super::auth::AuthUser;
+use crate::models::api::{self, APIError};
+
+use actix_web::{web, HttpResponse};
+
+use serde_derive::Deserialize;
+
+/// A folder of a building's files, sent as the body when sharing it and as
+/// a query parameter when unsharing it.
+#[derive(Debug, Deserialize)]
+pub struct Folder {
+    pub folder: String,
+}
+
+pub fn get_owner_buildings(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    let buildings = api::get_owner_buildings(&auth.conn, auth.user_id)?;
+
+    Ok(HttpResponse::Ok().json(buildings))
+}
+
+pub fn get_owner_building(auth: AuthUser, building_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let building = api::get_owner_building(&auth.conn, auth.user_id, building_id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(building))
+}
+
+pub fn get_shared_folders(auth: AuthUser, building_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let folders = api::get_shared_folders(&auth.conn, auth.user_id, building_id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(folders))
+}
+
+pub fn share_folder_with_owner(
+    auth: AuthUser,
+    building_id: web::Path<uuid::Uuid>,
+    body: web::Json<Folder>
+) -> Result<HttpResponse, APIError> {
+    api::share_folder_with_owner(&auth.conn, auth.user_id, building_id.into_inner(), body.into_inner().folder)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn unshare_folder_with_owner(
+    auth: AuthUser,
+    building_id: web::Path<uuid::Uuid>,
+    query: web::Query<Folder>
+) -> Result<HttpResponse, APIError> {
+    api::unshare_folder_with_owner(&auth.conn, auth.user_id, building_id.into_inner(), query.into_inner().folder)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
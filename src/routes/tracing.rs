@@ -0,0 +1,129 @@
+use crate::logging::{Instrumented, Span};
+
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+
+use futures::future::{ok, FutureResult};
+use futures::{Async, Future, Poll};
+
+use log::Level;
+
+use std::time::Instant;
+
+/// Carries the ID of a request, from the client or a proxy if it sends a
+/// valid one, and back in every response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest request ID taken from a client.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// Tells whether a request ID sent by a client can be logged as is.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Handles every request within a span carrying its ID, so that the lines
+/// logged meanwhile and the error responses tell which request they are
+/// about, and logs a line once the response is ready.
+pub struct RequestTracing;
+
+impl<S, B> Transform<S> for RequestTracing
+    where S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestTracingMiddleware<S>;
+    type Future = FutureResult<Self::Transform, Self::InitError>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestTracingMiddleware { service })
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestTracingMiddleware<S>
+    where S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = TracedResponse<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.service.poll_ready()
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let request_id = req.headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_owned)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let span = Span::new(&request_id, req.method().as_str(), req.path());
+
+        // Extractors run as the request is dispatched, before the first poll
+        let service = &mut self.service;
+        let inner = span.enter(|| service.call(req));
+
+        TracedResponse { inner: Instrumented::new(span, inner), started: Instant::now() }
+    }
+}
+
+pub struct TracedResponse<F> {
+    inner: Instrumented<F>,
+    started: Instant,
+}
+
+impl<F, B> Future for TracedResponse<F>
+    where F: Future<Item = ServiceResponse<B>, Error = Error>
+{
+    type Item = ServiceResponse<B>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut res = match self.inner.poll() {
+            Ok(Async::Ready(res)) => res,
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Err(e) => {
+                self.inner.span().enter(|| error!("Request failed: {}", e));
+                return Err(e);
+            }
+        };
+
+        let span = self.inner.span();
+        let status = res.status();
+        let elapsed = self.started.elapsed();
+        span.record_response(status.as_u16(), elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis()));
+        let level = if status.is_server_error() { Level::Error } else { Level::Info };
+        span.enter(|| log!(level, "Request completed"));
+
+        if let Ok(value) = HeaderValue::from_str(&span.fields().request_id) {
+            res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+        }
+
+        Ok(Async::Ready(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_request_id;
+
+    #[test]
+    fn test_request_id() {
+        assert!(is_valid_request_id("3f2c9a1e-support.42_b"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("two words"));
+        assert!(!is_valid_request_id("id\r\nSet-Cookie: x"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+}
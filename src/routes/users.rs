@@ -0,0 +1,135 @@
+use super::auth::{login_client, AuthUser, DbConn};
+use super::versions::{versioned, IfMatch};
+use crate::config::Config;
+use crate::mail::Mailer;
+use crate::models::api::{self, APIError};
+use crate::models::list_query::ListQuery;
+use crate::models::users::{Handover, NewUser, PasswordChange, UserFilter, UserPatch};
+
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use serde_derive::Deserialize;
+use serde_json::json;
+
+/// The token of a link mailed to verify an email.
+#[derive(Debug, Deserialize)]
+pub struct EmailVerificationToken {
+    pub token: String,
+}
+
+pub fn get_users(
+    auth: AuthUser,
+    filter: web::Query<UserFilter>,
+    query: web::Query<ListQuery>
+) -> Result<HttpResponse, APIError> {
+    let page = api::get_users(&auth.conn, auth.user_id, filter.into_inner(), query.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub fn create_new_user(auth: AuthUser, mailer: web::Data<Mailer>, fields: web::Json<NewUser>) -> Result<HttpResponse, APIError> {
+    let id = api::create_new_user(&auth.conn, &mailer, auth.user_id, fields.into_inner())?;
+
+    Ok(HttpResponse::Created().json(json!({"id": id})))
+}
+
+pub fn unlock_user(auth: AuthUser, user_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    api::unlock_user(&auth.conn, auth.user_id, user_id.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn deactivate_user(auth: AuthUser, user_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    api::deactivate_user(&auth.conn, auth.user_id, user_id.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn hand_over_user(
+    auth: AuthUser,
+    user_id: web::Path<uuid::Uuid>,
+    fields: web::Json<Handover>
+) -> Result<HttpResponse, APIError> {
+    let report = api::hand_over_user(&auth.conn, auth.user_id, user_id.into_inner(), fields.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub fn get_user_sessions(auth: AuthUser, user_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    let sessions = api::get_user_sessions(&auth.conn, auth.user_id, user_id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+pub fn revoke_user_sessions(auth: AuthUser, user_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    api::revoke_user_sessions(&auth.conn, auth.user_id, user_id.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn revoke_user_session(auth: AuthUser, path: web::Path<(uuid::Uuid, uuid::Uuid)>) -> Result<HttpResponse, APIError> {
+    let (user_id, session_id) = path.into_inner();
+    api::revoke_user_session(&auth.conn, auth.user_id, user_id, session_id)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn get_user_profile(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    let profile = api::get_user_profile(&auth.conn, auth.user_id)?;
+
+    Ok(versioned(profile.version, &profile))
+}
+
+pub fn modify_user_profile(
+    auth: AuthUser,
+    mailer: web::Data<Mailer>,
+    if_match: IfMatch,
+    patch: web::Json<UserPatch>
+) -> Result<HttpResponse, APIError> {
+    let profile = api::modify_user_profile(&auth.conn, &mailer, auth.user_id, if_match.0, patch.into_inner())?;
+
+    Ok(versioned(profile.version, &profile))
+}
+
+pub fn change_password(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    auth: AuthUser,
+    fields: web::Json<PasswordChange>
+) -> Result<HttpResponse, APIError> {
+    api::change_password(&auth.conn, auth.user_id, fields.into_inner(), &login_client(&req, &config))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn get_sessions(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    let sessions = api::get_sessions(&auth.conn, auth.user_id, &auth.token)?;
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+/// Logs the user out everywhere but here.
+pub fn revoke_other_sessions(auth: AuthUser) -> Result<HttpResponse, APIError> {
+    api::revoke_other_sessions(&auth.conn, auth.user_id, &auth.token)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn revoke_session(auth: AuthUser, session_id: web::Path<uuid::Uuid>) -> Result<HttpResponse, APIError> {
+    api::revoke_session(&auth.conn, auth.user_id, session_id.into_inner())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn resend_email_verification(auth: AuthUser, mailer: web::Data<Mailer>) -> Result<HttpResponse, APIError> {
+    api::resend_email_verification(&auth.conn, &mailer, auth.user_id)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Opened from the link mailed to the user, without a session.
+pub fn verify_email(conn: DbConn, token: web::Json<EmailVerificationToken>) -> Result<HttpResponse, APIError> {
+    api::verify_email(&conn, &token.token)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
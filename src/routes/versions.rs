@@ -0,0 +1,68 @@
+use crate::models::api::APIError;
+
+use actix_web::{dev::Payload, http::header, FromRequest, HttpRequest, HttpResponse};
+
+use serde::Serialize;
+
+/// Formats a version as the strong entity tag of its entity.
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Parses an `If-Match` header holding one entity tag, `*` matching any
+/// version. Weak tags never match an update so they are refused.
+pub fn parse_if_match(value: &str) -> Option<Option<i32>> {
+    let value = value.trim();
+
+    if value == "*" {
+        return Some(None);
+    }
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return None;
+    }
+
+    value[1..value.len() - 1].parse().ok().map(Some)
+}
+
+/// The version a modify request applies to, `None` without `If-Match`.
+pub struct IfMatch(pub Option<i32>);
+
+impl FromRequest for IfMatch {
+    type Config = ();
+    type Error = APIError;
+    type Future = Result<Self, Self::Error>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        match req.headers().get(header::IF_MATCH) {
+            None => Ok(IfMatch(None)),
+            Some(value) => value.to_str().ok()
+                .and_then(parse_if_match)
+                .map(IfMatch)
+                .ok_or_else(|| APIError::InvalidInput(String::from("Invalid If-Match header"))),
+        }
+    }
+}
+
+/// Responds with an entity and its version as `ETag`, for the client to send
+/// back in `If-Match` when modifying it.
+pub fn versioned<T: Serialize>(version: i32, body: &T) -> HttpResponse {
+    HttpResponse::Ok()
+        .header(header::ETAG, etag(version))
+        .json(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{etag, parse_if_match};
+
+    #[test]
+    fn test_parse_if_match() {
+        assert_eq!(parse_if_match(&etag(3)), Some(Some(3)));
+        assert_eq!(parse_if_match(" \"12\" "), Some(Some(12)));
+        assert_eq!(parse_if_match("*"), Some(None));
+        assert_eq!(parse_if_match("W/\"3\""), None);
+        assert_eq!(parse_if_match("3"), None);
+        assert_eq!(parse_if_match("\"three\""), None);
+        assert_eq!(parse_if_match("\""), None);
+    }
+}
@@ -1,3 +1,29 @@
+table! {
+    account_lockouts (user_id) {
+        user_id -> Uuid,
+        failures -> Int4,
+        locked_until -> Nullable<Timestamp>,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    api_keys (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        created_by -> Uuid,
+        name -> Varchar,
+        secret -> Varchar,
+        scopes -> Array<Varchar>,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
+    }
+}
+
 table! {
     building_owners (id) {
         id -> Uuid,
@@ -10,6 +36,8 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        kind -> Int2,
+        version -> Int4,
     }
 }
 
@@ -25,6 +53,7 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        version -> Int4,
     }
 }
 
@@ -42,6 +71,19 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        version -> Int4,
+        org_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    email_verifications (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        email -> Varchar,
+        secret -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
     }
 }
 
@@ -53,6 +95,7 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        folder -> Varchar,
     }
 }
 
@@ -80,6 +123,32 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        parent_id -> Nullable<Uuid>,
+        version -> Int4,
+    }
+}
+
+table! {
+    entities_notes_edits (id) {
+        id -> Uuid,
+        note_id -> Uuid,
+        user_id -> Uuid,
+        previous_note -> Varchar,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
+    }
+}
+
+table! {
+    entities_notes_mentions (note_id, user_id) {
+        note_id -> Uuid,
+        user_id -> Uuid,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
     }
 }
 
@@ -93,6 +162,74 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        metadata -> Jsonb,
+        version -> Int4,
+    }
+}
+
+table! {
+    files_notes (id) {
+        id -> Uuid,
+        file_id -> Uuid,
+        entity_id -> Uuid,
+        user_id -> Uuid,
+        note -> Varchar,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
+    }
+}
+
+table! {
+    login_attempts (id) {
+        id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        email -> Varchar,
+        ip_address -> Varchar,
+        user_agent -> Varchar,
+        outcome -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    oidc_identities (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        user_id -> Uuid,
+        subject -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    oidc_providers (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        issuer -> Varchar,
+        client_id -> Varchar,
+        client_secret -> Varchar,
+        authorization_endpoint -> Varchar,
+        token_endpoint -> Varchar,
+        redirect_uri -> Varchar,
+        groups_claim -> Varchar,
+        admin_groups -> Array<Varchar>,
+        member_groups -> Array<Varchar>,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
+    }
+}
+
+table! {
+    oidc_states (state) {
+        state -> Varchar,
+        org_id -> Uuid,
+        code_verifier -> Varchar,
+        nonce -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
@@ -106,6 +243,23 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        version -> Int4,
+        require_two_factor -> Bool,
+        password_min_length -> Int2,
+        password_min_entropy -> Int2,
+        password_reject_personal_info -> Bool,
+    }
+}
+
+table! {
+    recovery_codes (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        code -> Varchar,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
     }
 }
 
@@ -118,6 +272,34 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        version -> Int4,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        secret -> Varchar,
+        expires_at -> Timestamp,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
+        ip_address -> Varchar,
+        user_agent -> Varchar,
+        last_seen_at -> Timestamp,
+    }
+}
+
+table! {
+    shared_folders (building_id, folder) {
+        building_id -> Uuid,
+        folder -> Varchar,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
     }
 }
 
@@ -135,32 +317,84 @@ table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Timestamp,
+        version -> Int4,
+        email_verified -> Bool,
+        successor_id -> Nullable<Uuid>,
+        deactivated -> Bool,
+        deactivated_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    users_two_factor (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        secret -> Varchar,
+        enabled -> Bool,
+        deleted -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Timestamp,
+        last_used_step -> Int8,
     }
 }
 
+joinable!(account_lockouts -> users (user_id));
+joinable!(api_keys -> organizations (org_id));
+joinable!(api_keys -> users (created_by));
 joinable!(building_owners -> coordinates (coordinates_id));
 joinable!(building_owners -> organizations (org_id));
 joinable!(building_owners -> users (linked_user_id));
 joinable!(buildings -> building_owners (owner_id));
 joinable!(buildings -> organizations (org_id));
 joinable!(buildings -> users (respondant_id));
+joinable!(email_verifications -> users (user_id));
 joinable!(entities_files -> files (file_id));
 joinable!(entities_history -> files (file_id));
 joinable!(entities_history -> users (user_id));
 joinable!(entities_notes -> users (user_id));
+joinable!(entities_notes_edits -> entities_notes (note_id));
+joinable!(entities_notes_edits -> users (user_id));
+joinable!(entities_notes_mentions -> entities_notes (note_id));
+joinable!(entities_notes_mentions -> users (user_id));
+joinable!(files_notes -> files (file_id));
+joinable!(files_notes -> users (user_id));
+joinable!(login_attempts -> users (user_id));
+joinable!(oidc_identities -> organizations (org_id));
+joinable!(oidc_identities -> users (user_id));
+joinable!(oidc_providers -> organizations (org_id));
+joinable!(oidc_states -> organizations (org_id));
 joinable!(organizations -> coordinates (coordinates_id));
+joinable!(recovery_codes -> users (user_id));
 joinable!(registers -> buildings (building_id));
+joinable!(sessions -> users (user_id));
+joinable!(shared_folders -> buildings (building_id));
 joinable!(users -> organizations (org_id));
+joinable!(users_two_factor -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    account_lockouts,
+    api_keys,
     building_owners,
     buildings,
     coordinates,
+    email_verifications,
     entities_files,
     entities_history,
     entities_notes,
+    entities_notes_edits,
+    entities_notes_mentions,
     files,
+    files_notes,
+    login_attempts,
+    oidc_identities,
+    oidc_providers,
+    oidc_states,
     organizations,
+    recovery_codes,
     registers,
+    sessions,
+    shared_folders,
     users,
+    users_two_factor,
 );
@@ -1,5 +1,7 @@
 use libreauth::pass::{Algorithm, HashBuilder, Hasher};
 
+use sha2::{Digest, Sha256};
+
 pub const PWD_ALGORITHM: Algorithm = Algorithm::Argon2;
 pub const PWD_SCHEME_VERSION: usize = 1;
 
@@ -14,13 +16,33 @@ lazy_static! {
     };
 }
 
+/// Hashes a random token secret checked on every request. Such secrets are
+/// long enough not to need the slow password hasher, a SHA-256 digest does.
+pub fn token_digest(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Compares two values in a time that does not depend on where they differ.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{HASHER};
+    use super::{constant_time_eq, token_digest, HASHER};
 
     #[test]
     fn test_hasher() {
         let z = HASHER.hash("testpass");
         assert_ne!(z.unwrap(), "testpass");        
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_token_digest() {
+        let digest = token_digest("secret");
+        assert_eq!(digest, "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b");
+        assert!(constant_time_eq(digest.as_bytes(), token_digest("secret").as_bytes()));
+        assert!(!constant_time_eq(digest.as_bytes(), token_digest("secreT").as_bytes()));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod hasher;
+pub mod nullable;
+pub mod validators;
 
 pub use {self::hasher::*};
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Deserializer};
+
+/// Deserializes a nullable field of a PATCH request so that an absent field
+/// gives `None` and `null` gives `Some(None)`. Must be used along with
+/// `#[serde(default)]`.
+pub fn deserialize_nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::deserialize_nullable;
+    use serde_derive::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Patch {
+        #[serde(default, deserialize_with = "deserialize_nullable")]
+        coordinates_id: Option<Option<uuid::Uuid>>,
+    }
+
+    #[test]
+    fn test_deserialize_nullable() {
+        let absent: Patch = serde_json::from_str("{}").unwrap();
+        assert_eq!(absent.coordinates_id, None);
+
+        let null: Patch = serde_json::from_str(r#"{"coordinates_id": null}"#).unwrap();
+        assert_eq!(null.coordinates_id, Some(None));
+
+        let id = uuid::Uuid::new_v4();
+        let set: Patch = serde_json::from_str(&format!(r#"{{"coordinates_id": "{}"}}"#, id)).unwrap();
+        assert_eq!(set.coordinates_id, Some(Some(id)));
+    }
+}
@@ -0,0 +1,63 @@
+use validator::ValidationError;
+
+/// Rejects values made only of whitespace, which `length(min = 1)` accepts.
+pub fn validate_not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("blank"));
+    }
+    Ok(())
+}
+
+/// Accepts phone numbers written with digits, spaces and the usual `+-().`
+/// separators, optionally followed by an extension (`514 555-1234 ext 12`).
+pub fn validate_phone(value: &str) -> Result<(), ValidationError> {
+    let lowered = value.to_lowercase();
+    let number = match lowered.find("ext") {
+        Some(idx) => {
+            let extension = lowered[idx + 3..].trim_start_matches('.').trim();
+            if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_digit()) {
+                return Err(ValidationError::new("phone"));
+            }
+            &lowered[..idx]
+        },
+        None => &lowered[..],
+    };
+
+    let digits = number.chars().filter(|c| c.is_ascii_digit()).count();
+    let valid_chars = number.chars().all(|c| c.is_ascii_digit() || " +-().".contains(c));
+    let plus_first = number.trim_start().find('+').map_or(true, |idx| idx == 0) && number.matches('+').count() <= 1;
+
+    if !valid_chars || !plus_first || digits < 7 || digits > 15 {
+        return Err(ValidationError::new("phone"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_not_blank, validate_phone};
+
+    #[test]
+    fn test_validate_not_blank() {
+        assert!(validate_not_blank("Tour Ville-Marie").is_ok());
+        assert!(validate_not_blank("").is_err());
+        assert!(validate_not_blank(" \t\n").is_err());
+    }
+
+    #[test]
+    fn test_validate_phone() {
+        assert!(validate_phone("514-555-1234").is_ok());
+        assert!(validate_phone("(514) 555-1234").is_ok());
+        assert!(validate_phone("+1 514 555 1234").is_ok());
+        assert!(validate_phone("514.555.1234 ext 42").is_ok());
+        assert!(validate_phone("514-555-1234 Ext. 42").is_ok());
+
+        assert!(validate_phone("").is_err());
+        assert!(validate_phone("555-12").is_err());
+        assert!(validate_phone("1234567890123456").is_err());
+        assert!(validate_phone("514-555-CALL").is_err());
+        assert!(validate_phone("514 +555 1234").is_err());
+        assert!(validate_phone("514-555-1234 ext").is_err());
+        assert!(validate_phone("514-555-1234 ext 4a").is_err());
+    }
+}